@@ -0,0 +1,78 @@
+//! Access to the memory of a tracee, with `process_vm_readv()`/`writev()` and
+//! with `ptrace(PEEKDATA/POKEDATA)`:
+//!
+//! ```text
+//! cargo bench -p pronyx --bench tracee_memory
+//! ```
+
+use std::hint::black_box;
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use nix::sys::ptrace;
+use nix::sys::signal::{self, Signal};
+use nix::sys::wait::{self, WaitStatus};
+use nix::unistd::{self, ForkResult, Pid};
+
+use pronyx::memory;
+
+/// A path of a typical length, and a buffer as large as an `argv` or a
+/// socket address can be.
+static PATH: &[u8] = b"/usr/lib/x86_64-linux-gnu/perl5/5.36/auto/POSIX/POSIX.so\0";
+static BUFFER: [u8; 4096] = [0x55; 4096];
+
+/// Fork a tracee, stopped until it is killed. It shares the memory layout of
+/// the benchmark, hence the addresses of its data.
+fn spawn_tracee() -> Pid {
+    match unsafe { unistd::fork() }.expect("fork") {
+        ForkResult::Child => {
+            ptrace::traceme().expect("traceme");
+            signal::raise(Signal::SIGSTOP).expect("raise");
+            unsafe { libc::_exit(0) };
+        }
+        ForkResult::Parent { child } => {
+            assert_eq!(
+                wait::waitpid(child, None),
+                Ok(WaitStatus::Stopped(child, Signal::SIGSTOP))
+            );
+            child
+        }
+    }
+}
+
+fn tracee_memory(c: &mut Criterion) {
+    // `BUFFER` is in a read-only mapping, which is not what is usually written
+    let writable = vec![0u8; BUFFER.len()];
+    let pid = spawn_tracee();
+    let path = PATH.as_ptr() as libc::c_ulong;
+    let buffer = BUFFER.as_ptr() as libc::c_ulong;
+    let writable = writable.as_ptr() as libc::c_ulong;
+    let mut output = vec![0; BUFFER.len()];
+
+    for (method, process_vm) in [("process_vm", true), ("ptrace", false)] {
+        memory::use_process_vm(process_vm);
+
+        let mut group = c.benchmark_group("read_string");
+        group.bench_function(BenchmarkId::from_parameter(method), |b| {
+            b.iter(|| memory::read_string(pid, black_box(path), 4096).unwrap())
+        });
+        group.finish();
+
+        let mut group = c.benchmark_group("read_bytes_4k");
+        group.bench_function(BenchmarkId::from_parameter(method), |b| {
+            b.iter(|| memory::read_bytes(pid, black_box(buffer), &mut output).unwrap())
+        });
+        group.finish();
+
+        let mut group = c.benchmark_group("write_bytes_4k");
+        group.bench_function(BenchmarkId::from_parameter(method), |b| {
+            b.iter(|| memory::write_bytes(pid, black_box(writable), &BUFFER).unwrap())
+        });
+        group.finish();
+    }
+
+    let _ = signal::kill(pid, Signal::SIGKILL);
+    let _ = wait::waitpid(pid, None);
+}
+
+criterion_group!(benches, tracee_memory);
+criterion_main!(benches);
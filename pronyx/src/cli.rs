@@ -1,107 +1,589 @@
-use clap::{Arg, Command, ArgAction}; // App is dead, long live Command
+use std::path::PathBuf;
 
-use crate::errors::*;
-use crate::filesystem::FileSystem;
-use crate::utils::Config;
+use clap::parser::ValueSource;
+use clap::{Arg, ArgAction, ArgMatches, Command}; // App is dead, long live Command
 
-pub const DEFAULT_ROOTFS: &'static str = "/";
-pub const DEFAULT_CWD: &'static str = "/";
+use crate::filesystem::device::{device_validator, Device};
+use crate::filesystem::validation::{
+    binding_validator, env_validator, guest_path_validator, hostname_validator,
+    kernel_release_validator, path_validator, signal_validator, split_binding,
+};
+use crate::process::trace::filter_validator;
+use crate::sandbox::{
+    ExitPolicy, SandboxBuilder, SandboxError, TraceOptions, DEFAULT_CWD, DEFAULT_ROOTFS,
+};
 
-pub fn get_args_parser() -> Command {
-    Command::new("pronyx")
-        .about("chroot, mount --bind, and binfmt_misc without privilege/setup.")
-        .version(env!("CARGO_PKG_VERSION"))
-        .trailing_var_arg(true)
-        .arg(Arg::new("rootfs")
+/// What pronyx was asked to do.
+#[derive(Debug)]
+pub enum Action {
+    /// Run a command inside a box.
+    Run { sandbox: Box<SandboxBuilder> },
+    /// Validate a profile without running anything.
+    CheckProfile { profile: PathBuf },
+    /// Start a session, in the background unless `foreground` is set.
+    Daemon {
+        session: String,
+        sandbox: Box<SandboxBuilder>,
+        foreground: bool,
+    },
+    /// Run a command in a session.
+    Exec {
+        session: String,
+        command: Vec<String>,
+        env: Vec<(String, String)>,
+        cwd: Option<PathBuf>,
+    },
+    /// List the processes of a session.
+    Ps { session: String },
+    /// Send a signal to processes of a session (all of them if `pids` is
+    /// empty).
+    Kill {
+        session: String,
+        signal: i32,
+        pids: Vec<i32>,
+    },
+    /// Stop a session.
+    Stop { session: String },
+    /// Unpack an image into a new rootfs.
+    RootfsImport { image: PathBuf, dir: PathBuf },
+    /// Write a rootfs back out to an image.
+    RootfsExport { dir: PathBuf, image: PathBuf },
+}
+
+/// Options describing a box, shared by the main command, `daemon` and `attach`.
+fn box_args() -> Vec<Arg> {
+    vec![
+        Arg::new("profile")
+            .long("profile")
+            .env("PRONYX_PROFILE")
+            .help("Load the box description from a TOML profile. Flags override its values.")
+            .num_args(1),
+        Arg::new("rootfs")
             .short('r') // now takes a char, not a str
             .long("rootfs")
             .help("Use *path* as the new guest root file-system.")
             .num_args(1)
             .default_value(DEFAULT_ROOTFS)
-            // .value_parser(path_validator) // need to update validator signature
-        )
-        .arg(Arg::new("bind")
+            .value_parser(path_validator),
+        Arg::new("recommended_rootfs")
+            .short('R')
+            .help("Use *path* as the new guest root file-system, and bind the host files most programs need \
+                   (/dev, /proc, /sys, /run, /tmp, $HOME, /etc/resolv.conf, /etc/passwd...) when they exist.")
+            .value_name("rootfs")
+            .num_args(1)
+            .conflicts_with_all(["rootfs", "safe_rootfs"])
+            .value_parser(path_validator),
+        Arg::new("safe_rootfs")
+            .short('S')
+            .help("Like -R, but without $HOME, and pretending to be root (-0): to build or install packages.")
+            .value_name("rootfs")
+            .num_args(1)
+            .conflicts_with("rootfs")
+            .value_parser(path_validator),
+        Arg::new("bind")
             .short('b')
             .long("bind")
             .help("Make host_path accessible in guest. Format: host_path:guest_path")
             .action(ArgAction::Append) // replaces .multiple(true)
             .num_args(1)
-            // .value_parser(binding_validator)
-        )
-        .arg(Arg::new("cwd")
+            .value_parser(binding_validator),
+        Arg::new("bind_ro")
+            .long("bind-ro")
+            .help("Like --bind, but the guest can't modify the files of host_path. Format: host_path:guest_path")
+            .action(ArgAction::Append)
+            .num_args(1)
+            .value_parser(binding_validator),
+        Arg::new("cwd")
             .short('w')
             .long("cwd")
             .help("Set the initial working directory.")
             .num_args(1)
-            .default_value(DEFAULT_CWD))
-        .arg(Arg::new("root_id")
+            .default_value(DEFAULT_CWD)
+            .value_parser(guest_path_validator),
+        Arg::new("root_id")
             .short('0')
             .help("Pretend to be root (uid 0, gid 0)")
             .num_args(0)
-            .action(ArgAction::SetTrue),
-            )
-        .arg(Arg::new("link2symlink")
+            .action(ArgAction::SetTrue)
+            .overrides_with("no_root_id"),
+        Arg::new("no_root_id")
+            .long("no-root-id")
+            .help("Do not pretend to be root, even if the profile does.")
+            .num_args(0)
+            .action(ArgAction::SetTrue)
+            .overrides_with("root_id"),
+        Arg::new("link2symlink")
             .long("link2symlink")
             .help("Convert hard links to symbolic links")
             .num_args(0)
+            .action(ArgAction::SetTrue)
+            .overrides_with("no_link2symlink"),
+        Arg::new("no_link2symlink")
+            .long("no-link2symlink")
+            .help("Do not convert hard links to symbolic links, even if the profile does.")
+            .num_args(0)
+            .action(ArgAction::SetTrue)
+            .overrides_with("link2symlink"),
+        Arg::new("kernel_release")
+            .short('k')
+            .long("kernel-release")
+            .help("Make the guest see *string* as the kernel release.")
+            .num_args(1)
+            .value_parser(kernel_release_validator),
+        Arg::new("hostname")
+            .long("hostname")
+            .help("Make the guest see *string* as the host name.")
+            .num_args(1)
+            .value_parser(hostname_validator),
+        Arg::new("cpuinfo")
+            .long("cpuinfo")
+            .help("Make the guest see the content of *file* as /proc/cpuinfo.")
+            .value_name("file")
+            .num_args(1),
+        Arg::new("allow_device")
+            .long("allow-device")
+            .help("Let the device files created with -0 open the host device *device*. Format: c:major:minor or b:major:minor")
+            .value_name("device")
+            .action(ArgAction::Append)
+            .num_args(1)
+            .value_parser(device_validator),
+        Arg::new("env")
+            .short('e')
+            .long("env")
+            .help("Set the variable NAME to *value* in the guest. Format: NAME=value")
+            .action(ArgAction::Append)
+            .num_args(1)
+            .value_parser(env_validator),
+        Arg::new("unset_env")
+            .long("unset-env")
+            .help("Remove the variable NAME from the guest environment.")
+            .action(ArgAction::Append)
+            .num_args(1),
+        Arg::new("clear_env")
+            .long("clear-env")
+            .help("Start from an empty guest environment (no default PATH, HOME, TERM...)")
+            .num_args(0)
+            .action(ArgAction::SetTrue)
+            .overrides_with("no_clear_env"),
+        Arg::new("no_clear_env")
+            .long("no-clear-env")
+            .help("Start from the default guest environment, even if the profile clears it.")
+            .num_args(0)
+            .action(ArgAction::SetTrue)
+            .overrides_with("clear_env"),
+        Arg::new("trace")
+            .short('v')
+            .long("trace")
+            .help("Print the syscalls of the guest, strace-style, to stderr or to *file*.")
+            .value_name("file")
+            .num_args(0..=1)
+            .require_equals(true)
+            .default_missing_value("-"),
+        Arg::new("trace_filter")
+            .long("trace-filter")
+            .help("Only trace these syscalls, given by name or class (file, process, network, memory, signal).")
+            .value_delimiter(',')
+            .action(ArgAction::Append)
+            .value_parser(filter_validator),
+        Arg::new("trace_pid")
+            .long("trace-pid")
+            .help("Only trace the syscalls of this pid.")
+            .action(ArgAction::Append)
+            .num_args(1)
+            .value_parser(clap::value_parser!(i32)),
+        Arg::new("trace_timestamps")
+            .long("trace-timestamps")
+            .help("Prefix each traced syscall with its time (seconds since the epoch).")
+            .num_args(0)
             .action(ArgAction::SetTrue),
-            )
+        Arg::new("audit")
+            .long("audit")
+            .help("Write a JSON record of each file access of the guest to *file*.")
+            .value_name("file")
+            .num_args(1),
+        Arg::new("subreaper")
+            .long("subreaper")
+            .help("Make the processes started by pronyx child subreapers, with a real prctl(PR_SET_CHILD_SUBREAPER) on the host: the orphans of the guest are reparented to them.")
+            .num_args(0)
+            .action(ArgAction::SetTrue),
+        Arg::new("no_path_cache")
+            .long("no-path-cache")
+            .help("Do not cache the translations of the guest paths (for debugging).")
+            .num_args(0)
+            .action(ArgAction::SetTrue),
+    ]
+}
+
+/// Options of the commands which have an init process (the main command and
+/// `attach`).
+fn init_args() -> Vec<Arg> {
+    vec![
+        Arg::new("grace_period")
+            .long("grace-period")
+            .help("Seconds given to the guest to terminate after a forwarded SIGINT/SIGTERM/SIGHUP/SIGQUIT, before it is killed (0: forever).")
+            .value_name("seconds")
+            .num_args(1)
+            .value_parser(clap::value_parser!(u32)),
+        Arg::new("exit_policy")
+            .long("exit-policy")
+            .help("Terminate once the init process does (killing the processes it leaves behind), or once all the processes do.")
+            .num_args(1)
+            .value_parser(["init", "all"]),
+    ]
+}
+
+pub fn get_args_parser() -> Command {
+    Command::new("pronyx")
+        .about("chroot, mount --bind, and binfmt_misc without privilege/setup.")
+        .version(env!("CARGO_PKG_VERSION"))
+        .trailing_var_arg(true)
+        .args_conflicts_with_subcommands(true)
+        .args(box_args())
+        .args(init_args())
         .arg(Arg::new("command")
-            .required(true)
             .num_args(1..)
             .help("The command to run within Pronyx")
         )
+        .subcommand(Command::new("check-profile")
+            .about("Validate a profile without running it.")
+            .arg(Arg::new("profile")
+                .env("PRONYX_PROFILE")
+                .required(true)
+                .help("Path to the TOML profile")
+            )
+        )
+        .subcommand(Command::new("daemon")
+            .about("Start a session: a box kept alive in the background, to run commands in.")
+            .arg(session_arg())
+            .arg(Arg::new("foreground")
+                .long("foreground")
+                .help("Stay in the foreground instead of detaching.")
+                .num_args(0)
+                .action(ArgAction::SetTrue)
+            )
+            .args(box_args())
+        )
+        .subcommand(Command::new("attach")
+            .about("Attach an already running process (and its threads) to a box.")
+            .arg(Arg::new("pid")
+                .long("pid")
+                .help("Pid of the process, which is then the init process of the box.")
+                .num_args(1)
+                .required(true)
+                .value_parser(clap::value_parser!(i32))
+            )
+            .args(init_args())
+            .args(box_args())
+        )
+        .subcommand(Command::new("exec")
+            .about("Run a command in a session.")
+            .trailing_var_arg(true)
+            .arg(session_arg())
+            .arg(Arg::new("cwd")
+                .short('w')
+                .long("cwd")
+                .help("Set the working directory (the one of the session by default).")
+                .num_args(1)
+                .value_parser(guest_path_validator)
+            )
+            .arg(Arg::new("env")
+                .short('e')
+                .long("env")
+                .help("Set the variable NAME to *value* for this command. Format: NAME=value")
+                .action(ArgAction::Append)
+                .num_args(1)
+                .value_parser(env_validator)
+            )
+            .arg(Arg::new("command")
+                .num_args(1..)
+                .required(true)
+                .help("The command to run within the session")
+            )
+        )
+        .subcommand(Command::new("ps")
+            .about("List the processes of a session.")
+            .arg(session_arg())
+        )
+        .subcommand(Command::new("kill")
+            .about("Send a signal to processes of a session, or to all of them.")
+            .arg(session_arg())
+            .arg(Arg::new("signal")
+                .short('s')
+                .long("signal")
+                .help("Signal to send, by name or number.")
+                .num_args(1)
+                .default_value("TERM")
+                .value_parser(signal_validator)
+            )
+            .arg(Arg::new("pids")
+                .num_args(0..)
+                .help("Pids of the processes (all the processes of the session by default)")
+                .value_parser(clap::value_parser!(i32))
+            )
+        )
+        .subcommand(Command::new("stop")
+            .about("Kill every process of a session, and terminate it.")
+            .arg(session_arg())
+        )
+        .subcommand(Command::new("rootfs")
+            .about("Import or export a rootfs as a tarball.")
+            .subcommand_required(true)
+            .subcommand(Command::new("import")
+                .about("Unpack a tarball (optionally compressed with gzip, xz or zstd) into a new rootfs. \
+                        Ownership and devices are recorded in <dir>.manifest.toml.")
+                .arg(Arg::new("image")
+                    .required(true)
+                    .help("Path to the tarball")
+                )
+                .arg(Arg::new("dir")
+                    .required(true)
+                    .help("The new rootfs, which must be missing or empty")
+                )
+            )
+            .subcommand(Command::new("export")
+                .about("Write a rootfs to a tarball, with the ownership recorded on import. \
+                        It is compressed according to its extension (.gz, .xz or .zst).")
+                .arg(Arg::new("dir")
+                    .required(true)
+                    .help("Path to the rootfs")
+                )
+                .arg(Arg::new("image")
+                    .required(true)
+                    .help("Path to the tarball")
+                )
+            )
+        )
+}
+
+fn session_arg() -> Arg {
+    Arg::new("session")
+        .long("session")
+        .env("PRONYX_SESSION")
+        .help("Name of the session.")
+        .num_args(1)
+        .required(true)
+}
+
+pub fn parse_action() -> Result<Action, SandboxError> {
+    let matches = get_args_parser().get_matches();
+
+    debug!("pronyx startup with args:\n{:#?}", matches);
+
+    match matches.subcommand() {
+        Some(("check-profile", sub_matches)) => Ok(Action::CheckProfile {
+            profile: PathBuf::from(sub_matches.get_one::<String>("profile").unwrap()),
+        }),
+        Some(("rootfs", sub_matches)) => {
+            let (name, sub_matches) = sub_matches.subcommand().unwrap();
+            let path = |id: &str| PathBuf::from(sub_matches.get_one::<String>(id).unwrap());
+            Ok(match name {
+                "import" => Action::RootfsImport {
+                    image: path("image"),
+                    dir: path("dir"),
+                },
+                "export" => Action::RootfsExport {
+                    dir: path("dir"),
+                    image: path("image"),
+                },
+                _ => unreachable!("unknown subcommand rootfs {}", name),
+            })
+        }
+        Some(("attach", sub_matches)) => Ok(Action::Run {
+            sandbox: Box::new(
+                parse_config(sub_matches)?.attach(*sub_matches.get_one::<i32>("pid").unwrap()),
+            ),
+        }),
+        Some((name, sub_matches)) => {
+            let session = sub_matches.get_one::<String>("session").unwrap().clone();
+            Ok(match name {
+                "daemon" => Action::Daemon {
+                    session,
+                    sandbox: Box::new(parse_config(sub_matches)?),
+                    foreground: sub_matches.get_flag("foreground"),
+                },
+                "exec" => Action::Exec {
+                    session,
+                    command: sub_matches
+                        .get_many::<String>("command")
+                        .unwrap()
+                        .cloned()
+                        .collect(),
+                    env: sub_matches
+                        .get_many::<(String, String)>("env")
+                        .map(|assignments| assignments.cloned().collect())
+                        .unwrap_or_default(),
+                    cwd: sub_matches.get_one::<String>("cwd").map(PathBuf::from),
+                },
+                "ps" => Action::Ps { session },
+                "kill" => Action::Kill {
+                    session,
+                    signal: *sub_matches.get_one::<i32>("signal").unwrap(),
+                    pids: sub_matches
+                        .get_many::<i32>("pids")
+                        .map(|pids| pids.copied().collect())
+                        .unwrap_or_default(),
+                },
+                "stop" => Action::Stop { session },
+                _ => unreachable!("unknown subcommand {}", name),
+            })
+        }
+        _ => Ok(Action::Run {
+            sandbox: Box::new(parse_config(&matches)?),
+        }),
+    }
 }
 
-pub fn parse_config() -> Result<(FileSystem, Vec<String>, Config)> {
-    let app = get_args_parser();
-    let mut fs: FileSystem = FileSystem::new();
+/// Returns the value of a flag `id`, which can be turned off by `no_id`, only
+/// if one of them was given (the last one wins): otherwise the profile's value
+/// is kept.
+fn explicit_flag(matches: &ArgMatches, id: &str, no_id: &str) -> Option<bool> {
+    if matches.get_flag(id) {
+        Some(true)
+    } else if matches.get_flag(no_id) {
+        Some(false)
+    } else {
+        None
+    }
+}
 
-    let matches = app.get_matches();
+/// Returns the value of a flag only if it was explicitly given, so that it
+/// takes precedence over the profile (unlike clap's default values).
+fn explicit_value<'a>(matches: &'a ArgMatches, id: &str) -> Option<&'a String> {
+    match matches.value_source(id) {
+        Some(ValueSource::DefaultValue) | None => None,
+        Some(_) => matches.get_one::<String>(id),
+    }
+}
 
-    let config = Config {
-        root_id: matches.get_flag("root_id"),
-        link2symlink: matches.get_flag("link2symlink"),
-        // ...
+pub fn parse_config(matches: &ArgMatches) -> Result<SandboxBuilder, SandboxError> {
+    // the profile is applied first, so that flags take precedence over it
+    let mut sandbox = match matches.get_one::<String>("profile") {
+        Some(path) => SandboxBuilder::new().profile(path)?,
+        None => SandboxBuilder::new(),
     };
 
-    debug!("pronyx startup with args:\n{:#?}", matches);
+    // option -r
+    if let Some(rootfs) = explicit_value(matches, "rootfs") {
+        sandbox = sandbox.rootfs(rootfs);
+    }
+
+    // options -R and -S, whose bindings come before the ones of -b
+    if let Some(rootfs) = matches.get_one::<String>("recommended_rootfs") {
+        sandbox = sandbox.rootfs(rootfs).recommended_bindings(true);
+    }
+    if let Some(rootfs) = matches.get_one::<String>("safe_rootfs") {
+        sandbox = sandbox
+            .rootfs(rootfs)
+            .recommended_bindings(false)
+            .root_id(true);
+    }
 
-    // option -r: use get_one::<String>
-    // if let Some(root_id) = matches.get_one::<bool>("root_id") {
-    //     fs.set_root(root_id)?;
-    // }
-
-    // option -r: use get_one::<String>
-    if let Some(rootfs) = matches.get_one::<String>("rootfs") {
-        fs.set_root(rootfs)?;
-    }
-
-    // option(s) -b: use get_many::<String>
-    if let Some(bindings) = matches.get_many::<String>("bind") {
-        for raw_binding_str in bindings {
-            let parts: Vec<&str> = raw_binding_str.split_terminator(':').collect();
-            // make sure we actually have two parts before indexing [1]
-            if parts.len() == 2 {
-                fs.add_binding(parts[0], parts[1])?;
-            } else {
-                // maybe default to root if only one part is provided? 
-                // proot usually allows "host_path" to mean "host_path:host_path"
-                fs.add_binding(parts[0], parts[0])?;
-            }
+    // option(s) -b and --bind-ro, in the order of the command line: they come
+    // after the bindings of the profile, and thus take precedence over them
+    let mut bindings: Vec<(usize, &String, bool)> = vec![];
+    for (id, read_only) in [("bind", false), ("bind_ro", true)] {
+        if let (Some(indices), Some(values)) =
+            (matches.indices_of(id), matches.get_many::<String>(id))
+        {
+            bindings.extend(indices.zip(values).map(|(index, value)| (index, value, read_only)));
         }
     }
+    bindings.sort_by_key(|(index, _, _)| *index);
+    for (_, raw_binding_str, read_only) in bindings {
+        // the format was already checked by `binding_validator`
+        let (host_path, guest_path) = split_binding(raw_binding_str).unwrap();
+        sandbox = match read_only {
+            true => sandbox.bind_read_only(host_path, guest_path),
+            false => sandbox.bind(host_path, guest_path),
+        };
+    }
 
     // option -w
-    if let Some(cwd) = matches.get_one::<String>("cwd") {
-        fs.set_cwd(cwd)?;
+    if let Some(cwd) = explicit_value(matches, "cwd") {
+        sandbox = sandbox.cwd(cwd);
     }
 
-    // command: collect into Vec<String>
-    let command: Vec<String> = matches
-        .get_many::<String>("command")
-        .map(|vals| vals.map(|s| s.clone()).collect())
-        .unwrap_or_else(|| vec!["/bin/sh".to_string()]);
+    if let Some(root_id) = explicit_flag(matches, "root_id", "no_root_id") {
+        sandbox = sandbox.root_id(root_id);
+    }
+    if let Some(link2symlink) = explicit_flag(matches, "link2symlink", "no_link2symlink") {
+        sandbox = sandbox.link2symlink(link2symlink);
+    }
+    if let Some(hostname) = matches.get_one::<String>("hostname") {
+        sandbox = sandbox.hostname(hostname);
+    }
+    if let Some(release) = matches.get_one::<String>("kernel_release") {
+        sandbox = sandbox.kernel_release(release);
+    }
+    if let Some(path) = matches.get_one::<String>("cpuinfo") {
+        sandbox = sandbox.cpuinfo(path);
+    }
+    for device in matches.get_many::<Device>("allow_device").unwrap_or_default() {
+        sandbox = sandbox.allow_device(*device);
+    }
+
+    // options -e, --unset-env and --clear-env
+    if let Some(clear) = explicit_flag(matches, "clear_env", "no_clear_env") {
+        sandbox = sandbox.clear_env(clear);
+    }
+    if let Some(assignments) = matches.get_many::<(String, String)>("env") {
+        for (key, value) in assignments {
+            sandbox = sandbox.env(key, value);
+        }
+    }
+    if let Some(names) = matches.get_many::<String>("unset_env") {
+        for name in names {
+            sandbox = sandbox.unset_env(name);
+        }
+    }
+
+    // option -v and its filters
+    if let Some(output) = matches.get_one::<String>("trace") {
+        sandbox = sandbox.trace(TraceOptions {
+            output: match output.as_str() {
+                "-" => None,
+                path => Some(PathBuf::from(path)),
+            },
+            syscalls: matches
+                .get_many::<String>("trace_filter")
+                .map(|items| items.cloned().collect())
+                .unwrap_or_default(),
+            pids: matches
+                .get_many::<i32>("trace_pid")
+                .map(|pids| pids.copied().collect())
+                .unwrap_or_default(),
+            timestamps: matches.get_flag("trace_timestamps"),
+        });
+    }
+
+    // option --audit
+    if let Some(path) = matches.get_one::<String>("audit") {
+        sandbox = sandbox.audit(path);
+    }
+
+    if matches.get_flag("subreaper") {
+        sandbox = sandbox.subreaper(true);
+    }
+
+    if matches.get_flag("no_path_cache") {
+        sandbox = sandbox.translation_cache(false);
+    }
+
+    // options --grace-period and --exit-policy (which `daemon` does not have)
+    if let Ok(Some(seconds)) = matches.try_get_one::<u32>("grace_period") {
+        sandbox = sandbox.grace_period(*seconds);
+    }
+    if let Ok(Some(policy)) = matches.try_get_one::<String>("exit_policy") {
+        sandbox = sandbox.exit_policy(match policy.as_str() {
+            "init" => ExitPolicy::Init,
+            _ => ExitPolicy::All,
+        });
+    }
+
+    // command (which `daemon` does not have, its commands come from `exec`)
+    if let Ok(Some(command)) = matches.try_get_many::<String>("command") {
+        sandbox = sandbox.command(command);
+    }
 
-    Ok((fs, command, config))
+    Ok(sandbox)
 }
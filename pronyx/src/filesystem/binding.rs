@@ -29,6 +29,9 @@ pub struct Binding {
     /// substitution.
     need_substitution: bool,
     _must_exist: bool,
+    /// The guest can't modify the files of this binding, see
+    /// `FileSystem::check_writable()`.
+    read_only: bool,
 }
 
 impl Binding {
@@ -47,9 +50,20 @@ impl Binding {
             guest: guest,
             need_substitution: need_substitution,
             _must_exist: must_exist,
+            read_only: false,
         }
     }
 
+    #[inline]
+    pub fn is_read_only(&self) -> bool {
+        self.read_only
+    }
+
+    #[inline]
+    pub fn set_read_only(&mut self, read_only: bool) {
+        self.read_only = read_only;
+    }
+
     #[inline]
     pub fn get_path(&self, side: Side) -> &PathBuf {
         match side {
@@ -0,0 +1,325 @@
+//! A cache of the translations of guest paths to host paths.
+//!
+//! Translating a path means a `lstat()` of each of its components, and a
+//! `readlink()` of each symlink met on the way. The result of a translation
+//! only depends on a few directory entries though: the ones of the symlinks
+//! which were followed, and the one of the final component. Each cached
+//! translation keeps these entries, and the identity of the directories which
+//! contain them:
+//!
+//! - our own changes of these entries (`rename()`, `unlink()`, `symlink()`...)
+//!   drop the translation, see `TranslationCache::invalidate()`;
+//! - the changes done by anyone else are found on lookup, since they update
+//!   the modification time of the directory of the changed entry (or, for a
+//!   change of one of its ancestors, make its path designate another
+//!   directory).
+//!
+//! A directory which was modified very recently is not trusted: a change in
+//! the same tick of a coarse file-system clock would go unnoticed.
+
+use std::collections::{HashMap, VecDeque};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use nix::sys::stat::{self, FileStat};
+
+/// Maximum number of cached translations.
+const CAPACITY: usize = 4096;
+
+/// Directories changed less than this number of seconds ago are not trusted.
+/// Some file-systems (FAT) have a 2-second granularity.
+const RACY_DELAY: i64 = 2;
+
+/// What a lookup depends on: the guest path, and whether its final component
+/// is dereferenced.
+type Key = (PathBuf, bool);
+
+#[derive(Debug)]
+struct Entry {
+    canonical_guest_path: PathBuf,
+    host_path: PathBuf,
+    /// Host paths of the directory entries the translation depends on.
+    dependencies: Vec<PathBuf>,
+    /// Directories of these entries, as they were when the translation was
+    /// done.
+    directories: Vec<DirectoryStamp>,
+}
+
+/// The identity and last change of a directory.
+#[derive(Debug, PartialEq)]
+struct DirectoryStamp {
+    path: PathBuf,
+    dev: u64,
+    ino: u64,
+    mtime: (i64, i64),
+    ctime: (i64, i64),
+}
+
+impl DirectoryStamp {
+    // The types of the fields of `stat` depend on the architecture.
+    #[allow(clippy::unnecessary_cast)]
+    fn from_stat(path: &Path, stat: &FileStat) -> DirectoryStamp {
+        DirectoryStamp {
+            path: path.to_path_buf(),
+            dev: stat.st_dev as u64,
+            ino: stat.st_ino as u64,
+            mtime: (stat.st_mtime as i64, stat.st_mtime_nsec as i64),
+            ctime: (stat.st_ctime as i64, stat.st_ctime_nsec as i64),
+        }
+    }
+
+    /// Stamp the directory `path`, unless it can't be trusted yet.
+    #[allow(clippy::unnecessary_cast)]
+    fn new(path: &Path, now: i64) -> Option<DirectoryStamp> {
+        let stat = stat::stat(path).ok()?;
+        // `ctime` is updated by any change of `mtime`, and can't be set back.
+        if now - (stat.st_ctime as i64) < RACY_DELAY {
+            return None;
+        }
+        Some(DirectoryStamp::from_stat(path, &stat))
+    }
+
+    fn is_unchanged(&self) -> bool {
+        match stat::stat(&self.path) {
+            Ok(stat) => DirectoryStamp::from_stat(&self.path, &stat) == *self,
+            Err(_) => false,
+        }
+    }
+}
+
+/// A bounded cache of `(canonical_guest_path, host_path)` translations, see
+/// the module documentation. The oldest translations are dropped first.
+#[derive(Debug, Default)]
+pub struct TranslationCache {
+    entries: HashMap<Key, Entry>,
+    /// Keys in insertion order, possibly with some which were invalidated.
+    order: VecDeque<Key>,
+}
+
+impl TranslationCache {
+    pub fn new() -> TranslationCache {
+        TranslationCache::default()
+    }
+
+    /// Number of cached translations.
+    #[cfg(test)]
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Get the translation of the absolute `guest_path`, if it is cached and
+    /// still valid.
+    pub fn lookup(&mut self, guest_path: &Path, deref_final: bool) -> Option<(PathBuf, PathBuf)> {
+        let key = (guest_path.to_path_buf(), deref_final);
+        let entry = self.entries.get(&key)?;
+
+        if entry.directories.iter().all(DirectoryStamp::is_unchanged) {
+            Some((entry.canonical_guest_path.clone(), entry.host_path.clone()))
+        } else {
+            self.entries.remove(&key);
+            None
+        }
+    }
+
+    /// Cache the translation of `guest_path` into `canonical_guest_path` and
+    /// `host_path`, which depends on the directory entries `dependencies`
+    /// (host paths). Nothing is cached if one of their directories can't be
+    /// trusted.
+    pub fn insert(
+        &mut self,
+        guest_path: &Path,
+        deref_final: bool,
+        canonical_guest_path: &Path,
+        host_path: &Path,
+        dependencies: Vec<PathBuf>,
+    ) {
+        let now = match SystemTime::now().duration_since(UNIX_EPOCH) {
+            Ok(now) => now.as_secs() as i64,
+            Err(_) => return,
+        };
+        let mut directories: Vec<DirectoryStamp> = vec![];
+        for directory in dependencies.iter().filter_map(|path| path.parent()) {
+            if directories.iter().any(|stamp| stamp.path == directory) {
+                continue;
+            }
+            match DirectoryStamp::new(directory, now) {
+                Some(stamp) => directories.push(stamp),
+                None => return,
+            }
+        }
+        if directories.is_empty() {
+            return;
+        }
+
+        if self.entries.len() >= CAPACITY {
+            self.evict();
+        }
+        let key = (guest_path.to_path_buf(), deref_final);
+        let entry = Entry {
+            canonical_guest_path: canonical_guest_path.to_path_buf(),
+            host_path: host_path.to_path_buf(),
+            dependencies,
+            directories,
+        };
+        if self.entries.insert(key.clone(), entry).is_none() {
+            self.order.push_back(key);
+        }
+        // Forget the keys of the translations which were invalidated.
+        if self.order.len() > 2 * CAPACITY {
+            let entries = &self.entries;
+            self.order.retain(|key| entries.contains_key(key));
+        }
+    }
+
+    /// Drop the translations which depend on the directory entry `host_path`,
+    /// or on one below it. To be called when it is created, removed or
+    /// renamed.
+    pub fn invalidate(&mut self, host_path: &Path) {
+        self.entries.retain(|_, entry| {
+            !entry.host_path.starts_with(host_path)
+                && !entry
+                    .dependencies
+                    .iter()
+                    .any(|dependency| dependency.starts_with(host_path))
+        });
+    }
+
+    /// Drop the oldest translation.
+    fn evict(&mut self) {
+        while let Some(key) = self.order.pop_front() {
+            if self.entries.remove(&key).is_some() {
+                break;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::os::unix::fs::symlink;
+
+    use nix::sys::stat::{utimensat, UtimensatFlags};
+    use nix::sys::time::TimeSpec;
+
+    use super::*;
+
+    /// Make a new, empty, test directory.
+    fn make_test_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir(&dir).unwrap();
+        dir
+    }
+
+    /// Let the directory `dir` be trusted, by waiting until its last change is
+    /// older than `RACY_DELAY`.
+    fn settle(dir: &Path) {
+        let stat = stat::stat(dir).unwrap();
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs() as i64;
+        let wait = RACY_DELAY - (now - stat.st_ctime as i64);
+        if wait >= 0 {
+            std::thread::sleep(std::time::Duration::from_secs(wait as u64 + 1));
+        }
+    }
+
+    #[test]
+    fn test_translation_cache_validation() {
+        let dir = make_test_dir("test_translation_cache_validation");
+        fs::create_dir(dir.join("target")).unwrap();
+        symlink(dir.join("target"), dir.join("link")).unwrap();
+        settle(&dir);
+
+        let mut cache = TranslationCache::new();
+        let guest_path = Path::new("/link/file");
+        let host_path = dir.join("target/file");
+        let dependencies = vec![dir.join("link"), host_path.clone()];
+        cache.insert(
+            guest_path,
+            true,
+            Path::new("/target/file"),
+            &host_path,
+            dependencies,
+        );
+        assert_eq!(cache.len(), 1);
+        assert_eq!(
+            cache.lookup(guest_path, true),
+            Some((PathBuf::from("/target/file"), host_path.clone()))
+        );
+        assert_eq!(cache.lookup(guest_path, false), None);
+
+        // An external change of the symlink is noticed, even if the time of
+        // the directory is set back.
+        let stat = stat::stat(&dir).unwrap();
+        fs::remove_file(dir.join("link")).unwrap();
+        symlink("elsewhere", dir.join("link")).unwrap();
+        utimensat(
+            None,
+            &dir,
+            &TimeSpec::new(stat.st_atime, stat.st_atime_nsec),
+            &TimeSpec::new(stat.st_mtime, stat.st_mtime_nsec),
+            UtimensatFlags::FollowSymlink,
+        )
+        .unwrap();
+        assert_eq!(cache.lookup(guest_path, true), None);
+        assert_eq!(cache.len(), 0);
+
+        // Directories which were just changed are not trusted.
+        let dependencies = vec![dir.join("link"), host_path.clone()];
+        cache.insert(
+            guest_path,
+            true,
+            Path::new("/target/file"),
+            &host_path,
+            dependencies,
+        );
+        assert_eq!(cache.len(), 0);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_translation_cache_invalidate() {
+        let dir = make_test_dir("test_translation_cache_invalidate");
+        fs::create_dir(dir.join("a")).unwrap();
+        fs::create_dir(dir.join("b")).unwrap();
+        symlink(dir.join("b"), dir.join("a/link")).unwrap();
+        settle(&dir);
+        settle(&dir.join("a"));
+
+        let mut cache = TranslationCache::new();
+        let insert = |cache: &mut TranslationCache, guest: &str, host: PathBuf, link: bool| {
+            let mut dependencies = vec![host.clone()];
+            if link {
+                dependencies.push(dir.join("a/link"));
+            }
+            cache.insert(
+                Path::new(guest),
+                false,
+                Path::new(guest),
+                &host,
+                dependencies,
+            );
+        };
+        insert(&mut cache, "/a/file", dir.join("a/file"), false);
+        insert(&mut cache, "/a/link/file", dir.join("b/file"), true);
+        insert(&mut cache, "/b/file", dir.join("b/file"), false);
+        assert_eq!(cache.len(), 3);
+
+        // Only the translations which went through the entry are dropped.
+        cache.invalidate(&dir.join("a/link"));
+        assert_eq!(cache.len(), 2);
+        assert!(cache.lookup(Path::new("/a/file"), false).is_some());
+        assert!(cache.lookup(Path::new("/b/file"), false).is_some());
+
+        // Including the ones below it.
+        cache.invalidate(&dir.join("a"));
+        assert_eq!(cache.len(), 1);
+        assert!(cache.lookup(Path::new("/b/file"), false).is_some());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
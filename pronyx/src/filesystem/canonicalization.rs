@@ -52,7 +52,20 @@ impl Canonicalizer for FileSystem {
     ///   to the host side path
     /// - A non-final component in path is not a directory.
     fn canonicalize<P: AsRef<Path>>(&self, guest_path: P, deref_final: bool) -> Result<PathBuf> {
-        let guest_path = guest_path.as_ref();
+        self.canonicalize_recording(guest_path.as_ref(), deref_final, None)
+    }
+}
+
+impl FileSystem {
+    /// Same as `Canonicalizer::canonicalize()`, but the host paths of the
+    /// directory entries the result depends on (the symlinks which were
+    /// followed and the final component) are pushed to `dependencies`.
+    pub(super) fn canonicalize_recording(
+        &self,
+        guest_path: &Path,
+        deref_final: bool,
+        mut dependencies: Option<&mut Vec<PathBuf>>,
+    ) -> Result<PathBuf> {
         // The `guest_path` must be absolute path
         if guest_path.is_relative() {
             return Err(Error::errno_with_msg(
@@ -94,6 +107,9 @@ impl Canonicalizer for FileSystem {
                     // Resolve bindings and add glue if necessary
                     // TODO: replace with substitute_intermediary_and_glue() when glue is supported.
                     let host_path = self.substitute(&guest_path_new, Side::Guest)?;
+                    if is_last_component && let Some(dependencies) = dependencies.as_mut() {
+                        dependencies.push(host_path.clone());
+                    }
 
                     let metadata = host_path.symlink_metadata();
 
@@ -122,6 +138,9 @@ impl Canonicalizer for FileSystem {
                         // we need to deref
                         // TODO: add test for this
                         let link_value = host_path.read_link()?;
+                        if !is_last_component && let Some(dependencies) = dependencies.as_mut() {
+                            dependencies.push(host_path.clone());
+                        }
                         let mut new_user_path = if link_value.is_absolute() {
                             // link_value is a absolute path, so we need to replace user_path
                             // with link_value first.
@@ -144,7 +163,7 @@ impl Canonicalizer for FileSystem {
                         }
                         // use new_user_path to call this function again and return
                         // TODO: Can be optimized by replacing `it`
-                        return self.canonicalize(&new_user_path, deref_final);
+                        return self.canonicalize_recording(&new_user_path, deref_final, dependencies);
                     }
                     // we cannot go through a path which is neither a directory nor a symlink
                     if !is_last_component
@@ -0,0 +1,357 @@
+//! Device files created by the fake root.
+//!
+//! An unprivileged user can't create character or block devices, which image
+//! builds and `MAKEDEV` scripts do. With `-0`, `mknod()` creates an empty
+//! regular file instead, and the device it stands for is recorded in the
+//! manifest of the rootfs (`<rootfs>.manifest.toml`, see the `rootfs`
+//! module), like the devices of an imported image:
+//!
+//! - `stat()` and the like report the device type and number of the file;
+//! - opening it opens the host device of the same number, if it is allowed
+//!   (see `DEFAULT_ALLOWED_DEVICES` and `--allow-device`), and fails with
+//!   `ENXIO` otherwise.
+//!
+//! The entries of the manifest follow the renames done by the guest and are
+//! dropped when it removes the file, like the emulated extended attributes.
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+use std::os::unix::fs::MetadataExt;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::{Mutex, MutexGuard};
+
+use nix::errno::Errno::{EINVAL, ENXIO};
+
+use super::xattr::rename_keys;
+use crate::errors::{Error, Result};
+use crate::rootfs::{DeviceKind, Manifest, ManifestFile};
+
+/// A character or block device, by number.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Device {
+    pub kind: DeviceKind,
+    pub major: u32,
+    pub minor: u32,
+}
+
+/// The host devices which can be opened through a device file of the guest
+/// by default: `null`, `zero`, `full`, `random`, `urandom` and `tty`.
+pub const DEFAULT_ALLOWED_DEVICES: &[Device] = &[
+    Device::char(1, 3),
+    Device::char(1, 5),
+    Device::char(1, 7),
+    Device::char(1, 8),
+    Device::char(1, 9),
+    Device::char(5, 0),
+];
+
+impl Device {
+    pub const fn char(major: u32, minor: u32) -> Device {
+        Device {
+            kind: DeviceKind::Char,
+            major,
+            minor,
+        }
+    }
+
+    /// The device of the file type `mode` (`S_IFCHR` or `S_IFBLK`) and number
+    /// `rdev`, as encoded by the kernel for `mknod()`, if it is one.
+    pub fn from_mknod(mode: u32, rdev: u32) -> Option<Device> {
+        let kind = match mode & libc::S_IFMT {
+            libc::S_IFCHR => DeviceKind::Char,
+            libc::S_IFBLK => DeviceKind::Block,
+            _ => return None,
+        };
+        // see `new_decode_dev()` in <linux/kdev_t.h>
+        Some(Device {
+            kind,
+            major: (rdev & 0xfff00) >> 8,
+            minor: (rdev & 0xff) | ((rdev >> 12) & 0xfff00),
+        })
+    }
+
+    /// The file type of the device (`S_IFCHR` or `S_IFBLK`).
+    pub fn file_type(&self) -> u32 {
+        match self.kind {
+            DeviceKind::Char => libc::S_IFCHR,
+            DeviceKind::Block => libc::S_IFBLK,
+        }
+    }
+
+    /// The path of the device on the host: its link in `/dev/char` or
+    /// `/dev/block`, or else the device file of `/dev` with this number
+    /// (there is no such link without udev, e.g. in a container).
+    pub fn host_path(&self) -> Option<PathBuf> {
+        let kind = match self.kind {
+            DeviceKind::Char => "char",
+            DeviceKind::Block => "block",
+        };
+        let link = format!("/dev/{}/{}:{}", kind, self.major, self.minor);
+        if let Ok(path) = fs::canonicalize(link) {
+            return Some(path);
+        }
+        let rdev = libc::makedev(self.major, self.minor);
+        fs::read_dir("/dev")
+            .ok()?
+            .filter_map(|entry| entry.ok())
+            .find(|entry| {
+                entry.metadata().is_ok_and(|metadata| {
+                    metadata.mode() & libc::S_IFMT == self.file_type() && metadata.rdev() == rdev
+                })
+            })
+            .map(|entry| entry.path())
+    }
+}
+
+impl fmt::Display for Device {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let kind = match self.kind {
+            DeviceKind::Char => 'c',
+            DeviceKind::Block => 'b',
+        };
+        write!(f, "{}:{}:{}", kind, self.major, self.minor)
+    }
+}
+
+/// Parse `c:major:minor` or `b:major:minor`.
+impl FromStr for Device {
+    type Err = Error;
+
+    fn from_str(value: &str) -> Result<Device> {
+        let invalid = || {
+            Error::errno_with_msg(
+                EINVAL,
+                format!("{:?}: expected c:major:minor or b:major:minor", value),
+            )
+        };
+        let mut parts = value.splitn(3, ':');
+        let kind = match parts.next() {
+            Some("c") => DeviceKind::Char,
+            Some("b") => DeviceKind::Block,
+            _ => return Err(invalid()),
+        };
+        let mut number = || {
+            parts
+                .next()
+                .and_then(|number| number.parse().ok())
+                .ok_or_else(invalid)
+        };
+        Ok(Device {
+            kind,
+            major: number()?,
+            minor: number()?,
+        })
+    }
+}
+
+/// Parse the device of `--allow-device`.
+pub fn device_validator(device: &str) -> std::result::Result<Device, String> {
+    device
+        .parse()
+        .map_err(|_| format!("{} should be: c:major:minor or b:major:minor", device))
+}
+
+/// A device file created by the guest, or imported with the rootfs.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DeviceNode {
+    pub device: Device,
+    /// Permission bits, when they differ from the ones of the host file.
+    pub mode: Option<u32>,
+}
+
+/// The device files of a rootfs, see the module documentation.
+#[derive(Debug)]
+pub struct DeviceStore {
+    /// Host path of the rootfs.
+    root: PathBuf,
+    /// Whether the manifest is written when it changes.
+    save: bool,
+    /// The entries of the manifest, by path relative to the rootfs (or host
+    /// path out of it). Not all of them are devices.
+    files: Mutex<BTreeMap<PathBuf, ManifestFile>>,
+    /// The host devices which can be opened.
+    allowed: Vec<Device>,
+}
+
+impl DeviceStore {
+    /// The store of the rootfs `root` (a canonical host path), with the
+    /// devices of its manifest, if any, of which the `allowed` ones can be
+    /// opened.
+    ///
+    /// The host root has no manifest: its devices are only kept in memory.
+    pub fn load(root: &Path, allowed: Vec<Device>) -> Result<DeviceStore> {
+        let save = Manifest::is_supported(root);
+        let files = match save {
+            true => Manifest::load(root)?
+                .files
+                .into_iter()
+                .map(|file| (file.path.clone(), file))
+                .collect(),
+            false => BTreeMap::new(),
+        };
+        Ok(DeviceStore {
+            root: root.to_path_buf(),
+            save,
+            files: Mutex::new(files),
+            allowed,
+        })
+    }
+
+    /// A store of the rootfs `root` whose manifest is not written.
+    #[cfg(test)]
+    pub fn in_memory(root: &Path, allowed: Vec<Device>) -> DeviceStore {
+        DeviceStore {
+            root: root.to_path_buf(),
+            save: false,
+            files: Mutex::new(BTreeMap::new()),
+            allowed,
+        }
+    }
+
+    /// The key of the canonical host path `host_path`, as in the manifest.
+    fn key(&self, host_path: &Path) -> PathBuf {
+        match host_path.strip_prefix(&self.root) {
+            Ok(relative) => relative.to_path_buf(),
+            Err(_) => host_path.to_path_buf(),
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, BTreeMap<PathBuf, ManifestFile>> {
+        self.files.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    /// Write the manifest. A failure is only reported, the devices are still
+    /// served from memory.
+    fn save(&self, files: &BTreeMap<PathBuf, ManifestFile>) {
+        if !self.save {
+            return;
+        }
+        let result = Manifest::update(&self.root, |manifest| {
+            manifest.files = files.values().cloned().collect();
+        });
+        if let Err(error) = result {
+            warn!("cannot save device files: {}", error);
+        }
+    }
+
+    /// The device `host_path` stands for, if any.
+    pub fn get(&self, host_path: &Path) -> Option<DeviceNode> {
+        let files = self.lock();
+        let file = files.get(&self.key(host_path))?;
+        let device = Device {
+            kind: file.device?,
+            major: file.major,
+            minor: file.minor,
+        };
+        Some(DeviceNode {
+            device,
+            mode: file.mode().ok().flatten(),
+        })
+    }
+
+    /// Record that `host_path`, whose permission bits are `host_mode`, stands
+    /// for `device` with the permission bits `mode`.
+    pub fn record(&self, host_path: &Path, device: Device, mode: u32, host_mode: u32) {
+        let mut files = self.lock();
+        let key = self.key(host_path);
+        let file = ManifestFile {
+            path: key.clone(),
+            uid: 0,
+            gid: 0,
+            mode: (mode != host_mode).then(|| format!("{:04o}", mode)),
+            device: Some(device.kind),
+            major: device.major,
+            minor: device.minor,
+        };
+        files.insert(key, file);
+        self.save(&files);
+    }
+
+    /// The host path to open instead of a file standing for `device`.
+    pub fn open_path(&self, device: Device) -> Result<PathBuf> {
+        if !self.allowed.contains(&device) {
+            return Err(Error::errno_with_msg(
+                ENXIO,
+                format!("device {} is not allowed", device),
+            ));
+        }
+        device
+            .host_path()
+            .ok_or_else(|| Error::errno_with_msg(ENXIO, format!("no host device {}", device)))
+    }
+
+    /// Forget `host_path`, which was removed.
+    pub fn forget(&self, host_path: &Path) {
+        let mut files = self.lock();
+        if files.remove(&self.key(host_path)).is_some() {
+            self.save(&files);
+        }
+    }
+
+    /// Move the entries of `old_path` and of the files it contains to
+    /// `new_path`, which it was renamed to.
+    pub fn rename(&self, old_path: &Path, new_path: &Path) {
+        let mut files = self.lock();
+        let new_key = self.key(new_path);
+        if rename_keys(&mut files, &self.key(old_path), &new_key) {
+            for (key, file) in files.iter_mut().filter(|(key, _)| key.starts_with(&new_key)) {
+                file.path = key.clone();
+            }
+            self.save(&files);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_device_parse() {
+        assert_eq!("c:1:3".parse::<Device>().unwrap(), Device::char(1, 3));
+        let loop0 = "b:7:0".parse::<Device>().unwrap();
+        assert_eq!(loop0.kind, DeviceKind::Block);
+        assert_eq!(loop0.to_string(), "b:7:0");
+        assert!("c:1".parse::<Device>().is_err());
+        assert!("p:1:3".parse::<Device>().is_err());
+    }
+
+    #[test]
+    fn test_device_from_mknod() {
+        let null = Device::from_mknod(libc::S_IFCHR | 0o666, 0x103).unwrap();
+        assert_eq!(null, Device::char(1, 3));
+        let big = Device::from_mknod(libc::S_IFBLK, libc::makedev(259, 300) as u32).unwrap();
+        assert_eq!((big.kind, big.major, big.minor), (DeviceKind::Block, 259, 300));
+        assert_eq!(Device::from_mknod(libc::S_IFIFO, 0), None);
+    }
+
+    #[test]
+    fn test_device_store() {
+        let store = DeviceStore::in_memory(Path::new("/rootfs"), vec![Device::char(1, 3)]);
+        let null = Path::new("/rootfs/dev/null");
+        assert_eq!(store.get(null), None);
+
+        store.record(null, Device::char(1, 3), 0o666, 0o666);
+        let node = store.get(null).unwrap();
+        assert_eq!((node.device, node.mode), (Device::char(1, 3), None));
+        assert_eq!(store.open_path(node.device).unwrap(), Path::new("/dev/null"));
+        assert_eq!(Device::char(1, 3).host_path().unwrap(), Path::new("/dev/null"));
+
+        let sda = Path::new("/rootfs/dev/sda");
+        store.record(sda, "b:8:0".parse().unwrap(), 0o060, 0o660);
+        assert_eq!(store.get(sda).unwrap().mode, Some(0o060));
+        let error = store.open_path(store.get(sda).unwrap().device).unwrap_err();
+        assert_eq!(error.get_errno(), ENXIO);
+
+        store.rename(Path::new("/rootfs/dev"), Path::new("/rootfs/devices"));
+        assert_eq!(store.get(null), None);
+        let null = Path::new("/rootfs/devices/null");
+        assert_eq!(store.get(null).unwrap().device, Device::char(1, 3));
+        assert_eq!(store.lock()[Path::new("devices/null")].path, Path::new("devices/null"));
+
+        store.forget(null);
+        assert_eq!(store.get(null), None);
+    }
+}
@@ -1,4 +1,5 @@
 use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, MutexGuard};
 
 use nix::sys::{self, stat::Mode};
 use nix::unistd::{self, AccessFlags};
@@ -7,6 +8,10 @@ use crate::errors::*;
 use crate::filesystem::binding::Side::Host;
 use crate::filesystem::binding::{Binding, Side};
 
+use super::cache::TranslationCache;
+use super::device::DeviceStore;
+use super::virtual_file::VirtualFiles;
+use super::xattr::XattrStore;
 use super::{Canonicalizer, Substitutor, Translator};
 
 /// The file-system information associated with one or more tracee, which
@@ -30,6 +35,19 @@ pub struct FileSystem {
     root: PathBuf,
     /// Use for glue (//TODO: explain when implemented)
     glue_type: Mode,
+    /// Cache of path translations, shared with the copies of this instance
+    /// (hence by the tracees of a session) as long as they have the same
+    /// bindings. `None` if disabled.
+    translations: Option<Arc<Mutex<TranslationCache>>>,
+    /// Guest files generated by pronyx, shared with the copies of this
+    /// instance. `None` if disabled.
+    virtual_files: Option<Arc<VirtualFiles>>,
+    /// Extended attributes emulated for the fake root, shared with the copies
+    /// of this instance. `None` if disabled.
+    xattrs: Option<Arc<XattrStore>>,
+    /// Device files emulated for the fake root, shared with the copies of
+    /// this instance. `None` if disabled.
+    devices: Option<Arc<DeviceStore>>,
 }
 
 impl FileSystem {
@@ -39,6 +57,10 @@ impl FileSystem {
             cwd: PathBuf::from("/"),
             root: PathBuf::from("/"),
             glue_type: Mode::empty(),
+            translations: Some(Arc::new(Mutex::new(TranslationCache::new()))),
+            virtual_files: None,
+            xattrs: None,
+            devices: None,
         }
     }
 
@@ -75,9 +97,149 @@ impl FileSystem {
             0,
             Binding::new(canonical_host_path, canonical_guest_path, true),
         );
+        // The translations done so far, and the ones of the copies of this
+        // instance, don't know about this binding.
+        if self.translations.is_some() {
+            self.set_translation_cache(true);
+        }
         Ok(())
     }
 
+    /// Like `add_binding()`, but the guest can't modify the files of this
+    /// binding: see `check_writable()`.
+    pub fn add_read_only_binding<P1, P2>(&mut self, host_path: P1, guest_path: P2) -> Result<()>
+    where
+        P1: AsRef<Path>,
+        P2: AsRef<Path>,
+    {
+        self.add_binding(host_path, guest_path)?;
+        self.bindings[0].set_read_only(true);
+        Ok(())
+    }
+
+    /// Whether some bindings are read-only.
+    pub fn has_read_only_bindings(&self) -> bool {
+        self.bindings.iter().any(Binding::is_read_only)
+    }
+
+    /// Whether the canonical guest path `guest_path` is in a read-only
+    /// binding.
+    pub fn is_read_only(&self, guest_path: &Path) -> bool {
+        self.get_first_appropriate_binding(guest_path, Side::Guest)
+            .is_some_and(|binding| binding.is_read_only())
+    }
+
+    /// Fail with `EROFS` if the canonical guest path `guest_path`, which is
+    /// about to be created, modified or removed, is in a read-only binding,
+    /// as the kernel does on a read-only mount.
+    pub fn check_writable(&self, guest_path: &Path) -> Result<()> {
+        match self.is_read_only(guest_path) {
+            true => Err(Error::errno_with_msg(
+                Errno::EROFS,
+                format!("{:?} is in a read-only binding", guest_path),
+            )),
+            false => Ok(()),
+        }
+    }
+
+    /// Fail with `EXDEV` if the canonical guest path `guest_path`, about to be
+    /// hard linked, is in a read-only binding: the file could be modified
+    /// through the new link. The kernel fails alike for links across mounts.
+    pub fn check_linkable(&self, guest_path: &Path) -> Result<()> {
+        match self.is_read_only(guest_path) {
+            true => Err(Error::errno_with_msg(
+                Errno::EXDEV,
+                format!("cannot link {:?}, which is in a read-only binding", guest_path),
+            )),
+            false => Ok(()),
+        }
+    }
+
+    /// Enable or disable the cache of path translations (enabled by default),
+    /// see the `cache` module. Enabling it starts with an empty cache, which
+    /// is not shared with the copies of this instance.
+    pub fn set_translation_cache(&mut self, enabled: bool) {
+        self.translations = match enabled {
+            true => Some(Arc::new(Mutex::new(TranslationCache::new()))),
+            false => None,
+        };
+    }
+
+    /// The cache of path translations, if enabled.
+    pub(super) fn translation_cache(&self) -> Option<MutexGuard<'_, TranslationCache>> {
+        let cache = self.translations.as_ref()?;
+        // A panic while the cache was locked can't leave it inconsistent.
+        Some(cache.lock().unwrap_or_else(|error| error.into_inner()))
+    }
+
+    /// Set the guest files generated by pronyx (none by default), see the
+    /// `virtual_file` module.
+    pub fn set_virtual_files(&mut self, virtual_files: Option<VirtualFiles>) {
+        self.virtual_files = virtual_files.map(Arc::new);
+    }
+
+    /// The guest files generated by pronyx, if any.
+    pub fn virtual_files(&self) -> Option<&VirtualFiles> {
+        self.virtual_files.as_deref()
+    }
+
+    /// Set the store of the emulated extended attributes, see the `xattr`
+    /// module.
+    pub fn set_xattrs(&mut self, xattrs: Option<XattrStore>) {
+        self.xattrs = xattrs.map(Arc::new);
+    }
+
+    /// The emulated extended attributes, if enabled.
+    pub fn xattrs(&self) -> Option<&XattrStore> {
+        self.xattrs.as_deref()
+    }
+
+    /// Set the store of the emulated device files, see the `device` module.
+    pub fn set_devices(&mut self, devices: Option<DeviceStore>) {
+        self.devices = devices.map(Arc::new);
+    }
+
+    /// The emulated device files, if enabled.
+    pub fn devices(&self) -> Option<&DeviceStore> {
+        self.devices.as_deref()
+    }
+
+    /// Forget what is emulated about `host_path` (extended attributes,
+    /// device), which was removed.
+    pub fn forget_emulated(&self, host_path: &Path) {
+        if let Some(xattrs) = self.xattrs() {
+            xattrs.forget(host_path);
+        }
+        if let Some(devices) = self.devices() {
+            devices.forget(host_path);
+        }
+    }
+
+    /// Move what is emulated about `old_host_path` and the files it contains
+    /// to `new_host_path`, which it was renamed to.
+    pub fn rename_emulated(&self, old_host_path: &Path, new_host_path: &Path) {
+        if let Some(xattrs) = self.xattrs() {
+            xattrs.rename(old_host_path, new_host_path);
+        }
+        if let Some(devices) = self.devices() {
+            devices.rename(old_host_path, new_host_path);
+        }
+    }
+
+    /// The bindings, the most recent first (the first one which applies to a
+    /// path wins). The root binding is the last one.
+    pub fn get_bindings(&self) -> &[Binding] {
+        &self.bindings
+    }
+
+    /// Drop the cached translations which depend on `host_path`, which is
+    /// about to be created, removed or renamed.
+    pub fn forget_translations(&self, host_path: &Path) {
+        if let Some(mut cache) = self.translation_cache() {
+            cache.invalidate(host_path);
+        }
+    }
+
     #[inline]
     /// Checks if the translated `host_path` belongs to the guest rootfs,
     /// that is, if it isn't from a binding.
@@ -128,6 +290,35 @@ impl FileSystem {
         Ok(())
     }
 
+    /// Search `command` in the guest file-system, the way `execvp()` would do
+    /// with `search_path` as `PATH`. Each candidate is translated, so that
+    /// the host's `PATH` and file-system are never consulted.
+    ///
+    /// A command containing a slash is returned as-is. The result is a guest
+    /// side path.
+    pub fn which(&self, command: &str, search_path: &str) -> Result<PathBuf> {
+        if command.contains('/') {
+            return Ok(PathBuf::from(command));
+        }
+        if !command.is_empty() {
+            for dir in search_path.split(':') {
+                // An empty entry means the current working directory.
+                let dir = if dir.is_empty() { "." } else { dir };
+                let guest_path = Path::new(dir).join(command);
+                if let Ok((_, host_path)) = self.translate_path(&guest_path, true)
+                    && host_path.is_file()
+                    && Self::check_host_path_executable(&host_path).is_ok()
+                {
+                    return Ok(guest_path);
+                }
+            }
+        }
+        Err(Error::errno_with_msg(
+            ENOENT,
+            format!("{}: command not found in the guest PATH", command),
+        ))
+    }
+
     /// Get current work directory (cwd)
     /// This function will return a guest side path, which is always canonical.
     pub fn get_cwd(&self) -> &Path {
@@ -326,12 +517,50 @@ mod tests {
         // ); // same on the other side
     }
 
+    #[test]
+    fn test_fs_read_only_binding() {
+        let mut fs = FileSystem::with_root("/").unwrap();
+        fs.add_read_only_binding("/etc", "/tmp").unwrap();
+        assert!(fs.has_read_only_bindings());
+        assert!(fs.is_read_only(Path::new("/tmp/passwd")));
+        assert!(!fs.is_read_only(Path::new("/etc/passwd")));
+        assert_eq!(
+            fs.check_writable(Path::new("/tmp")).unwrap_err().get_errno(),
+            Errno::EROFS
+        );
+        fs.check_writable(Path::new("/etc")).unwrap();
+        assert_eq!(
+            fs.check_linkable(Path::new("/tmp/passwd")).unwrap_err().get_errno(),
+            Errno::EXDEV
+        );
+        fs.check_linkable(Path::new("/etc/passwd")).unwrap();
+
+        // a more recent binding takes precedence
+        fs.add_binding("/bin", "/tmp").unwrap();
+        assert!(!fs.is_read_only(Path::new("/tmp")));
+    }
+
     #[test]
     fn test_fs_is_path_executable() {
         assert!(FileSystem::check_host_path_executable(&PathBuf::from("/bin/sleep")).is_ok());
         assert!(FileSystem::check_host_path_executable(&PathBuf::from("/../sleep")).is_err());
     }
 
+    #[test]
+    fn test_fs_which() -> Result<()> {
+        let mut fs = FileSystem::with_root("/")?;
+        assert_eq!(fs.which("sleep", "/nonexistent:/bin")?, PathBuf::from("/bin/sleep"));
+        assert_eq!(fs.which("./sleep", "/bin")?, PathBuf::from("./sleep"));
+        assert_eq!(fs.which("sleep", "/nonexistent").unwrap_err().get_errno(), ENOENT);
+        // directories are not executables
+        assert!(fs.which("bin", "/").is_err());
+
+        // the search is done through the bindings
+        fs.add_binding("/bin", "/tmp")?;
+        assert_eq!(fs.which("sleep", "/tmp")?, PathBuf::from("/tmp/sleep"));
+        Ok(())
+    }
+
     /// Unit test for `FileSystem::is_path_canonical()`
     #[test]
     fn test_fs_is_path_canonical() -> Result<()> {
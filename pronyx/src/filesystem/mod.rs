@@ -1,12 +1,19 @@
 pub mod binding;
+pub mod cache;
 pub mod canonicalization;
+pub mod device;
 pub mod ext;
 mod fs;
+pub mod passwd;
+pub mod proc;
 pub mod readers;
 pub mod substitution;
+pub mod synthetic;
 pub mod temp;
 mod translation;
 pub mod validation;
+pub mod virtual_file;
+pub mod xattr;
 
 pub use self::canonicalization::Canonicalizer;
 pub use self::fs::FileSystem;
@@ -0,0 +1,83 @@
+use std::path::Path;
+
+use crate::errors::*;
+use crate::filesystem::{FileSystem, Translator};
+
+/// One entry of a `passwd(5)` file.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PasswdEntry {
+    pub name: String,
+    pub uid: u32,
+    pub gid: u32,
+    pub gecos: String,
+    pub home: String,
+    pub shell: String,
+}
+
+impl PasswdEntry {
+    /// Parse one line of a `passwd(5)` file. Comments, blank lines and
+    /// malformed lines give `None`.
+    pub fn parse(line: &str) -> Option<PasswdEntry> {
+        if line.trim().is_empty() || line.starts_with('#') {
+            return None;
+        }
+        let fields: Vec<&str> = line.split(':').collect();
+        if fields.len() != 7 {
+            return None;
+        }
+        Some(PasswdEntry {
+            name: fields[0].to_string(),
+            uid: fields[2].parse().ok()?,
+            gid: fields[3].parse().ok()?,
+            gecos: fields[4].to_string(),
+            home: fields[5].to_string(),
+            shell: fields[6].to_string(),
+        })
+    }
+}
+
+/// Parse the content of a `passwd(5)` file, skipping invalid lines.
+pub fn parse_passwd(content: &str) -> Vec<PasswdEntry> {
+    content.lines().filter_map(PasswdEntry::parse).collect()
+}
+
+/// Look up `uid` in the guest's `/etc/passwd`, going through path translation
+/// so that bindings are honored.
+pub fn lookup_guest_user(fs: &FileSystem, uid: u32) -> Result<Option<PasswdEntry>> {
+    let host_path = fs.translate_path(Path::new("/etc/passwd"), true)?.1;
+    let content = std::fs::read_to_string(host_path)?;
+    Ok(parse_passwd(&content)
+        .into_iter()
+        .find(|entry| entry.uid == uid))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_passwd() {
+        let entries = parse_passwd(
+            "# comment\n\
+             root:x:0:0:root:/root:/bin/sh\n\
+             \n\
+             broken:x:1\n\
+             user:x:1000:100:Some User,,,:/home/user:/bin/bash\n\
+             nan:x:a:b:::\n",
+        );
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].name, "root");
+        assert_eq!(entries[0].home, "/root");
+        assert_eq!(entries[1].uid, 1000);
+        assert_eq!(entries[1].gid, 100);
+        assert_eq!(entries[1].gecos, "Some User,,,");
+        assert_eq!(entries[1].shell, "/bin/bash");
+    }
+
+    #[test]
+    fn test_lookup_guest_user() {
+        let fs = FileSystem::with_root("/").unwrap();
+        let root = lookup_guest_user(&fs, 0).unwrap().unwrap();
+        assert_eq!(root.uid, 0);
+    }
+}
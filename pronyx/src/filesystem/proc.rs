@@ -0,0 +1,659 @@
+//! Virtual files of `/proc`, see [`VirtualFile`].
+//!
+//! The mount points of the host mean nothing in a box: `/proc/mounts` and
+//! `/proc/<pid>/mountinfo` list the bindings instead, as the mounts they
+//! emulate. `/proc/version` reports the kernel release set with `-k`, and
+//! `/proc/cpuinfo` can be replaced by a file of the host.
+//!
+//! The files mapped by the tracees are listed with guest paths in their
+//! `/proc/<pid>/maps` and `smaps`, where the loader of pronyx is replaced by
+//! the executable it loaded, see [`MappedFiles`].
+
+use std::fs;
+use std::io;
+use std::os::unix::ffi::OsStrExt;
+use std::mem::size_of;
+use std::path::{Component, Path, PathBuf};
+
+use nix::unistd::Pid;
+
+use crate::errors::{Result, WithContext};
+use crate::filesystem::binding::Side::{Guest, Host};
+use crate::filesystem::virtual_file::VirtualFile;
+use crate::filesystem::{FileSystem, Translator};
+use crate::process::proot::is_traced_by_us;
+use crate::register::{memory, Word};
+
+/// Mount ID of the first binding in `mountinfo`. The one below is the parent
+/// of the root binding, which isn't listed, as the one of a real root.
+const FIRST_MOUNT_ID: usize = 1000;
+
+/// A mount of the host, from `/proc/self/mountinfo`.
+#[derive(Debug, Clone, PartialEq)]
+struct HostMount {
+    mount_point: PathBuf,
+    /// Path of the mount point in its file-system.
+    root: PathBuf,
+    /// `major:minor`.
+    device: String,
+    options: String,
+    fstype: String,
+    source: String,
+    super_options: String,
+}
+
+/// A binding, as a mount of the guest.
+#[derive(Debug)]
+struct GuestMount<'a> {
+    guest: &'a Path,
+    host: &'a Path,
+    /// The mount of the host which contains `host`, if known.
+    mount: Option<&'a HostMount>,
+    /// The binding is read-only, whatever the mount of the host.
+    read_only: bool,
+}
+
+/// Decode the `\ooo` escapes of a field of `mountinfo`.
+fn unescape(field: &str) -> String {
+    let bytes = field.as_bytes();
+    let mut decoded = vec![];
+    let mut i = 0;
+    while i < bytes.len() {
+        let escape = bytes.get(i + 1..i + 4).filter(|digits| {
+            bytes[i] == b'\\' && digits.iter().all(|digit| (b'0'..=b'7').contains(digit))
+        });
+        match escape
+            .and_then(|digits| u8::from_str_radix(std::str::from_utf8(digits).ok()?, 8).ok())
+        {
+            Some(byte) => {
+                decoded.push(byte);
+                i += 4;
+            }
+            None => {
+                decoded.push(bytes[i]);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+/// Escape a path for a field of `mounts` or `mountinfo`.
+fn escape(path: &Path) -> String {
+    let mut escaped = String::new();
+    for c in path.to_string_lossy().chars() {
+        match c {
+            ' ' | '\t' | '\n' | '\\' => escaped.push_str(&format!("\\{:03o}", c as u32)),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Parse a `mountinfo` file, see `proc(5)`.
+fn parse_mountinfo(content: &str) -> Vec<HostMount> {
+    content
+        .lines()
+        .filter_map(|line| {
+            let (mount, super_block) = line.split_once(" - ")?;
+            let fields: Vec<&str> = mount.split(' ').collect();
+            let super_fields: Vec<&str> = super_block.split(' ').collect();
+            if fields.len() < 6 || super_fields.len() < 3 {
+                return None;
+            }
+            Some(HostMount {
+                mount_point: PathBuf::from(unescape(fields[4])),
+                root: PathBuf::from(unescape(fields[3])),
+                device: fields[2].to_string(),
+                options: fields[5].to_string(),
+                fstype: super_fields[0].to_string(),
+                source: super_fields[1].to_string(),
+                super_options: super_fields[2].to_string(),
+            })
+        })
+        .collect()
+}
+
+/// The mount of the host which contains `path`: the deepest one, and among
+/// several ones on the same mount point, the last one which hides the others.
+fn find_mount<'a>(mounts: &'a [HostMount], path: &Path) -> Option<&'a HostMount> {
+    mounts
+        .iter()
+        .filter(|mount| path.starts_with(&mount.mount_point))
+        .max_by_key(|mount| mount.mount_point.components().count())
+}
+
+/// The bindings of `fs`, parents first, as mounts of the guest.
+fn guest_mounts<'a>(fs: &'a FileSystem, host_mounts: &'a [HostMount]) -> Vec<GuestMount<'a>> {
+    let mut mounts: Vec<GuestMount> = vec![];
+    // The first binding of a guest path hides the other ones.
+    for binding in fs.get_bindings() {
+        let guest = binding.get_path(Guest).as_path();
+        if mounts.iter().all(|mount| mount.guest != guest) {
+            let host = binding.get_path(Host).as_path();
+            let mount = find_mount(host_mounts, host);
+            mounts.push(GuestMount {
+                guest,
+                host,
+                mount,
+                read_only: binding.is_read_only(),
+            });
+        }
+    }
+    mounts.sort_by(|a, b| a.guest.cmp(b.guest));
+    mounts
+}
+
+/// The options of a mount of the host, which starts with its read-only flag,
+/// for a binding.
+fn mount_options(options: &str, read_only: bool) -> String {
+    match options.strip_prefix("rw") {
+        Some(rest) if read_only => format!("ro{}", rest),
+        _ => options.to_string(),
+    }
+}
+
+/// Format `mounts` as `/proc/mounts` does.
+fn format_mounts(mounts: &[GuestMount]) -> String {
+    let mut content = String::new();
+    for mount in mounts {
+        let (source, fstype, options) = match mount.mount {
+            Some(host_mount) => {
+                // The options of the super block are merged in, except the
+                // read-only flag, which is the one of the mount.
+                let mut options = mount_options(&host_mount.options, mount.read_only);
+                for option in host_mount.super_options.split(',') {
+                    if option != "rw" && option != "ro" {
+                        options.push(',');
+                        options.push_str(option);
+                    }
+                }
+                (
+                    host_mount.source.as_str(),
+                    host_mount.fstype.as_str(),
+                    options,
+                )
+            }
+            None => ("none", "none", mount_options("rw", mount.read_only)),
+        };
+        content.push_str(&format!(
+            "{} {} {} {} 0 0\n",
+            source,
+            escape(mount.guest),
+            fstype,
+            options
+        ));
+    }
+    content
+}
+
+/// Format `mounts` as `/proc/<pid>/mountinfo` does.
+fn format_mountinfo(mounts: &[GuestMount]) -> String {
+    let mut content = String::new();
+    for (index, mount) in mounts.iter().enumerate() {
+        // The closest binding above this one, which comes before it.
+        let parent = mounts[..index]
+            .iter()
+            .rposition(|parent| mount.guest.starts_with(parent.guest))
+            .map_or(FIRST_MOUNT_ID - 1, |parent| FIRST_MOUNT_ID + parent);
+        let line = match mount.mount {
+            Some(host_mount) => {
+                let root = match mount.host.strip_prefix(&host_mount.mount_point) {
+                    Ok(suffix) => host_mount.root.join(suffix),
+                    Err(_) => host_mount.root.clone(),
+                };
+                format!(
+                    "{} {} {} {} {} {} - {} {} {}",
+                    FIRST_MOUNT_ID + index,
+                    parent,
+                    host_mount.device,
+                    escape(&root),
+                    escape(mount.guest),
+                    mount_options(&host_mount.options, mount.read_only),
+                    host_mount.fstype,
+                    host_mount.source,
+                    host_mount.super_options
+                )
+            }
+            None => format!(
+                "{} {} 0:0 / {} {} - none none rw",
+                FIRST_MOUNT_ID + index,
+                parent,
+                escape(mount.guest),
+                mount_options("rw", mount.read_only)
+            ),
+        };
+        content.push_str(&line);
+        content.push('\n');
+    }
+    content
+}
+
+/// The name of the file of `/proc/<pid>`, `/proc/<pid>/task/<tid>` or
+/// `/proc` (with `<pid>` also `self` or `thread-self`) which is `guest_path`.
+fn process_file(guest_path: &Path) -> Option<&str> {
+    let components: Vec<&str> = guest_path
+        .strip_prefix("/proc")
+        .ok()?
+        .components()
+        .map(|component| match component {
+            Component::Normal(name) => name.to_str().unwrap_or_default(),
+            _ => "",
+        })
+        .collect();
+    let is_id = |name: &str| !name.is_empty() && name.bytes().all(|c| c.is_ascii_digit());
+    let is_process = |name: &str| is_id(name) || name == "self" || name == "thread-self";
+    match components[..] {
+        [name] => Some(name),
+        [process, name] if is_process(process) => Some(name),
+        [process, "task", thread, name] if is_process(process) && is_id(thread) => Some(name),
+        _ => None,
+    }
+}
+
+/// The process of `/proc/<pid>/...`, `self` and `thread-self` excluded.
+pub fn process_id(guest_path: &Path) -> Option<Pid> {
+    let name = guest_path.strip_prefix("/proc").ok()?.iter().next()?.to_str()?;
+    name.parse().ok().map(Pid::from_raw)
+}
+
+/// `/proc/mounts` and `/proc/<pid>/mountinfo`, generated from the bindings.
+#[derive(Debug)]
+pub struct Mounts;
+
+impl VirtualFile for Mounts {
+    fn provides(&self, guest_path: &Path) -> bool {
+        matches!(process_file(guest_path), Some("mounts" | "mountinfo"))
+    }
+
+    fn generate(
+        &self,
+        fs: &FileSystem,
+        guest_path: &Path,
+        host_path: &Path,
+    ) -> Result<Option<Vec<u8>>> {
+        // There is no `/proc` in the box.
+        if !host_path.exists() {
+            return Ok(None);
+        }
+        let host_mounts = parse_mountinfo(&fs::read_to_string("/proc/self/mountinfo")?);
+        let mounts = guest_mounts(fs, &host_mounts);
+        let content = match process_file(guest_path) {
+            Some("mountinfo") => format_mountinfo(&mounts),
+            _ => format_mounts(&mounts),
+        };
+        Ok(Some(content.into_bytes()))
+    }
+}
+
+/// `/proc/cpuinfo`, replaced by a file of the host.
+#[derive(Debug)]
+pub struct CpuInfo {
+    path: PathBuf,
+}
+
+impl CpuInfo {
+    pub fn new<P: Into<PathBuf>>(path: P) -> CpuInfo {
+        CpuInfo { path: path.into() }
+    }
+}
+
+impl VirtualFile for CpuInfo {
+    fn provides(&self, guest_path: &Path) -> bool {
+        guest_path == Path::new("/proc/cpuinfo")
+    }
+
+    fn generate(&self, _fs: &FileSystem, _: &Path, _: &Path) -> Result<Option<Vec<u8>>> {
+        let content = fs::read(&self.path)
+            .with_context(|| format!("{}: cannot read cpuinfo", self.path.display()))?;
+        Ok(Some(content))
+    }
+}
+
+/// `/proc/version` and `/proc/sys/kernel/osrelease`, with the kernel release
+/// reported by `uname()`.
+#[derive(Debug)]
+pub struct KernelVersion {
+    release: String,
+}
+
+impl KernelVersion {
+    pub fn new<S: Into<String>>(release: S) -> KernelVersion {
+        KernelVersion {
+            release: release.into(),
+        }
+    }
+}
+
+/// Replace the release in `version`, formatted as `/proc/version`.
+fn with_release(version: &str, release: &str) -> String {
+    const PREFIX: &str = "Linux version ";
+    match version.strip_prefix(PREFIX) {
+        Some(rest) => {
+            let end = rest.find(' ').unwrap_or(rest.len());
+            format!("{}{}{}", PREFIX, release, &rest[end..])
+        }
+        None => version.to_string(),
+    }
+}
+
+impl VirtualFile for KernelVersion {
+    fn provides(&self, guest_path: &Path) -> bool {
+        guest_path == Path::new("/proc/version")
+            || guest_path == Path::new("/proc/sys/kernel/osrelease")
+    }
+
+    fn generate(
+        &self,
+        _fs: &FileSystem,
+        guest_path: &Path,
+        host_path: &Path,
+    ) -> Result<Option<Vec<u8>>> {
+        let content = match fs::read_to_string(host_path) {
+            Ok(content) => content,
+            // There is no `/proc` in the box.
+            Err(error) if error.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(error) => return Err(error.into()),
+        };
+        let content = match guest_path.file_name().and_then(|name| name.to_str()) {
+            Some("osrelease") => format!("{}\n", self.release),
+            _ => with_release(&content, &self.release),
+        };
+        Ok(Some(content.into_bytes()))
+    }
+}
+
+/// Split a line of `maps` (or a mapping line of `smaps`) into its address
+/// range, and its columns before the path of the mapped file.
+fn split_maps_line(line: &str) -> Option<((Word, Word), &str, &str)> {
+    let (start, end) = line.split(' ').next()?.split_once('-')?;
+    let range = (
+        Word::from_str_radix(start, 16).ok()?,
+        Word::from_str_radix(end, 16).ok()?,
+    );
+    let mut rest = line;
+    for _ in 0..5 {
+        rest = rest.trim_start_matches(' ');
+        let column = rest.find(' ').unwrap_or(rest.len());
+        if column == 0 {
+            return None;
+        }
+        rest = &rest[column..];
+    }
+    let path = rest.trim_start_matches(' ');
+    Some((range, &line[..line.len() - path.len()], path))
+}
+
+/// Address of the program headers of the executable of `pid`, from the
+/// auxiliary vector on its initial stack. `/proc/<pid>/auxv` can't be used:
+/// it is the vector the kernel gave to the loader, which the loader updates
+/// on the stack only.
+fn program_headers(pid: Pid) -> Option<Word> {
+    let stat = fs::read_to_string(format!("/proc/{}/stat", pid)).ok()?;
+    // `startstack` is the 28th field, the 2nd one (`comm`) may have spaces
+    let (_, fields) = stat.rsplit_once(')')?;
+    let stack: Word = fields.split_whitespace().nth(25)?.parse().ok()?;
+    let word = size_of::<Word>() as Word;
+
+    // argc, then argv and envp, both null-terminated
+    let mut argc = [0; size_of::<Word>()];
+    memory::read_bytes(pid, stack, &mut argc).ok()?;
+    let argc = Word::from_ne_bytes(argc);
+    let envp = stack + (argc + 2) * word;
+    let mut auxv = envp + (memory::read_words(pid, envp).ok()?.len() as Word + 1) * word;
+    loop {
+        let mut entry = [0; 2 * size_of::<Word>()];
+        memory::read_bytes(pid, auxv, &mut entry).ok()?;
+        let (key, value) = entry.split_at(size_of::<Word>());
+        match Word::from_ne_bytes(key.try_into().ok()?) {
+            key if key == libc::AT_NULL as Word => return None,
+            key if key == libc::AT_PHDR as Word => {
+                return Some(Word::from_ne_bytes(value.try_into().ok()?));
+            }
+            _ => auxv += 2 * word,
+        }
+    }
+}
+
+/// Translates the host paths of the files mapped by a tracee to guest paths.
+///
+/// A tracee runs the loader of pronyx, which maps its executable (and its
+/// interpreter) by itself: the loader is the executable the kernel knows
+/// (`/proc/<pid>/exe`), but the guest expects to see its own there. The
+/// executable is the file mapped at the program headers which the loader gave
+/// to the program (`AT_PHDR`).
+#[derive(Debug)]
+pub struct MappedFiles<'a> {
+    fs: &'a FileSystem,
+    /// Host path of the loader.
+    loader: Option<PathBuf>,
+    /// Guest path of the executable.
+    executable: Option<PathBuf>,
+}
+
+impl<'a> MappedFiles<'a> {
+    /// `maps` is the content of `/proc/<pid>/maps`.
+    pub fn new(fs: &'a FileSystem, pid: Pid, maps: &str) -> MappedFiles<'a> {
+        let loader = fs::read_link(format!("/proc/{}/exe", pid)).ok();
+        let mut mapped_files = MappedFiles {
+            fs,
+            loader,
+            executable: None,
+        };
+        if let Some(address) = program_headers(pid) {
+            mapped_files.executable = maps.lines().find_map(|line| {
+                let ((start, end), _, path) = split_maps_line(line)?;
+                let path = Path::new(path);
+                (start <= address && address < end && path.is_absolute())
+                    .then(|| mapped_files.detranslate(path))
+            });
+        }
+        mapped_files
+    }
+
+    /// The guest path of the mapped file `host_path`. Files out of the guest
+    /// file-system are left alone.
+    pub fn detranslate(&self, host_path: &Path) -> PathBuf {
+        if self.loader.as_deref() == Some(host_path)
+            && let Some(executable) = &self.executable
+        {
+            return executable.clone();
+        }
+        match self.fs.detranslate_path(host_path, None) {
+            Ok(Some(guest_path)) => guest_path,
+            _ => host_path.to_path_buf(),
+        }
+    }
+
+    /// Rewrite the paths of the mappings of a `maps` or `smaps` file.
+    pub fn rewrite(&self, content: &str) -> String {
+        let mut rewritten = String::with_capacity(content.len());
+        for line in content.split_inclusive('\n') {
+            let (line, newline) = match line.strip_suffix('\n') {
+                Some(line) => (line, "\n"),
+                None => (line, ""),
+            };
+            match split_maps_line(line) {
+                Some((_, columns, path)) if path.starts_with('/') => {
+                    let (path, deleted) = match path.strip_suffix(" (deleted)") {
+                        Some(path) => (path, " (deleted)"),
+                        None => (path, ""),
+                    };
+                    let guest_path = self.detranslate(Path::new(path));
+                    rewritten.push_str(columns);
+                    rewritten.push_str(&String::from_utf8_lossy(
+                        guest_path.as_os_str().as_bytes(),
+                    ));
+                    rewritten.push_str(deleted);
+                }
+                _ => rewritten.push_str(line),
+            }
+            rewritten.push_str(newline);
+        }
+        rewritten
+    }
+}
+
+/// `/proc/<pid>/maps` and `smaps` of the tracees, with guest paths, see
+/// [`MappedFiles`].
+#[derive(Debug)]
+pub struct Maps;
+
+impl VirtualFile for Maps {
+    fn provides(&self, guest_path: &Path) -> bool {
+        matches!(process_file(guest_path), Some("maps" | "smaps"))
+            && process_id(guest_path).is_some()
+    }
+
+    fn generate(
+        &self,
+        fs: &FileSystem,
+        guest_path: &Path,
+        host_path: &Path,
+    ) -> Result<Option<Vec<u8>>> {
+        // The processes out of the box see the host as it is.
+        let pid = match process_id(guest_path) {
+            Some(pid) if is_traced_by_us(pid) => pid,
+            _ => return Ok(None),
+        };
+        let content = match fs::read_to_string(host_path) {
+            Ok(content) => content,
+            Err(_) => return Ok(None),
+        };
+        let maps = match process_file(guest_path) {
+            Some("maps") => content.clone(),
+            _ => fs::read_to_string(format!("/proc/{}/maps", pid)).unwrap_or_default(),
+        };
+        let mapped_files = MappedFiles::new(fs, pid, &maps);
+        Ok(Some(mapped_files.rewrite(&content).into_bytes()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MOUNTINFO: &str = "\
+22 1 8:1 / / rw,relatime shared:1 - ext4 /dev/sda1 rw,errors=remount-ro
+23 22 0:21 / /proc rw,nosuid,nodev,noexec,relatime shared:12 - proc proc rw
+24 22 8:2 /data /home/my\\040files rw,relatime - ext4 /dev/sda2 rw
+";
+
+    #[test]
+    fn test_parse_mountinfo() {
+        let mounts = parse_mountinfo(MOUNTINFO);
+        assert_eq!(mounts.len(), 3);
+        assert_eq!(mounts[2].mount_point, Path::new("/home/my files"));
+        assert_eq!(mounts[2].root, Path::new("/data"));
+        assert_eq!(mounts[1].fstype, "proc");
+        assert_eq!(mounts[0].super_options, "rw,errors=remount-ro");
+
+        assert_eq!(find_mount(&mounts, Path::new("/proc/1")), Some(&mounts[1]));
+        assert_eq!(find_mount(&mounts, Path::new("/procfs")), Some(&mounts[0]));
+        assert_eq!(escape(&mounts[2].mount_point), "/home/my\\040files");
+    }
+
+    #[test]
+    fn test_format_mounts() {
+        let host_mounts = parse_mountinfo(MOUNTINFO);
+        let mut fs = FileSystem::with_root("/").unwrap();
+        fs.add_binding("/proc", "/proc").unwrap();
+        fs.add_binding("/etc", "/tmp").unwrap();
+        fs.add_read_only_binding("/proc", "/tmp").unwrap();
+        let mut mounts = guest_mounts(&fs, &host_mounts);
+        // the host paths of the test aren't in the test mounts
+        mounts[0].host = Path::new("/rootfs");
+        mounts[2].host = Path::new("/home/my files/guest");
+        for mount in mounts.iter_mut() {
+            mount.mount = find_mount(&host_mounts, mount.host);
+        }
+
+        assert_eq!(
+            format_mounts(&mounts),
+            "/dev/sda1 / ext4 rw,relatime,errors=remount-ro 0 0\n\
+             proc /proc proc rw,nosuid,nodev,noexec,relatime 0 0\n\
+             /dev/sda2 /tmp ext4 ro,relatime 0 0\n"
+        );
+        assert_eq!(
+            format_mountinfo(&mounts),
+            "1000 999 8:1 /rootfs / rw,relatime - ext4 /dev/sda1 rw,errors=remount-ro\n\
+             1001 1000 0:21 / /proc rw,nosuid,nodev,noexec,relatime - proc proc rw\n\
+             1002 1000 8:2 /data/guest /tmp ro,relatime - ext4 /dev/sda2 rw\n"
+        );
+    }
+
+    #[test]
+    fn test_process_file() {
+        assert_eq!(process_file(Path::new("/proc/mounts")), Some("mounts"));
+        assert_eq!(
+            process_file(Path::new("/proc/self/mountinfo")),
+            Some("mountinfo")
+        );
+        assert_eq!(
+            process_file(Path::new("/proc/12/task/13/mountinfo")),
+            Some("mountinfo")
+        );
+        assert_eq!(process_file(Path::new("/proc/12/fd/mountinfo")), None);
+        assert_eq!(process_file(Path::new("/etc/mounts")), None);
+        assert!(Mounts.provides(Path::new("/proc/thread-self/mounts")));
+        assert!(!Mounts.provides(Path::new("/proc/self/maps")));
+    }
+
+    #[test]
+    fn test_kernel_version() {
+        let version = "Linux version 6.1.0-13-amd64 (debian-kernel@lists.debian.org) #1 SMP\n";
+        assert_eq!(
+            with_release(version, "9.9.9"),
+            "Linux version 9.9.9 (debian-kernel@lists.debian.org) #1 SMP\n"
+        );
+        assert_eq!(with_release("garbage\n", "9.9.9"), "garbage\n");
+
+        let fs = FileSystem::with_root("/").unwrap();
+        let provider = KernelVersion::new("9.9.9");
+        let guest_path = Path::new("/proc/sys/kernel/osrelease");
+        assert!(provider.provides(guest_path));
+        assert_eq!(
+            provider.generate(&fs, guest_path, guest_path).unwrap(),
+            Some(b"9.9.9\n".to_vec())
+        );
+    }
+
+    #[test]
+    fn test_maps_rewrite() {
+        let line = "00400000-00452000 r-xp 00000000 08:02 173521      /usr/bin/dbus-daemon";
+        let ((start, end), columns, path) = split_maps_line(line).unwrap();
+        assert_eq!((start, end), (0x400000, 0x452000));
+        assert_eq!(columns.len() + path.len(), line.len());
+        assert_eq!(path, "/usr/bin/dbus-daemon");
+        assert_eq!(
+            split_maps_line("7ffd1000-7ffd2000 rw-p 00000000 00:00 0 ").map(|split| split.2),
+            Some("")
+        );
+        assert_eq!(split_maps_line("Size:   4 kB"), None);
+
+        let mut fs = FileSystem::with_root("/").unwrap();
+        fs.add_binding("/usr/bin", "/tmp").unwrap();
+        let mapped_files = MappedFiles {
+            fs: &fs,
+            loader: Some(PathBuf::from("/tmp/prooted-1-XXXXXX")),
+            executable: Some(PathBuf::from("/tmp/cat")),
+        };
+        assert_eq!(
+            mapped_files.rewrite(
+                "00200000-00201000 r--p 00000000 fe:00 12   /tmp/prooted-1-XXXXXX\n\
+                 500000000000-500000002000 r--p 00000000 fe:00 34   /usr/bin/cat (deleted)\n\
+                 Size:                  4 kB\n\
+                 7ffd1000-7ffd2000 rw-p 00000000 00:00 0    [stack]"
+            ),
+            "00200000-00201000 r--p 00000000 fe:00 12   /tmp/cat\n\
+             500000000000-500000002000 r--p 00000000 fe:00 34   /tmp/cat (deleted)\n\
+             Size:                  4 kB\n\
+             7ffd1000-7ffd2000 rw-p 00000000 00:00 0    [stack]"
+        );
+
+        assert_eq!(process_id(Path::new("/proc/12/maps")), Some(Pid::from_raw(12)));
+        assert_eq!(process_id(Path::new("/proc/self/maps")), None);
+        assert!(Maps.provides(Path::new("/proc/12/task/13/smaps")));
+        assert!(!Maps.provides(Path::new("/proc/self/maps")));
+    }
+}
@@ -0,0 +1,261 @@
+//! Guest files of `/etc` whose content is completed by pronyx.
+//!
+//! The user of a box is usually unknown to its rootfs: shells print "I have
+//! no name!", and `ssh`, `git` or `pip` fail. Instead of changing the rootfs,
+//! the guest `/etc/passwd` and `/etc/group` are virtual files (see
+//! [`VirtualFile`]) with an entry for this user (or for root with `-0`) when it
+//! is missing, and `/etc/hosts` one with the host name of the box.
+
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use nix::unistd::{self, Group, User};
+
+use crate::errors::Result;
+use crate::filesystem::passwd::parse_passwd;
+use crate::filesystem::virtual_file::VirtualFile;
+use crate::filesystem::FileSystem;
+use crate::utils::Config;
+
+/// The files which are completed.
+const COMPLETED_FILES: &[&str] = &["/etc/passwd", "/etc/group", "/etc/hosts"];
+
+/// The user of the box, as the guest sees it.
+#[derive(Debug, Clone, PartialEq)]
+struct Identity {
+    name: String,
+    uid: u32,
+    group: String,
+    gid: u32,
+    home: String,
+}
+
+impl Identity {
+    fn from_config(config: &Config) -> Identity {
+        if config.root_id {
+            return Identity {
+                name: "root".to_string(),
+                uid: 0,
+                group: "root".to_string(),
+                gid: 0,
+                home: "/root".to_string(),
+            };
+        }
+        let uid = unistd::getuid();
+        let gid = unistd::getgid();
+        let user = User::from_uid(uid).ok().flatten();
+        let name = user
+            .as_ref()
+            .map(|user| user.name.clone())
+            .unwrap_or_else(|| format!("user{}", uid));
+        let group = Group::from_gid(gid)
+            .ok()
+            .flatten()
+            .map(|group| group.name)
+            .unwrap_or_else(|| name.clone());
+        let home = user
+            .map(|user| user.dir.to_string_lossy().into_owned())
+            .unwrap_or_else(|| "/".to_string());
+        Identity {
+            name,
+            uid: uid.as_raw(),
+            group,
+            gid: gid.as_raw(),
+            home,
+        }
+    }
+}
+
+/// Provides the completed files of a box, see the module documentation.
+#[derive(Debug)]
+pub struct EtcFiles {
+    identity: Identity,
+    hostname: Option<String>,
+}
+
+impl EtcFiles {
+    pub fn new(config: &Config) -> EtcFiles {
+        let hostname = config.hostname.clone().or_else(|| {
+            fs::read_to_string("/proc/sys/kernel/hostname")
+                .ok()
+                .map(|hostname| hostname.trim().to_string())
+        });
+        EtcFiles {
+            identity: Identity::from_config(config),
+            hostname,
+        }
+    }
+}
+
+impl VirtualFile for EtcFiles {
+    fn provides(&self, guest_path: &Path) -> bool {
+        COMPLETED_FILES
+            .iter()
+            .any(|path| guest_path == Path::new(path))
+    }
+
+    /// Nothing is generated if nothing is missing in the file.
+    fn generate(
+        &self,
+        _fs: &FileSystem,
+        guest_path: &Path,
+        host_path: &Path,
+    ) -> Result<Option<Vec<u8>>> {
+        let content = match fs::read_to_string(host_path) {
+            Ok(content) => content,
+            Err(error) if error.kind() == io::ErrorKind::NotFound => String::new(),
+            // A directory, or a binary file: it is left alone.
+            Err(_) => return Ok(None),
+        };
+        let completed = match guest_path.file_name().and_then(|name| name.to_str()) {
+            Some("passwd") => complete_passwd(&content, &self.identity),
+            Some("group") => complete_group(&content, &self.identity),
+            Some("hosts") => match &self.hostname {
+                Some(hostname) => complete_hosts(&content, hostname),
+                None => None,
+            },
+            _ => None,
+        };
+        Ok(completed.map(String::into_bytes))
+    }
+
+    fn mode(&self) -> u32 {
+        0o644
+    }
+}
+
+/// Append `line` to `content`, with a newline if needed.
+fn append_line(content: &str, line: &str) -> String {
+    let mut completed = content.to_string();
+    if !completed.is_empty() && !completed.ends_with('\n') {
+        completed.push('\n');
+    }
+    completed.push_str(line);
+    completed.push('\n');
+    completed
+}
+
+/// Add the user to a `passwd(5)` file, unless its uid is already there.
+fn complete_passwd(content: &str, identity: &Identity) -> Option<String> {
+    if parse_passwd(content)
+        .iter()
+        .any(|entry| entry.uid == identity.uid)
+    {
+        return None;
+    }
+    let line = format!(
+        "{}:x:{}:{}:{}:{}:/bin/sh",
+        identity.name, identity.uid, identity.gid, identity.name, identity.home
+    );
+    Some(append_line(content, &line))
+}
+
+/// Add the group of the user to a `group(5)` file, unless its gid is already
+/// there.
+fn complete_group(content: &str, identity: &Identity) -> Option<String> {
+    let known = content.lines().any(|line| {
+        let fields: Vec<&str> = line.split(':').collect();
+        !line.starts_with('#') && fields.len() == 4 && fields[2].parse() == Ok(identity.gid)
+    });
+    if known {
+        return None;
+    }
+    let line = format!("{}:x:{}:", identity.group, identity.gid);
+    Some(append_line(content, &line))
+}
+
+/// Add the host name of the box to a `hosts(5)` file, unless it is already
+/// there.
+fn complete_hosts(content: &str, hostname: &str) -> Option<String> {
+    let known = content.lines().any(|line| {
+        let line = line.split('#').next().unwrap_or_default();
+        line.split_whitespace().skip(1).any(|name| name == hostname)
+    });
+    if known || hostname.is_empty() {
+        return None;
+    }
+    Some(append_line(content, &format!("127.0.1.1\t{}", hostname)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn identity() -> Identity {
+        Identity {
+            name: "me".to_string(),
+            uid: 1000,
+            group: "us".to_string(),
+            gid: 100,
+            home: "/home/me".to_string(),
+        }
+    }
+
+    #[test]
+    fn test_complete_passwd_and_group() {
+        let passwd = "root:x:0:0:root:/root:/bin/sh";
+        assert_eq!(
+            complete_passwd(passwd, &identity()).unwrap(),
+            "root:x:0:0:root:/root:/bin/sh\nme:x:1000:100:me:/home/me:/bin/sh\n"
+        );
+        assert_eq!(
+            complete_passwd("", &identity()).unwrap(),
+            "me:x:1000:100:me:/home/me:/bin/sh\n"
+        );
+        assert_eq!(
+            complete_passwd("other:x:1000:1000::/:/bin/sh\n", &identity()),
+            None
+        );
+
+        assert_eq!(
+            complete_group("root:x:0:\n", &identity()).unwrap(),
+            "root:x:0:\nus:x:100:\n"
+        );
+        assert_eq!(complete_group("users:x:100:me\n", &identity()), None);
+    }
+
+    #[test]
+    fn test_complete_hosts() {
+        let hosts = "127.0.0.1\tlocalhost\n# 10.0.0.1 box\n";
+        assert_eq!(
+            complete_hosts(hosts, "box").unwrap(),
+            "127.0.0.1\tlocalhost\n# 10.0.0.1 box\n127.0.1.1\tbox\n"
+        );
+        assert_eq!(complete_hosts("10.0.0.1 box.lan box\n", "box"), None);
+    }
+
+    #[test]
+    fn test_etc_files_generate() {
+        let config = Config {
+            root_id: true,
+            hostname: Some("box".to_string()),
+            ..Default::default()
+        };
+        let files = EtcFiles::new(&config);
+        let fs = FileSystem::with_root("/").unwrap();
+        assert!(files.provides(Path::new("/etc/passwd")));
+        assert!(!files.provides(Path::new("/etc/shadow")));
+
+        let guest = std::env::temp_dir().join(format!("pronyx-synthetic-{}", std::process::id()));
+        fs::write(&guest, "daemon:x:1:1::/:/bin/false\n").unwrap();
+        let content = files
+            .generate(&fs, Path::new("/etc/passwd"), &guest)
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            content,
+            b"daemon:x:1:1::/:/bin/false\nroot:x:0:0:root:/root:/bin/sh\n"
+        );
+
+        // nothing is missing
+        fs::write(&guest, "root:x:0:0:root:/root:/bin/sh\n").unwrap();
+        assert_eq!(
+            files
+                .generate(&fs, Path::new("/etc/passwd"), &guest)
+                .unwrap(),
+            None
+        );
+        fs::remove_file(&guest).unwrap();
+    }
+}
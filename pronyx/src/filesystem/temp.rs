@@ -1,5 +1,5 @@
 use crate::errors::Result;
-use nix::unistd::getpid;
+use nix::unistd::{getpid, mkdtemp};
 use std::env;
 use std::fs;
 use std::path::PathBuf;
@@ -39,6 +39,33 @@ impl Drop for TempFile {
     }
 }
 
+/// A private temporary directory, which is deleted with its content when
+/// dropped.
+#[derive(Debug)]
+pub struct TempDir {
+    pub path: PathBuf,
+}
+
+impl TempDir {
+    pub fn new(prefix: &str) -> Result<Self> {
+        let template = format!(
+            "{}/{}-{}-XXXXXX",
+            env::temp_dir().to_str().unwrap(),
+            prefix,
+            getpid()
+        );
+        Ok(Self {
+            path: mkdtemp(template.as_str())?,
+        })
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.path);
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -84,4 +111,15 @@ mod tests {
         // but it must be deleted when the TempFile is dropped (so when proot-rs stops)
         assert!(!temp_file_path.exists());
     }
+
+    #[test]
+    fn test_temp_dir_is_created_and_deleted() {
+        let path = {
+            let temp_dir = TempDir::new("test-3").unwrap();
+            assert!(temp_dir.path.is_dir());
+            fs::write(temp_dir.path.join("file"), "test").unwrap();
+            temp_dir.path.clone()
+        };
+        assert!(!path.exists());
+    }
 }
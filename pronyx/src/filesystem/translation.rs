@@ -1,7 +1,6 @@
 use crate::errors::Result;
 
 use crate::filesystem::binding::Side::{Guest, Host};
-use crate::filesystem::canonicalization::Canonicalizer;
 use crate::filesystem::substitution::Substitutor;
 use crate::filesystem::FileSystem;
 use std::path::{Path, PathBuf};
@@ -59,13 +58,23 @@ impl Translator for FileSystem {
         guest_path: P,
         deref_final: bool,
     ) -> Result<(PathBuf, PathBuf)> {
-        let trailing_slash = guest_path.with_trailing_slash();
-        let canonical_guest_path = self.canonicalize(&guest_path, deref_final)?;
-        let mut host_path = self.substitute(&canonical_guest_path, Guest)?;
-
-        if trailing_slash {
-            // recover the trailing slash
-            host_path.try_add_trailing_slash();
+        let guest_path = guest_path.as_ref();
+        let cached = self
+            .translation_cache()
+            .and_then(|mut cache| cache.lookup(guest_path, deref_final));
+        let (canonical_guest_path, host_path) = match cached {
+            Some(translation) => translation,
+            None => self.translate_uncached(guest_path, deref_final)?,
+        };
+
+        // The copies of virtual files are generated again by each translation,
+        // hence aren't cached. The directory entry of a virtual file is the
+        // real one.
+        if deref_final
+            && !guest_path.with_trailing_slash()
+            && let Some(copy) = self.virtual_copy(&canonical_guest_path, &host_path)
+        {
+            return Ok((canonical_guest_path, copy));
         }
         Ok((canonical_guest_path, host_path))
     }
@@ -141,6 +150,49 @@ impl Translator for FileSystem {
     }
 }
 
+impl FileSystem {
+    fn translate_uncached(
+        &self,
+        guest_path: &Path,
+        deref_final: bool,
+    ) -> Result<(PathBuf, PathBuf)> {
+        let trailing_slash = guest_path.with_trailing_slash();
+        let mut dependencies = vec![];
+        let canonical_guest_path =
+            self.canonicalize_recording(guest_path, deref_final, Some(&mut dependencies))?;
+        let mut host_path = self.substitute(&canonical_guest_path, Guest)?;
+
+        if trailing_slash {
+            // recover the trailing slash
+            host_path.try_add_trailing_slash();
+        }
+        if let Some(mut cache) = self.translation_cache() {
+            cache.insert(
+                guest_path,
+                deref_final,
+                &canonical_guest_path,
+                &host_path,
+                dependencies,
+            );
+        }
+        Ok((canonical_guest_path, host_path))
+    }
+
+    /// Generate the copy of `canonical_guest_path` if it is a virtual file,
+    /// see the `virtual_file` module. The real file is used if the copy can't
+    /// be generated.
+    fn virtual_copy(&self, canonical_guest_path: &Path, host_path: &Path) -> Option<PathBuf> {
+        let virtual_files = self.virtual_files()?;
+        match virtual_files.generate(self, canonical_guest_path, host_path) {
+            Ok(copy) => copy,
+            Err(error) => {
+                warn!("cannot generate {:?}: {}", canonical_guest_path, error);
+                None
+            }
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -1,7 +1,5 @@
 use std::path::Path;
 
-//TODO: replace all this by FileSystemNamespace's trait Validator
-
 /// Check wheter the path is a valid path (file that exists, or path that ends
 /// in /)
 pub fn is_valid_path(path: &str, error_message: String) -> Result<(), String> {
@@ -12,29 +10,113 @@ pub fn is_valid_path(path: &str, error_message: String) -> Result<(), String> {
     }
 }
 
-/// Check whether the path exists and is a folder
-pub fn path_validator(path: String) -> Result<(), String> {
-    is_valid_path(path.as_str(), path.to_string() + " is not a valid path.")
-    //TODO: check for folder path
+/// Check whether the path exists and is a folder.
+///
+/// The signature matches clap's value parsers, so it can be used directly
+/// with `Arg::value_parser()`.
+pub fn path_validator(path: &str) -> Result<String, String> {
+    is_valid_path(path, path.to_string() + " is not a valid path.")?;
+    if !Path::new(path).is_dir() {
+        return Err(path.to_string() + " is not a directory.");
+    }
+    Ok(path.to_string())
 }
 
-/// Check whether a path is of the type ```host_path:guest_path``` and that the
-/// host.
-pub fn binding_validator(binding_paths: String) -> Result<(), String> {
+/// Split a binding of the form ```host_path:guest_path``` into its two parts.
+pub fn split_binding(binding_paths: &str) -> Result<(&str, &str), String> {
     let parts: Vec<&str> = binding_paths.split_terminator(':').collect();
 
-    if parts.len() != 2 {
-        Err("should be: path_host:path_guest".to_string())
-    } else {
-        let host_path: &str = parts[0];
-
-        is_valid_path(host_path, host_path.to_string() + " is not a valid path.")
+    match parts.len() {
+        2 => Ok((parts[0], parts[1])),
+        _ => Err("should be: path_host:path_guest".to_string()),
     }
+}
+
+/// Check whether a path is of the type ```host_path:guest_path``` and that the
+/// host path exists.
+///
+/// The guest path can only be checked once the guest rootfs is known, which
+/// is done by `FileSystem::add_binding()`.
+pub fn binding_validator(binding_paths: &str) -> Result<String, String> {
+    let (host_path, _) = split_binding(binding_paths)?;
+    is_valid_path(host_path, host_path.to_string() + " is not a valid path.")?;
 
     //TODO: add a check to avoid equivalent paths bindings?
-    //TODO: add a check for guest path? (rootfs + guest path must exists?)
     //TODO: add a check to check both paths are of the same type (file:file or
     // folder:folder)
+    Ok(binding_paths.to_string())
+}
+
+/// Check whether a guest path is absolute, which is required for every path
+/// given on the guest side (bindings, cwd).
+pub fn guest_path_validator(path: &str) -> Result<String, String> {
+    if !Path::new(path).is_absolute() {
+        return Err(path.to_string() + " is not an absolute guest path.");
+    }
+    Ok(path.to_string())
+}
+
+/// Check whether an environment assignment is of the form ```NAME=value```,
+/// with a non-empty name.
+pub fn env_validator(assignment: &str) -> Result<(String, String), String> {
+    match assignment.split_once('=') {
+        Some((name, value)) if !name.is_empty() => Ok((name.to_string(), value.to_string())),
+        _ => Err(assignment.to_string() + " should be: NAME=value"),
+    }
+}
+
+/// Maximum length of a host name (`HOST_NAME_MAX`).
+pub const HOST_NAME_MAX: usize = 64;
+/// Maximum length of a kernel release, which has to fit in `utsname.release`.
+pub const KERNEL_RELEASE_MAX: usize = 64;
+
+/// Check a value which will be reported in one of the `struct utsname` fields.
+pub fn check_utsname_value(value: &str, max_len: usize) -> Result<(), String> {
+    if value.is_empty() {
+        return Err("must not be empty".to_string());
+    }
+    if value.len() > max_len {
+        return Err(format!("must be at most {} bytes long", max_len));
+    }
+    if value.contains('\0') {
+        return Err("must not contain null bytes".to_string());
+    }
+    Ok(())
+}
+
+/// Check a host name given to `--hostname`, as the `hostname` of a profile.
+pub fn hostname_validator(hostname: &str) -> Result<String, String> {
+    check_utsname_value(hostname, HOST_NAME_MAX)
+        .map(|_| hostname.to_string())
+        .map_err(|problem| format!("the host name {}", problem))
+}
+
+/// Check a kernel release given to `-k`, as the `kernel_release` of a
+/// profile.
+pub fn kernel_release_validator(release: &str) -> Result<String, String> {
+    check_utsname_value(release, KERNEL_RELEASE_MAX)
+        .map(|_| release.to_string())
+        .map_err(|problem| format!("the kernel release {}", problem))
+}
+
+/// Parse a signal given by name (`TERM`, `SIGTERM`) or by number.
+pub fn signal_validator(signal: &str) -> Result<i32, String> {
+    use std::str::FromStr;
+
+    if let Ok(number) = signal.parse::<i32>() {
+        return nix::sys::signal::Signal::try_from(number)
+            .map(|signal| signal as i32)
+            .map_err(|_| format!("{} is not a valid signal.", signal));
+    }
+    let name = signal.to_ascii_uppercase();
+    let name = if name.starts_with("SIG") {
+        name
+    } else {
+        format!("SIG{}", name)
+    };
+    nix::sys::signal::Signal::from_str(&name)
+        .map(|signal| signal as i32)
+        .map_err(|_| format!("{} is not a valid signal.", signal))
 }
 
 #[cfg(test)]
@@ -46,7 +128,7 @@ mod tests {
         let correct_paths = [".", "./", "..", "../", "./.."];
 
         for path in &correct_paths {
-            assert_eq!(path_validator(path.to_string()), Ok(()));
+            assert_eq!(path_validator(path), Ok(path.to_string()));
         }
     }
 
@@ -61,10 +143,14 @@ mod tests {
 
         for path in &incorrect_paths {
             assert_eq!(
-                path_validator(path.to_string()),
+                path_validator(path),
                 Err(path.to_string() + " is not a valid path.")
             );
         }
+        assert_eq!(
+            path_validator("/proc/self/exe"),
+            Err("/proc/self/exe is not a directory.".to_string())
+        );
     }
 
     #[test]
@@ -72,7 +158,7 @@ mod tests {
         let correct_bindings = [".:.", "..:..", ".:../../", ".:ignored"];
 
         for path in &correct_bindings {
-            assert_eq!(binding_validator(path.to_string()), Ok(()));
+            assert_eq!(binding_validator(path), Ok(path.to_string()));
         }
     }
 
@@ -82,13 +168,59 @@ mod tests {
 
         for path in &incorrect_paths {
             assert_eq!(
-                binding_validator(path.to_string()),
+                binding_validator(path),
                 Err("should be: path_host:path_guest".to_string())
             );
         }
         assert_eq!(
-            binding_validator("impossible path:.".to_string()),
+            binding_validator("impossible path:."),
             Err("impossible path is not a valid path.".to_string())
         );
     }
+
+    #[test]
+    fn test_env_validator() {
+        assert_eq!(
+            env_validator("A=b=c"),
+            Ok(("A".to_string(), "b=c".to_string()))
+        );
+        assert_eq!(env_validator("A="), Ok(("A".to_string(), String::new())));
+        assert_eq!(
+            env_validator("=b"),
+            Err("=b should be: NAME=value".to_string())
+        );
+        assert!(env_validator("A").is_err());
+    }
+
+    #[test]
+    fn test_guest_path_validator() {
+        assert_eq!(guest_path_validator("/etc"), Ok("/etc".to_string()));
+        assert_eq!(
+            guest_path_validator("etc"),
+            Err("etc is not an absolute guest path.".to_string())
+        );
+    }
+
+    #[test]
+    fn test_utsname_validators() {
+        assert_eq!(hostname_validator("box"), Ok("box".to_string()));
+        assert_eq!(
+            hostname_validator(""),
+            Err("the host name must not be empty".to_string())
+        );
+        assert_eq!(
+            kernel_release_validator(&"1".repeat(KERNEL_RELEASE_MAX + 1)),
+            Err("the kernel release must be at most 64 bytes long".to_string())
+        );
+        assert!(kernel_release_validator(&"1".repeat(KERNEL_RELEASE_MAX)).is_ok());
+    }
+
+    #[test]
+    fn test_signal_validator() {
+        assert_eq!(signal_validator("TERM"), Ok(libc::SIGTERM));
+        assert_eq!(signal_validator("sigkill"), Ok(libc::SIGKILL));
+        assert_eq!(signal_validator("2"), Ok(libc::SIGINT));
+        assert!(signal_validator("0").is_err());
+        assert!(signal_validator("NOTASIGNAL").is_err());
+    }
 }
@@ -0,0 +1,312 @@
+//! Guest files whose content is generated by pronyx.
+//!
+//! A [`VirtualFile`] provides the content of some guest paths (`/proc/mounts`,
+//! `/etc/passwd`...). When such a path is translated, its content is
+//! generated into a copy, in a private directory, and the translation gives
+//! the path of this copy instead of the one of the binding: the tracee opens,
+//! reads and `stat()`s the copy. See `FileSystem::translate_absolute_path()`.
+//!
+//! Only the translations which dereference the final component are
+//! concerned: the syscalls which work on the directory entry itself
+//! (`lstat()`, `unlink()`, `rename()`...) see the real file, if any. Opening a
+//! virtual file for writing opens the real file too, unless its provider has
+//! a write-back hook: the guest then writes to a private copy, whose new
+//! content is given to the hook once it is closed, see [`WriteBack`].
+
+use std::fmt::Debug;
+use std::fs;
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+
+use nix::errno::Errno::EROFS;
+
+use crate::errors::{Error, Result};
+use crate::filesystem::temp::TempDir;
+use crate::filesystem::{proc, synthetic, FileSystem};
+use crate::utils::Config;
+
+/// A provider of generated guest files.
+pub trait VirtualFile: Debug + Send + Sync {
+    /// Whether the content of the canonical guest path `guest_path` is
+    /// provided.
+    fn provides(&self, guest_path: &Path) -> bool;
+
+    /// Generate the content of `guest_path`, whose translation would be
+    /// `host_path` without this provider. `None` means that the real file is
+    /// used this time.
+    fn generate(
+        &self,
+        fs: &FileSystem,
+        guest_path: &Path,
+        host_path: &Path,
+    ) -> Result<Option<Vec<u8>>>;
+
+    /// Permission bits of the generated copies, as `stat()` reports them.
+    fn mode(&self) -> u32 {
+        0o444
+    }
+
+    /// Whether the guest writes to the copies, see `write_back()`.
+    fn is_writable(&self) -> bool {
+        false
+    }
+
+    /// Called with the new content of a copy which was opened for writing,
+    /// once it is closed.
+    fn write_back(&self, _fs: &FileSystem, guest_path: &Path, _content: &[u8]) -> Result<()> {
+        Err(Error::errno_with_msg(
+            EROFS,
+            format!("{}: read-only virtual file", guest_path.display()),
+        ))
+    }
+}
+
+/// The virtual files of a box, and the directory of their copies.
+#[derive(Debug, Default)]
+pub struct VirtualFiles {
+    providers: Vec<Box<dyn VirtualFile>>,
+    /// Created on first use.
+    dir: Mutex<Option<TempDir>>,
+    /// Number of private copies made so far, see `open_for_writing()`.
+    private_copies: AtomicUsize,
+}
+
+impl VirtualFiles {
+    pub fn new() -> VirtualFiles {
+        VirtualFiles::default()
+    }
+
+    /// The virtual files of pronyx: `/etc/passwd`, `/etc/group`,
+    /// `/etc/hosts`, `/proc/mounts`, `/proc/self/mountinfo`,
+    /// `/proc/self/maps`, and
+    /// `/proc/cpuinfo` and `/proc/version` if they are overridden.
+    pub fn with_builtins(config: &Config) -> VirtualFiles {
+        let mut virtual_files = VirtualFiles::new();
+        virtual_files.add(Box::new(synthetic::EtcFiles::new(config)));
+        virtual_files.add(Box::new(proc::Mounts));
+        virtual_files.add(Box::new(proc::Maps));
+        if let Some(path) = &config.cpuinfo {
+            virtual_files.add(Box::new(proc::CpuInfo::new(path)));
+        }
+        if let Some(release) = &config.kernel_release {
+            virtual_files.add(Box::new(proc::KernelVersion::new(release)));
+        }
+        virtual_files
+    }
+
+    /// Add a provider, which takes precedence over the previous ones.
+    pub fn add(&mut self, provider: Box<dyn VirtualFile>) {
+        self.providers.insert(0, provider);
+    }
+
+    fn provider(&self, guest_path: &Path) -> Option<&dyn VirtualFile> {
+        self.providers
+            .iter()
+            .find(|provider| provider.provides(guest_path))
+            .map(|provider| provider.as_ref())
+    }
+
+    /// Generate a copy of the canonical guest path `guest_path`, if it is a
+    /// virtual file, and return its host path. `host_path` is the regular
+    /// translation of `guest_path`.
+    pub fn generate(
+        &self,
+        fs: &FileSystem,
+        guest_path: &Path,
+        host_path: &Path,
+    ) -> Result<Option<PathBuf>> {
+        let provider = match self.provider(guest_path) {
+            Some(provider) => provider,
+            None => return Ok(None),
+        };
+        let content = match provider.generate(fs, guest_path, host_path)? {
+            Some(content) => content,
+            None => return Ok(None),
+        };
+
+        let mut dir = self.dir.lock().unwrap_or_else(|error| error.into_inner());
+        let dir = match &*dir {
+            Some(dir) => dir,
+            None => dir.insert(TempDir::new("pronyx-files")?),
+        };
+        let name = guest_path.to_string_lossy().replace('/', "%");
+        let path = dir.path.join(&name);
+        // A previous copy may still be open in a tracee, so it is replaced
+        // instead of being rewritten.
+        let new_path = dir.path.join(format!("{}.new", name));
+        fs::write(&new_path, content)?;
+        fs::set_permissions(&new_path, fs::Permissions::from_mode(provider.mode()))?;
+        fs::rename(&new_path, &path)?;
+        Ok(Some(path))
+    }
+
+    /// Whether `host_path` is the copy of a virtual file.
+    pub fn is_copy(&self, host_path: &Path) -> bool {
+        let dir = self.dir.lock().unwrap_or_else(|error| error.into_inner());
+        dir.as_ref()
+            .is_some_and(|dir| host_path.parent() == Some(dir.path.as_path()))
+    }
+
+    /// Whether the copies of `guest_path` are written by the guest.
+    pub fn is_writable(&self, guest_path: &Path) -> bool {
+        self.provider(guest_path)
+            .is_some_and(|provider| provider.is_writable())
+    }
+
+    /// Make a private copy of `copy`, the copy of `guest_path`, which the
+    /// guest opens for writing. Its content is written back when the returned
+    /// [`WriteBack`] is dropped.
+    pub fn open_for_writing(
+        &self,
+        fs: &FileSystem,
+        guest_path: &Path,
+        copy: &Path,
+    ) -> Result<WriteBack> {
+        let count = self.private_copies.fetch_add(1, Ordering::SeqCst);
+        let mut name = copy.file_name().unwrap_or_default().to_os_string();
+        name.push(format!(".{}", count));
+        let private_copy = copy.with_file_name(name);
+        let original = fs::read(copy)?;
+        fs::write(&private_copy, &original)?;
+        fs::set_permissions(&private_copy, fs::metadata(copy)?.permissions())?;
+        Ok(WriteBack {
+            fs: fs.clone(),
+            guest_path: guest_path.to_path_buf(),
+            copy: private_copy,
+            original,
+        })
+    }
+}
+
+/// The private copy of a virtual file opened for writing. Once dropped, that
+/// is when all the file descriptors of the copy are closed (see `FdTable`),
+/// its new content, if any, is given to the write-back hook of the file.
+#[derive(Debug)]
+pub struct WriteBack {
+    fs: FileSystem,
+    guest_path: PathBuf,
+    copy: PathBuf,
+    /// The content generated for the guest.
+    original: Vec<u8>,
+}
+
+impl WriteBack {
+    /// The host path of the private copy.
+    pub fn path(&self) -> &Path {
+        &self.copy
+    }
+
+    fn write_back(&self) -> Result<()> {
+        let content = fs::read(&self.copy)?;
+        match self
+            .fs
+            .virtual_files()
+            .and_then(|files| files.provider(&self.guest_path))
+        {
+            Some(provider) if content != self.original => {
+                provider.write_back(&self.fs, &self.guest_path, &content)
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+impl Drop for WriteBack {
+    fn drop(&mut self) {
+        if let Err(error) = self.write_back() {
+            warn!("cannot write back {:?}: {}", self.guest_path, error);
+        }
+        let _ = fs::remove_file(&self.copy);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+
+    /// A writable file which counts its generations and write-backs.
+    #[derive(Debug, Default)]
+    struct Counter {
+        generated: AtomicUsize,
+        written: Arc<Mutex<Vec<u8>>>,
+    }
+
+    impl VirtualFile for Counter {
+        fn provides(&self, guest_path: &Path) -> bool {
+            guest_path == Path::new("/counter")
+        }
+
+        fn generate(&self, _: &FileSystem, _: &Path, _: &Path) -> Result<Option<Vec<u8>>> {
+            let count = self.generated.fetch_add(1, Ordering::SeqCst) + 1;
+            Ok(Some(count.to_string().into_bytes()))
+        }
+
+        fn mode(&self) -> u32 {
+            0o644
+        }
+
+        fn is_writable(&self) -> bool {
+            true
+        }
+
+        fn write_back(&self, _: &FileSystem, _: &Path, content: &[u8]) -> Result<()> {
+            *self.written.lock().unwrap() = content.to_vec();
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_virtual_files_generate() {
+        let mut fs = FileSystem::with_root("/").unwrap();
+        let mut virtual_files = VirtualFiles::new();
+        let written = Arc::new(Mutex::new(vec![]));
+        virtual_files.add(Box::new(Counter {
+            written: written.clone(),
+            ..Default::default()
+        }));
+        fs.set_virtual_files(Some(virtual_files));
+        let virtual_files = fs.virtual_files().unwrap();
+
+        let real = Path::new("/counter");
+        assert_eq!(
+            virtual_files
+                .generate(&fs, Path::new("/other"), real)
+                .unwrap(),
+            None
+        );
+        // the content is generated again by each translation
+        let copy = virtual_files.generate(&fs, real, real).unwrap().unwrap();
+        assert_eq!(fs::read_to_string(&copy).unwrap(), "1");
+        let copy = virtual_files.generate(&fs, real, real).unwrap().unwrap();
+        assert_eq!(fs::read_to_string(&copy).unwrap(), "2");
+        assert_eq!(
+            fs::metadata(&copy).unwrap().permissions().mode() & 0o777,
+            0o644
+        );
+        assert!(virtual_files.is_copy(&copy));
+        assert!(!virtual_files.is_copy(real));
+        assert!(virtual_files.is_writable(real));
+
+        // nothing is written back if the content is unchanged
+        let write_back = virtual_files.open_for_writing(&fs, real, &copy).unwrap();
+        let private_copy = write_back.path().to_path_buf();
+        assert_ne!(private_copy, copy);
+        drop(write_back);
+        assert!(!private_copy.exists());
+        assert!(written.lock().unwrap().is_empty());
+
+        let write_back = virtual_files.open_for_writing(&fs, real, &copy).unwrap();
+        fs::write(write_back.path(), "42").unwrap();
+        drop(write_back);
+        assert_eq!(*written.lock().unwrap(), b"42");
+        assert_eq!(fs::read_to_string(&copy).unwrap(), "2");
+
+        // the copies go with the virtual files
+        drop(fs);
+        assert!(!copy.exists());
+    }
+}
@@ -0,0 +1,328 @@
+//! Extended attributes of the protected namespaces, for the fake root.
+//!
+//! An unprivileged user can't set the `security.*` and `trusted.*` attributes
+//! of its own files, which `setcap`, the file capabilities of dpkg and rpm or
+//! the extraction of SELinux-labelled images need. With `-0`, they are kept
+//! by pronyx in the manifest of the rootfs (`<rootfs>.manifest.toml`, see the
+//! `rootfs` module) and served back by `getxattr()`, `listxattr()` and
+//! `removexattr()`, while the other attributes (`user.*`...) are the ones of
+//! the real files. The ones of the host root `/`, which has no manifest, are
+//! only kept in memory.
+//!
+//! The attributes are recorded by host path, relative to the rootfs for its
+//! files, so they follow the renames done by the guest and are forgotten
+//! when it removes the file. Hard links don't share them.
+
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, MutexGuard};
+
+use nix::errno::Errno::{EEXIST, EINVAL, ENODATA};
+
+use crate::errors::{Error, Result};
+use crate::rootfs::{Manifest, ManifestXattrs};
+
+/// The namespaces whose attributes are emulated.
+const EMULATED_NAMESPACES: &[&str] = &["security.", "trusted."];
+
+/// Flags of `setxattr()`, see `<linux/xattr.h>`.
+pub const XATTR_CREATE: i32 = 0x1;
+pub const XATTR_REPLACE: i32 = 0x2;
+
+/// Attributes of a file, by name.
+type Attributes = BTreeMap<String, Vec<u8>>;
+
+/// The emulated attributes of a rootfs, see the module documentation.
+#[derive(Debug)]
+pub struct XattrStore {
+    /// Host path of the rootfs.
+    root: PathBuf,
+    /// Whether the manifest is written when the attributes change.
+    save: bool,
+    /// Attributes by file, see `key()`.
+    files: Mutex<BTreeMap<PathBuf, Attributes>>,
+}
+
+/// Whether the attribute `name` is emulated.
+pub fn is_emulated(name: &str) -> bool {
+    EMULATED_NAMESPACES
+        .iter()
+        .any(|namespace| name.starts_with(namespace))
+}
+
+fn encode(value: &[u8]) -> String {
+    value.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+fn decode(value: &str) -> Option<Vec<u8>> {
+    if !value.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..value.len())
+        .step_by(2)
+        .map(|index| u8::from_str_radix(value.get(index..index + 2)?, 16).ok())
+        .collect()
+}
+
+impl XattrStore {
+    /// The store of the rootfs `root` (a canonical host path), with the
+    /// attributes saved by previous boxes in its manifest, if any.
+    pub fn load(root: &Path) -> Result<XattrStore> {
+        let save = Manifest::is_supported(root);
+        let saved = match save {
+            true => Manifest::load(root)?.xattrs,
+            false => vec![],
+        };
+        let mut files = BTreeMap::new();
+        for file in saved {
+            let mut decoded = Attributes::new();
+            for (name, value) in file.attributes {
+                let value = decode(&value).ok_or_else(|| {
+                    Error::errno_with_msg(
+                        EINVAL,
+                        format!(
+                            "{}: {:?}: invalid value of {}",
+                            Manifest::path(root).display(),
+                            file.path,
+                            name
+                        ),
+                    )
+                })?;
+                decoded.insert(name, value);
+            }
+            files.insert(file.path, decoded);
+        }
+        Ok(XattrStore {
+            root: root.to_path_buf(),
+            save,
+            files: Mutex::new(files),
+        })
+    }
+
+    /// A store of the rootfs `root` which is not saved.
+    #[cfg(test)]
+    pub fn in_memory(root: &Path) -> XattrStore {
+        XattrStore {
+            root: root.to_path_buf(),
+            save: false,
+            files: Mutex::new(BTreeMap::new()),
+        }
+    }
+
+    /// The key of the canonical host path `host_path`: relative to the
+    /// rootfs for its files, so that the rootfs can be moved.
+    fn key(&self, host_path: &Path) -> PathBuf {
+        match host_path.strip_prefix(&self.root) {
+            Ok(relative) => relative.to_path_buf(),
+            Err(_) => host_path.to_path_buf(),
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, BTreeMap<PathBuf, Attributes>> {
+        self.files.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    /// Write the attributes to the manifest. A failure is only reported, the
+    /// attributes are still served from memory.
+    fn save(&self, files: &BTreeMap<PathBuf, Attributes>) {
+        if !self.save {
+            return;
+        }
+        let xattrs = files
+            .iter()
+            .map(|(path, attributes)| ManifestXattrs {
+                path: path.clone(),
+                attributes: attributes
+                    .iter()
+                    .map(|(name, value)| (name.clone(), encode(value)))
+                    .collect(),
+            })
+            .collect();
+        if let Err(error) = Manifest::update(&self.root, |manifest| manifest.xattrs = xattrs) {
+            warn!("cannot save extended attributes: {}", error);
+        }
+    }
+
+    /// The value of the attribute `name` of `host_path`.
+    pub fn get(&self, host_path: &Path, name: &str) -> Result<Vec<u8>> {
+        self.lock()
+            .get(&self.key(host_path))
+            .and_then(|attributes| attributes.get(name))
+            .cloned()
+            .ok_or_else(|| Error::errno(ENODATA))
+    }
+
+    /// The names of the attributes of `host_path`.
+    pub fn list(&self, host_path: &Path) -> Vec<String> {
+        self.lock()
+            .get(&self.key(host_path))
+            .map(|attributes| attributes.keys().cloned().collect())
+            .unwrap_or_default()
+    }
+
+    /// Set the attribute `name` of `host_path`, following the `flags` of
+    /// `setxattr()`.
+    pub fn set(&self, host_path: &Path, name: &str, value: &[u8], flags: i32) -> Result<()> {
+        let mut files = self.lock();
+        let key = self.key(host_path);
+        let exists = files
+            .get(&key)
+            .is_some_and(|attributes| attributes.contains_key(name));
+        match exists {
+            true if flags & XATTR_CREATE != 0 => return Err(Error::errno(EEXIST)),
+            false if flags & XATTR_REPLACE != 0 => return Err(Error::errno(ENODATA)),
+            _ => {}
+        }
+        files
+            .entry(key)
+            .or_default()
+            .insert(name.to_string(), value.to_vec());
+        self.save(&files);
+        Ok(())
+    }
+
+    /// Remove the attribute `name` of `host_path`.
+    pub fn remove(&self, host_path: &Path, name: &str) -> Result<()> {
+        let mut files = self.lock();
+        let key = self.key(host_path);
+        let attributes = files.get_mut(&key).ok_or_else(|| Error::errno(ENODATA))?;
+        attributes.remove(name).ok_or_else(|| Error::errno(ENODATA))?;
+        if attributes.is_empty() {
+            files.remove(&key);
+        }
+        self.save(&files);
+        Ok(())
+    }
+
+    /// Forget the attributes of `host_path`, which was removed.
+    pub fn forget(&self, host_path: &Path) {
+        let mut files = self.lock();
+        if files.remove(&self.key(host_path)).is_some() {
+            self.save(&files);
+        }
+    }
+
+    /// Move the attributes of `old_path` and of the files it contains to
+    /// `new_path`, which it was renamed to.
+    pub fn rename(&self, old_path: &Path, new_path: &Path) {
+        let mut files = self.lock();
+        if rename_keys(&mut files, &self.key(old_path), &self.key(new_path)) {
+            self.save(&files);
+        }
+    }
+}
+
+/// Move the entries of `old_key` and of the paths under it to `new_key`, and
+/// drop the former entry of `new_key`. Returns whether `files` changed.
+pub(super) fn rename_keys<T>(
+    files: &mut BTreeMap<PathBuf, T>,
+    old_key: &Path,
+    new_key: &Path,
+) -> bool {
+    let moved: Vec<PathBuf> = files
+        .keys()
+        .filter(|key| key.starts_with(old_key))
+        .cloned()
+        .collect();
+    // the file which was replaced, if any
+    let mut changed = files.remove(new_key).is_some();
+    for key in moved {
+        let entry = files.remove(&key).unwrap();
+        let suffix = key.strip_prefix(old_key).unwrap();
+        let new_key = match suffix.as_os_str().is_empty() {
+            true => new_key.to_path_buf(),
+            false => new_key.join(suffix),
+        };
+        files.insert(new_key, entry);
+        changed = true;
+    }
+    changed
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_xattr_is_emulated() {
+        assert!(is_emulated("security.capability"));
+        assert!(is_emulated("trusted.overlay.opaque"));
+        assert!(!is_emulated("user.mime_type"));
+        assert!(!is_emulated("securityfoo"));
+    }
+
+    #[test]
+    fn test_xattr_store() {
+        let store = XattrStore::in_memory(Path::new("/rootfs"));
+        let file = Path::new("/rootfs/usr/bin/ping");
+
+        assert_eq!(store.get(file, "security.capability").unwrap_err().get_errno(), ENODATA);
+        store.set(file, "security.capability", &[1, 0, 0, 2], 0).unwrap();
+        assert_eq!(store.get(file, "security.capability").unwrap(), vec![1, 0, 0, 2]);
+        assert_eq!(
+            store
+                .set(file, "security.capability", &[], XATTR_CREATE)
+                .unwrap_err()
+                .get_errno(),
+            EEXIST
+        );
+        assert_eq!(
+            store
+                .set(file, "security.selinux", &[], XATTR_REPLACE)
+                .unwrap_err()
+                .get_errno(),
+            ENODATA
+        );
+        store.set(file, "security.selinux", b"label\0", 0).unwrap();
+        assert_eq!(store.list(file), vec!["security.capability", "security.selinux"]);
+
+        // renaming a parent directory moves the attributes
+        store.rename(Path::new("/rootfs/usr/bin"), Path::new("/rootfs/bin"));
+        assert!(store.list(file).is_empty());
+        let file = Path::new("/rootfs/bin/ping");
+        assert_eq!(store.list(file).len(), 2);
+
+        store.remove(file, "security.selinux").unwrap();
+        assert_eq!(store.remove(file, "security.selinux").unwrap_err().get_errno(), ENODATA);
+        store.forget(file);
+        assert!(store.list(file).is_empty());
+    }
+
+    #[test]
+    fn test_xattr_store_saved() {
+        use crate::filesystem::device::{Device, DeviceStore};
+        use std::fs;
+
+        let root = std::env::temp_dir().join(format!("pronyx-xattr-{}", std::process::id()));
+        fs::create_dir_all(&root).unwrap();
+
+        let store = XattrStore::load(&root).unwrap();
+        store.set(&root.join("file"), "trusted.value", &[0xff, 0], 0).unwrap();
+        store.set(Path::new("/outside"), "security.selinux", b"x", 0).unwrap();
+        // the devices are kept in the same manifest
+        let devices = DeviceStore::load(&root, vec![]).unwrap();
+        devices.record(&root.join("null"), Device::char(1, 3), 0o666, 0o644);
+
+        let content = fs::read_to_string(Manifest::path(&root)).unwrap();
+        assert!(content.contains("[[xattr]]\npath = \"file\""), "{}", content);
+        assert!(content.contains("\"trusted.value\" = \"ff00\""), "{}", content);
+        let store = XattrStore::load(&root).unwrap();
+        assert_eq!(store.get(&root.join("file"), "trusted.value").unwrap(), vec![0xff, 0]);
+        assert_eq!(store.list(Path::new("/outside")), vec!["security.selinux"]);
+        store.forget(Path::new("/outside"));
+        let devices = DeviceStore::load(&root, vec![]).unwrap();
+        assert!(devices.get(&root.join("null")).is_some());
+
+        fs::remove_file(Manifest::path(&root)).unwrap();
+        fs::remove_dir(&root).unwrap();
+    }
+
+    #[test]
+    fn test_xattr_store_host_root() {
+        // nothing is read or written next to `/`
+        let store = XattrStore::load(Path::new("/")).unwrap();
+        assert!(!store.save);
+        store.set(Path::new("/tmp"), "security.selinux", b"x", 0).unwrap();
+        assert_eq!(store.list(Path::new("/tmp")), vec!["security.selinux"]);
+    }
+}
@@ -10,6 +10,12 @@ use crate::process::proot::InfoBag;
 use crate::process::tracee::Tracee;
 use crate::register::Original;
 
+/// Whether `translate()` (or `exit::translate()`) does anything for the
+/// syscall `sys_num`.
+pub fn is_needed(sys_num: usize) -> bool {
+    syscall_group_from_sysnum(sys_num as i64) != Ignored
+}
+
 pub fn translate(info_bag: &InfoBag, tracee: &mut Tracee) -> Result<()> {
     let sys_num = tracee.regs.get_sys_num(Original);
     let sys_type = syscall_group_from_sysnum(sys_num as i64);
@@ -22,11 +28,13 @@ pub fn translate(info_bag: &InfoBag, tracee: &mut Tracee) -> Result<()> {
         ChmodAccessMkNodAt => chmod_access_mknod_at::enter(tracee),
         DirLinkAttr => dir_link_attr::enter(tracee),
         Execve => execve::enter(tracee, &info_bag.loader),
+        FanotifyMark => fanotify_mark::enter(tracee),
         GetCwd => getcwd::enter(tracee),
         GetSockOrPeerName => get_sockorpeer_name::enter(),
-        InotifyAddWatch => inotify_add_watch::enter(),
+        InotifyAddWatch => inotify_add_watch::enter(tracee),
         Link => link_rename::enter(tracee),
         LinkAt => link_at::enter(tracee),
+        MkNod => mknod::enter(tracee),
         Mount => mount::enter(),
         Open => open::enter(tracee),
         OpenAt => open_at::enter(tracee),
@@ -39,10 +47,13 @@ pub fn translate(info_bag: &InfoBag, tracee: &mut Tracee) -> Result<()> {
         SocketCall => socketcall::enter(),
         StandardSyscall => standard_syscall::enter(tracee),
         StatAt => stat_at::enter(tracee),
+        StatFs => stat_fs::enter(tracee),
         SymLink => sym_link::enter(tracee),
         SymLinkAt => sym_link_at::enter(tracee),
         Wait => wait::enter(),
         UnlinkMkdirAt => unlink_mkdir_at::enter(tracee),
+        Xattr => xattr::enter(tracee),
+        Close => close::enter(tracee),
         _ => Ok(()),
     }
 }
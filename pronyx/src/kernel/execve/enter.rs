@@ -1,14 +1,70 @@
 use std::cell::RefCell;
+use std::os::unix::io::RawFd;
+use std::path::{Path, PathBuf};
 use std::rc::Rc;
 
+use nix::fcntl::AtFlags;
+
 use crate::errors::*;
+use crate::filesystem::binding::Side;
 use crate::kernel::execve::binfmt;
 use crate::kernel::execve::loader::LoaderFile;
 use crate::kernel::execve::params::{self, ExecveParameters};
 use crate::process::tracee::Tracee;
 use crate::register::PtraceWriter;
-use crate::register::{Current, PtraceReader, SysArg, SysArg1, SysArg2};
+use crate::register::{
+    Current, PtraceReader, SysArg, SysArg1, SysArg2, SysArg3, SysArg5, SysArgIndex,
+};
+
+/// The arguments of the path and of `argv`.
+fn arguments(sys_num: usize) -> (SysArgIndex, SysArgIndex) {
+    match sys_num as i64 {
+        crate::sc::nr::EXECVEAT => (SysArg2, SysArg3),
+        _ => (SysArg1, SysArg2),
+    }
+}
+
+/// The guest path of the file executed by `execveat()`: `path` relative to
+/// `dirfd`, or the file of `dirfd` itself with `AT_EMPTY_PATH` (`fexecve()`).
+/// A final symlink is refused with `AT_SYMLINK_NOFOLLOW`, like the kernel
+/// does.
+fn resolve_at(tracee: &Tracee, raw_path: PathBuf) -> Result<PathBuf> {
+    let dirfd = tracee.regs.get(Current, SysArg(SysArg1)) as RawFd;
+    let flags = AtFlags::from_bits_truncate(tracee.regs.get(Current, SysArg(SysArg5)) as _);
+    if raw_path.as_os_str().is_empty() && !flags.contains(AtFlags::AT_EMPTY_PATH) {
+        return Err(Error::errno_with_msg(ENOENT, "execveat() of an empty path"));
+    }
+    let deref_final = !flags.contains(AtFlags::AT_SYMLINK_NOFOLLOW);
+    let (guest_path, host_path) = tracee.translate_path_at(dirfd, raw_path, deref_final)?;
+    if !deref_final && host_path.is_symlink() {
+        return Err(Error::errno_with_msg(
+            ELOOP,
+            format!(
+                "execveat() of a symlink with AT_SYMLINK_NOFOLLOW: {:?}",
+                guest_path
+            ),
+        ));
+    }
+    Ok(guest_path)
+}
 
+/// The host file executed by `fexecve()` when it is not in the guest
+/// file-system, like a `memfd_create()` file or an unlinked file. It has no
+/// guest path to load, so the kernel executes it untranslated.
+fn host_file_without_guest_path(tracee: &Tracee, raw_path: &Path) -> Option<PathBuf> {
+    let flags = AtFlags::from_bits_truncate(tracee.regs.get(Current, SysArg(SysArg5)) as _);
+    if !raw_path.as_os_str().is_empty() || !flags.contains(AtFlags::AT_EMPTY_PATH) {
+        return None;
+    }
+    let dirfd = tracee.regs.get(Current, SysArg(SysArg1)) as RawFd;
+    let host_path = tracee.get_path_from_fd(dirfd, Side::Host).ok()?;
+    // `/proc/<pid>/fd/<n>` still opens it, but its path leads nowhere
+    (!host_path.exists()).then_some(host_path)
+}
+
+/// Translates `execve()` and `execveat()`: the executable is loaded by
+/// pronyx (see `binfmt`), and the loader is executed instead, by `execve()`
+/// or by `execveat()` relative to the working directory.
 pub fn translate(tracee: &mut Tracee, loader: &dyn LoaderFile) -> Result<()> {
     //TODO: implement this part for ptrace translation
     //	if (IS_NOTIFICATION_PTRACED_LOAD_DONE(tracee)) {
@@ -22,8 +78,19 @@ pub fn translate(tracee: &mut Tracee, loader: &dyn LoaderFile) -> Result<()> {
     //	}
 
     // Read required values from tracee
-    let raw_guest_path = tracee.regs.get_sysarg_path(SysArg1)?;
-    let argv_addr = tracee.regs.get(Current, SysArg(SysArg2));
+    let sys_num = tracee.regs.get_sys_num(Current);
+    let (path_arg, argv_arg) = arguments(sys_num);
+    let mut raw_guest_path = tracee.regs.get_sysarg_path(path_arg)?;
+    if sys_num as i64 == crate::sc::nr::EXECVEAT {
+        if let Some(host_path) = host_file_without_guest_path(tracee, &raw_guest_path) {
+            debug!("fexecve() of {:?}, left untranslated", host_path);
+            tracee.new_exe = Some(Rc::new(RefCell::new(host_path)));
+            tracee.load_info = None;
+            return Ok(());
+        }
+        raw_guest_path = resolve_at(tracee, raw_guest_path)?;
+    }
+    let argv_addr = tracee.regs.get(Current, SysArg(argv_arg));
     let argv = params::read_argv(tracee.pid, argv_addr as _)?;
 
     //TODO: implement runner for qemu
@@ -50,15 +117,27 @@ pub fn translate(tracee: &mut Tracee, loader: &dyn LoaderFile) -> Result<()> {
     // Save the loader path in the register, so that the loader will be executed
     // instead.
     tracee.regs.set_sysarg_path(
-        SysArg1,
+        path_arg,
         loader.get_loader_path(),
         "during enter execve translation, setting new loader path",
     )?;
+    if sys_num as i64 == crate::sc::nr::EXECVEAT {
+        tracee.regs.set(
+            SysArg(SysArg1),
+            libc::AT_FDCWD as _,
+            "during enter execveat translation, setting dirfd",
+        );
+        tracee.regs.set(
+            SysArg(SysArg5),
+            0,
+            "during enter execveat translation, clearing flags",
+        );
+    }
     // Update argv of `execve()`
     params::write_argv(tracee, &parameters.argv)
         .map(|addr| {
             tracee.regs.set(
-                SysArg(SysArg2),
+                SysArg(argv_arg),
                 addr as _,
                 "during enter execve translation, setting new argv",
             )
@@ -83,9 +162,12 @@ mod tests {
         register::{Current, Original, PtraceReader},
         utils::tests::get_test_rootfs_path,
     };
-    use nix::unistd::execvp;
-    use crate::sc::nr::{CLOCK_NANOSLEEP, EXECVE, NANOSLEEP};
+    use nix::fcntl::{open, OFlag};
+    use nix::sys::stat::Mode;
+    use nix::unistd::{execveat, execvp, fexecve};
+    use crate::sc::nr::{CLOCK_NANOSLEEP, EXECVE, EXECVEAT, NANOSLEEP};
     use std::ffi::CString;
+    use std::os::fd::AsRawFd;
 
     #[test]
     fn test_execve_translate_enter() {
@@ -132,4 +214,99 @@ mod tests {
             },
         );
     }
+
+    #[test]
+    fn test_execveat_translate_enter() {
+        let rootfs_path = get_test_rootfs_path();
+        let mut at_least_one_translation_occured = false;
+
+        fork_test(
+            rootfs_path,
+            // expecting a normal execution
+            0,
+            // parent
+            |tracee, info_bag| {
+                tracee.regs.save_current_regs(Original);
+                if tracee.regs.get_sys_num(Current) == EXECVEAT.try_into().unwrap() {
+                    assert_eq!(Ok(()), translate(tracee, &info_bag.loader));
+                    // the loader is executed instead, relative to the working directory
+                    assert_eq!(
+                        tracee.regs.get(Current, SysArg(SysArg1)) as i32,
+                        libc::AT_FDCWD
+                    );
+                    at_least_one_translation_occured = true;
+                    false
+                } else if tracee.regs.get_sys_num(Current) == NANOSLEEP.try_into().unwrap()
+                    || tracee.regs.get_sys_num(Current) == CLOCK_NANOSLEEP.try_into().unwrap()
+                {
+                    assert!(at_least_one_translation_occured);
+                    true
+                } else {
+                    false
+                }
+            },
+            // child
+            || {
+                // `sleep` relative to the directory `/bin`
+                let dirfd = open("/bin", OFlag::O_RDONLY | OFlag::O_DIRECTORY, Mode::empty())
+                    .expect("failed to open /bin");
+                execveat(
+                    Some(dirfd),
+                    &CString::new("sleep").unwrap(),
+                    &[CString::new(".").unwrap(), CString::new("0").unwrap()],
+                    &[] as &[CString],
+                    nix::fcntl::AtFlags::empty(),
+                )
+                .expect("failed execveat sleep");
+            },
+        );
+    }
+
+    #[test]
+    fn test_fexecve_memfd_untranslated() {
+        let rootfs_path = get_test_rootfs_path();
+        let mut at_least_one_translation_occured = false;
+
+        fork_test(
+            rootfs_path,
+            // expecting a normal execution
+            0,
+            // parent
+            |tracee, info_bag| {
+                tracee.regs.save_current_regs(Original);
+                if tracee.regs.get_sys_num(Current) == EXECVEAT.try_into().unwrap() {
+                    let dirfd = tracee.regs.get(Current, SysArg(SysArg1));
+                    assert_eq!(Ok(()), translate(tracee, &info_bag.loader));
+                    // the memfd is executed as is
+                    assert_eq!(tracee.regs.get(Current, SysArg(SysArg1)), dirfd);
+                    assert!(tracee.load_info.is_none());
+                    at_least_one_translation_occured = true;
+                    false
+                } else if tracee.regs.get_sys_num(Current) == NANOSLEEP.try_into().unwrap()
+                    || tracee.regs.get_sys_num(Current) == CLOCK_NANOSLEEP.try_into().unwrap()
+                {
+                    assert!(at_least_one_translation_occured);
+                    true
+                } else {
+                    false
+                }
+            },
+            // child
+            || {
+                let memfd = nix::sys::memfd::memfd_create(
+                    &CString::new("sleep").unwrap(),
+                    nix::sys::memfd::MemFdCreateFlag::empty(),
+                )
+                .expect("failed memfd_create");
+                let content = std::fs::read("/bin/sleep").expect("failed to read /bin/sleep");
+                nix::unistd::write(&memfd, &content).expect("failed to write the memfd");
+                fexecve(
+                    memfd.as_raw_fd(),
+                    &[CString::new(".").unwrap(), CString::new("0").unwrap()],
+                    &[] as &[CString],
+                )
+                .expect("failed fexecve sleep");
+            },
+        );
+    }
 }
@@ -29,10 +29,19 @@ pub fn translate(tracee: &mut Tracee) -> Result<()> {
         tracee.exe = tracee.new_exe.take();
     }
 
+    // The table of file descriptors is unshared, and the ones flagged with
+    // `FD_CLOEXEC` are closed.
+    tracee.unshare_fds();
+    tracee.fds.borrow_mut().exec();
+
     //TODO: implement heap
     // New processes have no heap.
     //bzero(tracee->heap, sizeof(Heap));
 
+    // Nothing was loaded by pronyx for an untranslated `fexecve()`.
+    if tracee.load_info.is_none() {
+        return Ok(());
+    }
     let res = transfert_load_script(tracee);
     tracee.load_info = None;
     res
@@ -2,11 +2,12 @@ use std::ffi::CString;
 use std::path::PathBuf;
 
 use libc::c_void;
-use nix::{sys::ptrace, unistd::Pid};
+use nix::unistd::Pid;
 
 use crate::errors::*;
 use crate::filesystem::{FileSystem, Translator};
 use crate::process::tracee::Tracee;
+use crate::register::memory;
 use crate::register::{PtraceWriter, Word};
 use crate::utils::AsU8Slice;
 
@@ -70,26 +71,17 @@ impl ExecveParameters {
 /// read. The contents of the C strings and the trailing null pointers will not
 /// be read.
 pub(super) fn read_argv(pid: Pid, addr: *const c_void) -> Result<Vec<Arg>> {
-    let mut argv = vec![];
-    let mut i = 0;
+    let words = memory::read_words(pid, addr as Word).with_context(|| {
+        format!(
+            "Failed to read argv from tracee. pid: {}, addr: 0x{:x?}",
+            pid, addr
+        )
+    })?;
 
-    loop {
-        let word = ptrace::read(pid, unsafe { (addr as *mut Word).offset(i) } as _).with_context(
-            || {
-                format!(
-                    "Failed to read argv from tracee. pid: {}, addr: 0x{:x?}, offset: {}",
-                    pid, addr, i
-                )
-            },
-        )?;
-        if word == 0 {
-            break;
-        }
-        argv.push(Arg::CStringInTracee(word as *const c_void));
-        i += 1;
-    }
-
-    Ok(argv)
+    Ok(words
+        .into_iter()
+        .map(|word| Arg::CStringInTracee(word as *const c_void))
+        .collect())
 }
 
 /// Write arguments list (`argv`) into a tracee's memory space.
@@ -1,3 +1,4 @@
+use crate::errors::Result;
 use crate::kernel::execve;
 use crate::kernel::groups::{syscall_group_from_sysnum, SyscallGroup};
 use crate::kernel::heap::*;
@@ -6,27 +7,55 @@ use crate::kernel::socket::*;
 use crate::kernel::standard::*;
 use crate::process::tracee::Tracee;
 use crate::register::{Original, SysResult, Word};
+use crate::utils::Config;
 
-pub fn translate(tracee: &mut Tracee) {
+/// The exit stage of a group of syscalls.
+type Handler = fn(&mut Tracee, &Config) -> Result<()>;
+
+/// The exit stage of the syscalls of `group`, if they have one.
+fn handler(group: SyscallGroup) -> Option<Handler> {
+    let handler: Handler = match group {
+        SyscallGroup::Brk => |_, _| brk::exit(),
+        SyscallGroup::GetCwd => |tracee, _| getcwd::exit(tracee),
+        SyscallGroup::Accept => |_, _| accept::exit(),
+        SyscallGroup::GetSockOrPeerName => |_, _| get_sockorpeer_name::exit(),
+        SyscallGroup::SocketCall => |_, _| socketcall::exit(),
+        SyscallGroup::Chdir => |tracee, _| chdir::exit(tracee),
+        SyscallGroup::Rename => |tracee, _| link_rename::exit(tracee),
+        SyscallGroup::RenameAt => |tracee, _| rename_at::exit(tracee),
+        SyscallGroup::ReadLink | SyscallGroup::ReadLinkAt => |tracee, _| readlink_at::exit(tracee),
+        SyscallGroup::Uname => uname::exit,
+        SyscallGroup::Execve => |tracee, _| execve::exit(tracee),
+        SyscallGroup::Ptrace => |_, _| ptrace::exit(),
+        SyscallGroup::Wait => |_, _| wait::exit(),
+        SyscallGroup::Open => |tracee, _| open::exit(tracee),
+        SyscallGroup::OpenAt => |tracee, _| open_at::exit(tracee),
+        SyscallGroup::Dup => |tracee, _| dup::exit(tracee),
+        SyscallGroup::Fcntl => |tracee, _| fcntl::exit(tracee),
+        SyscallGroup::StatFs => |tracee, _| stat_fs::exit(tracee),
+        SyscallGroup::Xattr => |tracee, _| xattr::exit(tracee),
+        SyscallGroup::DirLinkAttr => |tracee, _| dir_link_attr::exit(tracee),
+        SyscallGroup::UnlinkMkdirAt => |tracee, _| unlink_mkdir_at::exit(tracee),
+        SyscallGroup::MkNod => |tracee, _| mknod::exit(tracee),
+        SyscallGroup::StandardSyscall => |tracee, _| standard_syscall::exit(tracee),
+        SyscallGroup::StatAt => |tracee, _| stat_at::exit(tracee),
+        _ => return None,
+    };
+    Some(handler)
+}
+
+/// Whether `translate()` does anything for the syscall `syscall_number`.
+pub fn is_needed(syscall_number: usize) -> bool {
+    handler(syscall_group_from_sysnum(syscall_number as i64)).is_some()
+}
+
+pub fn translate(tracee: &mut Tracee, config: &Config) {
     let syscall_number = tracee.regs.get_sys_num(Original);
     let syscall_group = syscall_group_from_sysnum(syscall_number as i64);
 
-    let result = match syscall_group {
-        SyscallGroup::Brk => brk::exit(),
-        SyscallGroup::GetCwd => getcwd::exit(tracee),
-        SyscallGroup::Accept => accept::exit(),
-        SyscallGroup::GetSockOrPeerName => get_sockorpeer_name::exit(),
-        SyscallGroup::SocketCall => socketcall::exit(),
-        SyscallGroup::Chdir => chdir::exit(tracee),
-        SyscallGroup::Rename => link_rename::exit(tracee),
-        SyscallGroup::RenameAt => rename_at::exit(tracee),
-        SyscallGroup::ReadLinkAt => readlink_at::exit(),
-        #[cfg(all(target_os = "linux", target_arch = "x86_64"))]
-        SyscallGroup::Uname => uname::exit(),
-        SyscallGroup::Execve => execve::exit(tracee),
-        SyscallGroup::Ptrace => ptrace::exit(),
-        SyscallGroup::Wait => wait::exit(),
-        _ => Ok(()),
+    let result = match handler(syscall_group) {
+        Some(handler) => handler(tracee, config),
+        None => Ok(()),
     };
 
     if let Err(error) = result {
@@ -34,7 +63,7 @@ pub fn translate(tracee: &mut Tracee) {
         tracee.regs.set(
             SysResult,
             // errno is negative
-            error.get_errno() as Word,
+            (-(error.get_errno() as i32)) as Word,
             "following error during exit translation, setting errno",
         );
     };
@@ -21,6 +21,7 @@ pub enum SyscallGroup {
     StatAt,
     ChmodAccessMkNodAt,
     InotifyAddWatch,
+    FanotifyMark,
     DirLinkAttr,
     PivotRoot,
     LinkAt,
@@ -35,6 +36,12 @@ pub enum SyscallGroup {
     SymLinkAt,
     Uname,
     UnlinkMkdirAt,
+    Dup,
+    Fcntl,
+    Close,
+    StatFs,
+    Xattr,
+    MkNod,
 }
 
 
@@ -47,7 +54,7 @@ pub enum SyscallGroup {
 #[cfg(any(target_os = "linux", target_os = "android"))]
 pub fn syscall_group_from_sysnum(sysnum: i64) -> SyscallGroup {
     match sysnum {
-        crate::sc::nr::EXECVE => SyscallGroup::Execve,
+        crate::sc::nr::EXECVE | crate::sc::nr::EXECVEAT => SyscallGroup::Execve,
         crate::sc::nr::PTRACE => SyscallGroup::Ptrace,
         crate::sc::nr::WAIT4 => SyscallGroup::Wait,
         #[cfg(any(target_arch = "x86"))]
@@ -66,10 +73,6 @@ pub fn syscall_group_from_sysnum(sysnum: i64) -> SyscallGroup {
         // int syscall(const char *pathname, ...) follow symlink
         crate::sc::nr::ACCT
         | crate::sc::nr::CHROOT
-        | crate::sc::nr::GETXATTR
-        | crate::sc::nr::LISTXATTR
-        | crate::sc::nr::REMOVEXATTR
-        | crate::sc::nr::SETXATTR
         | crate::sc::nr::SWAPOFF
         | crate::sc::nr::SWAPON
         | crate::sc::nr::TRUNCATE
@@ -77,7 +80,7 @@ pub fn syscall_group_from_sysnum(sysnum: i64) -> SyscallGroup {
         #[cfg(any(target_arch = "x86"))]
         crate::sc::nr::OLDSTAT | crate::sc::nr::UMOUNT => SyscallGroup::StandardSyscall,
         #[cfg(any(target_arch = "x86", target_arch = "arm"))]
-        crate::sc::nr::CHOWN32 | crate::sc::nr::STAT64 | crate::sc::nr::STATFS64 | crate::sc::nr::TRUNCATE64 => {
+        crate::sc::nr::CHOWN32 | crate::sc::nr::STAT64 | crate::sc::nr::TRUNCATE64 => {
             SyscallGroup::StandardSyscall
         }
         #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
@@ -86,7 +89,6 @@ pub fn syscall_group_from_sysnum(sysnum: i64) -> SyscallGroup {
         crate::sc::nr::ACCESS
         | crate::sc::nr::CHMOD
         | crate::sc::nr::CHOWN
-        | crate::sc::nr::MKNOD
         | crate::sc::nr::CREAT
         | crate::sc::nr::STAT
         | crate::sc::nr::USELIB
@@ -106,16 +108,18 @@ pub fn syscall_group_from_sysnum(sysnum: i64) -> SyscallGroup {
         crate::sc::nr::NEWFSTATAT => SyscallGroup::StatAt,
 
         // int syscall(int dirfd, const char *pathname, ...)
-        crate::sc::nr::FCHMODAT | crate::sc::nr::FACCESSAT | crate::sc::nr::MKNODAT => SyscallGroup::ChmodAccessMkNodAt,
+        crate::sc::nr::FCHMODAT | crate::sc::nr::FACCESSAT => SyscallGroup::ChmodAccessMkNodAt,
         #[cfg(any(target_arch = "x86", target_arch = "x86_64", target_arch = "arm"))]
         crate::sc::nr::FUTIMESAT => SyscallGroup::ChmodAccessMkNodAt,
 
+        crate::sc::nr::MKNODAT => SyscallGroup::MkNod,
+        #[cfg(any(target_arch = "x86", target_arch = "x86_64", target_arch = "arm"))]
+        crate::sc::nr::MKNOD => SyscallGroup::MkNod,
+
         crate::sc::nr::INOTIFY_ADD_WATCH => SyscallGroup::InotifyAddWatch,
+        crate::sc::nr::FANOTIFY_MARK => SyscallGroup::FanotifyMark,
 
         // int syscall(const char *pathname, ...) not follow symlink
-        crate::sc::nr::LGETXATTR | crate::sc::nr::LLISTXATTR | crate::sc::nr::LREMOVEXATTR | crate::sc::nr::LSETXATTR => {
-            SyscallGroup::DirLinkAttr
-        }
         #[cfg(any(target_arch = "x86"))]
         crate::sc::nr::OLDLSTAT => SyscallGroup::DirLinkAttr,
         #[cfg(any(target_arch = "x86", target_arch = "arm"))]
@@ -137,11 +141,35 @@ pub fn syscall_group_from_sysnum(sysnum: i64) -> SyscallGroup {
         crate::sc::nr::LINK => SyscallGroup::Link,
         #[cfg(any(target_arch = "x86", target_arch = "x86_64", target_arch = "arm"))]
         crate::sc::nr::RENAME => SyscallGroup::Rename,
-        crate::sc::nr::RENAMEAT => SyscallGroup::RenameAt,
+        crate::sc::nr::RENAMEAT | crate::sc::nr::RENAMEAT2 => SyscallGroup::RenameAt,
         #[cfg(any(target_arch = "x86", target_arch = "x86_64", target_arch = "arm"))]
         crate::sc::nr::SYMLINK => SyscallGroup::SymLink,
         crate::sc::nr::SYMLINKAT => SyscallGroup::SymLinkAt,
         crate::sc::nr::UNAME => SyscallGroup::Uname,
+        crate::sc::nr::STATFS | crate::sc::nr::FSTATFS => SyscallGroup::StatFs,
+        crate::sc::nr::GETXATTR
+        | crate::sc::nr::LGETXATTR
+        | crate::sc::nr::FGETXATTR
+        | crate::sc::nr::LISTXATTR
+        | crate::sc::nr::LLISTXATTR
+        | crate::sc::nr::FLISTXATTR
+        | crate::sc::nr::REMOVEXATTR
+        | crate::sc::nr::LREMOVEXATTR
+        | crate::sc::nr::FREMOVEXATTR
+        | crate::sc::nr::SETXATTR
+        | crate::sc::nr::LSETXATTR
+        | crate::sc::nr::FSETXATTR => SyscallGroup::Xattr,
+        #[cfg(any(target_arch = "x86", target_arch = "arm"))]
+        crate::sc::nr::STATFS64 | crate::sc::nr::FSTATFS64 => SyscallGroup::StatFs,
+
+        // syscalls which only change the table of file descriptors
+        crate::sc::nr::DUP | crate::sc::nr::DUP3 => SyscallGroup::Dup,
+        #[cfg(any(target_arch = "x86", target_arch = "x86_64", target_arch = "arm"))]
+        crate::sc::nr::DUP2 => SyscallGroup::Dup,
+        crate::sc::nr::FCNTL => SyscallGroup::Fcntl,
+        #[cfg(any(target_arch = "x86", target_arch = "arm"))]
+        crate::sc::nr::FCNTL64 => SyscallGroup::Fcntl,
+        crate::sc::nr::CLOSE | crate::sc::nr::CLOSE_RANGE => SyscallGroup::Close,
         _ => SyscallGroup::Ignored,
     }
 }
@@ -1,6 +1,7 @@
 use std::os::unix::prelude::RawFd;
 
 use crate::errors::*;
+use crate::kernel::syscall;
 use crate::process::tracee::Tracee;
 use crate::register::PtraceWriter;
 use crate::register::{Current, PtraceReader, SysArg, SysArg1, SysArg2};
@@ -11,14 +12,16 @@ pub fn enter(tracee: &mut Tracee) -> Result<()> {
     let raw_path = tracee.regs.get_sysarg_path(SysArg2)?;
 
     let deref_final = match sys_num as i64 {
-        crate::sc::nr::MKNODAT => false, /* By default, mknodat() will not follow a symbolic link. https://man7.org/linux/man-pages/man2/mknod.2.html */
         #[cfg(any(target_arch = "x86", target_arch = "x86_64", target_arch = "arm"))]
         crate::sc::nr::FUTIMESAT => true,
         crate::sc::nr::FACCESSAT | crate::sc::nr::FCHMODAT => true,
         _ => true,
     };
 
-    let host_path = tracee.translate_path_at(dirfd, raw_path, deref_final)?.1;
+    let (guest_path, host_path) = tracee.translate_path_at(dirfd, raw_path, deref_final)?;
+    if syscall::modifies_path(sys_num) {
+        tracee.fs.borrow().check_writable(&guest_path)?;
+    }
 
     tracee.regs.set_sysarg_path(
         SysArg2,
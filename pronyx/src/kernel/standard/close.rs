@@ -0,0 +1,36 @@
+use std::os::unix::io::RawFd;
+
+use crate::errors::*;
+use crate::process::tracee::Tracee;
+use crate::register::{Current, SysArg, SysArg1, SysArg2, SysArg3};
+
+/// Flags of `close_range()`, see `<linux/close_range.h>`.
+const CLOSE_RANGE_UNSHARE: u32 = 1 << 1;
+const CLOSE_RANGE_CLOEXEC: u32 = 1 << 2;
+
+/// Translates `close()` and `close_range()`.
+///
+/// The file descriptors are forgotten at the enter stage, so that a
+/// descriptor opened by another thread right after the close can't be
+/// forgotten instead. Forgetting a descriptor which is not closed in the end
+/// is harmless, see `FdTable`.
+pub fn enter(tracee: &mut Tracee) -> Result<()> {
+    let first = tracee.regs.get(Current, SysArg(SysArg1)) as RawFd;
+
+    if tracee.regs.get_sys_num(Current) == crate::sc::nr::CLOSE as usize {
+        tracee.fds.borrow_mut().close(first);
+        return Ok(());
+    }
+
+    // `last` is an unsigned int, usually `~0U`.
+    let last = (tracee.regs.get(Current, SysArg(SysArg2)) as u32).min(RawFd::MAX as u32) as RawFd;
+    let flags = tracee.regs.get(Current, SysArg(SysArg3)) as u32;
+    if flags & CLOSE_RANGE_UNSHARE != 0 {
+        tracee.unshare_fds();
+    }
+    tracee
+        .fds
+        .borrow_mut()
+        .close_range(first, last, flags & CLOSE_RANGE_CLOEXEC != 0);
+    Ok(())
+}
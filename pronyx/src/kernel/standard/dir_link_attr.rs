@@ -1,10 +1,12 @@
 use crate::errors::*;
 
 use crate::filesystem::ext::PathExt;
-use crate::filesystem::Translator;
+use crate::kernel::standard::mknod::{self, StatBuffer};
+use crate::kernel::standard::unlink_mkdir_at;
+use crate::kernel::syscall;
 use crate::process::tracee::Tracee;
 use crate::register::PtraceWriter;
-use crate::register::{Current, PtraceReader, SysArg1};
+use crate::register::{Current, Original, PtraceReader, SysArg1, SysArg2};
 
 pub fn enter(tracee: &mut Tracee) -> Result<()> {
     let sys_num = tracee.regs.get_sys_num(Current);
@@ -25,7 +27,17 @@ pub fn enter(tracee: &mut Tracee) -> Result<()> {
             }
         }
     };
-    let host_path = tracee.fs.borrow().translate_path(raw_path, deref_final)?.1;
+    let (guest_path, host_path) = tracee.translate_path(raw_path, deref_final)?;
+    if syscall::modifies_path(sys_num) {
+        tracee.fs.borrow().check_writable(&guest_path)?;
+    }
+    #[cfg(any(target_arch = "x86", target_arch = "x86_64", target_arch = "arm"))]
+    if matches!(
+        sys_num as i64,
+        crate::sc::nr::UNLINK | crate::sc::nr::RMDIR | crate::sc::nr::MKDIR
+    ) {
+        tracee.fs.borrow().forget_translations(&host_path);
+    }
 
     tracee.regs.set_sysarg_path(
         SysArg1,
@@ -35,6 +47,18 @@ pub fn enter(tracee: &mut Tracee) -> Result<()> {
     Ok(())
 }
 
+pub fn exit(tracee: &mut Tracee) -> Result<()> {
+    match tracee.regs.get_sys_num(Original) as i64 {
+        #[cfg(any(target_arch = "x86", target_arch = "x86_64", target_arch = "arm"))]
+        crate::sc::nr::UNLINK | crate::sc::nr::RMDIR => unlink_mkdir_at::forget_removed(tracee, SysArg1),
+        #[cfg(target_arch = "x86_64")]
+        crate::sc::nr::LSTAT => mknod::report_device(tracee, SysArg1, SysArg2, StatBuffer::Stat),
+        #[cfg(any(target_arch = "x86", target_arch = "arm"))]
+        crate::sc::nr::LSTAT64 => mknod::report_device(tracee, SysArg1, SysArg2, StatBuffer::Stat),
+        _ => Ok(()),
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use std::fs::File;
@@ -0,0 +1,94 @@
+use std::os::unix::io::RawFd;
+
+use nix::fcntl::OFlag;
+
+use crate::errors::*;
+use crate::process::tracee::Tracee;
+use crate::register::{Current, Original, SysArg, SysArg1, SysArg3, SysResult};
+
+/// Translates `dup()`, `dup2()` and `dup3()`: the new file descriptor
+/// designates the same file as the old one.
+pub fn exit(tracee: &mut Tracee) -> Result<()> {
+    let new_fd = tracee.regs.get(Current, SysResult) as RawFd;
+    // Error reported by the kernel.
+    if new_fd < 0 {
+        return Ok(());
+    }
+
+    let old_fd = tracee.regs.get(Original, SysArg(SysArg1)) as RawFd;
+    let cloexec = tracee.regs.get_sys_num(Original) == crate::sc::nr::DUP3 as usize
+        && OFlag::from_bits_truncate(tracee.regs.get(Original, SysArg(SysArg3)) as _)
+            .contains(OFlag::O_CLOEXEC);
+    tracee.fds.borrow_mut().dup(old_fd, new_fd, cloexec);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::os::unix::io::AsRawFd;
+    use std::path::Path;
+
+    use nix::fcntl::{self, FcntlArg, FdFlag, OFlag};
+    use nix::sys::stat::Mode;
+    use nix::unistd::{self, Whence};
+
+    use crate::filesystem::binding::Side;
+    use crate::register::{Current, SysArg, SysArg1, SysArg2};
+    use crate::utils::tests::test_with_proot;
+
+    /// Unit test for the following syscalls:
+    /// - dup
+    /// - dup2
+    /// - dup3
+    /// - fcntl
+    /// - close
+    #[test]
+    fn test_dup_fcntl_close() {
+        test_with_proot(
+            |tracee, is_sysenter, before_translation| {
+                // The tracee tells with `lseek(fd, known, SEEK_CUR)` whether the
+                // path of `fd` should be known.
+                if is_sysenter
+                    && before_translation
+                    && tracee.regs.get_sys_num(Current) == crate::sc::nr::SYS_lseek as usize
+                {
+                    let fd = tracee.regs.get(Current, SysArg(SysArg1)) as i32;
+                    let known = tracee.regs.get(Current, SysArg(SysArg2)) == 1;
+                    let path = tracee.fds.borrow().get(fd).map(Path::to_path_buf);
+                    assert_eq!(path.as_deref(), known.then(|| Path::new("/etc")));
+                    if known {
+                        assert_eq!(
+                            tracee.get_path_from_fd(fd, Side::Guest).unwrap(),
+                            Path::new("/etc")
+                        );
+                    }
+                }
+            },
+            || {
+                let check = |fd, known: bool| {
+                    let _ = unistd::lseek(fd, known as i64, Whence::SeekCur);
+                };
+                let fd = fcntl::open("/etc", OFlag::O_RDONLY, Mode::empty()).unwrap();
+                check(fd, true);
+
+                let fd2 = unistd::dup(fd).unwrap();
+                check(fd2, true);
+                let fd3 = unistd::dup2(fd, 42).unwrap();
+                check(fd3, true);
+                let fd4 = unistd::dup3(fd, 43, OFlag::O_CLOEXEC).unwrap();
+                check(fd4, true);
+                let fd5 = fcntl::fcntl(fd, FcntlArg::F_DUPFD_CLOEXEC(50)).unwrap();
+                check(fd5, true);
+                fcntl::fcntl(fd5, FcntlArg::F_SETFD(FdFlag::empty())).unwrap();
+
+                unistd::close(fd2).unwrap();
+                check(fd2, false);
+                // replace a known descriptor with an unknown one
+                let (pipe, _) = unistd::pipe().unwrap();
+                unistd::dup2(pipe.as_raw_fd(), fd3).unwrap();
+                check(fd3, false);
+                check(fd, true);
+            },
+        )
+    }
+}
@@ -0,0 +1,110 @@
+use std::os::unix::io::RawFd;
+
+use crate::errors::Result;
+use crate::process::tracee::Tracee;
+use crate::register::{Current, PtraceReader, PtraceWriter, SysArg, SysArg2, SysArgIndex};
+#[cfg(any(target_arch = "x86_64", target_arch = "aarch64"))]
+use crate::register::{SysArg4, SysArg5};
+#[cfg(any(target_arch = "x86", target_arch = "arm"))]
+use crate::register::{SysArg5, SysArg6};
+
+/// Flags of `fanotify_mark()`, see `<sys/fanotify.h>`.
+const FAN_MARK_DONT_FOLLOW: u32 = 0x04;
+const FAN_MARK_FLUSH: u32 = 0x80;
+
+/// The arguments of the directory and of the path: the 64-bit mask takes two
+/// arguments on 32-bit architectures.
+#[cfg(any(target_arch = "x86_64", target_arch = "aarch64"))]
+const DIRFD_PATH_ARGS: (SysArgIndex, SysArgIndex) = (SysArg4, SysArg5);
+#[cfg(any(target_arch = "x86", target_arch = "arm"))]
+const DIRFD_PATH_ARGS: (SysArgIndex, SysArgIndex) = (SysArg5, SysArg6);
+
+/// Translates the path of `fanotify_mark()`, relative to its directory, so
+/// that the marked file is the one the guest sees. Without a path, the
+/// directory itself is marked and there is nothing to translate.
+pub fn enter(tracee: &mut Tracee) -> Result<()> {
+    let (dirfd_arg, path_arg) = DIRFD_PATH_ARGS;
+    let flags = tracee.regs.get(Current, SysArg(SysArg2)) as u32;
+    let raw_path = tracee.regs.get_sysarg_path(path_arg)?;
+    if flags & FAN_MARK_FLUSH != 0 || raw_path.as_os_str().is_empty() {
+        return Ok(());
+    }
+
+    let dirfd = tracee.regs.get(Current, SysArg(dirfd_arg)) as RawFd;
+    let deref_final = flags & FAN_MARK_DONT_FOLLOW == 0;
+    let host_path = tracee.translate_path_at(dirfd, raw_path, deref_final)?.1;
+
+    tracee.regs.set_sysarg_path(
+        path_arg,
+        &host_path,
+        "during enter fanotify_mark translation, setting host path",
+    )?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::ffi::CString;
+    use std::fs::File;
+    use std::ptr;
+
+    use nix::{errno::Errno, fcntl::OFlag, sys::stat::Mode};
+
+    use crate::utils::tests::test_with_proot;
+
+    /// Unit test for `fanotify_mark()`, with a path relative to a directory.
+    /// Skipped when fanotify is not available to the user.
+    ///
+    /// `nc::fanotify_mark()` frees the path before the syscall, hence libc.
+    #[test]
+    fn test_fanotify_mark() {
+        test_with_proot(
+            |_tracee, _is_sysenter, _before_translation| {},
+            || {
+                let filepath = "/tmp/file_for_test_fanotify_mark";
+                let filename = CString::new("file_for_test_fanotify_mark").unwrap();
+
+                let result = std::panic::catch_unwind(|| unsafe {
+                    File::create(filepath).unwrap();
+                    let group =
+                        libc::fanotify_init(libc::FAN_CLASS_NOTIF | libc::FAN_REPORT_FID, 0);
+                    if group < 0 {
+                        return;
+                    }
+                    let dirfd = nix::fcntl::open("/tmp", OFlag::O_RDONLY, Mode::empty()).unwrap();
+                    let mark = |flags, dirfd, path: *const libc::c_char| {
+                        Errno::result(libc::fanotify_mark(
+                            group,
+                            flags,
+                            libc::FAN_ATTRIB,
+                            dirfd,
+                            path,
+                        ))
+                    };
+
+                    mark(libc::FAN_MARK_ADD, dirfd, filename.as_ptr()).unwrap();
+                    let filepath = CString::new(filepath).unwrap();
+                    mark(
+                        libc::FAN_MARK_ADD | libc::FAN_MARK_DONT_FOLLOW,
+                        libc::AT_FDCWD,
+                        filepath.as_ptr(),
+                    )
+                    .unwrap();
+                    let missing = CString::new("non_existent_file").unwrap();
+                    assert_eq!(
+                        mark(libc::FAN_MARK_ADD, dirfd, missing.as_ptr()),
+                        Err(Errno::ENOENT)
+                    );
+                    // the directory itself
+                    mark(libc::FAN_MARK_ADD, dirfd, ptr::null()).unwrap();
+                    libc::close(group);
+                });
+                std::fs::remove_file(filepath).unwrap();
+                if let Err(err) = result {
+                    std::panic::resume_unwind(err);
+                }
+            },
+        )
+    }
+}
@@ -0,0 +1,33 @@
+use std::os::unix::io::RawFd;
+
+use nix::fcntl::FdFlag;
+
+use crate::errors::*;
+use crate::process::tracee::Tracee;
+use crate::register::{Current, Original, SysArg, SysArg1, SysArg2, SysArg3, SysResult};
+
+/// Translates `fcntl()`, which duplicates file descriptors (`F_DUPFD`,
+/// `F_DUPFD_CLOEXEC`) or sets their `FD_CLOEXEC` flag (`F_SETFD`).
+pub fn exit(tracee: &mut Tracee) -> Result<()> {
+    let result = tracee.regs.get(Current, SysResult) as RawFd;
+    // Error reported by the kernel.
+    if result < 0 {
+        return Ok(());
+    }
+
+    let fd = tracee.regs.get(Original, SysArg(SysArg1)) as RawFd;
+    let arg = tracee.regs.get(Original, SysArg(SysArg3));
+    match tracee.regs.get(Original, SysArg(SysArg2)) as i32 {
+        libc::F_DUPFD => tracee.fds.borrow_mut().dup(fd, result, false),
+        libc::F_DUPFD_CLOEXEC => tracee.fds.borrow_mut().dup(fd, result, true),
+        libc::F_SETFD => {
+            let flags = FdFlag::from_bits_truncate(arg as _);
+            tracee
+                .fds
+                .borrow_mut()
+                .set_cloexec(fd, flags.contains(FdFlag::FD_CLOEXEC));
+        }
+        _ => {}
+    }
+    Ok(())
+}
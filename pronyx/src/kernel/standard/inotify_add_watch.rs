@@ -1,12 +1,84 @@
 use crate::errors::Result;
+use crate::process::tracee::Tracee;
+use crate::register::PtraceWriter;
+use crate::register::{Current, PtraceReader, SysArg, SysArg2, SysArg3};
+
+/// Don't follow a final symlink, see `<sys/inotify.h>`.
+const IN_DONT_FOLLOW: u32 = 0x0200_0000;
+
+/// Translates the path of `inotify_add_watch()`, so that the watched file is
+/// the one the guest sees.
+pub fn enter(tracee: &mut Tracee) -> Result<()> {
+    let raw_path = tracee.regs.get_sysarg_path(SysArg2)?;
+    let mask = tracee.regs.get(Current, SysArg(SysArg3)) as u32;
+    let deref_final = mask & IN_DONT_FOLLOW == 0;
+
+    let host_path = tracee.translate_path(raw_path, deref_final)?.1;
+
+    tracee.regs.set_sysarg_path(
+        SysArg2,
+        &host_path,
+        "during enter inotify_add_watch translation, setting host path",
+    )?;
 
-pub fn enter() -> Result<()> {
     Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::File;
+    use std::os::unix::fs::PermissionsExt;
+
+    use crate::utils::tests::test_with_proot;
+
+    /// Unit test for `inotify_add_watch()`: the events of the watched file
+    /// are reported, and `IN_DONT_FOLLOW` watches the symlink itself.
+    #[test]
+    fn test_inotify_add_watch() {
+        test_with_proot(
+            |_tracee, _is_sysenter, _before_translation| {},
+            || {
+                let filepath = "/tmp/file_for_test_inotify_add_watch";
+                let linkpath = "/tmp/link_for_test_inotify_add_watch";
+
+                let result = std::panic::catch_unwind(|| {
+                    unsafe {
+                        File::create(filepath).unwrap();
+                        std::os::unix::fs::symlink(filepath, linkpath).unwrap();
+
+                        let fd = nc::inotify_init1(nc::IN_NONBLOCK).unwrap();
+                        let file_watch =
+                            nc::inotify_add_watch(fd, linkpath, nc::IN_ATTRIB).unwrap();
+                        // the same file, so the same watch
+                        assert_eq!(
+                            nc::inotify_add_watch(fd, filepath, nc::IN_ATTRIB).unwrap(),
+                            file_watch
+                        );
+                        let link_watch =
+                            nc::inotify_add_watch(fd, linkpath, nc::IN_ATTRIB | nc::IN_DONT_FOLLOW)
+                                .unwrap();
+                        assert_ne!(link_watch, file_watch);
+                        assert_eq!(
+                            nc::inotify_add_watch(fd, "/tmp/non_existent_file", nc::IN_ATTRIB),
+                            Err(nc::ENOENT)
+                        );
 
-    //                 flags = peek_reg(tracee, CURRENT, SYSARG_3);
-    //
-    //                 if ((flags & IN_DONT_FOLLOW) != 0)
-    //                     status = translate_sysarg(tracee, SYSARG_2, SYMLINK);
-    //                 else
-    //                     status = translate_sysarg(tracee, SYSARG_2, REGULAR);
+                        std::fs::set_permissions(filepath, std::fs::Permissions::from_mode(0o600))
+                            .unwrap();
+                        let mut buf = [0u8; 256];
+                        let length = nc::read(fd, &mut buf).unwrap() as usize;
+                        assert!(length >= std::mem::size_of::<libc::inotify_event>());
+                        let event = &*(buf.as_ptr() as *const libc::inotify_event);
+                        assert_eq!(event.wd, file_watch);
+                        nc::close(fd).unwrap();
+                    }
+                });
+                std::fs::remove_file(filepath).unwrap();
+                std::fs::remove_file(linkpath).unwrap();
+                if let Err(err) = result {
+                    std::panic::resume_unwind(err);
+                }
+            },
+        )
+    }
 }
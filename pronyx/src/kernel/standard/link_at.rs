@@ -17,8 +17,12 @@ pub fn enter(tracee: &mut Tracee) -> Result<()> {
     let flags = AtFlags::from_bits_truncate(tracee.regs.get(Current, SysArg(SysArg5)) as _);
     let deref_final = flags.contains(AtFlags::AT_SYMLINK_FOLLOW) || old_path.with_trailing_slash();
 
-    let old_host_path = tracee.translate_path_at(olddirfd, old_path, deref_final)?.1;
-    let new_host_path = tracee.translate_path_at(newdirfd, new_path, false)?.1;
+    let (old_guest_path, old_host_path) =
+        tracee.translate_path_at(olddirfd, old_path, deref_final)?;
+    let (new_guest_path, new_host_path) = tracee.translate_path_at(newdirfd, new_path, false)?;
+    tracee.fs.borrow().check_linkable(&old_guest_path)?;
+    tracee.fs.borrow().check_writable(&new_guest_path)?;
+    tracee.fs.borrow().forget_translations(&new_host_path);
 
     tracee.regs.set_sysarg_path(
         SysArg2,
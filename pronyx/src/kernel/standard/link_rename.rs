@@ -1,9 +1,11 @@
 use crate::errors::*;
+use crate::filesystem::binding::Side;
 use crate::filesystem::ext::PathExt;
-use crate::filesystem::Translator;
+use crate::filesystem::Substitutor;
+use crate::process::fd_table::FdChange;
 use crate::process::tracee::Tracee;
 use crate::register::PtraceWriter;
-use crate::register::{PtraceReader, SysArg1, SysArg2};
+use crate::register::{Current, Original, PtraceReader, SysArg1, SysArg2, SysResult};
 
 /// Translates link and rename kernel
 pub fn enter(tracee: &mut Tracee) -> Result<()> {
@@ -11,8 +13,18 @@ pub fn enter(tracee: &mut Tracee) -> Result<()> {
     let new_path = tracee.regs.get_sysarg_path(SysArg2)?;
     let deref_final = old_path.with_trailing_slash();
 
-    let old_host_path = tracee.fs.borrow().translate_path(old_path, deref_final)?.1;
-    let new_host_path = tracee.fs.borrow().translate_path(new_path, false)?.1;
+    let (old_guest_path, old_host_path) =
+        tracee.translate_path(old_path, deref_final)?;
+    let (new_guest_path, new_host_path) = tracee.translate_path(new_path, false)?;
+    #[cfg(any(target_arch = "x86", target_arch = "x86_64", target_arch = "arm"))]
+    if tracee.regs.get_sys_num(Original) == crate::sc::nr::RENAME as usize {
+        tracee.fs.borrow().check_writable(&old_guest_path)?;
+    } else {
+        tracee.fs.borrow().check_linkable(&old_guest_path)?;
+    }
+    tracee.fs.borrow().check_writable(&new_guest_path)?;
+    tracee.fs.borrow().forget_translations(&old_host_path);
+    tracee.fs.borrow().forget_translations(&new_host_path);
 
     tracee.regs.set_sysarg_path(
         SysArg1,
@@ -25,13 +37,32 @@ pub fn enter(tracee: &mut Tracee) -> Result<()> {
         "during enter open translation, setting host path",
     )?;
 
+    #[cfg(any(target_arch = "x86", target_arch = "x86_64", target_arch = "arm"))]
+    if tracee.regs.get_sys_num(Original) == crate::sc::nr::RENAME as usize {
+        tracee.fd_change = Some(FdChange::Rename(old_guest_path, new_guest_path));
+    }
+
     Ok(())
 }
 
 /// Translates `rename` and `rename_at` kernel
-pub fn exit(_tracee: &mut Tracee) -> Result<()> {
-    // TODO: How to track path changes of an opened file/dir?
-    // We also need to change the value of field `cwd` stored in tracee, if any part
+pub fn exit(tracee: &mut Tracee) -> Result<()> {
+    // Update the paths of the file descriptors of this tracee. The ones of the
+    // other tracees are resolved with /proc if they don't match anymore.
+    if let Some(FdChange::Rename(old_path, new_path)) = tracee.fd_change.take()
+        && (tracee.regs.get(Current, SysResult) as isize) >= 0
+    {
+        tracee.fds.borrow_mut().rename(&old_path, &new_path);
+        let fs = tracee.fs.borrow();
+        if let (Ok(old_host_path), Ok(new_host_path)) = (
+            fs.substitute(&old_path, Side::Guest),
+            fs.substitute(&new_path, Side::Guest),
+        ) {
+            fs.rename_emulated(&old_host_path, &new_host_path);
+        }
+    }
+
+    // TODO: We also need to change the value of field `cwd` stored in tracee, if any part
     // of cwd is modified by rename() or rename_at().
 
     //    char old_path[PATH_MAX];
@@ -118,6 +149,7 @@ mod tests {
     /// - link
     /// - rename
     /// - renameat
+    /// - renameat2
     #[test]
     fn test_link_rename() {
         test_with_proot(
@@ -181,6 +213,13 @@ mod tests {
                     assert_eq!(nc::lstat(renamed_filepath, &mut stat), Err(nc::ENOENT));
                     nc::lstat(rerenamed_filepath, &mut stat).unwrap();
                     assert_eq!((stat.st_mode as nc::mode_t & nc::S_IFMT), nc::S_IFREG);
+
+                    // test renameat2()
+                    nc::renameat2(fd, rerenamed_filename, fd, renamed_filename, nc::RENAME_NOREPLACE)
+                        .unwrap();
+                    assert_eq!(nc::lstat(rerenamed_filepath, &mut stat), Err(nc::ENOENT));
+                    nc::lstat(renamed_filepath, &mut stat).unwrap();
+                    assert_eq!((stat.st_mode as nc::mode_t & nc::S_IFMT), nc::S_IFREG);
                     }
                 });
 
@@ -0,0 +1,186 @@
+use std::fs::{self, OpenOptions, Permissions};
+use std::mem::offset_of;
+use std::os::unix::fs::PermissionsExt;
+use std::os::unix::io::RawFd;
+
+use libc::c_void;
+use nix::unistd::Pid;
+
+use crate::errors::*;
+use crate::filesystem::device::Device;
+use crate::process::tracee::Tracee;
+use crate::register::{memory, read_path, PtraceReader, PtraceWriter, Word};
+use crate::register::{Current, Modified, Original, SysArg, SysArgIndex, SysResult};
+use crate::register::{SysArg1, SysArg2, SysArg3, SysArg4};
+use crate::rootfs::OWNER_FILE_MODE;
+
+/// The buffer filled by `stat()`, `lstat()` and `newfstatat()`: `struct
+/// stat64` on 32-bit architectures, where the ones filling `struct stat` are
+/// not concerned.
+#[cfg(any(target_arch = "x86_64", target_arch = "aarch64"))]
+type KernelStat = libc::stat;
+#[cfg(any(target_arch = "x86", target_arch = "arm"))]
+type KernelStat = libc::stat64;
+
+/// Layout of the buffer filled by a syscall of the `stat()` family.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum StatBuffer {
+    Stat,
+    Statx,
+}
+
+/// The arguments of the path, the mode and the device number.
+fn arguments(sys_num: usize) -> (SysArgIndex, SysArgIndex, SysArgIndex) {
+    match sys_num as i64 {
+        crate::sc::nr::MKNODAT => (SysArg2, SysArg3, SysArg4),
+        _ => (SysArg1, SysArg2, SysArg3),
+    }
+}
+
+/// The umask of the process `pid`.
+fn umask(pid: Pid) -> Result<u32> {
+    let status = fs::read_to_string(format!("/proc/{}/status", pid))?;
+    status
+        .lines()
+        .find_map(|line| line.strip_prefix("Umask:"))
+        .and_then(|umask| u32::from_str_radix(umask.trim(), 8).ok())
+        .ok_or_else(|| Error::errno_with_msg(Errno::EINVAL, format!("{}: no umask", pid)))
+}
+
+/// Translates the path of `mknod()` and `mknodat()`. Under the fake root, a
+/// character or block device is created here as an empty regular file, and
+/// recorded in the store of the `device` module. The other types of files
+/// (regular files, FIFOs, sockets) are left to the kernel.
+pub fn enter(tracee: &mut Tracee) -> Result<()> {
+    let sys_num = tracee.regs.get_sys_num(Current);
+    let (path_arg, mode_arg, dev_arg) = arguments(sys_num);
+    let raw_path = tracee.regs.get_sysarg_path(path_arg)?;
+
+    // a final symlink is not followed: it exists, so mknod() fails
+    let (guest_path, host_path) = match sys_num as i64 {
+        crate::sc::nr::MKNODAT => {
+            let dirfd = tracee.regs.get(Current, SysArg(SysArg1)) as RawFd;
+            tracee.translate_path_at(dirfd, raw_path, false)?
+        }
+        _ => tracee.translate_path(raw_path, false)?,
+    };
+    tracee.fs.borrow().check_writable(&guest_path)?;
+
+    tracee.regs.set_sysarg_path(
+        path_arg,
+        &host_path,
+        "during enter mknod translation, setting host path",
+    )?;
+
+    let mode = tracee.regs.get(Current, SysArg(mode_arg)) as u32;
+    let rdev = tracee.regs.get(Current, SysArg(dev_arg)) as u32;
+    let device = match Device::from_mknod(mode, rdev) {
+        Some(device) if tracee.fs.borrow().devices().is_some() => device,
+        _ => return Ok(()),
+    };
+
+    let mode = mode & 0o7777 & !umask(tracee.pid)?;
+    // the owner can always read and write it, like an imported device
+    let host_mode = mode | OWNER_FILE_MODE;
+    OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(&host_path)?;
+    fs::set_permissions(&host_path, Permissions::from_mode(host_mode))?;
+    let fs = tracee.fs.borrow();
+    fs.devices()
+        .unwrap()
+        .record(&host_path, device, mode, host_mode);
+    drop(fs);
+    tracee
+        .regs
+        .cancel_syscall("device file emulated by pronyx, cancelling the syscall");
+
+    Ok(())
+}
+
+/// Reports the success of the emulated `mknod()`, see `enter()`.
+pub fn exit(tracee: &mut Tracee) -> Result<()> {
+    if tracee.fs.borrow().devices().is_none() {
+        return Ok(());
+    }
+    let (_, mode_arg, dev_arg) = arguments(tracee.regs.get_sys_num(Original));
+    let mode = tracee.regs.get(Original, SysArg(mode_arg)) as u32;
+    let rdev = tracee.regs.get(Original, SysArg(dev_arg)) as u32;
+    if Device::from_mknod(mode, rdev).is_some() {
+        tracee.regs.set(
+            SysResult,
+            0,
+            "during exit mknod translation, setting result",
+        );
+    }
+    Ok(())
+}
+
+/// Makes the result of a `stat()`-like syscall report the device type and
+/// number of an emulated device file. The host path of the file is in the
+/// argument `path_arg`, and the buffer (of layout `buffer`) in `buf_arg`.
+pub fn report_device(
+    tracee: &mut Tracee,
+    path_arg: SysArgIndex,
+    buf_arg: SysArgIndex,
+    buffer: StatBuffer,
+) -> Result<()> {
+    if tracee.fs.borrow().devices().is_none()
+        || (tracee.regs.get(Current, SysResult) as isize) < 0
+    {
+        return Ok(());
+    }
+    let host_path = read_path(
+        tracee.pid,
+        tracee.regs.get(Modified, SysArg(path_arg)) as *mut Word,
+    )?;
+    // `fstat()` and the like: the file descriptor is the host device itself
+    if host_path.as_os_str().is_empty() {
+        return Ok(());
+    }
+    let node = match tracee.fs.borrow().devices().unwrap().get(&host_path) {
+        Some(node) => node,
+        None => return Ok(()),
+    };
+
+    let address = tracee.regs.get(Original, SysArg(buf_arg));
+    let Device { major, minor, .. } = node.device;
+    match buffer {
+        StatBuffer::Stat => {
+            let mode_address = address + offset_of!(KernelStat, st_mode) as Word;
+            let mut mode = [0u8; size_of::<libc::mode_t>()];
+            memory::read_bytes(tracee.pid, mode_address, &mut mode)?;
+            let mode = libc::mode_t::from_ne_bytes(mode);
+            let mode = node.device.file_type() | node.mode.unwrap_or(mode & 0o7777);
+            tracee
+                .regs
+                .write_data(mode_address as *mut c_void, &mode.to_ne_bytes(), false)?;
+
+            let rdev_address = address + offset_of!(KernelStat, st_rdev) as Word;
+            let rdev: libc::dev_t = libc::makedev(major, minor);
+            tracee
+                .regs
+                .write_data(rdev_address as *mut c_void, &rdev.to_ne_bytes(), false)
+        }
+        StatBuffer::Statx => {
+            let mode_address = address + offset_of!(libc::statx, stx_mode) as Word;
+            let mut mode = [0u8; size_of::<u16>()];
+            memory::read_bytes(tracee.pid, mode_address, &mut mode)?;
+            let mode = u16::from_ne_bytes(mode) as u32;
+            let mode = node.device.file_type() | node.mode.unwrap_or(mode & 0o7777);
+            tracee.regs.write_data(
+                mode_address as *mut c_void,
+                &(mode as u16).to_ne_bytes(),
+                false,
+            )?;
+
+            // `stx_rdev_major` is followed by `stx_rdev_minor`
+            let rdev_address = address + offset_of!(libc::statx, stx_rdev_major) as Word;
+            let rdev = [major.to_ne_bytes(), minor.to_ne_bytes()].concat();
+            tracee
+                .regs
+                .write_data(rdev_address as *mut c_void, &rdev, false)
+        }
+    }
+}
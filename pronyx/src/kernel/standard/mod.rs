@@ -1,10 +1,15 @@
 pub mod chdir;
 pub mod chmod_access_mknod_at;
+pub mod close;
 pub mod dir_link_attr;
+pub mod dup;
+pub mod fanotify_mark;
+pub mod fcntl;
 pub mod getcwd;
 pub mod inotify_add_watch;
 pub mod link_at;
 pub mod link_rename;
+pub mod mknod;
 pub mod mount;
 pub mod open;
 pub mod open_at;
@@ -13,7 +18,9 @@ pub mod readlink_at;
 pub mod rename_at;
 pub mod standard_syscall;
 pub mod stat_at;
+pub mod stat_fs;
 pub mod sym_link;
 pub mod sym_link_at;
 pub mod uname;
 pub mod unlink_mkdir_at;
+pub mod xattr;
@@ -1,11 +1,16 @@
+use std::os::unix::io::RawFd;
+use std::path::PathBuf;
+
 use nix::fcntl::OFlag;
 
 use crate::errors::*;
 
-use crate::filesystem::Translator;
+use crate::filesystem::binding::Side::Guest;
+use crate::filesystem::Substitutor;
+use crate::process::fd_table::FdChange;
 use crate::process::tracee::Tracee;
 use crate::register::PtraceWriter;
-use crate::register::{Current, PtraceReader, SysArg, SysArg1, SysArg2};
+use crate::register::{Current, Original, PtraceReader, SysArg, SysArg1, SysArg2, SysResult};
 
 pub fn enter(tracee: &mut Tracee) -> Result<()> {
     let raw_path = tracee.regs.get_sysarg_path(SysArg1)?;
@@ -14,17 +19,88 @@ pub fn enter(tracee: &mut Tracee) -> Result<()> {
 
     let deref_final = !(flags.contains(OFlag::O_NOFOLLOW)
         || (flags.contains(OFlag::O_EXCL) && flags.contains(OFlag::O_CREAT)));
-    let host_path = tracee.fs.borrow().translate_path(raw_path, deref_final)?.1;
+    let (guest_path, host_path) = tracee.translate_path(raw_path, deref_final)?;
+    let (host_path, fd_change) = open_virtual(tracee, guest_path, host_path, flags)?;
 
     tracee.regs.set_sysarg_path(
         SysArg1,
         &host_path,
         "during enter open translation, setting host path",
     )?;
+    tracee.fd_change = Some(fd_change);
+
+    Ok(())
+}
 
+pub fn exit(tracee: &mut Tracee) -> Result<()> {
+    let flags = OFlag::from_bits_truncate(tracee.regs.get(Original, SysArg(SysArg2)) as _);
+    record_fd(tracee, flags);
     Ok(())
 }
 
+/// Get the host path to open and the change of the file descriptors, for
+/// `flags`. The translation `host_path` of `guest_path` may be the copy of a
+/// virtual file (see `VirtualFiles`): opening it for writing opens the real
+/// file instead, unless the copy is written back. Opening a file of a
+/// read-only binding for writing fails, and opening an emulated device file
+/// (see the `device` module) opens the host device.
+pub fn open_virtual(
+    tracee: &Tracee,
+    guest_path: PathBuf,
+    host_path: PathBuf,
+    flags: OFlag,
+) -> Result<(PathBuf, FdChange)> {
+    let writing = (flags & OFlag::O_ACCMODE) != OFlag::O_RDONLY
+        || flags.intersects(OFlag::O_CREAT | OFlag::O_TRUNC);
+    let fs = tracee.fs.borrow();
+    // like on a read-only mount, `O_CREAT` alone only fails for a new file
+    if (flags & OFlag::O_ACCMODE) != OFlag::O_RDONLY
+        || flags.contains(OFlag::O_TRUNC)
+        || (flags.contains(OFlag::O_CREAT) && host_path.symlink_metadata().is_err())
+    {
+        fs.check_writable(&guest_path)?;
+    }
+    // a device file of the fake root opens the host device
+    if let Some(devices) = fs.devices()
+        && let Some(node) = devices.get(&host_path)
+    {
+        let host_path = devices.open_path(node.device)?;
+        return Ok((host_path, FdChange::Open(guest_path)));
+    }
+    match fs.virtual_files() {
+        Some(virtual_files) if writing && virtual_files.is_copy(&host_path) => {
+            if virtual_files.is_writable(&guest_path) {
+                let write_back = virtual_files.open_for_writing(&fs, &guest_path, &host_path)?;
+                let host_path = write_back.path().to_path_buf();
+                Ok((host_path, FdChange::OpenVirtual(guest_path, write_back)))
+            } else {
+                let host_path = fs.substitute(&guest_path, Guest)?;
+                Ok((host_path, FdChange::Open(guest_path)))
+            }
+        }
+        _ => Ok((host_path, FdChange::Open(guest_path))),
+    }
+}
+
+/// Record the file descriptor returned by `open()` or `openat()`, opened with
+/// `flags`, in the table of the tracee.
+pub fn record_fd(tracee: &mut Tracee, flags: OFlag) {
+    let fd = tracee.regs.get(Current, SysResult) as RawFd;
+    let cloexec = flags.contains(OFlag::O_CLOEXEC);
+    match tracee.fd_change.take() {
+        Some(FdChange::Open(guest_path)) if fd >= 0 => {
+            tracee.fds.borrow_mut().open(fd, guest_path, cloexec);
+        }
+        Some(FdChange::OpenVirtual(guest_path, write_back)) if fd >= 0 => {
+            tracee
+                .fds
+                .borrow_mut()
+                .open_virtual(fd, guest_path, cloexec, write_back);
+        }
+        _ => {}
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use nix::fcntl::OFlag;
@@ -3,9 +3,10 @@ use std::os::unix::prelude::RawFd;
 use nix::fcntl::OFlag;
 
 use crate::errors::*;
+use crate::kernel::standard::open;
 use crate::process::tracee::Tracee;
 use crate::register::PtraceWriter;
-use crate::register::{Current, PtraceReader, SysArg, SysArg1, SysArg2, SysArg3};
+use crate::register::{Current, Original, PtraceReader, SysArg, SysArg1, SysArg2, SysArg3};
 
 pub fn enter(tracee: &mut Tracee) -> Result<()> {
     let dirfd = tracee.regs.get(Current, SysArg(SysArg1)) as RawFd;
@@ -15,7 +16,8 @@ pub fn enter(tracee: &mut Tracee) -> Result<()> {
     let deref_final = !(flags.contains(OFlag::O_NOFOLLOW)
         || (flags.contains(OFlag::O_EXCL) && flags.contains(OFlag::O_CREAT)));
 
-    let host_path = tracee.translate_path_at(dirfd, raw_path, deref_final)?.1;
+    let (guest_path, host_path) = tracee.translate_path_at(dirfd, raw_path, deref_final)?;
+    let (host_path, fd_change) = open::open_virtual(tracee, guest_path, host_path, flags)?;
 
     tracee.regs.set_sysarg_path(
         SysArg2,
@@ -23,10 +25,17 @@ pub fn enter(tracee: &mut Tracee) -> Result<()> {
         "during enter open translation, setting host path",
     )?;
     // We don't need to modify SysArg1 because the SysArg2 is an absolute path now
+    tracee.fd_change = Some(fd_change);
 
     Ok(())
 }
 
+pub fn exit(tracee: &mut Tracee) -> Result<()> {
+    let flags = OFlag::from_bits_truncate(tracee.regs.get(Original, SysArg(SysArg3)) as _);
+    open::record_fd(tracee, flags);
+    Ok(())
+}
+
 #[cfg(test)]
 mod tests {
     use nix::{fcntl::OFlag, sys::stat::Mode};
@@ -1,84 +1,71 @@
-use crate::errors::Result;
+use std::ffi::OsStr;
+use std::os::unix::ffi::OsStrExt;
+use std::path::Path;
 
-pub fn exit() -> Result<()> {
-    // TODO: Find out why we need to call detranslate_path() on the result of
-    // readlinkat() here.
+use libc::c_void;
+use nix::unistd::Pid;
 
-    //    char referee[PATH_MAX];
-    //    char referer[PATH_MAX];
-    //    size_t old_size;
-    //    size_t new_size;
-    //    size_t max_size;
-    //    word_t input;
-    //    word_t output;
-    //
-    //    /* Error reported by the kernel.  */
-    //    if ((int) syscall_result < 0)
-    //        return SyscallExitResult::None;
-    //
-    //    old_size = syscall_result;
-    //
-    //    if (syscall_number == PR_readlink) {
-    //        output   = peek_reg(tracee, ORIGINAL, SYSARG_2);
-    //        max_size = peek_reg(tracee, ORIGINAL, SYSARG_3);
-    //        input    = peek_reg(tracee, MODIFIED, SYSARG_1);
-    //    }
-    //    else {
-    //        output   = peek_reg(tracee, ORIGINAL,  SYSARG_3);
-    //        max_size = peek_reg(tracee, ORIGINAL, SYSARG_4);
-    //        input    = peek_reg(tracee, MODIFIED, SYSARG_2);
-    //    }
-    //
-    //    if (max_size > PATH_MAX)
-    //        max_size = PATH_MAX;
-    //
-    //    if (max_size == 0) {
-    //        return SyscallExitResult::Value(-EINVAL);
-    //    }
-    //
-    //    /* The kernel does NOT put the NULL terminating byte for
-    //     * readlink(2).  */
-    //    status = read_data(tracee, referee, output, old_size);
-    //    if (status < 0)
-    //        break;
-    //    referee[old_size] = '\0';
-    //
-    //    /* Not optimal but safe (path is fully translated).  */
-    //    status = read_path(tracee, referer, input);
-    //    if (status < 0)
-    //        return SyscallExitResult::Value(status);
-    //
-    //    if (status >= PATH_MAX) {
-    //        return SyscallExitResult::Value(-ENAMETOOLONG);
-    //    }
-    //
-    //    status = detranslate_path(tracee, referee, referer);
-    //    if (status < 0)
-    //        return SyscallExitResult::Value(status);
-    //
-    //    /* The original path doesn't require any transformation, i.e
-    //     * it is a symetric binding.  */
-    //    if (status == 0)
-    //        return SyscallExitResult::None;
-    //
-    //    /* Overwrite the path.  Note: the output buffer might be
-    //     * initialized with zeros but it was updated with the kernel
-    //     * result, and then with the detranslated result.  This later
-    //     * might be shorter than the former, so it's safier to add a
-    //     * NULL terminating byte when possible.  This problem was
-    //     * exposed by IDA Demo 6.3.  */
-    //    if ((size_t) status < max_size) {
-    //        new_size = status - 1;
-    //        status = write_data(tracee, output, referee, status);
-    //    }
-    //    else {
-    //        new_size = max_size;
-    //        status = write_data(tracee, output, referee, max_size);
-    //    }
-    //    if (status < 0)
-    //        return SyscallExitResult::Value(status);
-    //
-    // The value of "status" is used to update the returned value in
-    // translate_syscall_exit().
+use crate::errors::*;
+use crate::filesystem::proc::{self, MappedFiles};
+use crate::kernel::groups::{syscall_group_from_sysnum, SyscallGroup};
+use crate::process::proot::is_traced_by_us;
+use crate::process::tracee::Tracee;
+use crate::register::{memory, read_path, PtraceWriter, Word};
+use crate::register::{Current, Modified, Original, SysResult};
+use crate::register::{SysArg, SysArg1, SysArg2, SysArg3, SysArg4};
+
+/// The links of `/proc/<pid>/map_files` point to the host paths of the mapped
+/// files: they are detranslated like the paths of `/proc/<pid>/maps`. The
+/// other links are left alone.
+pub fn exit(tracee: &mut Tracee) -> Result<()> {
+    let result = tracee.regs.get(Current, SysResult) as isize;
+    if result < 0 {
+        return Ok(());
+    }
+
+    let sys_num = tracee.regs.get_sys_num(Original) as i64;
+    let (input, output, max_size) = match syscall_group_from_sysnum(sys_num) {
+        SyscallGroup::ReadLink => (SysArg1, SysArg2, SysArg3),
+        _ => (SysArg2, SysArg3, SysArg4),
+    };
+    let link = read_path(
+        tracee.pid,
+        tracee.regs.get(Modified, SysArg(input)) as *mut Word,
+    )?;
+    let pid = match map_files_owner(&link) {
+        Some(pid) => pid,
+        None => return Ok(()),
+    };
+    let maps = match std::fs::read_to_string(format!("/proc/{}/maps", pid)) {
+        Ok(maps) => maps,
+        Err(_) => return Ok(()),
+    };
+
+    let output = tracee.regs.get(Original, SysArg(output));
+    let max_size = tracee.regs.get(Original, SysArg(max_size)) as usize;
+    let mut referee = vec![0; result as usize];
+    memory::read_bytes(tracee.pid, output, &mut referee)?;
+
+    let fs = tracee.fs.borrow();
+    let guest_path =
+        MappedFiles::new(&fs, pid, &maps).detranslate(Path::new(OsStr::from_bytes(&referee)));
+    // like the kernel, the result is truncated without a null byte
+    let bytes = guest_path.as_os_str().as_bytes();
+    let bytes = &bytes[..bytes.len().min(max_size)];
+    tracee
+        .regs
+        .write_data(output as *mut c_void, bytes, false)?;
+    tracee.regs.set(
+        SysResult,
+        bytes.len() as Word,
+        "update return value in readlink_at::exit()",
+    );
     Ok(())
 }
+
+/// The pid of `/proc/<pid>/map_files/<range>`, if it is a tracee.
+fn map_files_owner(link: &Path) -> Option<Pid> {
+    let pid = proc::process_id(link)?;
+    let range = link.strip_prefix(format!("/proc/{}/map_files", pid)).ok()?;
+    (range.components().count() == 1 && is_traced_by_us(pid)).then_some(pid)
+}
@@ -3,10 +3,14 @@ use std::os::unix::prelude::RawFd;
 use crate::errors::*;
 use crate::filesystem::ext::PathExt;
 use crate::kernel::standard::link_rename;
+use crate::process::fd_table::FdChange;
 use crate::process::tracee::Tracee;
 use crate::register::PtraceWriter;
-use crate::register::{Current, PtraceReader, SysArg, SysArg1, SysArg2, SysArg3, SysArg4};
+use crate::register::{
+    Current, Original, PtraceReader, SysArg, SysArg1, SysArg2, SysArg3, SysArg4, SysArg5,
+};
 
+/// Translates `renameat` and `renameat2`
 pub fn enter(tracee: &mut Tracee) -> Result<()> {
     let olddirfd = tracee.regs.get(Current, SysArg(SysArg1)) as RawFd;
     let newdirfd = tracee.regs.get(Current, SysArg(SysArg3)) as RawFd;
@@ -15,8 +19,13 @@ pub fn enter(tracee: &mut Tracee) -> Result<()> {
 
     let deref_final = old_path.with_trailing_slash();
 
-    let old_host_path = tracee.translate_path_at(olddirfd, old_path, deref_final)?.1;
-    let new_host_path = tracee.translate_path_at(newdirfd, new_path, false)?.1;
+    let (old_guest_path, old_host_path) =
+        tracee.translate_path_at(olddirfd, old_path, deref_final)?;
+    let (new_guest_path, new_host_path) = tracee.translate_path_at(newdirfd, new_path, false)?;
+    tracee.fs.borrow().check_writable(&old_guest_path)?;
+    tracee.fs.borrow().check_writable(&new_guest_path)?;
+    tracee.fs.borrow().forget_translations(&old_host_path);
+    tracee.fs.borrow().forget_translations(&new_host_path);
 
     tracee.regs.set_sysarg_path(
         SysArg2,
@@ -29,6 +38,14 @@ pub fn enter(tracee: &mut Tracee) -> Result<()> {
         "during enter open translation, setting host path",
     )?;
 
+    // With `RENAME_EXCHANGE`, both paths stay: the file descriptors on them
+    // are resolved with /proc once they don't match anymore.
+    let exchange = tracee.regs.get_sys_num(Original) == crate::sc::nr::RENAMEAT2 as usize
+        && tracee.regs.get(Current, SysArg(SysArg5)) as u32 & libc::RENAME_EXCHANGE != 0;
+    if !exchange {
+        tracee.fd_change = Some(FdChange::Rename(old_guest_path, new_guest_path));
+    }
+
     Ok(())
 }
 
@@ -1,14 +1,18 @@
 use crate::errors::*;
 
-use crate::filesystem::Translator;
+use crate::kernel::standard::mknod::{self, StatBuffer};
+use crate::kernel::syscall;
 use crate::process::tracee::Tracee;
 use crate::register::PtraceWriter;
-use crate::register::{PtraceReader, SysArg1};
+use crate::register::{Current, Original, PtraceReader, SysArg1, SysArg2};
 
 pub fn enter(tracee: &mut Tracee) -> Result<()> {
     let raw_path = tracee.regs.get_sysarg_path(SysArg1)?;
 
-    let host_path = tracee.fs.borrow().translate_path(raw_path, true)?.1;
+    let (guest_path, host_path) = tracee.translate_path(raw_path, true)?;
+    if syscall::modifies_path(tracee.regs.get_sys_num(Current)) {
+        tracee.fs.borrow().check_writable(&guest_path)?;
+    }
 
     tracee.regs.set_sysarg_path(
         SysArg1,
@@ -19,6 +23,16 @@ pub fn enter(tracee: &mut Tracee) -> Result<()> {
     Ok(())
 }
 
+pub fn exit(tracee: &mut Tracee) -> Result<()> {
+    match tracee.regs.get_sys_num(Original) as i64 {
+        #[cfg(target_arch = "x86_64")]
+        crate::sc::nr::STAT => mknod::report_device(tracee, SysArg1, SysArg2, StatBuffer::Stat),
+        #[cfg(any(target_arch = "x86", target_arch = "arm"))]
+        crate::sc::nr::STAT64 => mknod::report_device(tracee, SysArg1, SysArg2, StatBuffer::Stat),
+        _ => Ok(()),
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use std::fs::File;
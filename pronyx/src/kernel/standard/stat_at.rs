@@ -4,9 +4,10 @@ use nix::fcntl::AtFlags;
 
 use crate::errors::*;
 use crate::filesystem::ext::PathExt;
+use crate::kernel::standard::mknod::{self, StatBuffer};
 use crate::kernel::syscall;
 use crate::register::PtraceWriter;
-use crate::register::{Current, PtraceReader, SysArg, SysArg1, SysArg2, SysArg3, SysArg4, SysArg5};
+use crate::register::{Current, Original, PtraceReader, SysArg, SysArg1, SysArg2, SysArg3, SysArg4, SysArg5};
 use crate::{errors::Result, process::tracee::Tracee};
 
 pub fn enter(tracee: &mut Tracee) -> Result<()> {
@@ -36,6 +37,13 @@ pub fn enter(tracee: &mut Tracee) -> Result<()> {
     };
     let flags = AtFlags::from_bits_truncate(tracee.regs.get(Current, SysArg(flags_arg_index)) as _);
 
+    // `fstat()` and the like: the file descriptor itself is used, which may
+    // not even have a guest path (pipe, file opened before entering the
+    // box...).
+    if raw_path.as_os_str().is_empty() && flags.contains(AtFlags::AT_EMPTY_PATH) {
+        return Ok(());
+    }
+
     // Determines whether we need to dereference a path if it is a symbolic link.
     // Some system calls will dereference the path by default, while others do not,
     // which can also be controlled by `flags`.
@@ -53,7 +61,10 @@ pub fn enter(tracee: &mut Tracee) -> Result<()> {
         _ => true,
     };
 
-    let host_path = tracee.translate_path_at(dirfd, raw_path, deref_final)?.1;
+    let (guest_path, host_path) = tracee.translate_path_at(dirfd, raw_path, deref_final)?;
+    if syscall::modifies_path(sys_num) {
+        tracee.fs.borrow().check_writable(&guest_path)?;
+    }
 
     tracee.regs.set_sysarg_path(
         SysArg2,
@@ -64,6 +75,17 @@ pub fn enter(tracee: &mut Tracee) -> Result<()> {
     Ok(())
 }
 
+pub fn exit(tracee: &mut Tracee) -> Result<()> {
+    match tracee.regs.get_sys_num(Original) as i64 {
+        #[cfg(any(target_arch = "x86", target_arch = "arm"))]
+        crate::sc::nr::FSTATAT64 => mknod::report_device(tracee, SysArg2, SysArg3, StatBuffer::Stat),
+        #[cfg(any(target_arch = "x86_64", target_arch = "aarch64"))]
+        crate::sc::nr::NEWFSTATAT => mknod::report_device(tracee, SysArg2, SysArg3, StatBuffer::Stat),
+        crate::sc::nr::STATX => mknod::report_device(tracee, SysArg2, SysArg5, StatBuffer::Statx),
+        _ => Ok(()),
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use std::fs::File;
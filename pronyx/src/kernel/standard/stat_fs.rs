@@ -0,0 +1,128 @@
+use std::mem::offset_of;
+use std::os::unix::io::RawFd;
+
+use libc::c_void;
+
+use crate::errors::*;
+use crate::filesystem::binding::Side;
+use crate::process::tracee::Tracee;
+use crate::register::{memory, read_path, PtraceReader, PtraceWriter, Word};
+use crate::register::{Current, Original, SysArg, SysArg1, SysArg2, SysResult};
+
+/// Type of the fields of `struct statfs`, and of `f_flags` in `struct
+/// statfs64` (`__statfs_word` in `<asm-generic/statfs.h>`).
+type StatFsWord = libc::c_ulong;
+
+/// Offset of `f_flags` in `struct statfs`, right after `f_frsize` (`f_fsid`
+/// before them is two words long on 32-bit architectures, but a single one on
+/// 64-bit ones). The `libc` crate hides `f_flags` in its padding.
+const STATFS_FLAGS_OFFSET: Word =
+    (offset_of!(libc::statfs, f_frsize) + size_of::<StatFsWord>()) as Word;
+
+/// Offset of `f_flags` in `struct statfs64`, whose block and file counts are
+/// 64 bits wide.
+#[cfg(any(target_arch = "x86", target_arch = "arm"))]
+const STATFS64_FLAGS_OFFSET: Word = 64;
+
+/// Whether the syscall `sys_num` of the `statfs()` family takes a file
+/// descriptor rather than a path.
+fn takes_fd(sys_num: usize) -> bool {
+    match sys_num as i64 {
+        crate::sc::nr::FSTATFS => true,
+        #[cfg(any(target_arch = "x86", target_arch = "arm"))]
+        crate::sc::nr::FSTATFS64 => true,
+        _ => false,
+    }
+}
+
+/// Translates the path of `statfs()`, so that the statistics are the ones of
+/// the host file-system of the binding (or of the rootfs) the path is in.
+pub fn enter(tracee: &mut Tracee) -> Result<()> {
+    if takes_fd(tracee.regs.get_sys_num(Current)) {
+        return Ok(());
+    }
+
+    let raw_path = tracee.regs.get_sysarg_path(SysArg1)?;
+    let host_path = tracee.translate_path(raw_path, true)?.1;
+
+    tracee.regs.set_sysarg_path(
+        SysArg1,
+        &host_path,
+        "during enter statfs translation, setting host path",
+    )?;
+
+    Ok(())
+}
+
+/// Reports `ST_RDONLY` in `f_flags` for the files of read-only bindings, as
+/// the kernel does for read-only mounts.
+pub fn exit(tracee: &mut Tracee) -> Result<()> {
+    let result = tracee.regs.get(Current, SysResult) as isize;
+    if result < 0 || !tracee.fs.borrow().has_read_only_bindings() {
+        return Ok(());
+    }
+
+    let sys_num = tracee.regs.get_sys_num(Original);
+    let guest_path = if takes_fd(sys_num) {
+        let fd = tracee.regs.get(Original, SysArg(SysArg1)) as RawFd;
+        match tracee.get_path_from_fd(fd, Side::Guest) {
+            Ok(guest_path) => guest_path,
+            // pipe, socket...
+            Err(_) => return Ok(()),
+        }
+    } else {
+        let raw_path = read_path(
+            tracee.pid,
+            tracee.regs.get(Original, SysArg(SysArg1)) as *mut Word,
+        )?;
+        tracee.translate_path(raw_path, true)?.0
+    };
+    if !tracee.fs.borrow().is_read_only(&guest_path) {
+        return Ok(());
+    }
+
+    let offset = match sys_num as i64 {
+        #[cfg(any(target_arch = "x86", target_arch = "arm"))]
+        crate::sc::nr::STATFS64 | crate::sc::nr::FSTATFS64 => STATFS64_FLAGS_OFFSET,
+        _ => STATFS_FLAGS_OFFSET,
+    };
+    let address = tracee.regs.get(Original, SysArg(SysArg2)) + offset;
+    let mut flags = [0u8; size_of::<StatFsWord>()];
+    memory::read_bytes(tracee.pid, address, &mut flags)?;
+    let flags = StatFsWord::from_ne_bytes(flags) | libc::ST_RDONLY as StatFsWord;
+    tracee
+        .regs
+        .write_data(address as *mut c_void, &flags.to_ne_bytes(), false)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::tests::test_with_proot;
+
+    #[test]
+    fn test_statfs_flags_offset() {
+        // 7 words, `f_fsid`, `f_namelen` and `f_frsize`
+        #[cfg(target_pointer_width = "64")]
+        assert_eq!(STATFS_FLAGS_OFFSET, 80);
+        #[cfg(target_pointer_width = "32")]
+        assert_eq!(STATFS_FLAGS_OFFSET, 44);
+    }
+
+    /// Unit test for `statfs()` and `fstatfs()`: the statistics are the ones
+    /// of the file-system the path is on.
+    #[test]
+    fn test_stat_fs() {
+        test_with_proot(
+            |_tracee, _is_sysenter, _before_translation| {},
+            || {
+                let by_path = nix::sys::statfs::statfs("/proc").unwrap();
+                assert_eq!(by_path.filesystem_type(), nix::sys::statfs::PROC_SUPER_MAGIC);
+
+                let dir = std::fs::File::open("/proc").unwrap();
+                let by_fd = nix::sys::statfs::fstatfs(&dir).unwrap();
+                assert_eq!(by_fd.filesystem_type(), nix::sys::statfs::PROC_SUPER_MAGIC);
+            },
+        )
+    }
+}
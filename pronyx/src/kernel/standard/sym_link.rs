@@ -1,6 +1,5 @@
 use crate::errors::*;
 
-use crate::filesystem::Translator;
 use crate::process::tracee::Tracee;
 use crate::register::PtraceWriter;
 use crate::register::{PtraceReader, SysArg2};
@@ -8,7 +7,9 @@ use crate::register::{PtraceReader, SysArg2};
 pub fn enter(tracee: &mut Tracee) -> Result<()> {
     let raw_path = tracee.regs.get_sysarg_path(SysArg2)?;
     // create/delete/rename related system calls cannot follow final component.
-    let host_path = tracee.fs.borrow().translate_path(raw_path, false)?.1;
+    let (guest_path, host_path) = tracee.translate_path(raw_path, false)?;
+    tracee.fs.borrow().check_writable(&guest_path)?;
+    tracee.fs.borrow().forget_translations(&host_path);
 
     tracee.regs.set_sysarg_path(
         SysArg2,
@@ -10,7 +10,9 @@ pub fn enter(tracee: &mut Tracee) -> Result<()> {
     let raw_path = tracee.regs.get_sysarg_path(SysArg3)?;
 
     // create/delete/rename related system calls cannot follow final component.
-    let host_path = tracee.translate_path_at(dirfd, raw_path, false)?.1;
+    let (guest_path, host_path) = tracee.translate_path_at(dirfd, raw_path, false)?;
+    tracee.fs.borrow().check_writable(&guest_path)?;
+    tracee.fs.borrow().forget_translations(&host_path);
 
     tracee.regs.set_sysarg_path(
         SysArg3,
@@ -1,33 +1,52 @@
+use libc::c_void;
+
 use crate::errors::*;
+use crate::process::tracee::Tracee;
+use crate::register::{Current, Original, PtraceWriter, SysArg, SysArg1, SysResult, Word};
+use crate::utils::Config;
+
+/// Size of each field of `struct utsname` (see `<sys/utsname.h>`), which is
+/// the same on every Linux architecture.
+const UTSNAME_FIELD_SIZE: usize = 65;
+/// Offset of `utsname.nodename` (the second field).
+const UTSNAME_NODENAME_OFFSET: usize = UTSNAME_FIELD_SIZE;
+/// Offset of `utsname.release` (the third field).
+const UTSNAME_RELEASE_OFFSET: usize = 2 * UTSNAME_FIELD_SIZE;
+
+pub fn exit(tracee: &mut Tracee, config: &Config) -> Result<()> {
+    if config.hostname.is_none() && config.kernel_release.is_none() {
+        return Ok(());
+    }
+
+    // Error reported by the kernel.
+    if (tracee.regs.get(Current, SysResult) as isize) < 0 {
+        return Ok(());
+    }
+
+    let address = tracee.regs.get(Original, SysArg(SysArg1));
 
-#[cfg(all(target_os = "linux", target_arch = "x86_64"))]
-pub fn exit() -> Result<()> {
-    //    struct utsname utsname;
-    //    word_t address;
-    //    size_t size;
-    //
-    //    if (get_abi(tracee) != ABI_2)
-    //        return SyscallExitResult::None;
-    //
-    //    /* Error reported by the kernel.  */
-    //    if ((int) syscall_result < 0)
-    //        return SyscallExitResult::None;
-    //
-    //    address = peek_reg(tracee, ORIGINAL, SYSARG_1);
-    //
-    //    status = read_data(tracee, &utsname, address, sizeof(utsname));
-    //    if (status < 0)
-    //        return SyscallExitResult::Value(status);
-    //
-    //    /* Some 32-bit programs like package managers can be
-    //     * confused when the kernel reports "x86_64".  */
-    //    size = sizeof(utsname.machine);
-    //    strncpy(utsname.machine, "i686", size);
-    //    utsname.machine[size - 1] = '\0';
-    //
-    //    status = write_data(tracee, address, &utsname, sizeof(utsname));
-    //    if (status < 0)
-    //        return SyscallExitResult::Value(status);
-    //
+    if let Some(hostname) = &config.hostname {
+        write_utsname_field(tracee, address, UTSNAME_NODENAME_OFFSET, hostname)?;
+    }
+    if let Some(release) = &config.kernel_release {
+        write_utsname_field(tracee, address, UTSNAME_RELEASE_OFFSET, release)?;
+    }
+
+    //TODO: Some 32-bit programs like package managers can be confused when
+    // the kernel reports "x86_64" in `utsname.machine`.
     Ok(())
 }
+
+/// Overwrite one field of the `struct utsname` located at `address` in the
+/// tracee's memory. The value is truncated and padded with null bytes so that
+/// the whole field is replaced.
+fn write_utsname_field(tracee: &Tracee, address: Word, offset: usize, value: &str) -> Result<()> {
+    let mut field = [0u8; UTSNAME_FIELD_SIZE];
+    let bytes = value.as_bytes();
+    let len = bytes.len().min(UTSNAME_FIELD_SIZE - 1);
+    field[..len].copy_from_slice(&bytes[..len]);
+
+    tracee
+        .regs
+        .write_data((address as usize + offset) as *mut c_void, &field, false)
+}
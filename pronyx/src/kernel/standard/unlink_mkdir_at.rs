@@ -2,9 +2,11 @@ use std::os::unix::prelude::RawFd;
 
 use crate::errors::*;
 use crate::filesystem::ext::PathExt;
+use crate::kernel::syscall;
 use crate::process::tracee::Tracee;
-use crate::register::PtraceWriter;
-use crate::register::{Current, PtraceReader, SysArg, SysArg1, SysArg2};
+use crate::register::{read_path, PtraceWriter, Word};
+use crate::register::{Current, Modified, Original, PtraceReader, SysArg, SysArgIndex};
+use crate::register::{SysArg1, SysArg2, SysResult};
 
 pub fn enter(tracee: &mut Tracee) -> Result<()> {
     let sys_num = tracee.regs.get_sys_num(Current);
@@ -16,7 +18,11 @@ pub fn enter(tracee: &mut Tracee) -> Result<()> {
         _ => raw_path.with_trailing_slash(),
     };
 
-    let host_path = tracee.translate_path_at(dirfd, raw_path, deref_final)?.1;
+    let (guest_path, host_path) = tracee.translate_path_at(dirfd, raw_path, deref_final)?;
+    if syscall::modifies_path(sys_num) {
+        tracee.fs.borrow().check_writable(&guest_path)?;
+    }
+    tracee.fs.borrow().forget_translations(&host_path);
 
     tracee.regs.set_sysarg_path(
         SysArg2,
@@ -27,6 +33,30 @@ pub fn enter(tracee: &mut Tracee) -> Result<()> {
     Ok(())
 }
 
+pub fn exit(tracee: &mut Tracee) -> Result<()> {
+    match tracee.regs.get_sys_num(Original) as i64 {
+        crate::sc::nr::UNLINKAT => forget_removed(tracee, SysArg2),
+        _ => Ok(()),
+    }
+}
+
+/// Forget what is emulated about the file whose host path is in the argument
+/// `sys_arg` (see `FileSystem::forget_emulated()`), once it was removed.
+pub fn forget_removed(tracee: &Tracee, sys_arg: SysArgIndex) -> Result<()> {
+    let fs = tracee.fs.borrow();
+    if (fs.xattrs().is_none() && fs.devices().is_none())
+        || (tracee.regs.get(Current, SysResult) as isize) < 0
+    {
+        return Ok(());
+    }
+    let host_path = read_path(
+        tracee.pid,
+        tracee.regs.get(Modified, SysArg(sys_arg)) as *mut Word,
+    )?;
+    fs.forget_emulated(&host_path);
+    Ok(())
+}
+
 #[cfg(test)]
 mod tests {
     use nix::{fcntl::OFlag, sys::stat::Mode};
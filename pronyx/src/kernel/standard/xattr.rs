@@ -0,0 +1,229 @@
+use std::os::unix::io::RawFd;
+use std::path::PathBuf;
+
+use libc::c_void;
+
+use crate::errors::*;
+use crate::filesystem::binding::Side;
+use crate::filesystem::xattr;
+use crate::process::tracee::Tracee;
+use crate::register::{memory, read_path, PtraceReader, PtraceWriter, Word};
+use crate::register::{Current, Modified, Original, SysArg, SysResult};
+use crate::register::{SysArg1, SysArg2, SysArg3, SysArg4, SysArg5};
+
+/// Maximum length of the name of an attribute (`XATTR_NAME_MAX`).
+const XATTR_NAME_MAX: usize = 255;
+/// Maximum size of the value of an attribute (`XATTR_SIZE_MAX`).
+const XATTR_SIZE_MAX: usize = 65536;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Operation {
+    Get,
+    List,
+    Remove,
+    Set,
+}
+
+/// The file of an attribute syscall.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Target {
+    /// A path, whose final symlink is dereferenced or not.
+    Path { deref_final: bool },
+    Fd,
+}
+
+fn decode(sys_num: usize) -> (Operation, Target) {
+    use Operation::*;
+
+    let path = Target::Path { deref_final: true };
+    let link = Target::Path { deref_final: false };
+    match sys_num as i64 {
+        crate::sc::nr::GETXATTR => (Get, path),
+        crate::sc::nr::LGETXATTR => (Get, link),
+        crate::sc::nr::FGETXATTR => (Get, Target::Fd),
+        crate::sc::nr::LISTXATTR => (List, path),
+        crate::sc::nr::LLISTXATTR => (List, link),
+        crate::sc::nr::FLISTXATTR => (List, Target::Fd),
+        crate::sc::nr::REMOVEXATTR => (Remove, path),
+        crate::sc::nr::LREMOVEXATTR => (Remove, link),
+        crate::sc::nr::FREMOVEXATTR => (Remove, Target::Fd),
+        crate::sc::nr::SETXATTR => (Set, path),
+        crate::sc::nr::LSETXATTR => (Set, link),
+        _ => (Set, Target::Fd),
+    }
+}
+
+/// The name of the attribute, if it is emulated, see the `xattr` module.
+fn emulated_name(tracee: &Tracee, operation: Operation) -> Result<Option<String>> {
+    if operation == Operation::List || tracee.fs.borrow().xattrs().is_none() {
+        return Ok(None);
+    }
+    let address = tracee.regs.get(Original, SysArg(SysArg2));
+    let name = memory::read_string(tracee.pid, address, XATTR_NAME_MAX + 1)?;
+    if name.len() > XATTR_NAME_MAX {
+        return Err(Error::errno(Errno::ERANGE));
+    }
+    Ok(String::from_utf8(name)
+        .ok()
+        .filter(|name| xattr::is_emulated(name)))
+}
+
+/// The host path of the file, once its path argument is translated.
+fn host_path(tracee: &Tracee, target: Target) -> Result<PathBuf> {
+    match target {
+        Target::Path { .. } => read_path(
+            tracee.pid,
+            tracee.regs.get(Modified, SysArg(SysArg1)) as *mut Word,
+        ),
+        Target::Fd => {
+            let fd = tracee.regs.get(Original, SysArg(SysArg1)) as RawFd;
+            tracee.get_path_from_fd(fd, Side::Host)
+        }
+    }
+}
+
+/// Translates the path of the `*xattr()` syscalls. Under the fake root, the
+/// attributes of the protected namespaces are set and removed here, in the
+/// store of the `xattr` module, instead of by the kernel.
+pub fn enter(tracee: &mut Tracee) -> Result<()> {
+    let (operation, target) = decode(tracee.regs.get_sys_num(Current));
+
+    let host_path = match target {
+        Target::Path { deref_final } => {
+            let raw_path = tracee.regs.get_sysarg_path(SysArg1)?;
+            let (guest_path, host_path) = tracee.translate_path(raw_path, deref_final)?;
+            if matches!(operation, Operation::Set | Operation::Remove) {
+                tracee.fs.borrow().check_writable(&guest_path)?;
+            }
+            tracee.regs.set_sysarg_path(
+                SysArg1,
+                &host_path,
+                "during enter xattr translation, setting host path",
+            )?;
+            host_path
+        }
+        Target::Fd => match host_path(tracee, target) {
+            Ok(host_path) => host_path,
+            // pipe, socket... left to the kernel
+            Err(_) => return Ok(()),
+        },
+    };
+
+    let name = match emulated_name(tracee, operation)? {
+        Some(name) => name,
+        None => return Ok(()),
+    };
+    // like the kernel, fail on a missing file or a dangling symlink
+    if let Target::Path { deref_final } = target {
+        match deref_final {
+            true => host_path.metadata()?,
+            false => host_path.symlink_metadata()?,
+        };
+    }
+    let fs = tracee.fs.borrow();
+    let xattrs = fs.xattrs().unwrap();
+    match operation {
+        Operation::Set => {
+            let size = tracee.regs.get(Original, SysArg(SysArg4)) as usize;
+            if size > XATTR_SIZE_MAX {
+                return Err(Error::errno(Errno::E2BIG));
+            }
+            let mut value = vec![0; size];
+            memory::read_bytes(tracee.pid, tracee.regs.get(Original, SysArg(SysArg3)), &mut value)?;
+            let flags = tracee.regs.get(Original, SysArg(SysArg5)) as i32;
+            xattrs.set(&host_path, &name, &value, flags)?;
+        }
+        Operation::Remove => xattrs.remove(&host_path, &name)?,
+        // the value is copied at the exit stage
+        _ => {}
+    }
+    drop(fs);
+    tracee
+        .regs
+        .cancel_syscall("xattr emulated by pronyx, cancelling the syscall");
+
+    Ok(())
+}
+
+/// Reports the result of the emulated syscalls, and adds the emulated
+/// attributes to the list of the real ones.
+pub fn exit(tracee: &mut Tracee) -> Result<()> {
+    let (operation, target) = decode(tracee.regs.get_sys_num(Original));
+    if tracee.fs.borrow().xattrs().is_none() {
+        return Ok(());
+    }
+
+    let emulated = match operation {
+        Operation::List => None,
+        _ => match emulated_name(tracee, operation)? {
+            Some(name) => Some(name),
+            None => return Ok(()),
+        },
+    };
+    let host_path = match host_path(tracee, target) {
+        Ok(host_path) => host_path,
+        Err(_) => return Ok(()),
+    };
+    let fs = tracee.fs.borrow();
+    let xattrs = fs.xattrs().unwrap();
+    let (buf, size) = match operation {
+        Operation::List => (SysArg2, SysArg3),
+        _ => (SysArg3, SysArg4),
+    };
+    let buf = tracee.regs.get(Original, SysArg(buf));
+    let size = tracee.regs.get(Original, SysArg(size)) as usize;
+
+    let result = match (operation, emulated) {
+        (Operation::Get, Some(name)) => {
+            let value = xattrs.get(&host_path, &name)?;
+            if size != 0 {
+                if value.len() > size {
+                    return Err(Error::errno(Errno::ERANGE));
+                }
+                tracee.regs.write_data(buf as *mut c_void, &value, false)?;
+            }
+            value.len()
+        }
+        (Operation::List, _) => {
+            let result = tracee.regs.get(Current, SysResult) as isize;
+            let names = xattrs.list(&host_path);
+            if result < 0 || names.is_empty() {
+                return Ok(());
+            }
+            let result = result as usize;
+            if size == 0 {
+                // an upper bound is enough
+                result + names.iter().map(|name| name.len() + 1).sum::<usize>()
+            } else {
+                // the emulated attributes hide the real ones of the same name
+                let mut real = vec![0; result];
+                memory::read_bytes(tracee.pid, buf, &mut real)?;
+                let mut list: Vec<u8> = vec![];
+                for name in real.split(|&byte| byte == b'\0').filter(|name| !name.is_empty()) {
+                    if !names.iter().any(|emulated| emulated.as_bytes() == name) {
+                        list.extend_from_slice(name);
+                        list.push(b'\0');
+                    }
+                }
+                for name in &names {
+                    list.extend_from_slice(name.as_bytes());
+                    list.push(b'\0');
+                }
+                if list.len() > size {
+                    return Err(Error::errno(Errno::ERANGE));
+                }
+                tracee.regs.write_data(buf as *mut c_void, &list, false)?;
+                list.len()
+            }
+        }
+        // set and remove, done at the enter stage
+        _ => 0,
+    };
+    drop(fs);
+    tracee.regs.set(
+        SysResult,
+        result as Word,
+        "during exit xattr translation, setting result",
+    );
+    Ok(())
+}
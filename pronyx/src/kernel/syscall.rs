@@ -13,6 +13,56 @@ pub fn name_of_syscall(sysnum: usize) -> Option<&'static str> {
     Sysno::new(sysnum).map(|s| s.name())
 }
 
+/// Position of the path arguments of the syscall `name` (as given by
+/// `name_of_syscall()`).
+pub fn path_args(name: &str) -> &'static [usize] {
+    match name {
+        "open" | "creat" | "stat" | "lstat" | "stat64" | "lstat64" | "oldstat" | "oldlstat"
+        | "access" | "chdir" | "chmod" | "chown" | "chown32" | "lchown" | "lchown32" | "mkdir"
+        | "rmdir" | "unlink" | "readlink" | "truncate" | "truncate64" | "execve" | "chroot"
+        | "acct" | "getxattr" | "lgetxattr" | "setxattr" | "lsetxattr" | "listxattr"
+        | "llistxattr" | "removexattr" | "lremovexattr" | "statfs" | "statfs64" | "utime"
+        | "utimes" | "mknod" | "swapon" | "swapoff" | "umount" | "umount2" | "uselib" => &[0],
+        "link" | "rename" | "pivot_root" => &[0, 1],
+        "symlink" | "inotify_add_watch" | "mount" => &[1],
+        "openat" | "openat2" | "newfstatat" | "fstatat64" | "statx" | "faccessat"
+        | "faccessat2" | "fchmodat" | "fchmodat2" | "fchownat" | "mkdirat" | "mknodat"
+        | "unlinkat" | "readlinkat" | "utimensat" | "futimesat" | "name_to_handle_at"
+        | "execveat" | "open_tree" => &[1],
+        "linkat" | "renameat" | "renameat2" => &[1, 3],
+        "symlinkat" => &[2],
+        _ => &[],
+    }
+}
+
+/// Whether the syscall `sysnum` creates, modifies or removes the files of its
+/// path arguments, the first one excepted for `link()` and the like. The
+/// `open()` family depends on its flags, and isn't concerned.
+pub fn modifies_path(sysnum: usize) -> bool {
+    matches!(
+        name_of_syscall(sysnum).unwrap_or_default(),
+        "creat" | "chmod" | "fchmodat" | "fchmodat2" | "chown" | "chown32" | "lchown"
+            | "lchown32" | "fchownat" | "truncate" | "truncate64" | "utime" | "utimes"
+            | "utimensat" | "futimesat" | "setxattr" | "lsetxattr" | "removexattr"
+            | "lremovexattr" | "mknod" | "mknodat" | "mkdir" | "mkdirat" | "rmdir" | "unlink"
+            | "unlinkat" | "link" | "linkat" | "rename" | "renameat" | "renameat2" | "symlink"
+            | "symlinkat"
+    )
+}
+
+/// Whether `handle_syscall()` rewrites the syscall `sysnum` with `config`
+/// (fake root identity, `link2symlink`): it must then go through both stages
+/// even if nothing else translates it.
+pub fn is_handled(sysnum: usize, config: &Config) -> bool {
+    match Sysno::new(sysnum) {
+        Some(Sysno::getuid) | Some(Sysno::geteuid) | Some(Sysno::getgid)
+        | Some(Sysno::getegid) | Some(Sysno::getresuid) | Some(Sysno::getresgid)
+        | Some(Sysno::chown) | Some(Sysno::fchown) | Some(Sysno::lchown) => config.root_id,
+        Some(Sysno::link) | Some(Sysno::linkat) => config.link2symlink,
+        _ => false,
+    }
+}
+
 pub fn handle_syscall(tracee: &mut Tracee, config: &Config) {
     let sysnum = tracee.regs.get_sys_num(Original);
     let sysno = Sysno::new(sysnum);
@@ -0,0 +1,38 @@
+//! pronyx: chroot, mount --bind, and binfmt_misc without privilege/setup.
+//!
+//! This crate can be embedded to launch and supervise boxes, see
+//! [`Sandbox`]. The `pronyx` binary is a thin client of this API.
+
+#![allow(clippy::redundant_static_lifetimes)]
+#![allow(clippy::redundant_field_names)]
+
+pub mod sc_shim;
+pub use crate::sc_shim::sc as sc; // this creates a 'pseudo-crate' named sc
+
+#[macro_use]
+extern crate log;
+
+#[doc(hidden)]
+pub mod cli;
+mod errors;
+mod filesystem;
+mod kernel;
+mod process;
+mod profile;
+mod register;
+mod rootfs;
+mod sandbox;
+mod session;
+mod utils;
+
+/// Access to the memory of the tracees, only public for the benchmarks.
+#[doc(hidden)]
+pub use crate::register::memory;
+
+pub use crate::filesystem::device::Device;
+pub use crate::rootfs::{export_rootfs, import_rootfs, DeviceKind};
+pub use crate::sandbox::{
+    check_profile, ErrorKind, ExitPolicy, ExitStatus, Orphan, Sandbox, SandboxBuilder, SandboxError,
+    TraceOptions,
+};
+pub use crate::session::{ProcessInfo, Session, SessionClient, SessionProcess};
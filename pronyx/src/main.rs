@@ -1,55 +1,110 @@
-#![allow(clippy::redundant_static_lifetimes)]
-#![allow(clippy::redundant_field_names)]
-
-pub mod sc_shim;
-pub use crate::sc_shim::sc as sc; // this creates a 'pseudo-crate' named sc
-
 #[macro_use]
 extern crate log;
 
-mod cli;
-mod errors;
-mod filesystem;
-mod kernel;
-mod process;
-mod register;
-mod utils;
-
-use crate::errors::Result;
-use crate::process::proot::{show_info, stop_program, PRoot};
-use crate::process::sigactions;
-
-fn run() -> Result<()> {
-    // step 1: CLI parsing
-    let (fs, command, config) = cli::parse_config()?;
-
-    let mut proot: PRoot = PRoot::new(config);
-
-    // step 2: initialize Proot and start the first tracee
-    proot.init()?;
-    proot.launch_process(fs, command)?;
-
-    // what follows (event loop) is only for the main thread,
-    // as the child thread will stop after executing the `kernel.execve` command
+use pronyx::cli::{self, Action};
+use pronyx::{Orphan, SandboxError, SessionClient};
 
-    // step 3: Configure the signal actions
-    sigactions::prepare_sigactions(stop_program, show_info);
-
-    // step 4: Listen to and deal with tracees events
-    proot.event_loop()?;
+fn run() -> Result<i32, SandboxError> {
+    match cli::parse_action()? {
+        Action::Run { sandbox } => {
+            let mut sandbox = sandbox.install_sigactions(true).spawn()?;
+            let status = sandbox.wait()?;
+            debug!("first tracee exit with exit code: {}", status.code());
+            report_orphans(sandbox.orphans());
+            Ok(status.code())
+        }
+        Action::CheckProfile { profile } => {
+            pronyx::check_profile(&profile)?;
+            println!("{}: ok", profile.display());
+            Ok(0)
+        }
+        Action::Daemon {
+            session,
+            sandbox,
+            foreground,
+        } => {
+            let session = sandbox.session(&session)?;
+            if foreground {
+                session.serve()?;
+            } else {
+                session.serve_in_background()?;
+            }
+            Ok(0)
+        }
+        Action::Exec {
+            session,
+            command,
+            env,
+            cwd,
+        } => {
+            let mut client = SessionClient::connect(&session)?;
+            for (key, value) in env {
+                client = client.env(key, value);
+            }
+            if let Some(cwd) = cwd {
+                client = client.cwd(cwd);
+            }
+            let status = client.exec(command)?.wait()?;
+            Ok(status.code())
+        }
+        Action::Ps { session } => {
+            println!("{:>7} COMMAND", "PID");
+            for process in SessionClient::connect(&session)?.ps()? {
+                let command = match (process.command.is_empty(), &process.exe) {
+                    (false, _) => process.command.join(" "),
+                    (true, Some(exe)) => format!("[{}]", exe.display()),
+                    (true, None) => "?".to_string(),
+                };
+                println!("{:>7} {}", process.pid, command);
+            }
+            Ok(0)
+        }
+        Action::Kill {
+            session,
+            signal,
+            pids,
+        } => {
+            SessionClient::connect(&session)?.kill(signal, &pids)?;
+            Ok(0)
+        }
+        Action::Stop { session } => {
+            SessionClient::connect(&session)?.stop()?;
+            Ok(0)
+        }
+        Action::RootfsImport { image, dir } => {
+            let count = pronyx::import_rootfs(&image, &dir)?;
+            println!("{}: {} entries imported", dir.display(), count);
+            Ok(0)
+        }
+        Action::RootfsExport { dir, image } => {
+            let count = pronyx::export_rootfs(&dir, &image)?;
+            println!("{}: {} entries exported", image.display(), count);
+            Ok(0)
+        }
+    }
+}
 
-    debug!(
-        "first tracee exit with exit code: {}",
-        proot.init_exit_code.unwrap()
+/// Tell which processes were killed because the init process terminated.
+fn report_orphans(orphans: &[Orphan]) {
+    if orphans.is_empty() {
+        return;
+    }
+    eprintln!(
+        "pronyx: the init process terminated, killed {} orphan process(es):",
+        orphans.len()
     );
-
-    std::process::exit(proot.init_exit_code.unwrap());
+    for orphan in orphans {
+        eprintln!("{:>7} {}", orphan.pid, orphan.command.join(" "));
+    }
 }
 
 fn main() {
     env_logger::init();
-    if let Err(err) = run() {
-        error!("Exited with error: {}", err);
-        std::process::exit(1);
+    match run() {
+        Ok(exit_code) => std::process::exit(exit_code),
+        Err(err) => {
+            error!("Exited with error: {}", err);
+            std::process::exit(1);
+        }
     }
 }
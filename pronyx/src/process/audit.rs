@@ -0,0 +1,384 @@
+//! File-access audit log (`--audit`).
+//!
+//! A JSON record is written (one per line) for every path argument of the
+//! path-translating syscalls, once the syscall is finished, e.g.:
+//!
+//! ```text
+//! {"time":1697712000.123456,"pid":42,"exe":"/usr/bin/cat","syscall":"openat","op":"read",
+//!  "guest_path":"/mnt/passwd","host_path":"/etc/passwd",
+//!  "binding":{"host":"/etc","guest":"/mnt"},"result":3,"errno":null}
+//! ```
+//!
+//! `exe` is the host path of the executable of the tracee, as `Tracee.exe`.
+
+use std::cell::RefCell;
+use std::ffi::OsString;
+use std::fmt::{self, Write as _};
+use std::fs::File;
+use std::io::{LineWriter, Write};
+use std::os::unix::ffi::OsStringExt;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use nix::unistd::Pid;
+
+use crate::errors::*;
+use crate::filesystem::Translator;
+use crate::filesystem::binding::Side;
+use crate::kernel::syscall::{name_of_syscall, path_args};
+use crate::process::tracee::Tracee;
+use crate::register::{memory, read_path};
+use crate::register::{Current, Modified, Original, SysArg, SysArgIndex, SysResult, Word};
+
+/// Class of a file access.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AuditOp {
+    Read,
+    Write,
+    Exec,
+    Create,
+    Delete,
+    Rename,
+    Socket,
+}
+
+impl AuditOp {
+    /// Class of the syscall `name`, whose flags (for `open()`-like syscalls)
+    /// are `flags`.
+    fn of_syscall(name: &str, flags: Option<Word>) -> AuditOp {
+        match name {
+            "open" | "openat" | "openat2" => {
+                let flags = flags.unwrap_or(0) as i32;
+                if flags & libc::O_CREAT != 0 {
+                    AuditOp::Create
+                } else if flags & (libc::O_WRONLY | libc::O_RDWR | libc::O_TRUNC) != 0 {
+                    AuditOp::Write
+                } else {
+                    AuditOp::Read
+                }
+            }
+            "execve" | "execveat" | "uselib" => AuditOp::Exec,
+            "creat" | "mkdir" | "mkdirat" | "mknod" | "mknodat" | "symlink" | "symlinkat"
+            | "link" | "linkat" => AuditOp::Create,
+            "unlink" | "unlinkat" | "rmdir" => AuditOp::Delete,
+            "rename" | "renameat" | "renameat2" => AuditOp::Rename,
+            "truncate" | "truncate64" | "chmod" | "fchmodat" | "fchmodat2" | "chown"
+            | "chown32" | "lchown" | "lchown32" | "fchownat" | "utime" | "utimes" | "utimensat"
+            | "futimesat" | "setxattr" | "lsetxattr" | "removexattr" | "lremovexattr" => {
+                AuditOp::Write
+            }
+            "bind" | "connect" => AuditOp::Socket,
+            _ => AuditOp::Read,
+        }
+    }
+
+    fn as_str(&self) -> &'static str {
+        match self {
+            AuditOp::Read => "read",
+            AuditOp::Write => "write",
+            AuditOp::Exec => "exec",
+            AuditOp::Create => "create",
+            AuditOp::Delete => "delete",
+            AuditOp::Rename => "rename",
+            AuditOp::Socket => "socket",
+        }
+    }
+}
+
+#[derive(Debug)]
+struct AuditBinding {
+    host: PathBuf,
+    guest: PathBuf,
+}
+
+/// One audited path, recorded at the syscall entry and written at its exit.
+#[derive(Debug)]
+pub struct AuditRecord {
+    time: f64,
+    pid: i32,
+    exe: Option<PathBuf>,
+    syscall: &'static str,
+    op: AuditOp,
+    guest_path: PathBuf,
+    host_path: Option<PathBuf>,
+    binding: Option<AuditBinding>,
+    result: Option<i64>,
+    errno: Option<String>,
+}
+
+impl AuditRecord {
+    /// Serialize the record as a single-line JSON object.
+    fn to_json(&self) -> String {
+        let path = |path: &Path| json_string(&path.to_string_lossy());
+        let or_null = |value: Option<String>| value.unwrap_or_else(|| "null".to_string());
+
+        format!(
+            "{{\"time\":{},\"pid\":{},\"exe\":{},\"syscall\":{},\"op\":{},\
+             \"guest_path\":{},\"host_path\":{},\"binding\":{},\"result\":{},\"errno\":{}}}",
+            self.time,
+            self.pid,
+            or_null(self.exe.as_deref().map(path)),
+            json_string(self.syscall),
+            json_string(self.op.as_str()),
+            path(&self.guest_path),
+            or_null(self.host_path.as_deref().map(path)),
+            or_null(self.binding.as_ref().map(|binding| format!(
+                "{{\"host\":{},\"guest\":{}}}",
+                path(&binding.host),
+                path(&binding.guest)
+            ))),
+            or_null(self.result.map(|result| result.to_string())),
+            or_null(self.errno.as_deref().map(json_string)),
+        )
+    }
+}
+
+/// Quote and escape `value` as a JSON string.
+fn json_string(value: &str) -> String {
+    let mut quoted = String::with_capacity(value.len() + 2);
+    quoted.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\r' => quoted.push_str("\\r"),
+            '\t' => quoted.push_str("\\t"),
+            c if (c as u32) < 0x20 => {
+                let _ = write!(quoted, "\\u{:04x}", c as u32);
+            }
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+/// Writes the audit log of all the tracees.
+pub struct AuditLog {
+    output: RefCell<LineWriter<File>>,
+}
+
+impl fmt::Debug for AuditLog {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AuditLog").finish_non_exhaustive()
+    }
+}
+
+impl AuditLog {
+    pub fn new(path: &Path) -> Result<AuditLog> {
+        let file = File::create(path)
+            .with_context(|| format!("cannot create the audit log {:?}", path))?;
+        Ok(AuditLog {
+            output: RefCell::new(LineWriter::new(file)),
+        })
+    }
+
+    /// Whether the syscall `sysnum` can access paths, and thus be recorded.
+    pub fn audits(&self, sysnum: usize) -> bool {
+        name_of_syscall(sysnum).is_some_and(|name| {
+            name == "bind" || name == "connect" || !path_args(name).is_empty()
+        })
+    }
+
+    /// Record the paths accessed by the syscall of `tracee`, at the end of
+    /// its enter stage.
+    pub fn record_enter(&self, tracee: &Tracee) -> Vec<AuditRecord> {
+        let name = match name_of_syscall(tracee.regs.get_sys_num(Original)) {
+            Some(name) => name,
+            None => return vec![],
+        };
+        let time = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs_f64();
+        let exe = tracee.exe.as_ref().map(|exe| exe.borrow().clone());
+        let arg = |index: usize| {
+            tracee
+                .regs
+                .get(Original, SysArg(SysArgIndex::from_index(index)))
+        };
+
+        let record = |op: AuditOp, guest_path: PathBuf, host_path: Option<PathBuf>| {
+            let fs = tracee.fs.borrow();
+            // the guest path is given as seen by the tracee (possibly relative),
+            // so the canonical one is derived from the translated path
+            let guest_path = match &host_path {
+                Some(host_path) if host_path.is_absolute() => fs
+                    .detranslate_path(host_path, None)
+                    .ok()
+                    .flatten()
+                    .unwrap_or_else(|| host_path.clone()),
+                _ => guest_path,
+            };
+            let binding = fs
+                .get_first_appropriate_binding(&guest_path, Side::Guest)
+                .map(|binding| AuditBinding {
+                    host: binding.get_path(Side::Host).clone(),
+                    guest: binding.get_path(Side::Guest).clone(),
+                });
+            AuditRecord {
+                time,
+                pid: tracee.pid.as_raw(),
+                exe: exe.clone(),
+                syscall: name,
+                op,
+                guest_path,
+                host_path,
+                binding,
+                result: None,
+                errno: None,
+            }
+        };
+
+        if name == "bind" || name == "connect" {
+            return read_unix_socket_path(tracee, arg(1))
+                .map(|path| vec![record(AuditOp::Socket, path, None)])
+                .unwrap_or_default();
+        }
+
+        let flags = match name {
+            "open" => Some(arg(1)),
+            "openat" => Some(arg(2)),
+            "openat2" => read_open_how_flags(tracee.pid, arg(2)),
+            _ => None,
+        };
+        let op = AuditOp::of_syscall(name, flags);
+
+        path_args(name)
+            .iter()
+            .filter_map(|&index| {
+                let original = arg(index);
+                if original == 0 {
+                    return None;
+                }
+                let guest_path = read_path(tracee.pid, original as *mut Word).ok()?;
+                let host_path = if op == AuditOp::Exec {
+                    // the path argument was replaced by the loader
+                    tracee.new_exe.as_ref().map(|exe| exe.borrow().clone())
+                } else {
+                    let modified = tracee
+                        .regs
+                        .get(Modified, SysArg(SysArgIndex::from_index(index)));
+                    read_path(tracee.pid, modified as *mut Word).ok()
+                };
+                Some(record(op, guest_path, host_path))
+            })
+            .collect()
+    }
+
+    /// Write records made by `record_enter()`. `tracee` is at the end of the
+    /// exit stage, or `None` if the syscall has no exit stage.
+    pub fn write(&self, records: Vec<AuditRecord>, tracee: Option<&Tracee>) {
+        let result = tracee.map(|tracee| tracee.regs.get(Current, SysResult) as i64);
+        let mut output = self.output.borrow_mut();
+        for mut record in records {
+            record.result = result;
+            if let Some(result) = result
+                && (-4095..0).contains(&result)
+            {
+                record.errno = Some(format!("{:?}", Errno::from_raw(-result as i32)));
+            }
+            let _ = writeln!(output, "{}", record.to_json());
+        }
+    }
+}
+
+/// `flags` of the `struct open_how` (given to `openat2()`) located at
+/// `address` in the memory of the tracee `pid`.
+fn read_open_how_flags(pid: Pid, address: Word) -> Option<Word> {
+    if address == 0 {
+        return None;
+    }
+    // `flags` is the first field, and is 64 bits wide on every architecture
+    let mut flags = [0; std::mem::size_of::<u64>()];
+    memory::read_bytes(pid, address, &mut flags).ok()?;
+    Some(u64::from_ne_bytes(flags) as Word)
+}
+
+/// Path of a `struct sockaddr_un` located at `address` in the memory of
+/// `tracee`. Other address families and abstract sockets give `None`.
+fn read_unix_socket_path(tracee: &Tracee, address: Word) -> Option<PathBuf> {
+    if address == 0 {
+        return None;
+    }
+    let mut family = [0; std::mem::size_of::<libc::sa_family_t>()];
+    memory::read_bytes(tracee.pid, address, &mut family).ok()?;
+    if libc::sa_family_t::from_ne_bytes(family) != libc::AF_UNIX as libc::sa_family_t {
+        return None;
+    }
+    // `sun_path` is not necessarily null-terminated when it is full
+    let sun_path = address + family.len() as Word;
+    let sun_path_size = std::mem::size_of::<libc::sockaddr_un>() - family.len();
+    let path = memory::read_string(tracee.pid, sun_path, sun_path_size).ok()?;
+    if path.is_empty() {
+        None
+    } else {
+        Some(PathBuf::from(OsString::from_vec(path)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_audit_op() {
+        let op = |name, flags| AuditOp::of_syscall(name, flags);
+        assert_eq!(op("openat", Some(libc::O_RDONLY as Word)), AuditOp::Read);
+        assert_eq!(op("openat", Some(libc::O_WRONLY as Word)), AuditOp::Write);
+        assert_eq!(
+            op("open", Some((libc::O_CREAT | libc::O_WRONLY) as Word)),
+            AuditOp::Create
+        );
+        assert_eq!(
+            op("openat2", Some((libc::O_CREAT | libc::O_RDWR) as Word)),
+            AuditOp::Create
+        );
+        assert_eq!(op("execve", None), AuditOp::Exec);
+        assert_eq!(op("unlinkat", None), AuditOp::Delete);
+        assert_eq!(op("renameat2", None), AuditOp::Rename);
+        assert_eq!(op("connect", None), AuditOp::Socket);
+        assert_eq!(op("newfstatat", None), AuditOp::Read);
+    }
+
+    #[test]
+    fn test_audit_open_how_flags() {
+        // flags, mode and resolve of a `struct open_how`
+        let how: [u64; 3] = [(libc::O_WRONLY | libc::O_TRUNC) as u64, 0o644, 0];
+        let address = how.as_ptr() as Word;
+        let flags = read_open_how_flags(nix::unistd::getpid(), address);
+        assert_eq!(flags, Some((libc::O_WRONLY | libc::O_TRUNC) as Word));
+        assert_eq!(AuditOp::of_syscall("openat2", flags), AuditOp::Write);
+        assert_eq!(read_open_how_flags(nix::unistd::getpid(), 0), None);
+    }
+
+    #[test]
+    fn test_audit_record_json() {
+        let record = AuditRecord {
+            time: 1.5,
+            pid: 42,
+            exe: None,
+            syscall: "unlinkat",
+            op: AuditOp::Delete,
+            guest_path: PathBuf::from("/tmp/a"),
+            host_path: Some(PathBuf::from("/rootfs/tmp/a")),
+            binding: Some(AuditBinding {
+                host: PathBuf::from("/rootfs"),
+                guest: PathBuf::from("/"),
+            }),
+            result: Some(-2),
+            errno: Some("ENOENT".to_string()),
+        };
+        assert_eq!(
+            record.to_json(),
+            r#"{"time":1.5,"pid":42,"exe":null,"syscall":"unlinkat","op":"delete","guest_path":"/tmp/a","host_path":"/rootfs/tmp/a","binding":{"host":"/rootfs","guest":"/"},"result":-2,"errno":"ENOENT"}"#
+        );
+    }
+
+    #[test]
+    fn test_audit_json_string() {
+        assert_eq!(json_string("/tmp/a"), r#""/tmp/a""#);
+        assert_eq!(json_string("a\"b\\c\nd\u{1}"), r#""a\"b\\c\nd\u0001""#);
+    }
+}
@@ -0,0 +1,164 @@
+use std::collections::BTreeMap;
+use std::ffi::CString;
+
+use crate::errors::*;
+use crate::filesystem::passwd::lookup_guest_user;
+use crate::filesystem::FileSystem;
+
+/// `PATH` given to the guest when it is not set explicitly.
+pub const DEFAULT_PATH: &'static str =
+    "/usr/local/sbin:/usr/local/bin:/usr/sbin:/usr/bin:/sbin:/bin";
+
+/// Host variables passed through to the guest by default. They describe the
+/// user's terminal and locale, and are meaningful on both sides.
+const INHERITED_VARIABLES: &[&str] = &[
+    "TERM",
+    "COLORTERM",
+    "LANG",
+    "LANGUAGE",
+    "TZ",
+    "DISPLAY",
+    "WAYLAND_DISPLAY",
+];
+
+/// Describes how the environment of the initial command is built.
+///
+/// By default, the guest gets a sane environment: a few terminal and locale
+/// variables of the host, a guest `PATH`, and `HOME`, `USER`, `LOGNAME` and
+/// `SHELL` from the guest's `/etc/passwd`. Other host variables (e.g.
+/// `LD_LIBRARY_PATH`) are not leaked into the guest.
+#[derive(Debug, Clone, Default)]
+pub struct EnvironmentSpec {
+    /// Start from an empty environment instead of the default one.
+    pub clear: bool,
+    /// Variables to set, in order (later ones win).
+    pub set: Vec<(String, String)>,
+    /// Variables to remove, applied after `set`.
+    pub unset: Vec<String>,
+}
+
+impl EnvironmentSpec {
+    /// Set the variable `key`, overriding an earlier `unset_var()` of it.
+    pub fn set_var(&mut self, key: String, value: String) {
+        self.unset.retain(|name| *name != key);
+        self.set.push((key, value));
+    }
+
+    /// Remove the variable `key`, overriding an earlier `set_var()` of it.
+    pub fn unset_var(&mut self, key: String) {
+        self.set.retain(|(name, _)| *name != key);
+        self.unset.push(key);
+    }
+
+    /// Build the guest environment for a user `uid`, whose identity is looked
+    /// up in the guest file-system `fs`.
+    pub fn build(&self, fs: &FileSystem, uid: u32) -> BTreeMap<String, String> {
+        let mut env = BTreeMap::new();
+
+        if !self.clear {
+            for (key, value) in std::env::vars() {
+                if INHERITED_VARIABLES.contains(&key.as_str()) || key.starts_with("LC_") {
+                    env.insert(key, value);
+                }
+            }
+            env.insert("PATH".to_string(), DEFAULT_PATH.to_string());
+
+            match lookup_guest_user(fs, uid) {
+                Ok(Some(user)) => {
+                    env.insert("HOME".to_string(), user.home);
+                    env.insert("USER".to_string(), user.name.clone());
+                    env.insert("LOGNAME".to_string(), user.name);
+                    if !user.shell.is_empty() {
+                        env.insert("SHELL".to_string(), user.shell);
+                    }
+                }
+                Ok(None) => {
+                    env.insert("HOME".to_string(), "/".to_string());
+                }
+                Err(error) => {
+                    debug!("cannot read guest /etc/passwd: {}", error);
+                    env.insert("HOME".to_string(), "/".to_string());
+                }
+            }
+        }
+
+        for (key, value) in &self.set {
+            env.insert(key.clone(), value.clone());
+        }
+        for key in &self.unset {
+            env.remove(key);
+        }
+        env
+    }
+}
+
+/// Convert an environment into the `KEY=VALUE` strings expected by
+/// `execve()`.
+pub fn to_envp(env: &BTreeMap<String, String>) -> Result<Vec<CString>> {
+    env.iter()
+        .map(|(key, value)| {
+            CString::new(format!("{}={}", key, value)).map_err(|_| {
+                Error::errno_with_msg(EINVAL, format!("illegal environment variable: {}", key))
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_environment_default() {
+        let fs = FileSystem::with_root("/").unwrap();
+        let env = EnvironmentSpec::default().build(&fs, 0);
+
+        assert_eq!(env.get("PATH").map(String::as_str), Some(DEFAULT_PATH));
+        assert_eq!(env.get("USER").map(String::as_str), Some("root"));
+        assert!(env.contains_key("HOME"));
+        assert!(!env.contains_key("LD_LIBRARY_PATH"));
+        assert!(!env.contains_key("CARGO_PKG_NAME"));
+    }
+
+    #[test]
+    fn test_environment_set_unset_clear() {
+        let fs = FileSystem::with_root("/").unwrap();
+        let spec = EnvironmentSpec {
+            clear: false,
+            set: vec![
+                ("PATH".to_string(), "/bin".to_string()),
+                ("FOO".to_string(), "bar".to_string()),
+            ],
+            unset: vec!["HOME".to_string()],
+        };
+        let env = spec.build(&fs, 0);
+        assert_eq!(env.get("PATH").map(String::as_str), Some("/bin"));
+        assert_eq!(env.get("FOO").map(String::as_str), Some("bar"));
+        assert!(!env.contains_key("HOME"));
+
+        let spec = EnvironmentSpec {
+            clear: true,
+            set: vec![("FOO".to_string(), "bar".to_string())],
+            unset: vec![],
+        };
+        let env = spec.build(&fs, 0);
+        assert_eq!(env.len(), 1);
+        assert_eq!(
+            to_envp(&env).unwrap(),
+            vec![CString::new("FOO=bar").unwrap()]
+        );
+    }
+
+    #[test]
+    fn test_environment_later_wins() {
+        let fs = FileSystem::with_root("/").unwrap();
+        let mut spec = EnvironmentSpec::default();
+        spec.unset_var("FOO".to_string());
+        spec.set_var("FOO".to_string(), "bar".to_string());
+        spec.set_var("BAZ".to_string(), "qux".to_string());
+        spec.unset_var("BAZ".to_string());
+        let env = spec.build(&fs, 0);
+        assert_eq!(env.get("FOO").map(String::as_str), Some("bar"));
+        assert!(!env.contains_key("BAZ"));
+    }
+}
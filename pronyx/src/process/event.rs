@@ -4,6 +4,7 @@ use std::rc::Rc;
 use libc::pid_t;
 use nix::sched::CloneFlags;
 use nix::sys::ptrace::Event as PtraceEvent;
+use nix::sys::signal::Signal;
 use nix::unistd::Pid;
 
 use crate::errors::*;
@@ -11,7 +12,7 @@ use crate::filesystem::FileSystem;
 use crate::process::proot::InfoBag;
 use crate::process::tracee::{Tracee, TraceeRestartMethod, TraceeStatus};
 use crate::process::translation::SyscallTranslator;
-use crate::register::{RegVersion, SysArg, SysArg1};
+use crate::register::{memory, RegVersion, SysArg, SysArg1};
 use crate::utils::Config;
 
 use super::tracee::SigStopStatus;
@@ -19,13 +20,13 @@ use super::tracee::SigStopStatus;
 pub trait EventHandler {
     fn handle_syscall_stop_event(
         &mut self,
-        config: Config,
+        config: &Config,
         info_bag: &mut InfoBag,
         #[cfg(test)] func_syscall_hook: &Option<Box<dyn Fn(&Tracee, bool, bool)>>,
     );
-    fn handle_sigstop_event(&mut self);
+    fn handle_sigstop_event(&mut self, signal: Signal);
     fn handle_seccomp_event(&mut self, info_bag: &mut InfoBag, event: PtraceEvent);
-    fn handle_exec_vfork_event(&mut self);
+    fn handle_exec_vfork_event(&mut self, event: PtraceEvent) -> Result<Option<Pid>>;
     fn handle_new_child_event(&mut self) -> Result<Tracee>;
 }
 
@@ -34,7 +35,7 @@ impl EventHandler for Tracee {
     /// parameters and restart it
     fn handle_syscall_stop_event(
         &mut self,
-        config: Config,
+        config: &Config,
         info_bag: &mut InfoBag,
         #[cfg(test)] func_syscall_hook: &Option<Box<dyn Fn(&Tracee, bool, bool)>>,
     ) {
@@ -68,24 +69,29 @@ impl EventHandler for Tracee {
         );
     }
 
-    fn handle_sigstop_event(&mut self) {
-        debug!("sigstop! {}", self.pid);
-
-        // Stop this tracee until PRoot has received
-        // the EVENT_*FORK|CLONE notification.
-        // if self.exe.is_none() {
-        //     tracee->sigstop = SIGSTOP_PENDING;
-        //     self.restart_how = TraceeRestartMethod::None;
-        //     return TraceeRestartSignal::Stopped;
-        // }
+    /// Group-stop of the tracee, after the stop signal `signal` (`SIGSTOP`,
+    /// `SIGTSTP`, `SIGTTIN` or `SIGTTOU`) was delivered: it stays stopped, as
+    /// it would without being traced, until a `SIGCONT` ends the group-stop.
+    /// Only a seized tracee tells its group-stops apart (`PTRACE_EVENT_STOP`).
+    fn handle_sigstop_event(&mut self, signal: Signal) {
+        debug!("-- {}, group-stop by {:?}", self.pid, signal);
+        self.restart_how = TraceeRestartMethod::Listen;
     }
 
     fn handle_seccomp_event(&mut self, info_bag: &mut InfoBag, signal: PtraceEvent) {
         debug!("seccomp event! {:?}, {:?}", info_bag, signal);
     }
 
-    fn handle_exec_vfork_event(&mut self) {
-        debug!("EXEC or VFORK event");
+    /// Returns the former pid of the thread which executed a program, when it
+    /// is not this tracee: it then took the pid of its thread group leader,
+    /// this tracee (see "execve(2) under ptrace" in ptrace(2)).
+    fn handle_exec_vfork_event(&mut self, event: PtraceEvent) -> Result<Option<Pid>> {
+        debug!("{:?} event", event);
+        if event != PtraceEvent::PTRACE_EVENT_EXEC {
+            return Ok(None);
+        }
+        let former_pid = Pid::from_raw(nix::sys::ptrace::getevent(self.pid)? as pid_t);
+        Ok(Some(former_pid).filter(|former_pid| *former_pid != self.pid))
     }
 
     fn handle_new_child_event(&mut self) -> Result<Tracee> {
@@ -94,12 +100,22 @@ impl EventHandler for Tracee {
         self.regs.fetch_regs()?;
         let sysnum = self.regs.get_sys_num(RegVersion::Current);
 
-        let clone_flags = match sysnum {
+        let clone_flags = match sysnum as i64 {
             #[cfg(any(target_arch = "x86_64", target_arch = "arm", target_arch = "x86"))]
-            VFORK => CloneFlags::CLONE_VFORK,
-            CLONE => CloneFlags::from_bits_truncate(
+            crate::sc::nr::VFORK => CloneFlags::CLONE_VFORK,
+            crate::sc::nr::CLONE => CloneFlags::from_bits_truncate(
                 self.regs.get(RegVersion::Current, SysArg(SysArg1)) as i32,
             ),
+            // The flags are the first field of `struct clone_args`.
+            crate::sc::nr::CLONE3 => {
+                let mut flags = [0; 8];
+                memory::read_bytes(
+                    self.pid,
+                    self.regs.get(RegVersion::Current, SysArg(SysArg1)),
+                    &mut flags,
+                )?;
+                CloneFlags::from_bits_truncate(u64::from_ne_bytes(flags) as i32)
+            }
             _ => CloneFlags::empty(),
         };
 
@@ -159,6 +175,11 @@ impl EventHandler for Tracee {
             Rc::new(RefCell::new(fs))
         };
         let mut child_tracee = Tracee::new(child_pid, fs);
+        if clone_flags.contains(CloneFlags::CLONE_FILES) {
+            child_tracee.fds = self.fds.clone();
+        } else {
+            child_tracee.fds = Rc::new(RefCell::new(self.fds.borrow().clone()));
+        }
 
         // The path to the executable is unshared only once the child process does a
         // call to execve(2).
@@ -199,7 +220,14 @@ impl EventHandler for Tracee {
         //         (void) restart_tracee(child, 0);
         // }
 
-        child_tracee.sigstop_status = SigStopStatus::WaitForSigStopClone;
+        // A child of a seized tracee starts with a PTRACE_EVENT_STOP instead of the
+        // SIGSTOP, which thus must not be eliminated.
+        child_tracee.seized = self.seized;
+        child_tracee.sigstop_status = if self.seized {
+            SigStopStatus::AllowDelivery
+        } else {
+            SigStopStatus::WaitForSigStopClone
+        };
 
         Ok(child_tracee)
     }
@@ -0,0 +1,196 @@
+use std::collections::HashMap;
+use std::os::unix::io::RawFd;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+use crate::filesystem::virtual_file::WriteBack;
+
+/// The guest paths of the file descriptors of one or more tracee, which
+/// corresponds to the [`files_struct`] structure in the kernel. If clone() is
+/// called with `CLONE_FILES` set, then both parent tracee and child tracee
+/// will share this structure, otherwise a copy will be created.
+///
+/// Only the file descriptors opened by `open()`/`openat()`, and their
+/// duplicates, are known. The other ones (inherited from the tracer, received
+/// through a socket...) are resolved with `/proc/<pid>/fd`, see
+/// `Tracee::get_path_from_fd()`: forgetting a file descriptor is always safe.
+///
+/// [`files_struct`]: https://elixir.bootlin.com/linux/latest/source/include/linux/fdtable.h
+#[derive(Debug, Clone, Default)]
+pub struct FdTable {
+    entries: HashMap<RawFd, FdEntry>,
+}
+
+#[derive(Debug, Clone)]
+struct FdEntry {
+    /// Canonical guest path of the file the descriptor was opened with.
+    path: PathBuf,
+    /// Whether the descriptor is closed by `execve()` (`FD_CLOEXEC`).
+    cloexec: bool,
+    /// The private copy of the virtual file this descriptor writes to,
+    /// shared with its duplicates (in this table or in a copy of it). It is
+    /// written back once they are all closed, when it is dropped.
+    _write_back: Option<Rc<WriteBack>>,
+}
+
+/// A change of the file descriptors which is done at the exit stage of a
+/// syscall, if it succeeds, with the guest paths known at its enter stage.
+#[derive(Debug)]
+pub enum FdChange {
+    /// `open()`/`openat()` of this canonical guest path.
+    Open(PathBuf),
+    /// `open()`/`openat()` for writing of this canonical guest path, a virtual
+    /// file whose private copy is written back once closed.
+    OpenVirtual(PathBuf, WriteBack),
+    /// `rename()`/`renameat()` of the first canonical guest path to the
+    /// second one.
+    Rename(PathBuf, PathBuf),
+}
+
+impl FdTable {
+    pub fn new() -> FdTable {
+        FdTable::default()
+    }
+
+    /// Get the canonical guest path of the file descriptor `fd`, if known.
+    pub fn get(&self, fd: RawFd) -> Option<&Path> {
+        self.entries.get(&fd).map(|entry| entry.path.as_path())
+    }
+
+    /// Record that `fd` was opened with the canonical guest path `path`.
+    pub fn open(&mut self, fd: RawFd, path: PathBuf, cloexec: bool) {
+        let _write_back = None;
+        self.entries.insert(fd, FdEntry { path, cloexec, _write_back });
+    }
+
+    /// Record that `fd` was opened with the canonical guest path `path`, a
+    /// virtual file which is written back once `fd` and its duplicates are
+    /// closed.
+    pub fn open_virtual(
+        &mut self,
+        fd: RawFd,
+        path: PathBuf,
+        cloexec: bool,
+        write_back: WriteBack,
+    ) {
+        let _write_back = Some(Rc::new(write_back));
+        self.entries.insert(fd, FdEntry { path, cloexec, _write_back });
+    }
+
+    /// Record that `new_fd` is a duplicate of `old_fd` (`dup()`,
+    /// `fcntl(F_DUPFD)`...), which closed `new_fd` before if needed.
+    pub fn dup(&mut self, old_fd: RawFd, new_fd: RawFd, cloexec: bool) {
+        match self.entries.get(&old_fd) {
+            Some(entry) => {
+                let entry = FdEntry {
+                    cloexec,
+                    ..entry.clone()
+                };
+                self.entries.insert(new_fd, entry);
+            }
+            None => self.close(new_fd),
+        }
+    }
+
+    /// Set or clear the `FD_CLOEXEC` flag of `fd`.
+    pub fn set_cloexec(&mut self, fd: RawFd, cloexec: bool) {
+        if let Some(entry) = self.entries.get_mut(&fd) {
+            entry.cloexec = cloexec;
+        }
+    }
+
+    pub fn close(&mut self, fd: RawFd) {
+        self.entries.remove(&fd);
+    }
+
+    /// Close (or, if `cloexec` is set, set the `FD_CLOEXEC` flag of) the file
+    /// descriptors from `first` to `last` included, see `close_range()`.
+    pub fn close_range(&mut self, first: RawFd, last: RawFd, cloexec: bool) {
+        if cloexec {
+            self.entries
+                .iter_mut()
+                .filter(|(fd, _)| (first..=last).contains(*fd))
+                .for_each(|(_, entry)| entry.cloexec = true);
+        } else {
+            self.entries.retain(|fd, _| !(first..=last).contains(fd));
+        }
+    }
+
+    /// Close the file descriptors flagged with `FD_CLOEXEC`, as a successful
+    /// `execve()` does.
+    pub fn exec(&mut self) {
+        self.entries.retain(|_, entry| !entry.cloexec);
+    }
+
+    /// Update the paths of the file descriptors after the rename of the
+    /// canonical guest path `old_path` to `new_path`.
+    pub fn rename(&mut self, old_path: &Path, new_path: &Path) {
+        for entry in self.entries.values_mut() {
+            if let Ok(suffix) = entry.path.strip_prefix(old_path) {
+                entry.path = if suffix.as_os_str().is_empty() {
+                    new_path.to_path_buf()
+                } else {
+                    new_path.join(suffix)
+                };
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fd_table_dup_and_close() {
+        let mut table = FdTable::new();
+        table.open(3, "/etc".into(), false);
+        table.open(4, "/home".into(), true);
+
+        table.dup(3, 5, true);
+        assert_eq!(table.get(5), Some(Path::new("/etc")));
+        // duplicating an unknown descriptor forgets the one it replaces
+        table.dup(42, 5, false);
+        assert_eq!(table.get(5), None);
+
+        table.close(3);
+        assert_eq!(table.get(3), None);
+        assert_eq!(table.get(4), Some(Path::new("/home")));
+
+        table.open(3, "/etc".into(), false);
+        table.close_range(4, RawFd::MAX, false);
+        assert_eq!(table.get(3), Some(Path::new("/etc")));
+        assert_eq!(table.get(4), None);
+    }
+
+    #[test]
+    fn test_fd_table_exec() {
+        let mut table = FdTable::new();
+        table.open(3, "/etc".into(), false);
+        table.open(4, "/home".into(), true);
+        table.open(5, "/tmp".into(), false);
+        table.open(6, "/usr".into(), true);
+        table.set_cloexec(5, true);
+        table.set_cloexec(6, false);
+        table.close_range(3, 3, true);
+
+        table.exec();
+        assert_eq!(table.get(3), None);
+        assert_eq!(table.get(4), None);
+        assert_eq!(table.get(5), None);
+        assert_eq!(table.get(6), Some(Path::new("/usr")));
+    }
+
+    #[test]
+    fn test_fd_table_rename() {
+        let mut table = FdTable::new();
+        table.open(3, "/a".into(), false);
+        table.open(4, "/a/b/c".into(), false);
+        table.open(5, "/ab".into(), false);
+
+        table.rename(Path::new("/a"), Path::new("/d"));
+        assert_eq!(table.get(3), Some(Path::new("/d")));
+        assert_eq!(table.get(4), Some(Path::new("/d/b/c")));
+        assert_eq!(table.get(5), Some(Path::new("/ab")));
+    }
+}
@@ -1,5 +1,9 @@
+pub mod audit;
+pub mod environment;
 pub mod event;
+pub mod fd_table;
 pub mod proot;
 pub mod sigactions;
+pub mod trace;
 pub mod tracee;
 pub mod translation;
@@ -1,22 +1,29 @@
 use std::cell::RefCell;
 use std::ffi::CString;
-
-use std::process;
+use std::os::fd::{AsRawFd, OwnedFd, RawFd};
+use std::os::unix::ffi::OsStrExt;
+use std::path::PathBuf;
 use std::rc::Rc;
-use std::{collections::HashMap, convert::TryFrom};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::collections::HashMap;
 
 use libc::{c_int, c_void, pid_t, siginfo_t};
 use nix::sys::ptrace::{self, Event as PtraceEvent};
-use nix::sys::signal::{Signal};
-use nix::sys::wait::{self, WaitPidFlag, WaitStatus::*};
+use nix::sys::signal::{self, SigSet, SigmaskHow, Signal};
+use nix::sys::wait::{self, WaitPidFlag, WaitStatus, WaitStatus::*};
 use nix::unistd::{self, ForkResult, Pid};
 
 use crate::kernel::execve::loader::LoaderFile;
+use crate::process::environment::{self, EnvironmentSpec, DEFAULT_PATH};
+use crate::process::audit::AuditLog;
 use crate::process::event::EventHandler;
+use crate::process::sigactions;
+use crate::process::trace::SyscallTracer;
 use crate::process::tracee::{SigStopStatus, Tracee};
 use crate::{
     errors::*,
-    filesystem::{temp::TempFile, FileSystem},
+    filesystem::{temp::TempFile, FileSystem, Translator},
 };
 use crate::utils::Config;
 
@@ -26,23 +33,70 @@ use libc::kill;
 /// `Configuration`?
 #[derive(Debug)]
 pub struct InfoBag {
-    /// Used to know if the ptrace options is already set.
-    pub options_already_set: bool,
     /// Binary loader, used by `execve`.
     /// The content of the binary is actually inlined in `proot-rs`
     /// (see `src/kernel/execve/loader`), and is extracted into a temporary file
     /// before use. This temporary file struct makes sure the file is
     /// deleted when it's dropped.
     pub loader: TempFile,
+    /// Writes the syscall trace, if enabled.
+    pub tracer: Option<SyscallTracer>,
+    /// Writes the file-access audit log, if enabled.
+    pub audit: Option<AuditLog>,
 }
 
 impl InfoBag {
     pub fn new() -> InfoBag {
         InfoBag {
-            options_already_set: false,
             loader: TempFile::new("prooted"),
+            tracer: None,
+            audit: None,
+        }
+    }
+}
+
+/// When the event loop returns.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ExitPolicy {
+    /// Once the init process terminates: the tracees it leaves behind (e.g.
+    /// daemons) are then killed, see `PRoot::orphans()`.
+    Init,
+    /// Once every tracee terminates.
+    #[default]
+    All,
+}
+
+/// A tracee killed because the init process terminated, with
+/// `ExitPolicy::Init`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Orphan {
+    pub pid: i32,
+    /// Its arguments, empty if they could not be read.
+    pub command: Vec<String>,
+}
+
+/// How the init process (i.e. the first tracee) terminated.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExitStatus {
+    /// The init process exited with this exit code.
+    Exited(i32),
+    /// The init process was killed by this signal.
+    Signaled(i32),
+}
+
+impl ExitStatus {
+    /// The exit code a shell would report: the exit code itself, or 128+signal
+    /// when the process was killed by a signal.
+    pub fn code(&self) -> i32 {
+        match *self {
+            ExitStatus::Exited(code) => code,
+            ExitStatus::Signaled(signal) => 128 + signal,
         }
     }
+
+    pub fn success(&self) -> bool {
+        *self == ExitStatus::Exited(0)
+    }
 }
 
 pub struct PRoot {
@@ -52,8 +106,15 @@ pub struct PRoot {
     alive_tracees: Vec<Pid>,
     /// The `pid` of init process (i.e. the first tracee)
     pub init_pid: Option<Pid>,
-    /// The exit code of the init process (i.e. the first tracee)
-    pub init_exit_code: Option<i32>,
+    /// How the init process (i.e. the first tracee) terminated
+    pub init_status: Option<ExitStatus>,
+    /// Processes started by `spawn_process()`, and how they terminated.
+    spawned: HashMap<Pid, Option<ExitStatus>>,
+    /// Tracees killed because the init process terminated.
+    orphans: Vec<Orphan>,
+    /// Set (possibly from another thread) to ask the event loop to kill every
+    /// tracee.
+    kill_requested: Arc<AtomicBool>,
     /// A pointer to a function used to check the running status of Proot.
     /// For each syscall-stop, it will be called four times (at the beginning
     /// and end of both syscall-enter-stop and syscall-exit-stop).
@@ -72,12 +133,22 @@ impl PRoot {
             tracees: HashMap::new(),
             alive_tracees: vec![],
             init_pid: None,
-            init_exit_code: None,
+            init_status: None,
+            spawned: HashMap::new(),
+            orphans: vec![],
+            kill_requested: Arc::new(AtomicBool::new(false)),
             #[cfg(test)]
             func_syscall_hook: None,
         }
     }
 
+    /// Flag used to ask the event loop to kill every tracee. Since the event
+    /// loop may be blocked in `waitpid()`, the caller should also kill the init
+    /// process after setting it.
+    pub fn kill_switch(&self) -> Arc<AtomicBool> {
+        self.kill_requested.clone()
+    }
+
     /// Some initialization is required before proot can generate tracee, and it
     /// only needs to be initialized once
     pub fn init(&mut self) -> Result<()> {
@@ -86,6 +157,12 @@ impl PRoot {
             .loader
             .prepare_loader()
             .context("Error while prepare loader file")?;
+        if let Some(options) = &self.config.trace {
+            self.info_bag.tracer = Some(SyscallTracer::new(options)?);
+        }
+        if let Some(path) = &self.config.audit {
+            self.info_bag.audit = Some(AuditLog::new(path)?);
+        }
         Ok(())
     }
 
@@ -99,11 +176,30 @@ impl PRoot {
     /// (heap, libraries...), so both of them will have their own (owned)
     /// version of the PRoot memory.
     pub fn launch_process(&mut self, initial_fs: FileSystem, command: Vec<String>) -> Result<()> {
-        debug!(
-            "launch_process with fs:\n{:#?}\ncommand: {:?}",
-            initial_fs, command
-        );
+        let env = self.config.env.clone();
+        let pid = self.spawn_process(initial_fs, command, &env, None)?;
+        self.init_pid = Some(pid);
+        Ok(())
+    }
 
+    /// Start a new tracee running `command`, with the environment described
+    /// by `env`. If `stdio` is given, these file descriptors become the
+    /// standard input, output and error of the tracee; otherwise they are
+    /// inherited from the tracer.
+    ///
+    /// Its exit status is then available with `take_exit_status()`.
+    pub fn spawn_process(
+        &mut self,
+        fs: FileSystem,
+        command: Vec<String>,
+        env: &EnvironmentSpec,
+        stdio: Option<&[OwnedFd; 3]>,
+    ) -> Result<Pid> {
+        debug!("spawn_process with fs:\n{:#?}\ncommand: {:?}", fs, command);
+
+        if command.is_empty() {
+            return Err(Error::errno_with_msg(EINVAL, "the command must not be empty"));
+        }
         // parse command
         let args = command
             .iter()
@@ -117,22 +213,71 @@ impl PRoot {
                 })
             })
             .collect::<Result<Vec<CString>>>()?;
-        let filename = &args[0];
+
+        // The environment and the program are resolved on the guest side, so
+        // that nothing from the host's PATH or environment leaks into the box.
+        let uid = if self.config.root_id {
+            0
+        } else {
+            unistd::getuid().as_raw()
+        };
+        let env = env.build(&fs, uid);
+        let envp = environment::to_envp(&env)?;
+        let search_path = env.get("PATH").map_or(DEFAULT_PATH, String::as_str);
+        let program = fs.which(&command[0], search_path)?;
+        let filename = CString::new(program.as_os_str().as_bytes())
+            .map_err(|_| Error::errno_with_msg(EINVAL, "illegal command"))?;
+        debug!("command resolved to {:?}", program);
+
+        // The child waits for the end of this pipe to be closed, once it is
+        // seized: `PTRACE_SEIZE` lets its group-stops be told apart (see
+        // `EventHandler::handle_sigstop_event()`), unlike `PTRACE_TRACEME`.
+        let (seized_reader, seized_writer) =
+            unistd::pipe().context("Failed to create a pipe when starting process")?;
         match unsafe { unistd::fork() }.context("Failed to fork() when starting process")? {
             ForkResult::Parent { child } => {
-                // create the first tracee
-                self.create_tracee(
-                    child,
-                    Rc::new(RefCell::new(initial_fs)),
-                    SigStopStatus::EventloopSync,
-                );
-                self.init_pid = Some(child);
+                drop(seized_reader);
+                let fs = Rc::new(RefCell::new(fs));
+                if let Err(error) =
+                    self.create_tracee(child, fs, SigStopStatus::EventloopSync, true)
+                {
+                    let _ = signal::kill(child, Signal::SIGKILL);
+                    let _ = wait::waitpid(child, None);
+                    return Err(error);
+                }
+                drop(seized_writer);
+                self.spawned.insert(child, None);
+                Ok(child)
             }
             ForkResult::Child => {
+                drop(seized_writer);
                 let init_child_func = || -> Result<()> {
-                    // Declare the tracee as ptraceable
-                    ptrace::traceme()
-                        .context("Failed to execute ptrace::traceme() in a child process")?;
+                    if self.config.subreaper {
+                        // The orphans of the guest are reparented to this process
+                        // instead of a host one, as with the init of a container.
+                        // The setting is kept across execve().
+                        if unsafe { libc::prctl(libc::PR_SET_CHILD_SUBREAPER, 1, 0, 0, 0) } < 0 {
+                            return Err(Error::errno_with_msg(
+                                Errno::last(),
+                                "Failed to make the process a child subreaper",
+                            ));
+                        }
+                    }
+                    if let Some(stdio) = stdio {
+                        for (target, fd) in stdio.iter().enumerate() {
+                            unistd::dup2(fd.as_raw_fd(), target as RawFd)
+                                .context("Failed to set up the standard streams")?;
+                        }
+                    }
+                    // The tracer may block some signals (e.g. `SIGCHLD` in a session), which
+                    // must not be inherited by the guest.
+                    signal::sigprocmask(SigmaskHow::SIG_SETMASK, Some(&SigSet::empty()), None)
+                        .context("Failed to reset the signal mask")?;
+                    sigactions::reset_job_control_sigactions();
+                    // Wait until the tracer has seized this process
+                    unistd::read(seized_reader.as_raw_fd(), &mut [0])
+                        .context("Failed to wait for the tracer in a child process")?;
+                    drop(seized_reader);
                     // Synchronise with the parent's event loop by waiting until it's ready
                     // (otherwise the execvp is executed too quickly)
                     unsafe {
@@ -141,22 +286,140 @@ impl PRoot {
                     //TODO: seccomp
                     //if (getenv("PROOT_NO_SECCOMP") == NULL)
                     //    (void) enable_syscall_filtering(tracee);
-                    unistd::execvp(&filename, &args).with_context(|| {
-                        format!("Failed to call execvp() with command: {:?}", command)
+                    unistd::execve(&filename, &args, &envp).with_context(|| {
+                        format!("Failed to call execve() with command: {:?}", command)
                     })?;
                     unreachable!()
                 };
 
                 if let Err(e) = init_child_func() {
                     error!("Failed to initialize the child process: {}", e);
-                    // Ensure that child processes will not return to the main function
-                    process::exit(1);
                 }
+                // Ensure that child processes will not return to the main function.
+                // `_exit()` is used because the parent may be multithreaded (e.g. when
+                // embedded), and its `atexit()` handlers must not run in the child.
+                unsafe { libc::_exit(1) };
             }
-        };
+        }
+    }
+
+    /// Trace the already running process `pid`, all of its threads included,
+    /// as if it was started in the box described by `fs`: its syscalls, and
+    /// the ones of its future children, are translated from then on. It is
+    /// then the init process.
+    ///
+    /// Its working directory is kept, as seen from the guest side, unless it
+    /// is not visible in the box: the one of `fs` is used instead.
+    pub fn attach_process(&mut self, mut fs: FileSystem, pid: Pid) -> Result<()> {
+        debug!("attach_process {} with fs:\n{:#?}", pid, fs);
+
+        let proc_dir = PathBuf::from(format!("/proc/{}", pid));
+        let host_cwd = std::fs::read_link(proc_dir.join("cwd"))
+            .with_context(|| format!("Cannot attach to process {}", pid))?;
+        let guest_cwd = fs.detranslate_path(&host_cwd, None).ok().flatten();
+        match guest_cwd.map(|guest_cwd| fs.set_cwd(&guest_cwd)) {
+            Some(Ok(())) => {}
+            _ => warn!(
+                "the working directory {:?} of process {} is not visible in the box, using {:?}",
+                host_cwd,
+                pid,
+                fs.get_cwd()
+            ),
+        }
+        let exe = std::fs::read_link(proc_dir.join("exe"))
+            .ok()
+            .map(|exe| Rc::new(RefCell::new(exe)));
+        let fs = Rc::new(RefCell::new(fs));
+
+        // Threads may be created until all of them are stopped, so the list is
+        // read again until no new one shows up.
+        let mut known = vec![];
+        loop {
+            let mut threads: Vec<Pid> = std::fs::read_dir(proc_dir.join("task"))
+                .with_context(|| format!("Cannot list the threads of process {}", pid))?
+                .filter_map(|entry| entry.ok()?.file_name().to_str()?.parse().ok())
+                .map(Pid::from_raw)
+                .filter(|tid| !known.contains(tid))
+                .collect();
+            if threads.is_empty() {
+                break;
+            }
+            // the main thread first, so that nothing is stopped if it cannot be traced
+            threads.sort_by_key(|tid| *tid != pid);
+            for tid in threads {
+                known.push(tid);
+                match ptrace::seize(tid, Tracee::ptrace_options()) {
+                    Ok(()) => {}
+                    // the thread is already terminated
+                    Err(Errno::ESRCH) if tid != pid => continue,
+                    // the thread was created by a thread already seized, and is
+                    // then traced by us: it is registered with its clone event
+                    Err(Errno::EPERM) if tid != pid && is_traced_by_us(tid) => continue,
+                    Err(Errno::EPERM) => {
+                        return Err(Error::errno_with_msg(
+                            EPERM,
+                            format!(
+                                "Not allowed to trace process {} (already traced, or \
+                                 restricted by /proc/sys/kernel/yama/ptrace_scope)",
+                                pid
+                            ),
+                        ));
+                    }
+                    Err(errno) => {
+                        return Err(Error::errno_with_msg(
+                            errno,
+                            format!("Cannot attach to thread {} of process {}", tid, pid),
+                        ));
+                    }
+                }
+                // Stop it, so that it is restarted with PTRACE_SYSCALL by the event loop.
+                match ptrace::interrupt(tid) {
+                    Ok(()) | Err(Errno::ESRCH) => {}
+                    Err(errno) => {
+                        return Err(Error::errno_with_msg(
+                            errno,
+                            format!("Cannot stop thread {} of process {}", tid, pid),
+                        ));
+                    }
+                }
+                let mut tracee = Tracee::new(tid, fs.clone());
+                tracee.seized = true;
+                tracee.exe = exe.clone();
+                self.insert_new_tracee(tracee);
+            }
+        }
+        info!("attached to process {} ({} threads)", pid, self.alive_tracees.len());
+        self.init_pid = Some(pid);
         Ok(())
     }
 
+    /// Exit status of a process started with `spawn_process()`, once it is
+    /// terminated. It is only returned once.
+    pub fn take_exit_status(&mut self, pid: Pid) -> Option<ExitStatus> {
+        let status = (*self.spawned.get(&pid)?)?;
+        self.spawned.remove(&pid);
+        Some(status)
+    }
+
+    /// Tracees killed because the init process terminated (see
+    /// `ExitPolicy::Init`), once the event loop returned.
+    pub fn orphans(&self) -> &[Orphan] {
+        &self.orphans
+    }
+
+    /// Pids of the tracees which are still alive.
+    pub fn alive_tracees(&self) -> &[Pid] {
+        &self.alive_tracees
+    }
+
+    /// Path of the executable of a tracee, on the guest side.
+    pub fn guest_exe(&self, pid: Pid) -> Option<PathBuf> {
+        let tracee = self.tracees.get(&pid)?;
+        let exe = tracee.exe.as_ref()?.borrow().clone();
+        let guest = tracee.fs.borrow().detranslate_path(&exe, None).ok().flatten();
+        Some(guest.unwrap_or(exe))
+    }
+
     /// Infinite loop where PRoot will wait for tracees signals with `waitpid`.
     /// Tracees will be stopped when they use a system call.
     /// The tracer will be notified through `waitpid` and will be able to alter
@@ -164,248 +427,375 @@ impl PRoot {
     pub fn event_loop(&mut self) -> Result<()> {
         // TODO: what should we do if there is a terrible error in eventloop?
         while !self.alive_tracees.is_empty() {
-            match wait::waitpid(Pid::from_raw(-1), Some(WaitPidFlag::__WALL))
-                .context("Error calling waitpid() in event loop")?
+            if self.kill_requested.swap(false, Ordering::SeqCst)
+                || sigactions::grace_period_expired()
             {
-                Exited(pid, exit_status) => {
-                    trace!("-- {}, Exited with status: {}", pid, exit_status);
-                    self.register_tracee_finished(pid);
-                    if Some(pid) == self.init_pid {
-                        // The "init" process was exited. We need to record the exit code.
-                        debug!("init process exited with exit code: {}", exit_status);
-                        self.init_exit_code = Some(exit_status);
-                        // TODO: maybe we also need to take care of all the
-                        // "orphans" process?
-                    }
+                debug!("killing all tracees: {:?}", self.alive_tracees);
+                for pid in &self.alive_tracees {
+                    let _ = signal::kill(*pid, Signal::SIGKILL);
                 }
-                Signaled(pid, term_signal, dumped_core) => {
-                    trace!(
-                        "-- {}, Killed by signal: {:?}, and dump core: {}",
-                        pid,
-                        term_signal,
-                        dumped_core
-                    );
-                    self.register_tracee_finished(pid);
-                    if Some(pid) == self.init_pid {
-                        // The "init" process was killed by a signal, the exit code should be
-                        // 128+signal
-                        debug!("init process was killed by a signal: {:?}", term_signal);
-                        self.init_exit_code = Some(128 + (term_signal as i32));
-                        // TODO: maybe we also need to take care of all the
-                        // "orphans" process?
-                    }
+            }
+            if self.init_status.is_some() && self.config.exit_policy == ExitPolicy::Init {
+                self.kill_orphans();
+            }
+            // `__WNOTHREAD` restricts the wait to the tracees of this thread, so
+            // that the children of an embedding process are left alone.
+            let status = match wait::waitpid(
+                Pid::from_raw(-1),
+                Some(WaitPidFlag::__WALL | WaitPidFlag::__WNOTHREAD),
+            ) {
+                // interrupted by a signal forwarded to the guest, see `sigactions`
+                Err(Errno::EINTR) => continue,
+                status => status.context("Error calling waitpid() in event loop")?,
+            };
+            self.handle_wait_status(status)?;
+        }
+
+        Ok(())
+    }
+
+    /// Deal with the events already pending, without blocking. Used when the
+    /// tracer has to wait for other things than tracees (see `session`).
+    pub fn poll_events(&mut self) -> Result<()> {
+        while !self.alive_tracees.is_empty() {
+            let status = match wait::waitpid(
+                Pid::from_raw(-1),
+                Some(WaitPidFlag::__WALL | WaitPidFlag::__WNOTHREAD | WaitPidFlag::WNOHANG),
+            ) {
+                Ok(StillAlive) | Err(Errno::ECHILD) => break,
+                status => status.context("Error calling waitpid() in event loop")?,
+            };
+            self.handle_wait_status(status)?;
+        }
+        Ok(())
+    }
+
+    /// Kill the tracees left behind by the init process, the ones created since
+    /// the last call included.
+    fn kill_orphans(&mut self) {
+        for &pid in &self.alive_tracees {
+            if self.orphans.iter().any(|orphan| orphan.pid == pid.as_raw()) {
+                continue;
+            }
+            debug!("killing the orphan {}", pid);
+            // the threads of a process are killed with it, so only the process is reported
+            if thread_group_id(pid) == Some(pid) {
+                self.orphans.push(Orphan {
+                    pid: pid.as_raw(),
+                    command: read_cmdline(pid),
+                });
+            }
+            let _ = signal::kill(pid, Signal::SIGKILL);
+        }
+    }
+
+    /// Read again the command of the orphan `pid`, which is exiting: the one
+    /// read when it was killed is outdated if it was in the middle of an
+    /// `execve()`, which may complete before the kill.
+    fn update_orphan_command(&mut self, pid: Pid) {
+        if let Some(orphan) = self
+            .orphans
+            .iter_mut()
+            .find(|orphan| orphan.pid == pid.as_raw())
+        {
+            let command = read_cmdline(pid);
+            if !command.is_empty() {
+                orphan.command = command;
+            }
+        }
+    }
+
+    /// Deal with one event of a tracee, as returned by `waitpid()`.
+    fn handle_wait_status(&mut self, status: WaitStatus) -> Result<()> {
+        match status {
+            Exited(pid, exit_status) => {
+                trace!("-- {}, Exited with status: {}", pid, exit_status);
+                self.register_tracee_finished(pid);
+                self.register_exit_status(pid, ExitStatus::Exited(exit_status));
+                if Some(pid) == self.init_pid {
+                    // The "init" process was exited. We need to record the exit code.
+                    debug!("init process exited with exit code: {}", exit_status);
+                    self.init_status = Some(ExitStatus::Exited(exit_status));
                 }
-                // The tracee was stopped by a normal signal (signal-delivery-stop), or was stopped
-                // by a system call (syscall-stop) with PTRACE_O_TRACESYSGOOD not effect.
-                Stopped(pid, stop_signal) => {
-                    trace!(
-                        "-- {}, Stopped, {:?}, {}",
-                        pid,
-                        stop_signal,
-                        stop_signal as c_int
-                    );
+            }
+            Signaled(pid, term_signal, dumped_core) => {
+                trace!(
+                    "-- {}, Killed by signal: {:?}, and dump core: {}",
+                    pid, term_signal, dumped_core
+                );
+                self.register_tracee_finished(pid);
+                self.register_exit_status(pid, ExitStatus::Signaled(term_signal as i32));
+                if Some(pid) == self.init_pid {
+                    // The "init" process was killed by a signal, the exit code will be
+                    // 128+signal
+                    debug!("init process was killed by a signal: {:?}", term_signal);
+                    self.init_status = Some(ExitStatus::Signaled(term_signal as i32));
+                }
+            }
+            // The tracee was stopped by a normal signal (signal-delivery-stop), or was stopped
+            // by a system call (syscall-stop) with PTRACE_O_TRACESYSGOOD not effect.
+            Stopped(pid, stop_signal) => {
+                trace!(
+                    "-- {}, Stopped, {:?}, {}",
+                    pid, stop_signal, stop_signal as c_int
+                );
 
-                    let mut signal_to_delivery = Some(stop_signal);
-
-                    let maybe_tracee = self.tracees.get_mut(&pid);
-
-                    let tracee = if maybe_tracee.is_none() {
-                        if stop_signal == Signal::SIGSTOP {
-                            debug!("-- {}, SIGSTOP arrives before ptrace event but tracee is not initialized, so create a placeholder to record this.", pid);
-                            // Get tracee instance of init process, note that at this point
-                            // `init_pid` must not be none, so we can unwrap() it safely.
-                            let init_tracee = self.tracees.get(&self.init_pid.unwrap()).unwrap();
-                            // Create a new tracee instance as placeholder, only for record the pid
-                            // and sigstop status of this newly created process.
-                            // Since the `fs` field cannot be none value, we'll temporarily use the
-                            // value of the init process's fs field in its place, even though it
-                            // should be actually derived from the parent process. But please
-                            // remember that the `fs` field should not be used until the tracee is
-                            // fully initialized in the ptrace event handler function.
-                            let mut tracee = Tracee::new(pid, init_tracee.fs.clone());
-                            // We are waiting for a ptrace event to initialize this tracee.
-                            tracee.sigstop_status = SigStopStatus::WaitForEventClone;
-                            self.insert_new_tracee(tracee);
-                            signal_to_delivery = None;
-                            self.tracees.get_mut(&pid).unwrap()
-                        } else {
-                            error!("-- {}, Received a signal from an unknown tracee.", pid);
-                            // Deliver this SIGSTOP signal to this unknown tracee
-                            ptrace::syscall(pid, Some(stop_signal))
-                                .expect("deliver stop signal to unknown tracee");
-                            // continue the event loop
-                            continue;
-                        }
-                    } else {
-                        maybe_tracee.unwrap()
-                    };
-                    tracee.reset_restart_how();
-                    match stop_signal {
-                        Signal::SIGSTOP => {
-                            if tracee.sigstop_status == SigStopStatus::EventloopSync {
-                                // When the first child process starts, it sends a SIGSTOP to
-                                // itself. And we need to set ptrace
-                                // options at this point.
-                                tracee.check_and_set_ptrace_options(&mut self.info_bag)?;
-                                signal_to_delivery = None;
-                                tracee.sigstop_status = SigStopStatus::AllowDelivery;
-                            } else if tracee.sigstop_status == SigStopStatus::WaitForSigStopClone {
-                                signal_to_delivery = None;
-                                tracee.sigstop_status = SigStopStatus::AllowDelivery;
-                            }
+                let mut signal_to_delivery = Some(stop_signal);
+
+                let maybe_tracee = self.tracees.get_mut(&pid);
 
-                            tracee.handle_sigstop_event();
+                let tracee = if maybe_tracee.is_none() {
+                    if stop_signal == Signal::SIGSTOP {
+                        debug!(
+                            "-- {}, SIGSTOP arrives before ptrace event but tracee is not initialized, so create a placeholder to record this.",
+                            pid
+                        );
+                        self.insert_placeholder(pid, false);
+                        return Ok(());
+                    } else {
+                        error!("-- {}, Received a signal from an unknown tracee.", pid);
+                        // Deliver this SIGSTOP signal to this unknown tracee
+                        ptrace::syscall(pid, Some(stop_signal))
+                            .expect("deliver stop signal to unknown tracee");
+                        return Ok(());
+                    }
+                } else {
+                    maybe_tracee.unwrap()
+                };
+                tracee.reset_restart_how();
+                match stop_signal {
+                    Signal::SIGSTOP => {
+                        if tracee.sigstop_status == SigStopStatus::EventloopSync {
+                            // When the first child process starts, it sends a SIGSTOP to
+                            // itself. And we need to set ptrace
+                            // options at this point.
+                            tracee.set_ptrace_options()?;
+                            signal_to_delivery = None;
+                            tracee.sigstop_status = SigStopStatus::AllowDelivery;
+                        } else if tracee.sigstop_status == SigStopStatus::WaitForSigStopClone {
+                            signal_to_delivery = None;
+                            tracee.sigstop_status = SigStopStatus::AllowDelivery;
+                        } else if sigcont_pending(pid) {
+                            signal_to_delivery = None;
                         }
-                        Signal::SIGTRAP => {
-                            // Since PTRACE_O_TRACESYSGOOD is not supported on older versions of
-                            // Linux (version<2.4.6) and some architectures, we need to use
-                            // PTRACE_GETSIGINFO to distinguish a real syscall-stop from
-                            // signal-delivery-stop on these devices.
-                            // NOTE: this may be somewhat expensive.
-                            // See ptrace(2): Syscall-stops
-                            if let Ok(siginfo) = ptrace::getsiginfo(pid) {
-                                if siginfo.si_code == Signal::SIGTRAP as i32
-                                    || siginfo.si_code == (Signal::SIGTRAP as i32 | 0x80)
-                                {
-                                    tracee.handle_syscall_stop_event(
-                                        self.config,
-                                        &mut self.info_bag,
-                                        #[cfg(test)]
-                                        &self.func_syscall_hook,
-                                    );
-                                }
-                            }
+                    }
+                    // A `SIGCONT` sent since this signal was dequeued would have
+                    // discarded it, were the tracee not stopped here meanwhile.
+                    Signal::SIGTSTP | Signal::SIGTTIN | Signal::SIGTTOU if sigcont_pending(pid) => {
+                        debug!(
+                            "-- {}, {:?} discarded by a pending SIGCONT",
+                            pid, stop_signal
+                        );
+                        signal_to_delivery = None;
+                    }
+                    Signal::SIGTRAP => {
+                        // Since PTRACE_O_TRACESYSGOOD is not supported on older versions of
+                        // Linux (version<2.4.6) and some architectures, we need to use
+                        // PTRACE_GETSIGINFO to distinguish a real syscall-stop from
+                        // signal-delivery-stop on these devices.
+                        // NOTE: this may be somewhat expensive.
+                        // See ptrace(2): Syscall-stops
+                        if let Ok(siginfo) = ptrace::getsiginfo(pid)
+                            && (siginfo.si_code == Signal::SIGTRAP as i32
+                                || siginfo.si_code == (Signal::SIGTRAP as i32 | 0x80))
+                        {
+                            tracee.handle_syscall_stop_event(
+                                &self.config,
+                                &mut self.info_bag,
+                                #[cfg(test)]
+                                &self.func_syscall_hook,
+                            );
                         }
-                        _ => {}
                     }
-
-                    // ptrace(2): If the tracer doesn't suppress the signal, it passes the signal to
-                    // the tracee in the next ptrace restart request.
-                    // TODO: we should deliver this signal(sig) with ptrace(PTRACE_restart, pid, 0,
-                    // sig)
-                    tracee.restart(signal_to_delivery);
+                    _ => {}
                 }
-                // The tracee was stopped by a SIGTRAP with additional status (PTRACE_EVENT stops).
-                PtraceEvent(pid, signal, status_additional) => {
-                    let maybe_event = match status_additional {
-                        x if x == PtraceEvent::PTRACE_EVENT_FORK as i32 => {
-                            Some(PtraceEvent::PTRACE_EVENT_FORK)
-                        }
-                        x if x == PtraceEvent::PTRACE_EVENT_VFORK as i32 => {
-                            Some(PtraceEvent::PTRACE_EVENT_VFORK)
-                        }
-                        x if x == PtraceEvent::PTRACE_EVENT_CLONE as i32 => {
-                            Some(PtraceEvent::PTRACE_EVENT_CLONE)
-                        }
-                        x if x == PtraceEvent::PTRACE_EVENT_EXEC as i32 => {
-                            Some(PtraceEvent::PTRACE_EVENT_EXEC)
-                        }
-                        x if x == PtraceEvent::PTRACE_EVENT_VFORK_DONE as i32 => {
-                            Some(PtraceEvent::PTRACE_EVENT_VFORK_DONE)
-                        }
-                        x if x == PtraceEvent::PTRACE_EVENT_EXIT as i32 => {
-                            Some(PtraceEvent::PTRACE_EVENT_EXIT)
-                        }
-                        x if x == PtraceEvent::PTRACE_EVENT_SECCOMP as i32 => {
-                            Some(PtraceEvent::PTRACE_EVENT_SECCOMP)
-                        }
-                        _ => None,
-                    };
-
-                    trace!("-- {}, Ptrace event, {:?}, {:?}", pid, signal, maybe_event);
-                    let tracee = self.tracees.get_mut(&pid).expect("get stopped tracee");
-                    tracee.reset_restart_how();
 
-                    match maybe_event {
-                        // handle_new_child_event
+                // ptrace(2): If the tracer doesn't suppress the signal, it passes the signal to
+                // the tracee in the next ptrace restart request.
+                // TODO: we should deliver this signal(sig) with ptrace(PTRACE_restart, pid, 0,
+                // sig)
+                tracee.restart(signal_to_delivery);
+            }
+            // The tracee was stopped by a SIGTRAP with additional status (PTRACE_EVENT stops).
+            PtraceEvent(pid, signal, status_additional) => {
+                let maybe_event = match status_additional {
+                    x if x == PtraceEvent::PTRACE_EVENT_FORK as i32 => {
                         Some(PtraceEvent::PTRACE_EVENT_FORK)
-                        | Some(PtraceEvent::PTRACE_EVENT_VFORK)
-                        | Some(PtraceEvent::PTRACE_EVENT_CLONE) => {
-                            match tracee.handle_new_child_event() {
-                                Ok(mut child_tracee) => {
-                                    info!("-- {}, new process with pid {}", pid, child_tracee.pid);
-                                    // If a placeholder exists, replace it with fully initialized
-                                    // tracee.
-                                    if let Some(tracee_placeholder) =
-                                        self.tracees.get(&child_tracee.pid)
-                                    {
-                                        if tracee_placeholder.sigstop_status
-                                            == SigStopStatus::WaitForEventClone
-                                        {
-                                            child_tracee.sigstop_status =
-                                                SigStopStatus::AllowDelivery;
-                                        }
-                                    }
-                                    self.insert_new_tracee(child_tracee)
+                    }
+                    x if x == PtraceEvent::PTRACE_EVENT_VFORK as i32 => {
+                        Some(PtraceEvent::PTRACE_EVENT_VFORK)
+                    }
+                    x if x == PtraceEvent::PTRACE_EVENT_CLONE as i32 => {
+                        Some(PtraceEvent::PTRACE_EVENT_CLONE)
+                    }
+                    x if x == PtraceEvent::PTRACE_EVENT_EXEC as i32 => {
+                        Some(PtraceEvent::PTRACE_EVENT_EXEC)
+                    }
+                    x if x == PtraceEvent::PTRACE_EVENT_VFORK_DONE as i32 => {
+                        Some(PtraceEvent::PTRACE_EVENT_VFORK_DONE)
+                    }
+                    x if x == PtraceEvent::PTRACE_EVENT_EXIT as i32 => {
+                        Some(PtraceEvent::PTRACE_EVENT_EXIT)
+                    }
+                    x if x == PtraceEvent::PTRACE_EVENT_SECCOMP as i32 => {
+                        Some(PtraceEvent::PTRACE_EVENT_SECCOMP)
+                    }
+                    x if x == PtraceEvent::PTRACE_EVENT_STOP as i32 => {
+                        Some(PtraceEvent::PTRACE_EVENT_STOP)
+                    }
+                    _ => None,
+                };
+
+                trace!("-- {}, Ptrace event, {:?}, {:?}", pid, signal, maybe_event);
+                // A new child of a seized tracee starts with a PTRACE_EVENT_STOP, which
+                // may arrive before the ptrace event of its parent, as the SIGSTOP above.
+                if status_additional == libc::PTRACE_EVENT_STOP && !self.tracees.contains_key(&pid)
+                {
+                    debug!(
+                        "-- {}, PTRACE_EVENT_STOP arrives before ptrace event but tracee is not initialized, so create a placeholder to record this.",
+                        pid
+                    );
+                    self.insert_placeholder(pid, true);
+                    return Ok(());
+                }
+                let tracee = self.tracees.get_mut(&pid).expect("get stopped tracee");
+                tracee.reset_restart_how();
+                let mut init_stopped = false;
+                let mut exiting = false;
+
+                match maybe_event {
+                    // handle_new_child_event
+                    Some(PtraceEvent::PTRACE_EVENT_FORK)
+                    | Some(PtraceEvent::PTRACE_EVENT_VFORK)
+                    | Some(PtraceEvent::PTRACE_EVENT_CLONE) => {
+                        match tracee.handle_new_child_event() {
+                            Ok(mut child_tracee) => {
+                                info!("-- {}, new process with pid {}", pid, child_tracee.pid);
+                                // If a placeholder exists, replace it with fully initialized
+                                // tracee, and restart it since its first stop is over.
+                                let placeholder =
+                                    self.tracees.get(&child_tracee.pid).is_some_and(|tracee| {
+                                        tracee.sigstop_status == SigStopStatus::WaitForEventClone
+                                    });
+                                if placeholder {
+                                    child_tracee.sigstop_status = SigStopStatus::AllowDelivery;
+                                    child_tracee.reset_restart_how();
+                                    child_tracee.restart(None);
                                 }
-                                Err(error) => {
-                                    error!(
+                                self.insert_new_tracee(child_tracee)
+                            }
+                            Err(error) => {
+                                error!(
                                     "Error while handling new child process event for pid {}. {}",
                                     tracee.pid, error
                                 );
-                                }
                             }
                         }
-                        // handle_exec_vfork_event
-                        Some(PtraceEvent::PTRACE_EVENT_EXEC)
-                        | Some(PtraceEvent::PTRACE_EVENT_VFORK_DONE) => {
-                            tracee.handle_exec_vfork_event();
-                        }
-                        // handle_seccomp_event
-                        Some(PtraceEvent::PTRACE_EVENT_SECCOMP) => {
-                            // TODO: consider PTRACE_EVENT_SECCOMP2
-                            tracee.handle_seccomp_event(
-                                &mut self.info_bag,
-                                PtraceEvent::PTRACE_EVENT_SECCOMP,
-                            )
+                    }
+                    // handle_exec_vfork_event
+                    Some(event @ PtraceEvent::PTRACE_EVENT_EXEC)
+                    | Some(event @ PtraceEvent::PTRACE_EVENT_VFORK_DONE) => {
+                        match tracee.handle_exec_vfork_event(event) {
+                            Ok(Some(former_pid)) => self.register_exec_by_thread(former_pid, pid),
+                            Ok(None) => {}
+                            Err(error) => {
+                                error!(
+                                    "Error while handling exec event for pid {}. {}",
+                                    pid, error
+                                );
+                            }
                         }
-                        Some(_) | None => {}
-                    };
-                    // Re-acquire tracee as we cannot borrow `*self` as mutable more than once at a
-                    // time in rust.
-                    let tracee = self.tracees.get_mut(&pid).expect("get stopped tracee");
-                    tracee.restart(None);
-                }
-                // The tracee was stopped by execution of a system call (syscall-stop), and
-                // PTRACE_O_TRACESYSGOOD was effect. PTRACE_O_TRACESYSGOOD is used to make it
-                // easy for the tracer to distinguish syscall-stop from signal-delivery-stop.
-                PtraceSyscall(pid) => {
-                    trace!("-- {}, Syscall", pid);
-                    let tracee = self.tracees.get_mut(&pid).expect("get stopped tracee");
-                    tracee.reset_restart_how();
-                    
-                    // pass &self.config here
-                    tracee.handle_syscall_stop_event(
-                        self.config,
-                        &mut self.info_bag,
-                        #[cfg(test)]
-                        &self.func_syscall_hook,
-                    );
-                    tracee.restart(None);
-                }
-                Continued(pid) => {
-                    trace!("-- {}, Continued", pid);
+                    }
+                    // handle_seccomp_event
+                    Some(PtraceEvent::PTRACE_EVENT_SECCOMP) => {
+                        // TODO: consider PTRACE_EVENT_SECCOMP2
+                        tracee.handle_seccomp_event(
+                            &mut self.info_bag,
+                            PtraceEvent::PTRACE_EVENT_SECCOMP,
+                        )
+                    }
+                    // handle_sigstop_event: a group-stop, the other stops of a seized tracee (its
+                    // first one, `PTRACE_INTERRUPT` or the end of a group-stop) being with SIGTRAP
+                    Some(PtraceEvent::PTRACE_EVENT_STOP)
+                        if sigactions::STOP_SIGNALS.contains(&signal) =>
+                    {
+                        tracee.handle_sigstop_event(signal);
+                        init_stopped = Some(pid) == self.init_pid;
+                    }
+                    // its memory is still there, and so is its command line
+                    Some(PtraceEvent::PTRACE_EVENT_EXIT) => exiting = true,
+                    Some(_) | None => {}
+                };
+                if exiting {
+                    self.update_orphan_command(pid);
                 }
-                StillAlive => {
-                    trace!("-- Still alive");
+                // Re-acquire tracee as we cannot borrow `*self` as mutable more than once at a
+                // time in rust.
+                let tracee = self.tracees.get_mut(&pid).expect("get stopped tracee");
+                tracee.restart(None);
+                if init_stopped {
+                    sigactions::stop_like(signal);
                 }
             }
+            // The tracee was stopped by execution of a system call (syscall-stop), and
+            // PTRACE_O_TRACESYSGOOD was effect. PTRACE_O_TRACESYSGOOD is used to make it
+            // easy for the tracer to distinguish syscall-stop from signal-delivery-stop.
+            PtraceSyscall(pid) => {
+                trace!("-- {}, Syscall", pid);
+                let tracee = self.tracees.get_mut(&pid).expect("get stopped tracee");
+                tracee.reset_restart_how();
+
+                tracee.handle_syscall_stop_event(
+                    &self.config,
+                    &mut self.info_bag,
+                    #[cfg(test)]
+                    &self.func_syscall_hook,
+                );
+                tracee.restart(None);
+            }
+            Continued(pid) => {
+                trace!("-- {}, Continued", pid);
+            }
+            StillAlive => {
+                trace!("-- Still alive");
+            }
         }
-
         Ok(())
     }
 
+    /// Record a new process whose first stop arrives before the ptrace event
+    /// of its parent (see `SigStopStatus::WaitForEventClone`).
+    ///
+    /// The placeholder is kept stopped until this event arrives: its
+    /// file-system (and everything else) should be derived from the parent
+    /// process, which is not known yet, so it must not run any syscall.
+    fn insert_placeholder(&mut self, pid: Pid, seized: bool) {
+        let mut tracee = Tracee::new(pid, Rc::new(RefCell::new(FileSystem::new())));
+        tracee.seized = seized;
+        tracee.sigstop_status = SigStopStatus::WaitForEventClone;
+        self.insert_new_tracee(tracee);
+    }
+
+    /// Register the new process `pid` as a tracee. If `seized`, it is
+    /// attached here with `PTRACE_SEIZE` and the options of every tracee;
+    /// otherwise it is expected to call `PTRACE_TRACEME` itself.
     pub fn create_tracee(
         &mut self,
         pid: Pid,
         fs: Rc<RefCell<FileSystem>>,
         sigstop_status: SigStopStatus,
-    ) -> Option<&Tracee> {
+        seized: bool,
+    ) -> Result<&Tracee> {
+        if seized {
+            ptrace::seize(pid, Tracee::ptrace_options())
+                .with_context(|| format!("Failed to seize the new process {}", pid))?;
+        }
         let mut tracee = Tracee::new(pid, fs);
         tracee.sigstop_status = sigstop_status;
+        tracee.seized = seized;
         self.tracees.insert(pid, tracee);
         self.register_alive_tracee(pid);
-        self.tracees.get(&pid)
+        Ok(&self.tracees[&pid])
     }
 
     pub fn insert_new_tracee(&mut self, tracee: Tracee) {
@@ -415,20 +805,109 @@ impl PRoot {
     }
 
     fn register_alive_tracee(&mut self, pid: Pid) {
-        self.alive_tracees.push(pid);
+        // a placeholder is registered already
+        if !self.alive_tracees.contains(&pid) {
+            self.alive_tracees.push(pid);
+        }
+    }
+
+    fn register_exit_status(&mut self, pid: Pid, status: ExitStatus) {
+        if let Some(spawned) = self.spawned.get_mut(&pid) {
+            *spawned = Some(status);
+        }
+    }
+
+    /// The thread `former_pid` executed a program and took the pid `leader_pid`
+    /// of its thread group leader. Neither is reported as terminated: the state
+    /// of the thread (the program being loaded...) replaces the one of the
+    /// leader. The other threads are reported as exited by the kernel.
+    fn register_exec_by_thread(&mut self, former_pid: Pid, leader_pid: Pid) {
+        debug!("-- {}, executed a program as {}", former_pid, leader_pid);
+        let mut tracee = match self.tracees.remove(&former_pid) {
+            Some(tracee) => tracee,
+            None => return,
+        };
+        self.alive_tracees.retain(|pid| *pid != former_pid);
+        self.register_tracee_finished(leader_pid);
+        tracee.pid = leader_pid;
+        tracee.regs.set_pid(leader_pid);
+        // it is restarted from the exec event, in place of the leader
+        tracee.reset_restart_how();
+        self.insert_new_tracee(tracee);
     }
 
     fn register_tracee_finished(&mut self, finished_pid: Pid) {
         self.alive_tracees.retain(|pid| *pid != finished_pid);
-        self.tracees.remove(&finished_pid);
+        // a syscall like exit_group() never reaches its exit stage
+        if let Some(tracee) = self.tracees.remove(&finished_pid) {
+            if let Some(record) = tracee.trace_record
+                && let Some(tracer) = &self.info_bag.tracer
+            {
+                tracer.print(finished_pid.as_raw(), record, None);
+            }
+            if let Some(audit) = &self.info_bag.audit {
+                audit.write(tracee.audit_records, None);
+            }
+        }
     }
 }
 
-/// Proot has received a fatal error from one of the tracee,
-/// and must therefore stop the program's execution.
+/// Value of the field `name` of `/proc/<tid>/status`.
+fn read_status_field(tid: Pid, name: &str) -> Option<String> {
+    let status = std::fs::read_to_string(format!("/proc/{}/status", tid)).ok()?;
+    status.lines().find_map(|line| {
+        let value = line.strip_prefix(name)?.strip_prefix(':')?;
+        Some(value.trim().to_string())
+    })
+}
+
+/// Whether a `SIGCONT` is pending for the thread `tid`, or its process.
+fn sigcont_pending(tid: Pid) -> bool {
+    let sigcont = 1 << (Signal::SIGCONT as u64 - 1);
+    ["SigPnd", "ShdPnd"].iter().any(|name| {
+        read_status_field(tid, name)
+            .and_then(|mask| u64::from_str_radix(&mask, 16).ok())
+            .is_some_and(|mask| mask & sigcont != 0)
+    })
+}
+
+/// Whether the thread `tid` is traced by the calling thread.
+pub fn is_traced_by_us(tid: Pid) -> bool {
+    read_status_field(tid, "TracerPid") == Some(unistd::gettid().to_string())
+}
+
+/// Pid of the process the thread `tid` belongs to.
+pub fn thread_group_id(tid: Pid) -> Option<Pid> {
+    read_status_field(tid, "Tgid")?.parse().ok().map(Pid::from_raw)
+}
+
+/// Arguments of a process, as shown by `ps`.
+pub fn read_cmdline(pid: Pid) -> Vec<String> {
+    std::fs::read(format!("/proc/{}/cmdline", pid))
+        .map(|cmdline| {
+            cmdline
+                .split(|&byte| byte == 0)
+                .filter(|arg| !arg.is_empty())
+                .map(|arg| String::from_utf8_lossy(arg).into_owned())
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Proot has received a fatal error (e.g. `SIGSEGV`), and must therefore stop
+/// the program's execution.
+///
+/// The signal is raised again with its default action, so that the tracer
+/// terminates as it would without this handler: every tracee is then killed by
+/// the kernel, since they are traced with `PTRACE_O_EXITKILL`. Only
+/// async-signal-safe functions are used here.
 pub extern "C" fn stop_program(sig_num: c_int, _: *mut siginfo_t, _: *mut c_void) {
-    let signal = Signal::try_from(sig_num);
-    panic!("abnormal signal received: {:?}", signal);
+    let msg = b"pronyx: fatal signal received, killing the tracees\n";
+    unsafe {
+        libc::write(libc::STDERR_FILENO, msg.as_ptr() as *const c_void, msg.len());
+        libc::signal(sig_num, libc::SIG_DFL);
+        libc::raise(sig_num);
+    }
 }
 
 pub extern "C" fn show_info(pid: pid_t) {
@@ -442,9 +921,7 @@ mod tests {
 
     #[test]
     fn create_proot_and_tracee() {
-        let config = Config { 
-            root_id: false, link2symlink: false
-        };
+        let config = Config::new(false, false);
         let fs = FileSystem::new();
         let mut proot = PRoot::new(config);
 
@@ -459,7 +936,9 @@ mod tests {
                 Pid::from_raw(0),
                 Rc::new(RefCell::new(fs)),
                 SigStopStatus::AllowDelivery,
-            );
+                false,
+            )
+            .unwrap();
         }
 
         // tracee 0 should exist
@@ -1,4 +1,6 @@
 // signals
+use std::sync::atomic::{AtomicBool, AtomicI32, AtomicU32, Ordering};
+
 use libc::{c_int, c_void, pid_t, siginfo_t};
 use nix::sys::signal::{
     Signal,
@@ -6,10 +8,33 @@ use nix::sys::signal::{
     SigSet,
     SigAction,
     SigHandler,
+    raise,
     sigaction
 };
 
-use crate::sigactions::Signal::*;
+use nix::sys::signal::Signal::*;
+use nix::unistd::Pid;
+
+/// Pid of the init tracee, to which the termination signals are forwarded (0
+/// until it is started).
+static FORWARD_TO: AtomicI32 = AtomicI32::new(0);
+/// Seconds given to the tracees after a forwarded signal, before all of them
+/// are killed (0 for never).
+static GRACE_PERIOD: AtomicU32 = AtomicU32::new(0);
+static GRACE_PERIOD_STARTED: AtomicBool = AtomicBool::new(false);
+static GRACE_PERIOD_EXPIRED: AtomicBool = AtomicBool::new(false);
+/// Thread running the event loop, whose `waitpid()` is interrupted once the
+/// grace period is over.
+static TRACER_TID: AtomicI32 = AtomicI32::new(0);
+/// Interval at which `SIGALRM` is sent again after the grace period, until the
+/// event loop notices it (a signal received right before `waitpid()` is
+/// called doesn't interrupt it).
+const GRACE_PERIOD_RETRY_USEC: libc::suseconds_t = 100_000;
+/// Whether pronyx takes part in job control, see `stop_like()`.
+static JOB_CONTROL: AtomicBool = AtomicBool::new(false);
+
+/// Signals which stop a process, and take part in job control.
+pub const STOP_SIGNALS: &[Signal] = &[SIGSTOP, SIGTSTP, SIGTTIN, SIGTTOU];
 
 /// Configures the actions associated with specific critical signals.
 /// All signals are blocked when the signal handler is called.
@@ -27,18 +52,38 @@ pub fn prepare_sigactions(
 
         // setting the action when receiving certain signals
         match signal {
-            SIGQUIT | SIGILL | SIGABRT | SIGFPE | SIGSEGV => {
+            SIGILL | SIGABRT | SIGFPE | SIGSEGV => {
                 // tracees on abnormal termination signals
                 signal_handler = SigHandler::SigAction(stop_program);
             }
+            SIGINT | SIGTERM | SIGHUP | SIGQUIT | SIGALRM => {
+                // forwarded to the guest, see `forward_termination_signals()`;
+                // without SA_RESTART, so that `waitpid()` in the event loop is
+                // interrupted
+                let signal_action = SigAction::new(
+                    SigHandler::SigAction(forward_signal),
+                    SaFlags::SA_SIGINFO,
+                    signal_set,
+                );
+                let _sigaction_result = unsafe { sigaction(signal, &signal_action) };
+                continue;
+            }
             SIGUSR1 | SIGUSR2 => {
                 // can be used for inter-process communication
                 signal_handler = SigHandler::Handler(show_info);
             }
-            SIGCHLD | SIGCONT | SIGTSTP | SIGTTIN | SIGTTOU | SIGSTOP | SIGKILL => {
-                // these signals are related to tty and job control,
-                // or cannot be used with sigaction (stop and kill),
-                // so we keep the default action for them
+            SIGTSTP | SIGTTIN | SIGTTOU => {
+                // sent by the terminal to its foreground process group, whose
+                // tracees got them already: pronyx stops once the init process
+                // does instead, see `stop_like()`. It can also write to the
+                // terminal while the guest has it.
+                signal_handler = SigHandler::SigIgn;
+            }
+            SIGCONT | SIGCHLD | SIGSTOP | SIGKILL => {
+                // `SIGCONT` continues pronyx after `stop_like()`, and the
+                // others cannot be used with sigaction (stop and kill) or
+                // are needed by `waitpid()`, so we keep the default action
+                // for them
                 continue;
             }
             _ => {} // all other signals (even ^C) are ignored
@@ -47,6 +92,107 @@ pub fn prepare_sigactions(
         let signal_action = SigAction::new(signal_handler, sa_flags, signal_set);
         let _sigaction_result = unsafe { sigaction(signal, &signal_action) };
     }
+    JOB_CONTROL.store(true, Ordering::SeqCst);
+}
+
+/// Stop pronyx with the stop signal `signal`, as the init process was: the
+/// shell which started pronyx sees its job stopped, and continues it with
+/// `SIGCONT` (`fg`, `bg`), which the tracees of the job get too. Returns once
+/// pronyx is continued.
+///
+/// This requires the signal actions set by `prepare_sigactions()`, so that a
+/// program embedding pronyx is never stopped.
+pub fn stop_like(signal: Signal) {
+    if !JOB_CONTROL.load(Ordering::SeqCst) {
+        return;
+    }
+    // `SIGSTOP` always has its default action
+    let default_action = SigAction::new(SigHandler::SigDfl, SaFlags::empty(), SigSet::empty());
+    let previous_action = unsafe { sigaction(signal, &default_action) };
+    let _ = raise(signal);
+    if let Ok(previous_action) = previous_action {
+        let _ = unsafe { sigaction(signal, &previous_action) };
+    }
+}
+
+/// Give back their default action to the job control signals, ignored by
+/// `prepare_sigactions()`, in a new tracee.
+pub fn reset_job_control_sigactions() {
+    let default_action = SigAction::new(SigHandler::SigDfl, SaFlags::empty(), SigSet::empty());
+    for &signal in STOP_SIGNALS.iter().filter(|&&signal| signal != SIGSTOP) {
+        let _ = unsafe { sigaction(signal, &default_action) };
+    }
+}
+
+/// Forward the termination signals (`SIGINT`, `SIGTERM`, `SIGHUP` and
+/// `SIGQUIT`) received from now on to `init_pid`, and kill every tracee
+/// `grace_period` seconds after the first one (never if 0).
+///
+/// This must be called from the thread running the event loop, and requires
+/// the signal actions set by `prepare_sigactions()`.
+pub fn forward_termination_signals(init_pid: Pid, grace_period: u32) {
+    GRACE_PERIOD.store(grace_period, Ordering::SeqCst);
+    TRACER_TID.store(nix::unistd::gettid().as_raw(), Ordering::SeqCst);
+    FORWARD_TO.store(init_pid.as_raw(), Ordering::SeqCst);
+}
+
+/// Whether the grace period given to the tracees after a termination signal
+/// is over, in which case the event loop must kill them. Only returns `true`
+/// once.
+pub fn grace_period_expired() -> bool {
+    if !GRACE_PERIOD_EXPIRED.swap(false, Ordering::SeqCst) {
+        return false;
+    }
+    set_alarm_timer(0, 0);
+    true
+}
+
+/// Send `SIGALRM` in `seconds`, and then every `interval` microseconds (0 to
+/// disarm the timer).
+fn set_alarm_timer(seconds: libc::time_t, interval: libc::suseconds_t) {
+    let timer = libc::itimerval {
+        it_interval: libc::timeval {
+            tv_sec: 0,
+            tv_usec: interval,
+        },
+        it_value: libc::timeval {
+            tv_sec: seconds,
+            tv_usec: 0,
+        },
+    };
+    unsafe { libc::setitimer(libc::ITIMER_REAL, &timer, std::ptr::null_mut()) };
+}
+
+/// Handler of the termination signals, and of `SIGALRM` which ends the grace
+/// period. Only async-signal-safe functions are used here.
+extern "C" fn forward_signal(sig_num: c_int, info: *mut siginfo_t, _: *mut c_void) {
+    let init_pid = FORWARD_TO.load(Ordering::SeqCst);
+    if init_pid == 0 {
+        return;
+    }
+    if sig_num == SIGALRM as c_int {
+        GRACE_PERIOD_EXPIRED.store(true, Ordering::SeqCst);
+        // Interrupt the `waitpid()` of the event loop, which then kills the
+        // remaining tracees: the signal may have been delivered to another
+        // thread.
+        let tracer_tid = TRACER_TID.load(Ordering::SeqCst);
+        unsafe {
+            if libc::gettid() != tracer_tid {
+                libc::syscall(libc::SYS_tgkill, libc::getpid(), tracer_tid, libc::SIGALRM);
+            }
+        }
+        return;
+    }
+    // Signals generated by the terminal (^C...) are sent by the kernel to its
+    // whole foreground process group: the tracees got them already.
+    if unsafe { (*info).si_code } == libc::SI_KERNEL {
+        return;
+    }
+    unsafe { libc::kill(init_pid, sig_num) };
+    let grace_period = GRACE_PERIOD.load(Ordering::SeqCst);
+    if grace_period > 0 && !GRACE_PERIOD_STARTED.swap(true, Ordering::SeqCst) {
+        set_alarm_timer(grace_period as libc::time_t, GRACE_PERIOD_RETRY_USEC);
+    }
 }
 
 #[cfg(test)]
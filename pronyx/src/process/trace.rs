@@ -0,0 +1,327 @@
+//! strace-style output of the syscalls made by the tracees (`--trace`).
+//!
+//! One line is printed per syscall, once it is finished, e.g.:
+//!
+//! ```text
+//! [pid 1234] openat(AT_FDCWD, "/etc/passwd" => "/rootfs/etc/passwd", 0x80000, 0) = 3
+//! ```
+//!
+//! Path arguments are shown as seen by the guest, followed by the host path
+//! they were translated to (when the translation changed them).
+
+use std::cell::RefCell;
+use std::fmt;
+use std::fs::File;
+use std::io::{self, LineWriter, Write};
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use syscalls::Sysno;
+
+use crate::errors::*;
+use crate::kernel::syscall::{name_of_syscall, path_args};
+use crate::process::tracee::Tracee;
+use crate::register::read_path;
+use crate::register::{Current, Modified, Original, SysArg, SysArgIndex, SysResult, Word};
+
+/// Options of the syscall trace.
+#[derive(Debug, Clone, Default)]
+pub struct TraceOptions {
+    /// File the trace is written to, `None` for stderr.
+    pub output: Option<PathBuf>,
+    /// Syscalls to trace, given by name or class (see `CLASSES`). All the
+    /// syscalls are traced when it is empty.
+    pub syscalls: Vec<String>,
+    /// Pids to trace. All the tracees are traced when it is empty.
+    pub pids: Vec<i32>,
+    /// Prefix each line with the time of the syscall entry.
+    pub timestamps: bool,
+}
+
+/// Classes of syscalls that can be used in filters, like strace's `%file`.
+pub const CLASSES: &[&str] = &["file", "process", "network", "memory", "signal"];
+
+const PROCESS_SYSCALLS: &[&str] = &[
+    "clone", "clone3", "fork", "vfork", "execve", "execveat", "exit", "exit_group", "wait4",
+    "waitid", "waitpid", "kill", "tkill", "tgkill", "ptrace", "prctl", "unshare", "setns",
+];
+const NETWORK_SYSCALLS: &[&str] = &[
+    "socket", "socketpair", "bind", "connect", "listen", "accept", "accept4", "getsockname",
+    "getpeername", "sendto", "recvfrom", "sendmsg", "recvmsg", "sendmmsg", "recvmmsg",
+    "shutdown", "setsockopt", "getsockopt", "socketcall",
+];
+const MEMORY_SYSCALLS: &[&str] = &[
+    "brk", "mmap", "mmap2", "munmap", "mremap", "mprotect", "madvise", "mlock", "munlock",
+    "msync",
+];
+const SIGNAL_SYSCALLS: &[&str] = &[
+    "rt_sigaction", "rt_sigprocmask", "rt_sigreturn", "rt_sigsuspend", "rt_sigpending",
+    "rt_sigtimedwait", "rt_sigqueueinfo", "rt_tgsigqueueinfo", "sigaltstack", "signalfd",
+    "signalfd4", "kill", "tkill", "tgkill", "pause",
+];
+
+/// Check a filter item given to `--trace-filter`.
+pub fn filter_validator(item: &str) -> std::result::Result<String, String> {
+    if CLASSES.contains(&item) || Sysno::from_str(item).is_ok() {
+        Ok(item.to_string())
+    } else {
+        Err(format!(
+            "{} is neither a syscall nor a class ({})",
+            item,
+            CLASSES.join(", ")
+        ))
+    }
+}
+
+/// Name of the syscall `sysnum`, made up for unknown ones.
+fn syscall_name(sysnum: usize) -> String {
+    match name_of_syscall(sysnum) {
+        Some(name) => name.to_string(),
+        None => format!("syscall_{}", sysnum),
+    }
+}
+
+/// Position of the dirfd arguments of a syscall, shown as `AT_FDCWD` when
+/// relevant.
+fn dirfd_args(name: &str) -> &'static [usize] {
+    match name {
+        "linkat" | "renameat" | "renameat2" => &[0, 2],
+        "symlinkat" => &[1],
+        name if !path_args(name).is_empty() && path_args(name)[0] == 1 => &[0],
+        _ => &[],
+    }
+}
+
+/// Number of arguments of a syscall. Unknown syscalls show all of them.
+fn arg_count(name: &str) -> usize {
+    match name {
+        "fork" | "vfork" | "getpid" | "getppid" | "gettid" | "getuid" | "geteuid" | "getgid"
+        | "getegid" | "getpgrp" | "setsid" | "sync" | "pause" | "rt_sigreturn" | "sched_yield"
+        | "inotify_init" => 0,
+        "close" | "dup" | "exit" | "exit_group" | "brk" | "chdir" | "fchdir" | "rmdir"
+        | "unlink" | "uname" | "umask" | "chroot" | "acct" | "setuid" | "setgid" | "getpgid"
+        | "getsid" | "pipe" | "set_tid_address" | "fsync" | "fdatasync" | "syncfs"
+        | "swapoff" | "uselib" | "epoll_create" | "epoll_create1" | "inotify_init1"
+        | "alarm" | "time" | "sysinfo" | "times" | "unshare" | "personality" => 1,
+        "dup2" | "pipe2" | "access" | "creat" | "chmod" | "fchmod" | "mkdir" | "link"
+        | "symlink" | "rename" | "stat" | "lstat" | "fstat" | "stat64" | "lstat64"
+        | "fstat64" | "statfs" | "fstatfs" | "truncate" | "ftruncate" | "flock" | "kill"
+        | "tkill" | "getcwd" | "munmap" | "listen" | "shutdown" | "socketcall" | "utime"
+        | "utimes" | "umount2" | "swapon" | "gettimeofday" | "settimeofday" | "nanosleep"
+        | "clock_gettime" | "clock_getres" | "getrlimit" | "setrlimit" | "getrusage"
+        | "setpgid" | "setreuid" | "setregid" | "getgroups" | "setgroups" | "sigaltstack"
+        | "setns" | "pivot_root" | "rt_sigsuspend" | "rt_sigpending" | "arch_prctl"
+        | "inotify_rm_watch" | "timerfd_create" | "eventfd2" | "memfd_create"
+        | "set_robust_list" | "capget" | "capset" | "close_range" => 2,
+        "open" | "read" | "write" | "lseek" | "ioctl" | "fcntl" | "readv" | "writev" | "dup3"
+        | "chown" | "lchown" | "fchown" | "mknod" | "readlink" | "execve" | "getdents"
+        | "getdents64" | "mprotect" | "madvise" | "msync" | "socket" | "connect" | "bind"
+        | "accept" | "getsockname" | "getpeername" | "sendmsg" | "recvmsg" | "tgkill"
+        | "setresuid" | "setresgid" | "getresuid" | "getresgid" | "faccessat" | "fchmodat"
+        | "mkdirat" | "unlinkat" | "symlinkat" | "inotify_add_watch" | "listxattr"
+        | "llistxattr" | "flistxattr" | "removexattr" | "lremovexattr" | "getrandom"
+        | "futimesat" | "statfs64" | "fstatfs64" | "poll" | "sched_getaffinity"
+        | "rt_sigqueueinfo" | "waitpid" => 3,
+        "openat" | "newfstatat" | "fstatat64" | "fchmodat2" | "faccessat2" | "mknodat"
+        | "readlinkat" | "renameat" | "utimensat" | "rt_sigaction" | "rt_sigprocmask"
+        | "wait4" | "accept4" | "socketpair" | "pread64" | "pwrite64" | "sendfile"
+        | "getxattr" | "lgetxattr" | "fgetxattr" | "epoll_ctl" | "epoll_wait" | "openat2"
+        | "timerfd_settime" | "rt_sigtimedwait" | "ppoll" | "fadvise64" | "tee"
+        | "rt_tgsigqueueinfo" | "prlimit64" => 4,
+        "statx" | "linkat" | "renameat2" | "fchownat" | "setxattr" | "lsetxattr"
+        | "fsetxattr" | "execveat" | "mount" | "waitid" | "prctl" | "mremap" | "select"
+        | "pselect6" | "name_to_handle_at" | "getsockopt" | "setsockopt" | "clone" => 5,
+        _ => 6,
+    }
+}
+
+/// What is recorded at the syscall entry, and printed at its exit.
+#[derive(Debug)]
+pub struct TraceRecord {
+    time: SystemTime,
+    name: String,
+    args: Vec<String>,
+}
+
+/// Writes the syscall trace of all the tracees.
+pub struct SyscallTracer {
+    output: RefCell<Box<dyn Write>>,
+    syscalls: Vec<String>,
+    pids: Vec<i32>,
+    timestamps: bool,
+}
+
+impl fmt::Debug for SyscallTracer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SyscallTracer")
+            .field("syscalls", &self.syscalls)
+            .field("pids", &self.pids)
+            .field("timestamps", &self.timestamps)
+            .finish_non_exhaustive()
+    }
+}
+
+impl SyscallTracer {
+    pub fn new(options: &TraceOptions) -> Result<SyscallTracer> {
+        let output: Box<dyn Write> = match &options.output {
+            Some(path) => Box::new(LineWriter::new(File::create(path).with_context(|| {
+                format!("cannot create the trace file {:?}", path)
+            })?)),
+            None => Box::new(io::stderr()),
+        };
+        Ok(SyscallTracer {
+            output: RefCell::new(output),
+            syscalls: options.syscalls.clone(),
+            pids: options.pids.clone(),
+            timestamps: options.timestamps,
+        })
+    }
+
+    /// Whether the syscall `name` made by `pid` passes the filters.
+    fn is_traced(&self, pid: i32, name: &str) -> bool {
+        if !self.pids.is_empty() && !self.pids.contains(&pid) {
+            return false;
+        }
+        self.syscalls.is_empty()
+            || self.syscalls.iter().any(|item| match item.as_str() {
+                "file" => !path_args(name).is_empty(),
+                "process" => PROCESS_SYSCALLS.contains(&name),
+                "network" => NETWORK_SYSCALLS.contains(&name),
+                "memory" => MEMORY_SYSCALLS.contains(&name),
+                "signal" => SIGNAL_SYSCALLS.contains(&name),
+                item => item == name,
+            })
+    }
+
+    /// Whether the syscall `sysnum` made by `pid` is traced.
+    pub fn traces(&self, pid: i32, sysnum: usize) -> bool {
+        self.is_traced(pid, &syscall_name(sysnum))
+    }
+
+    /// Record the syscall of `tracee`, at the end of its enter stage.
+    pub fn record_enter(&self, tracee: &Tracee) -> Option<TraceRecord> {
+        let name = syscall_name(tracee.regs.get_sys_num(Original));
+        if !self.is_traced(tracee.pid.as_raw(), &name) {
+            return None;
+        }
+
+        let args = (0..arg_count(&name))
+            .map(|index| {
+                let arg = SysArg(SysArgIndex::from_index(index));
+                let value = tracee.regs.get(Original, arg);
+                if path_args(&name).contains(&index) {
+                    let modified = tracee.regs.get(Modified, arg);
+                    format_path_arg(tracee, value, modified)
+                } else if dirfd_args(&name).contains(&index) && value as i32 == libc::AT_FDCWD {
+                    "AT_FDCWD".to_string()
+                } else {
+                    format_arg(value)
+                }
+            })
+            .collect();
+
+        Some(TraceRecord {
+            time: SystemTime::now(),
+            name,
+            args,
+        })
+    }
+
+    /// Print a syscall recorded by `record_enter()`. `tracee` is at the end of
+    /// the exit stage, or `None` if the syscall has no exit stage.
+    pub fn print(&self, pid: i32, record: TraceRecord, tracee: Option<&Tracee>) {
+        let mut line = format!("[pid {}] ", pid);
+        if self.timestamps {
+            let time = record.time.duration_since(UNIX_EPOCH).unwrap_or_default();
+            line += &format!("{}.{:06} ", time.as_secs(), time.subsec_micros());
+        }
+        line += &format!("{}({})", record.name, record.args.join(", "));
+        match tracee {
+            Some(tracee) => {
+                let result = tracee.regs.get(Current, SysResult) as isize;
+                if (-4095..0).contains(&result) {
+                    let errno = Errno::from_raw(-result as i32);
+                    line += &format!(" = -1 {:?} ({})", errno, errno.desc());
+                } else if result.unsigned_abs() < 0x10000 {
+                    line += &format!(" = {}", result);
+                } else {
+                    line += &format!(" = {:#x}", result);
+                }
+            }
+            None => line += " = ?",
+        }
+        let _ = writeln!(self.output.borrow_mut(), "{}", line);
+    }
+}
+
+fn format_arg(value: Word) -> String {
+    let signed = value as isize;
+    // small negative `int` arguments (e.g. `fd = -1`) are not sign-extended
+    if (value as u32 as i32) < 0 && (value as u32 as i32) > -0x10000 && value <= u32::MAX as Word {
+        (value as u32 as i32).to_string()
+    } else if signed.unsigned_abs() < 0x10000 {
+        signed.to_string()
+    } else {
+        format!("{:#x}", value)
+    }
+}
+
+/// Show a path argument as the guest sees it, and the host path it was
+/// translated to, if any.
+fn format_path_arg(tracee: &Tracee, original: Word, modified: Word) -> String {
+    let read = |address: Word| -> Option<String> {
+        if address == 0 {
+            return None;
+        }
+        read_path(tracee.pid, address as *mut Word)
+            .ok()
+            .map(|path| format!("{:?}", path.to_string_lossy()))
+    };
+
+    let guest = read(original).unwrap_or_else(|| format_arg(original));
+    if modified == original {
+        return guest;
+    }
+    match read(modified) {
+        Some(host) if host != guest => format!("{} => {}", guest, host),
+        _ => guest,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_trace_filters() {
+        let tracer = SyscallTracer::new(&TraceOptions {
+            syscalls: vec!["file".to_string(), "brk".to_string()],
+            pids: vec![42],
+            ..Default::default()
+        })
+        .unwrap();
+
+        assert!(tracer.is_traced(42, "openat"));
+        assert!(tracer.is_traced(42, "execve"));
+        assert!(tracer.is_traced(42, "brk"));
+        assert!(!tracer.is_traced(42, "read"));
+        assert!(!tracer.is_traced(43, "openat"));
+
+        assert!(filter_validator("network").is_ok());
+        assert!(filter_validator("openat").is_ok());
+        assert!(filter_validator("not_a_syscall").is_err());
+    }
+
+    #[test]
+    fn test_trace_format_arg() {
+        assert_eq!(format_arg(3), "3");
+        assert_eq!(format_arg(-1i64 as Word), "-1");
+        assert_eq!(format_arg(0xffffffff), "-1");
+        assert_eq!(format_arg(0x80000), "0x80000");
+        assert_eq!(arg_count("openat"), 4);
+        assert_eq!(path_args("renameat2"), &[1, 3]);
+        assert_eq!(dirfd_args("openat"), &[0]);
+        assert_eq!(dirfd_args("open"), &[] as &[usize]);
+    }
+}
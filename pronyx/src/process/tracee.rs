@@ -6,14 +6,16 @@ use std::rc::Rc;
 use nix::sys::ptrace::{self, Options};
 use nix::sys::signal::Signal;
 use nix::unistd::Pid;
-use nix::NixPath;
 
 use crate::errors::*;
 use crate::filesystem::Substitutor;
 use crate::filesystem::Translator;
 use crate::filesystem::{binding::Side, FileSystem};
 use crate::kernel::execve::load_info::LoadInfo;
-use crate::process::proot::InfoBag;
+use crate::process::audit::AuditRecord;
+use crate::process::fd_table::{FdChange, FdTable};
+use crate::process::proot;
+use crate::process::trace::TraceRecord;
 use crate::register::{Registers, Word};
 
 #[derive(Debug, PartialEq)]
@@ -49,6 +51,8 @@ pub enum TraceeRestartMethod {
     WithoutExitStage, // PTRACE_CONT
     /// Restart the tracee, with the exit stage
     WithExitStage, // PTRACE_SYSCALL,
+    /// Keep the tracee in its group-stop until it is continued by `SIGCONT`
+    Listen, // PTRACE_LISTEN
     /// Do not restart the tracee
     None,
 }
@@ -71,7 +75,8 @@ pub enum SigStopStatus {
     /// See the description of PTRACE_O_TRACE(FORK|VFORK|CLONE) in ptrace(2).
     WaitForSigStopClone,
     /// The current process is a new process created by
-    /// `fork()`/`vfork()`/`clone()`, and the SIGSTOP signal arrives before the
+    /// `fork()`/`vfork()`/`clone()`, and the SIGSTOP signal (or the
+    /// PTRACE_EVENT_STOP, for a child of a seized tracee) arrives before the
     /// ptrace events. In this case, initialization of this tracee object is not
     /// completed, because we have no way to known the parent id of this tracee.
     /// So that we are waiting for one of the ptrace events
@@ -91,12 +96,20 @@ pub struct Tracee {
     pub restart_how: TraceeRestartMethod,
     /// Contains the bindings and functions used for path translation.
     pub fs: Rc<RefCell<FileSystem>>,
+    /// Guest paths of the file descriptors.
+    pub fds: Rc<RefCell<FdTable>>,
+    /// Change of the file descriptors to be done at the exit stage of the
+    /// current syscall.
+    pub fd_change: Option<FdChange>,
     /// Cached version of the process' general purpose registers.
     pub regs: Registers,
     /// State of the seccomp acceleration for this tracee.
     pub seccomp: bool,
     /// Ensure the sysexit stage is always hit under seccomp.
     pub sysexit_pending: bool,
+    /// The enter stage of the current syscall found nothing to do at its exit
+    /// stage: the registers are then neither fetched nor pushed there.
+    pub skip_sysexit: bool,
     /// Path to the executable, à la /proc/self/exe. Used in `execve` enter.
     /// Shared with parent until the tracee makes a call to execve().
     pub new_exe: Option<Rc<RefCell<PathBuf>>>,
@@ -108,6 +121,15 @@ pub struct Tracee {
     pub load_info: Option<LoadInfo>,
     /// State for the special handling of SIGSTOP.
     pub sigstop_status: SigStopStatus,
+    /// Syscall being traced, between its enter and exit stages.
+    pub trace_record: Option<TraceRecord>,
+    /// Paths accessed by the syscall being audited, between its enter and exit
+    /// stages.
+    pub audit_records: Vec<AuditRecord>,
+    /// Attached with `PTRACE_SEIZE` (see `PRoot::attach_process()`), or
+    /// created by such a tracee: its first stop is then a
+    /// `PTRACE_EVENT_STOP`, not a `SIGSTOP`.
+    pub seized: bool,
 }
 
 impl Tracee {
@@ -117,13 +139,19 @@ impl Tracee {
             status: TraceeStatus::SysEnter, // it always starts by the enter stage
             restart_how: TraceeRestartMethod::None,
             fs: fs,
+            fds: Rc::new(RefCell::new(FdTable::new())),
+            fd_change: None,
             regs: Registers::new(pid),
             seccomp: false,
             sysexit_pending: false,
+            skip_sysexit: false,
             new_exe: None,
             exe: None,
             load_info: None,
             sigstop_status: SigStopStatus::AllowDelivery,
+            trace_record: None,
+            audit_records: vec![],
+            seized: false,
         }
     }
 
@@ -155,6 +183,19 @@ impl Tracee {
             TraceeRestartMethod::WithExitStage => {
                 ptrace::syscall(self.pid, sig).expect("exit tracee with exit stage");
             }
+            TraceeRestartMethod::Listen => {
+                let result = unsafe {
+                    libc::ptrace(libc::PTRACE_LISTEN as _, self.pid.as_raw(), 0, 0)
+                };
+                match Errno::result(result) {
+                    Ok(_) => {}
+                    // killed meanwhile: it is dropped once `waitpid()` reports its end
+                    Err(Errno::ESRCH) => debug!("-- {}, gone before PTRACE_LISTEN", self.pid),
+                    Err(errno) => {
+                        error!("-- {}, cannot keep it in its group-stop: {}", self.pid, errno)
+                    }
+                }
+            }
             TraceeRestartMethod::None => {}
         };
 
@@ -166,28 +207,34 @@ impl Tracee {
     /// automatically trace each new process with
     /// the same options.
     ///
-    /// Note that only the first bare SIGTRAP is
-    /// related to the tracing loop, others SIGTRAP
-    /// carry tracing information because of
-    /// TRACE*FORK/CLONE/EXEC.
-    pub fn check_and_set_ptrace_options(&self, info_bag: &mut InfoBag) -> Result<()> {
-        if info_bag.options_already_set {
-            return Ok(());
-        } else {
-            info_bag.options_already_set = true;
-        }
+    /// Only the processes started by the tracer need
+    /// this: the ones created by a tracee inherit the
+    /// options of their parent.
+    pub fn set_ptrace_options(&self) -> Result<()> {
+        ptrace::setoptions(self.pid, Tracee::ptrace_options())
+            .context("Failed to set ptrace options")
+    }
 
-        let default_options = Options::PTRACE_O_TRACESYSGOOD
+    /// Options every tracee is traced with.
+    pub fn ptrace_options() -> Options {
+        //TODO: seccomp
+        Options::PTRACE_O_TRACESYSGOOD
             | Options::PTRACE_O_TRACEFORK
             | Options::PTRACE_O_TRACEVFORK
             | Options::PTRACE_O_TRACEVFORKDONE
             | Options::PTRACE_O_TRACEEXEC
             | Options::PTRACE_O_TRACECLONE
             | Options::PTRACE_O_EXITKILL
-            | Options::PTRACE_O_TRACEEXIT;
+            | Options::PTRACE_O_TRACEEXIT
+    }
 
-        //TODO: seccomp
-        ptrace::setoptions(self.pid, default_options).context("Failed to set ptrace options")
+    /// Give the tracee its own copy of its file descriptor table, as
+    /// `execve()` and `unshare(CLONE_FILES)` do.
+    pub fn unshare_fds(&mut self) {
+        if Rc::strong_count(&self.fds) > 1 {
+            let fds = self.fds.borrow().clone();
+            self.fds = Rc::new(RefCell::new(fds));
+        }
     }
 
     /// Return the byte size of a Word in tracee
@@ -197,6 +244,9 @@ impl Tracee {
 
     /// Get file path from file descriptor,
     ///
+    /// The file descriptors opened by the tracee are looked up in its table,
+    /// the other ones are resolved through `/proc/<pid>/fd`.
+    ///
     /// The returned path is always canonical.
     pub fn get_path_from_fd(&self, fd: RawFd, side: Side) -> Result<PathBuf> {
         #[cfg(any(target_os = "linux", target_os = "android"))]
@@ -210,6 +260,12 @@ impl Tracee {
                     Side::Host => self.fs.borrow().substitute(guest_path, Side::Guest)?,
                     Side::Guest => guest_path.into(),
                 })
+            } else if let Some(guest_path) = self.fds.borrow().get(fd) {
+                // the paths of the table are canonical too.
+                Ok(match side {
+                    Side::Host => self.fs.borrow().substitute(guest_path, Side::Guest)?,
+                    Side::Guest => guest_path.into(),
+                })
             } else {
                 let proc_fd = format!("/proc/{}/fd/{}", self.pid, fd);
                 let maybe_path = PathBuf::from(nix::fcntl::readlink(proc_fd.as_str())?);
@@ -259,20 +315,66 @@ impl Tracee {
         guest_path: P,
         deref_final: bool,
     ) -> Result<(PathBuf, PathBuf)> {
-        if guest_path.as_ref().is_relative() {
+        let guest_path = guest_path.as_ref();
+        let mut absolute_path = if guest_path.is_relative() {
             let mut dir_path = self.get_path_from_fd(dirfd, Side::Guest)?;
             // Check if guest_path is empty to avoid side effects of .push()
-            if !guest_path.as_ref().is_empty() {
+            if !guest_path.as_os_str().is_empty() {
                 dir_path.push(guest_path);
             }
-            self.fs
-                .borrow()
-                .translate_absolute_path(dir_path, deref_final)
+            dir_path
         } else {
-            self.fs
-                .borrow()
-                .translate_absolute_path(guest_path, deref_final)
+            guest_path.to_path_buf()
+        };
+        if let Some(path) = self.resolve_proc_self(&absolute_path, deref_final) {
+            absolute_path = path;
+        }
+        self.fs
+            .borrow()
+            .translate_absolute_path(absolute_path, deref_final)
+    }
+
+    /// Same as `translate_path_at()`, for a path relative to the working
+    /// directory, like `Translator::translate_path()`.
+    pub fn translate_path<P: AsRef<Path>>(
+        &self,
+        guest_path: P,
+        deref_final: bool,
+    ) -> Result<(PathBuf, PathBuf)> {
+        self.translate_path_at(libc::AT_FDCWD, guest_path, deref_final)
+    }
+
+    /// `/proc/self` and `/proc/thread-self` are symlinks whose target depends
+    /// on the process which follows them: pronyx would find itself there.
+    /// They are replaced at the start of the absolute `guest_path` by the
+    /// directory of this tracee, as long as the guest `/proc` is the one of the
+    /// host.
+    fn resolve_proc_self(&self, guest_path: &Path, deref_final: bool) -> Option<PathBuf> {
+        let (rest, thread) = match guest_path.strip_prefix("/proc/self") {
+            Ok(rest) => (rest, false),
+            Err(_) => (guest_path.strip_prefix("/proc/thread-self").ok()?, true),
+        };
+        // The symlink itself is left alone (`readlink()`, `lstat()`...).
+        if rest.as_os_str().is_empty() && !deref_final {
+            return None;
+        }
+        let host_path = self
+            .fs
+            .borrow()
+            .substitute(Path::new("/proc/self"), Side::Guest)
+            .ok()?;
+        if host_path != Path::new("/proc/self") {
+            return None;
+        }
+        let pid = proot::thread_group_id(self.pid)?;
+        let mut path = PathBuf::from(format!("/proc/{}", pid));
+        if thread {
+            path.push(format!("task/{}", self.pid));
+        }
+        if !rest.as_os_str().is_empty() {
+            path.push(rest);
         }
+        Some(path)
     }
 }
 
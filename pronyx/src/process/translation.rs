@@ -2,31 +2,67 @@ use crate::kernel::syscall;
 use crate::kernel::{enter, exit};
 use crate::process::proot::InfoBag;
 use crate::process::tracee::{Tracee, TraceeRestartMethod, TraceeStatus};
-use crate::register::{Modified, Original, StackPointer, SysResult, Word};
+use crate::register::{Current, Modified, Original, StackPointer, SysResult, Word};
 use crate::utils::Config;
 
 pub trait SyscallTranslator {
     fn translate_syscall(
         &mut self,
-        config: Config,
+        config: &Config,
         info_bag: &InfoBag,
         #[cfg(test)] func_syscall_hook: &Option<Box<dyn Fn(&Tracee, bool, bool)>>,
     );
     fn translate_syscall_enter(&mut self, config: &Config, info_bag: &InfoBag);
     fn translate_syscall_exit(&mut self, config: &Config);
+    /// Whether the syscall `sys_num` has to go through the enter stage.
+    fn needs_translation(&self, sys_num: usize, config: &Config, info_bag: &InfoBag) -> bool;
+    /// Go through the exit stage of the current syscall without doing
+    /// anything there.
+    fn skip_sysexit_stage(&mut self);
 }
 
 impl SyscallTranslator for Tracee {
     /// Retrieves the registers,
     /// handles either the enter or exit stage of the system call,
     /// and pushes the registers.
+    ///
+    /// Most syscalls are neither translated nor traced, which is told by their
+    /// number alone: their registers are then neither fetched nor pushed, at
+    /// their enter stage as at their exit stage.
     fn translate_syscall(
         &mut self,
-        config: Config,
+        config: &Config,
         info_bag: &InfoBag,
         #[cfg(test)] func_syscall_hook: &Option<Box<dyn Fn(&Tracee, bool, bool)>>,
     ) {
-        if let Err(error) = self.regs.fetch_regs() {
+        let mut fetched = false;
+        match self.status {
+            TraceeStatus::SysEnter => {
+                let sys_num = match self.regs.peek_sys_num() {
+                    Some(sys_num) => sys_num,
+                    None => {
+                        if let Err(error) = self.regs.fetch_regs() {
+                            error!("proot error: Error while fetching regs: {}", error);
+                            return;
+                        }
+                        fetched = true;
+                        self.regs.get_sys_num(Current)
+                    }
+                };
+                if !self.needs_translation(sys_num, config, info_bag) {
+                    self.skip_sysexit_stage();
+                    return;
+                }
+            }
+            _ if self.skip_sysexit => {
+                self.skip_sysexit = false;
+                self.status = TraceeStatus::SysEnter;
+                return;
+            }
+            _ => {}
+        }
+
+        if !fetched && let Err(error) = self.regs.fetch_regs() {
             error!("proot error: Error while fetching regs: {}", error);
             return;
         }
@@ -37,7 +73,7 @@ impl SyscallTranslator for Tracee {
                 func_syscall_hook
                     .as_ref()
                     .map(|func| func(self, true, true));
-                self.translate_syscall_enter(&config, info_bag);
+                self.translate_syscall_enter(config, info_bag);
                 true
             }
             TraceeStatus::SysExit | TraceeStatus::Error(_) => {
@@ -45,7 +81,7 @@ impl SyscallTranslator for Tracee {
                 func_syscall_hook
                     .as_ref()
                     .map(|func| func(self, false, true));
-                self.translate_syscall_exit(&config);
+                self.translate_syscall_exit(config);
                 false
             }
         };
@@ -59,10 +95,71 @@ impl SyscallTranslator for Tracee {
             .as_ref()
             .map(|func| func(self, is_sysenter, false));
 
+        if let Some(tracer) = &info_bag.tracer {
+            if is_sysenter {
+                self.trace_record = tracer.record_enter(self);
+                // There will be no exit stage to print the result at.
+                if self.restart_how == TraceeRestartMethod::WithoutExitStage
+                    && let Some(record) = self.trace_record.take()
+                {
+                    tracer.print(self.pid.as_raw(), record, None);
+                }
+            } else if let Some(record) = self.trace_record.take() {
+                tracer.print(self.pid.as_raw(), record, Some(self));
+            }
+        }
+
+        if let Some(audit) = &info_bag.audit {
+            if is_sysenter {
+                self.audit_records = audit.record_enter(self);
+                if self.restart_how == TraceeRestartMethod::WithoutExitStage {
+                    audit.write(std::mem::take(&mut self.audit_records), None);
+                }
+            } else if !self.audit_records.is_empty() {
+                audit.write(std::mem::take(&mut self.audit_records), Some(self));
+            }
+        }
+
         if is_sysenter {
-            syscall::print_syscall(self, "sysenter end", &config);
+            syscall::print_syscall(self, "sysenter end", config);
+            // nothing to translate, restore or report at the exit stage
+            if matches!(self.status, TraceeStatus::SysExit)
+                && !self.regs.were_changed()
+                && !exit::is_needed(self.regs.get_sys_num(Original))
+                && !syscall::is_handled(self.regs.get_sys_num(Original), config)
+                && self.trace_record.is_none()
+                && self.audit_records.is_empty()
+            {
+                self.skip_sysexit_stage();
+            }
+        } else {
+            syscall::print_syscall(self, "sysexit end", config);
+        }
+    }
+
+    fn needs_translation(&self, sys_num: usize, config: &Config, info_bag: &InfoBag) -> bool {
+        enter::is_needed(sys_num)
+            || syscall::is_handled(sys_num, config)
+            || info_bag
+                .tracer
+                .as_ref()
+                .is_some_and(|tracer| tracer.traces(self.pid.as_raw(), sys_num))
+            || info_bag
+                .audit
+                .as_ref()
+                .is_some_and(|audit| audit.audits(sys_num))
+    }
+
+    fn skip_sysexit_stage(&mut self) {
+        if self.seccomp {
+            // the next sysenter will be notified by seccomp
+            self.restart_how = TraceeRestartMethod::WithoutExitStage;
+            self.sysexit_pending = false;
+            self.status = TraceeStatus::SysEnter;
         } else {
-            syscall::print_syscall(self, "sysexit end", &config);
+            // the exit stage can't be avoided with ptrace alone
+            self.skip_sysexit = true;
+            self.status = TraceeStatus::SysExit;
         }
     }
 
@@ -76,7 +173,7 @@ impl SyscallTranslator for Tracee {
         // the syscall number, in case this one is changed during the enter stage).
         self.regs.save_current_regs(Original);
 
-        syscall::print_syscall(self, "sysenter start", &config);
+        syscall::print_syscall(self, "sysenter start", config);
 
         //TODO: notify extensions for SYSCALL_ENTER_START
         // status = notify_extensions(tracee, SYSCALL_ENTER_START, 0, 0);
@@ -85,6 +182,9 @@ impl SyscallTranslator for Tracee {
         // if (status > 0)
         //     return 0;
 
+        // Left over by a syscall which didn't reach its exit stage.
+        self.fd_change = None;
+
         let status = enter::translate(info_bag, self);
 
         //TODO: notify extensions for SYSCALL_ENTER_END event
@@ -129,7 +229,7 @@ impl SyscallTranslator for Tracee {
         // By default, restore original register values at the end of this stage.
         self.regs.set_restore_original_regs(true);
 
-        syscall::print_syscall(self, "sysexit start", &config);
+        syscall::print_syscall(self, "sysexit start", config);
 
         //TODO: notify extensions for SYSCALL_EXIT_START event
         // status = notify_extensions(tracee, SYSCALL_EXIT_START, 0, 0);
@@ -141,7 +241,7 @@ impl SyscallTranslator for Tracee {
         //     return;
 
         if self.status.is_ok() {
-            exit::translate(self);
+            exit::translate(self, config);
         } else {
             self.regs.set(
                 SysResult,
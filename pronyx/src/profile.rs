@@ -0,0 +1,515 @@
+//! Declarative box profiles.
+//!
+//! A profile is a TOML file describing everything needed to start a box, so
+//! that callers do not have to assemble long command lines. For example:
+//!
+//! ```toml
+//! rootfs = "./alpine"
+//! cwd = "/root"
+//! command = ["/bin/sh", "-l"]
+//! hostname = "box"
+//! kernel_release = "5.15.0"
+//! unset_env = ["LANG"]
+//! recommended_bindings = "safe"
+//! exit_policy = "init"
+//! grace_period = 10
+//!
+//! [identity]
+//! root = true
+//!
+//! [trace]
+//! output = "trace.log"
+//! filter = ["file", "execve"]
+//!
+//! [env]
+//! TERM = "xterm-256color"
+//!
+//! [[bind]]
+//! host = "/etc/resolv.conf"
+//! guest = "/etc/resolv.conf"
+//! optional = true
+//!
+//! [[bind]]
+//! host = "/usr/share"
+//! read_only = true
+//! ```
+//!
+//! Relative host paths are resolved against the directory containing the
+//! profile. Flags given on the command line take precedence over the profile:
+//! each key has the name of its flag (`recommended_bindings` being `-R` with
+//! `"full"` and `-S` with `"safe"`, and `path_cache = false` being
+//! `--no-path-cache`).
+
+use std::collections::BTreeMap;
+use std::fmt::Display;
+use std::path::{Path, PathBuf};
+
+use serde::Deserialize;
+
+use crate::errors::*;
+use crate::filesystem::device::Device;
+use crate::filesystem::validation::{check_utsname_value, HOST_NAME_MAX, KERNEL_RELEASE_MAX};
+use crate::filesystem::FileSystem;
+use crate::process::proot::ExitPolicy;
+use crate::process::trace::{filter_validator, TraceOptions};
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Profile {
+    /// Host path of the guest root file-system.
+    pub rootfs: Option<PathBuf>,
+    /// Initial working directory, on the guest side.
+    pub cwd: Option<PathBuf>,
+    /// Command run inside the box when none is given on the command line.
+    pub command: Option<Vec<String>>,
+    /// Bindings, applied in order (later ones take precedence).
+    #[serde(default)]
+    pub bind: Vec<ProfileBinding>,
+    #[serde(default)]
+    pub identity: ProfileIdentity,
+    /// Environment variables set for the initial command.
+    #[serde(default)]
+    pub env: BTreeMap<String, String>,
+    /// Variables removed from the environment of the initial command.
+    #[serde(default)]
+    pub unset_env: Vec<String>,
+    /// Start from an empty environment instead of the default one.
+    pub clear_env: Option<bool>,
+    /// Host name reported by `uname()`.
+    pub hostname: Option<String>,
+    /// Kernel release reported by `uname()`.
+    pub kernel_release: Option<String>,
+    /// Convert hard links to symbolic links.
+    pub link2symlink: Option<bool>,
+    /// Bind the host files most programs need: `"full"` like `-R`, or
+    /// `"safe"` like `-S`.
+    pub recommended_bindings: Option<String>,
+    /// Host file shown as `/proc/cpuinfo`.
+    pub cpuinfo: Option<PathBuf>,
+    /// Host devices the device files of the fake root may open, as
+    /// `c:major:minor` or `b:major:minor`.
+    #[serde(default)]
+    pub allow_device: Vec<String>,
+    /// `"init"` or `"all"`, see `ExitPolicy`.
+    pub exit_policy: Option<String>,
+    /// Seconds given to the guest to terminate after a forwarded signal.
+    pub grace_period: Option<u32>,
+    /// Make the processes started by pronyx child subreapers.
+    pub subreaper: Option<bool>,
+    /// Cache the translations of the guest paths.
+    pub path_cache: Option<bool>,
+    /// Host file the audit records are written to.
+    pub audit: Option<PathBuf>,
+    pub trace: Option<ProfileTrace>,
+    /// Directory containing the profile, used to resolve relative host paths.
+    #[serde(skip)]
+    base_dir: PathBuf,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ProfileBinding {
+    /// Host side path, which must exist unless the binding is optional.
+    pub host: PathBuf,
+    /// Guest side path, defaults to the host path.
+    pub guest: Option<PathBuf>,
+    /// Skip this binding instead of failing when the host path is missing.
+    #[serde(default)]
+    pub optional: bool,
+    /// Forbid the guest to modify the files of this binding.
+    #[serde(default)]
+    pub read_only: bool,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ProfileIdentity {
+    /// Pretend to be root (uid 0, gid 0), like `-0`.
+    pub root: Option<bool>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ProfileTrace {
+    /// Host file the trace is written to, stderr if missing.
+    pub output: Option<PathBuf>,
+    /// Syscalls to trace, by name or class.
+    #[serde(default)]
+    pub filter: Vec<String>,
+    /// Pids to trace.
+    #[serde(default)]
+    pub pids: Vec<i32>,
+    /// Prefix each line with the time of the syscall entry.
+    #[serde(default)]
+    pub timestamps: bool,
+}
+
+impl Profile {
+    /// Read and validate the profile stored at `path`.
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Profile> {
+        let path = path.as_ref();
+        let content = std::fs::read_to_string(path)
+            .with_context(|| format!("{}: cannot read profile", path.display()))?;
+        let mut profile = Profile::parse(&content).map_err(|error| {
+            let msg = format!("{}: {}", path.display(), error_msg(&error));
+            error.with_msg(msg)
+        })?;
+        profile.base_dir = path
+            .parent()
+            .map(Path::to_path_buf)
+            .unwrap_or_default();
+        profile.validate().map_err(|error| {
+            let msg = format!("{}: {}", path.display(), error_msg(&error));
+            error.with_msg(msg)
+        })?;
+        Ok(profile)
+    }
+
+    /// Parse a profile from its TOML content, without validating it.
+    pub fn parse(content: &str) -> Result<Profile> {
+        toml::from_str(content).map_err(|error| Error::errno_with_msg(EINVAL, error))
+    }
+
+    /// Check the values of the profile, and report every invalid key.
+    pub fn validate(&self) -> Result<()> {
+        let mut problems: Vec<String> = vec![];
+        let mut report = |key: &str, problem: &dyn Display| {
+            problems.push(format!("{}: {}", key, problem));
+        };
+
+        if let Some(rootfs) = &self.rootfs {
+            let rootfs = self.resolve_host_path(rootfs);
+            if !rootfs.is_dir() {
+                report("rootfs", &format!("{:?} is not an existing directory", rootfs));
+            }
+        }
+        if let Some(cwd) = &self.cwd
+            && cwd.is_relative()
+        {
+            report("cwd", &format!("{:?} is not an absolute guest path", cwd));
+        }
+        if let Some(command) = &self.command {
+            if command.is_empty() {
+                report("command", &"must contain at least the program to run");
+            }
+            if command.iter().any(|arg| arg.contains('\0')) {
+                report("command", &"arguments must not contain null bytes");
+            }
+        }
+        for (index, binding) in self.bind.iter().enumerate() {
+            let host = self.resolve_host_path(&binding.host);
+            if !binding.optional && !host.exists() {
+                report(
+                    &format!("bind[{}].host", index),
+                    &format!("{:?} does not exist", host),
+                );
+            }
+            if let Some(guest) = &binding.guest
+                && guest.is_relative()
+            {
+                report(
+                    &format!("bind[{}].guest", index),
+                    &format!("{:?} is not an absolute guest path", guest),
+                );
+            }
+        }
+        for (key, value) in &self.env {
+            if key.is_empty() || key.contains('=') || key.contains('\0') {
+                report(&format!("env.{:?}", key), &"is not a valid variable name");
+            }
+            if value.contains('\0') {
+                report(
+                    &format!("env.{:?}", key),
+                    &"value must not contain null bytes",
+                );
+            }
+        }
+        if let Some(hostname) = &self.hostname
+            && let Err(problem) = check_utsname_value(hostname, HOST_NAME_MAX)
+        {
+            report("hostname", &problem);
+        }
+        if let Some(release) = &self.kernel_release
+            && let Err(problem) = check_utsname_value(release, KERNEL_RELEASE_MAX)
+        {
+            report("kernel_release", &problem);
+        }
+        if let Some(mode) = &self.recommended_bindings
+            && !["full", "safe"].contains(&mode.as_str())
+        {
+            report(
+                "recommended_bindings",
+                &format!("{:?} is neither \"full\" nor \"safe\"", mode),
+            );
+        }
+        if let Some(cpuinfo) = &self.cpuinfo {
+            let cpuinfo = self.resolve_host_path(cpuinfo);
+            if !cpuinfo.is_file() {
+                report("cpuinfo", &format!("{:?} is not an existing file", cpuinfo));
+            }
+        }
+        for (index, device) in self.allow_device.iter().enumerate() {
+            if let Err(error) = device.parse::<Device>() {
+                report(&format!("allow_device[{}]", index), &error_msg(&error));
+            }
+        }
+        if self.exit_policy().is_err() {
+            report("exit_policy", &"must be \"init\" or \"all\"");
+        }
+        for (index, item) in self
+            .trace
+            .iter()
+            .flat_map(|trace| trace.filter.iter().enumerate())
+        {
+            if let Err(problem) = filter_validator(item) {
+                report(&format!("trace.filter[{}]", index), &problem);
+            }
+        }
+
+        if problems.is_empty() {
+            Ok(())
+        } else {
+            Err(Error::errno_with_msg(EINVAL, problems.join("\n")))
+        }
+    }
+
+    /// Resolve a host path of this profile: relative paths are relative to
+    /// the directory containing the profile.
+    pub fn resolve_host_path(&self, path: &Path) -> PathBuf {
+        self.base_dir.join(path)
+    }
+
+    /// The exit policy of the profile, if it defines one.
+    pub fn exit_policy(&self) -> Result<Option<ExitPolicy>> {
+        match self.exit_policy.as_deref() {
+            None => Ok(None),
+            Some("init") => Ok(Some(ExitPolicy::Init)),
+            Some("all") => Ok(Some(ExitPolicy::All)),
+            Some(policy) => Err(Error::errno_with_msg(
+                EINVAL,
+                format!("unknown exit policy {:?}", policy),
+            )),
+        }
+    }
+
+    /// The host devices of `allow_device`, ignoring the invalid ones (see
+    /// `validate()`).
+    pub fn allowed_devices(&self) -> Vec<Device> {
+        self.allow_device
+            .iter()
+            .filter_map(|device| device.parse().ok())
+            .collect()
+    }
+
+    /// The options of `[trace]`, if the profile has this section.
+    pub fn trace_options(&self) -> Option<TraceOptions> {
+        self.trace.as_ref().map(|trace| TraceOptions {
+            output: trace
+                .output
+                .as_ref()
+                .map(|path| self.resolve_host_path(path)),
+            syscalls: trace.filter.clone(),
+            pids: trace.pids.clone(),
+            timestamps: trace.timestamps,
+        })
+    }
+
+    /// Host path of the rootfs, if the profile defines one.
+    pub fn rootfs(&self) -> Option<PathBuf> {
+        self.rootfs.as_ref().map(|path| self.resolve_host_path(path))
+    }
+
+    /// Add the bindings of this profile to `fs`, whose root must already be
+    /// set. Optional bindings whose host path is missing are skipped.
+    pub fn add_bindings(&self, fs: &mut FileSystem) -> Result<()> {
+        for (index, binding) in self.bind.iter().enumerate() {
+            let host = self.resolve_host_path(&binding.host);
+            if binding.optional && !host.exists() {
+                debug!("skipping optional binding {:?}: host path is missing", host);
+                continue;
+            }
+            let guest = binding.guest.as_deref().unwrap_or(&binding.host);
+            let result = match binding.read_only {
+                true => fs.add_read_only_binding(&host, guest),
+                false => fs.add_binding(&host, guest),
+            };
+            result.map_err(|error| {
+                let msg = format!("bind[{}]: cannot bind {:?} to {:?}: {}", index, host, guest, error);
+                error.with_msg(msg)
+            })?;
+        }
+        Ok(())
+    }
+
+    /// Build the guest file-system described by this profile alone, which
+    /// checks the keys that can only be verified against the rootfs.
+    pub fn check(&self) -> Result<FileSystem> {
+        let mut fs = FileSystem::new();
+        let rootfs = self.rootfs().unwrap_or_else(|| PathBuf::from("/"));
+        fs.set_root(&rootfs)
+            .map_err(|error| error.with_msg(format!("rootfs: cannot use {:?}", rootfs)))?;
+        self.add_bindings(&mut fs)?;
+        if let Some(cwd) = &self.cwd {
+            fs.set_cwd(cwd)
+                .map_err(|error| error.with_msg(format!("cwd: cannot change to {:?}", cwd)))?;
+        }
+        Ok(fs)
+    }
+}
+
+/// Extract the message of an error, falling back to its full description.
+fn error_msg(error: &Error) -> String {
+    match &error.msg {
+        Some(msg) => msg.to_string(),
+        None => error.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rootfs::DeviceKind;
+
+    #[test]
+    fn test_profile_parse() {
+        let profile = Profile::parse(
+            r#"
+            rootfs = "/"
+            cwd = "/etc"
+            command = ["/bin/true"]
+            hostname = "box"
+            kernel_release = "5.15.0"
+            unset_env = ["LANG"]
+            clear_env = true
+            recommended_bindings = "full"
+            exit_policy = "all"
+            grace_period = 5
+            allow_device = ["b:7:0"]
+            audit = "audit.json"
+
+            [identity]
+            root = true
+
+            [env]
+            TERM = "dumb"
+
+            [trace]
+            filter = ["network", "openat"]
+            pids = [1]
+
+            [[bind]]
+            host = "/tmp"
+            guest = "/mnt"
+            read_only = true
+
+            [[bind]]
+            host = "/non_existing_path"
+            optional = true
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(profile.rootfs(), Some(PathBuf::from("/")));
+        assert_eq!(profile.cwd, Some(PathBuf::from("/etc")));
+        assert_eq!(profile.command, Some(vec!["/bin/true".to_string()]));
+        assert_eq!(profile.identity.root, Some(true));
+        assert_eq!(profile.env.get("TERM").map(String::as_str), Some("dumb"));
+        assert_eq!(profile.unset_env, vec!["LANG".to_string()]);
+        assert_eq!(profile.clear_env, Some(true));
+        assert_eq!(profile.bind.len(), 2);
+        assert!(profile.bind[0].read_only);
+        assert!(profile.bind[1].optional);
+        assert_eq!(profile.exit_policy().unwrap(), Some(ExitPolicy::All));
+        assert_eq!(profile.grace_period, Some(5));
+        assert_eq!(
+            profile.allowed_devices(),
+            vec![Device {
+                kind: DeviceKind::Block,
+                major: 7,
+                minor: 0
+            }]
+        );
+        let trace = profile.trace_options().unwrap();
+        assert_eq!(trace.output, None);
+        assert_eq!(trace.pids, vec![1]);
+        profile.validate().unwrap();
+
+        let fs = profile.check().unwrap();
+        assert_eq!(fs.get_cwd(), Path::new("/etc"));
+        assert!(fs.is_read_only(Path::new("/mnt")));
+        assert!(!fs.is_read_only(Path::new("/etc")));
+    }
+
+    #[test]
+    fn test_profile_unknown_key() {
+        let error = Profile::parse("rotfs = \"/\"").unwrap_err();
+        assert_eq!(error.get_errno(), EINVAL);
+        assert!(error_msg(&error).contains("unknown field `rotfs`"));
+
+        let error = Profile::parse("[[bind]]\nhost = \"/\"\nreadonly = true").unwrap_err();
+        assert!(error_msg(&error).contains("unknown field `readonly`"));
+    }
+
+    #[test]
+    fn test_profile_validate_reports_keys() {
+        let profile = Profile::parse(
+            r#"
+            rootfs = "/non_existing_path"
+            cwd = "relative"
+            command = []
+            hostname = ""
+            recommended_bindings = "all"
+            exit_policy = "never"
+            allow_device = ["c:1:3", "x:1:3"]
+
+            [env]
+            "A=B" = "c"
+            "C" = "d\u0000"
+
+            [trace]
+            filter = ["file", "nosuchcall"]
+
+            [[bind]]
+            host = "/"
+            guest = "/mnt"
+
+            [[bind]]
+            host = "/non_existing_path"
+            guest = "mnt"
+            "#,
+        )
+        .unwrap();
+
+        let msg = error_msg(&profile.validate().unwrap_err());
+        for key in [
+            "rootfs:",
+            "cwd:",
+            "command:",
+            "hostname:",
+            "env.\"A=B\":",
+            "env.\"C\":",
+            "recommended_bindings:",
+            "exit_policy:",
+            "allow_device[1]:",
+            "trace.filter[1]:",
+            "bind[1].host:",
+            "bind[1].guest:",
+        ] {
+            assert!(msg.contains(key), "{:?} not reported in {:?}", key, msg);
+        }
+        assert!(!msg.contains("bind[0]"));
+        assert!(!msg.contains("allow_device[0]"));
+        assert!(!msg.contains("trace.filter[0]"));
+    }
+
+    #[test]
+    fn test_profile_relative_host_paths() {
+        let mut profile = Profile::parse("rootfs = \"rootfs\"").unwrap();
+        profile.base_dir = PathBuf::from("/srv/boxes");
+        assert_eq!(profile.rootfs(), Some(PathBuf::from("/srv/boxes/rootfs")));
+        assert_eq!(
+            profile.resolve_host_path(Path::new("/abs")),
+            PathBuf::from("/abs")
+        );
+    }
+}
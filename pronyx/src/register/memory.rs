@@ -0,0 +1,353 @@
+//! Access to the memory of the tracees.
+//!
+//! Data are moved with `process_vm_readv()` and `process_vm_writev()`, which
+//! copy a whole buffer in a single syscall, where `ptrace(PEEKDATA/POKEDATA)`
+//! copies a single word. The latter is still used when the former fails:
+//! kernels built without `CONFIG_CROSS_MEMORY_ATTACH`, and pages that the
+//! tracee itself cannot access (e.g. read-only mappings), which only ptrace
+//! is allowed to force.
+
+use std::io::IoSliceMut;
+use std::mem::size_of;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use lazy_static::lazy_static;
+use nix::sys::ptrace;
+use nix::sys::uio::{self, RemoteIoVec};
+use nix::unistd::{sysconf, Pid, SysconfVar};
+
+use crate::errors::*;
+use crate::register::reader::convert_word_to_bytes;
+use crate::register::writer::convert_bytes_to_word;
+use crate::register::Word;
+
+lazy_static! {
+    static ref PAGE_SIZE: usize = match sysconf(SysconfVar::PAGE_SIZE) {
+        Ok(Some(value)) => value as usize,
+        _ => 0x1000,
+    };
+}
+
+/// Set once `process_vm_readv()`/`process_vm_writev()` are known to be
+/// unusable, so that they are not tried for every access.
+static PROCESS_VM_DISABLED: AtomicBool = AtomicBool::new(false);
+
+/// Whether `process_vm_readv()`/`process_vm_writev()` are used (the default);
+/// otherwise, only ptrace is. This is mainly meant to compare both methods.
+pub fn use_process_vm(enabled: bool) {
+    PROCESS_VM_DISABLED.store(!enabled, Ordering::Relaxed);
+}
+
+/// Fill `buffer` with the bytes at `address` in the memory of the tracee
+/// `pid`.
+pub fn read_bytes(pid: Pid, address: Word, buffer: &mut [u8]) -> Result<()> {
+    let mut done = 0;
+    if !PROCESS_VM_DISABLED.load(Ordering::Relaxed) {
+        while done < buffer.len() {
+            let remote = [RemoteIoVec {
+                base: address as usize + done,
+                len: buffer.len() - done,
+            }];
+            match uio::process_vm_readv(pid, &mut [IoSliceMut::new(&mut buffer[done..])], &remote) {
+                Ok(0) => break,
+                Ok(size) => done += size,
+                Err(errno) if fall_back_to_ptrace(errno) => break,
+                Err(errno) => return Err(errno.into()),
+            }
+        }
+    }
+    if done < buffer.len() {
+        peek_bytes(pid, address + done as Word, &mut buffer[done..])?;
+    }
+    Ok(())
+}
+
+/// Read the C string at `address` in the memory of the tracee `pid`, without
+/// its terminating null byte. At most `max_size` bytes are read: a longer
+/// string is truncated to `max_size` bytes, which the caller can check.
+pub fn read_string(pid: Pid, address: Word, max_size: usize) -> Result<Vec<u8>> {
+    read_until(pid, address, max_size, 1, |chunk| {
+        chunk.iter().position(|&byte| byte == b'\0')
+    })
+}
+
+/// Read the null-terminated array of words at `address` in the memory of the
+/// tracee `pid` (e.g. `argv`), without its terminating null word.
+pub fn read_words(pid: Pid, address: Word) -> Result<Vec<Word>> {
+    let word_size = size_of::<Word>();
+    let bytes = read_until(pid, address, usize::MAX, word_size, |chunk| {
+        chunk
+            .chunks_exact(word_size)
+            .position(|word| word.iter().all(|&byte| byte == 0))
+            .map(|index| index * word_size)
+    })?;
+    Ok(bytes
+        .chunks_exact(word_size)
+        .map(|word| convert_bytes_to_word(word.try_into().unwrap()))
+        .collect())
+}
+
+/// Read the memory of the tracee `pid` from `address` one page (or one word,
+/// with ptrace) at a time, so that the pages after the data are never touched
+/// (they may not be mapped), until `find_end` finds the end of the data in the
+/// last chunk read, or `max_size` bytes are read. The chunks are made of whole
+/// `unit`s.
+fn read_until<F>(
+    pid: Pid,
+    address: Word,
+    max_size: usize,
+    unit: usize,
+    mut find_end: F,
+) -> Result<Vec<u8>>
+where
+    F: FnMut(&[u8]) -> Option<usize>,
+{
+    let mut bytes = vec![];
+    while bytes.len() < max_size {
+        let current = address as usize + bytes.len();
+        // with ptrace, a word costs a syscall: the end is looked for after each
+        let chunk = if PROCESS_VM_DISABLED.load(Ordering::Relaxed) {
+            size_of::<Word>()
+        } else {
+            *PAGE_SIZE
+        };
+        let to_chunk_end = chunk - current % chunk;
+        let size = (to_chunk_end.min(max_size - bytes.len()) / unit).max(1) * unit;
+
+        let start = bytes.len();
+        bytes.resize(start + size, 0);
+        read_bytes(pid, current as Word, &mut bytes[start..])?;
+        if let Some(end) = find_end(&bytes[start..]) {
+            bytes.truncate(start + end);
+            return Ok(bytes);
+        }
+    }
+    bytes.truncate(max_size);
+    Ok(bytes)
+}
+
+/// Copy `data` at `address` in the memory of the tracee `pid`.
+pub fn write_bytes(pid: Pid, address: Word, data: &[u8]) -> Result<()> {
+    let mut done = 0;
+    if !PROCESS_VM_DISABLED.load(Ordering::Relaxed) {
+        while done < data.len() {
+            let remote = [RemoteIoVec {
+                base: address as usize + done,
+                len: data.len() - done,
+            }];
+            match uio::process_vm_writev(pid, &[std::io::IoSlice::new(&data[done..])], &remote) {
+                Ok(0) => break,
+                Ok(size) => done += size,
+                Err(errno) if fall_back_to_ptrace(errno) => break,
+                Err(errno) => return Err(errno.into()),
+            }
+        }
+    }
+    if done < data.len() {
+        poke_bytes(pid, address + done as Word, &data[done..])?;
+    }
+    Ok(())
+}
+
+/// Whether an access which failed with `errno` should be retried with
+/// ptrace. `process_vm_*()` are not tried anymore if they are not supported.
+fn fall_back_to_ptrace(errno: Errno) -> bool {
+    match errno {
+        Errno::ENOSYS => {
+            debug!("process_vm_readv/writev are not supported, falling back to ptrace");
+            PROCESS_VM_DISABLED.store(true, Ordering::Relaxed);
+            true
+        }
+        // EFAULT: the range is not accessible by the tracee itself
+        Errno::EFAULT | Errno::EPERM => true,
+        _ => false,
+    }
+}
+
+/// Like `read_bytes()`, with `ptrace(PEEKDATA)`. Only aligned words are read,
+/// so that the word after the data may not cross into an unmapped page.
+fn peek_bytes(pid: Pid, address: Word, buffer: &mut [u8]) -> Result<()> {
+    let word_size = size_of::<Word>();
+    let mut word_address = address - address % word_size as Word;
+    let mut done = 0;
+    while done < buffer.len() {
+        let word = ptrace::read(pid, word_address as *mut libc::c_void)
+            .map_err(|errno| memory_error(errno, "cannot read the tracee memory"))?;
+        let bytes = convert_word_to_bytes(word as Word);
+        let skip = (address as usize + done) - word_address as usize;
+        let size = (word_size - skip).min(buffer.len() - done);
+        buffer[done..done + size].copy_from_slice(&bytes[skip..skip + size]);
+        done += size;
+        word_address += word_size as Word;
+    }
+    Ok(())
+}
+
+/// Like `write_bytes()`, with `ptrace(POKEDATA)`. Only aligned words are
+/// written, those which are partly covered by `data` are merged with their
+/// current content.
+fn poke_bytes(pid: Pid, address: Word, data: &[u8]) -> Result<()> {
+    let word_size = size_of::<Word>();
+    let mut word_address = address - address % word_size as Word;
+    let mut done = 0;
+    while done < data.len() {
+        let skip = (address as usize + done) - word_address as usize;
+        let size = (word_size - skip).min(data.len() - done);
+        let mut bytes = [0; size_of::<Word>()];
+        if size < word_size {
+            peek_bytes(pid, word_address, &mut bytes)?;
+        }
+        bytes[skip..skip + size].copy_from_slice(&data[done..done + size]);
+        ptrace::write(
+            pid,
+            word_address as *mut libc::c_void,
+            convert_bytes_to_word(bytes) as libc::c_long,
+        )
+        .map_err(|errno| memory_error(errno, "cannot write the tracee memory"))?;
+        done += size;
+        word_address += word_size as Word;
+    }
+    Ok(())
+}
+
+/// ptrace reports an invalid address with `EIO`, where the syscalls of the
+/// tracee would report `EFAULT`.
+fn memory_error(errno: Errno, msg: &'static str) -> Error {
+    match errno {
+        Errno::EIO => Error::errno_with_msg(EFAULT, msg),
+        errno => Error::errno_with_msg(errno, msg),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::tests::test_in_subprocess;
+    use nix::sys::mman::{self, MapFlags, ProtFlags};
+    use nix::sys::signal::{self, Signal};
+    use nix::sys::wait::{self, WaitStatus};
+    use nix::unistd::{self, ForkResult};
+    use std::num::NonZeroUsize;
+    use std::ptr::NonNull;
+
+    /// Map two pages, the second one being unmapped afterwards, so that
+    /// anything read past the first one fails.
+    fn map_last_page(prot: ProtFlags) -> *mut u8 {
+        let page_size = *PAGE_SIZE;
+        let area = unsafe {
+            mman::mmap_anonymous(
+                None,
+                NonZeroUsize::new(2 * page_size).unwrap(),
+                ProtFlags::PROT_READ | ProtFlags::PROT_WRITE,
+                MapFlags::MAP_PRIVATE,
+            )
+        }
+        .unwrap();
+        let area = area.as_ptr() as *mut u8;
+        unsafe {
+            let second = NonNull::new(area.add(page_size) as *mut libc::c_void).unwrap();
+            mman::munmap(second, page_size).unwrap();
+            let first = NonNull::new(area as *mut libc::c_void).unwrap();
+            mman::mprotect(first, page_size, prot).unwrap();
+        }
+        area
+    }
+
+    /// Run `func` with the pid of a stopped tracee, which shares the memory
+    /// layout of the caller as it is a fork of it.
+    fn with_tracee<F: FnOnce(Pid)>(func: F) {
+        test_in_subprocess(|| match unsafe { unistd::fork() }.unwrap() {
+            ForkResult::Child => {
+                ptrace::traceme().unwrap();
+                signal::raise(Signal::SIGSTOP).unwrap();
+                unsafe { libc::_exit(0) };
+            }
+            ForkResult::Parent { child } => {
+                assert_eq!(
+                    wait::waitpid(child, None),
+                    Ok(WaitStatus::Stopped(child, Signal::SIGSTOP))
+                );
+                func(child);
+                signal::kill(child, Signal::SIGKILL).unwrap();
+                let _ = wait::waitpid(child, None);
+            }
+        })
+    }
+
+    #[test]
+    fn test_memory_read_string_at_the_end_of_a_mapping() {
+        let page = map_last_page(ProtFlags::PROT_READ | ProtFlags::PROT_WRITE);
+        let string = b"/etc/passwd\0";
+        let address = unsafe {
+            let address = page.add(*PAGE_SIZE - string.len());
+            std::ptr::copy_nonoverlapping(string.as_ptr(), address, string.len());
+            address as Word
+        };
+
+        with_tracee(|pid| {
+            assert_eq!(read_string(pid, address, 4096).unwrap(), b"/etc/passwd");
+            assert_eq!(read_string(pid, address, 4).unwrap(), b"/etc");
+            assert_eq!(read_string(pid, address + 2, 4096).unwrap(), b"tc/passwd");
+            assert_eq!(
+                read_string(pid, address + string.len() as Word, 4096)
+                    .unwrap_err()
+                    .get_errno(),
+                EFAULT
+            );
+
+            let mut bytes = [0; 5];
+            peek_bytes(pid, address + 1, &mut bytes).unwrap();
+            assert_eq!(&bytes, b"etc/p");
+        });
+    }
+
+    #[test]
+    fn test_memory_read_words() {
+        let argv: [Word; 4] = [1, 2, 3, 0];
+        let page = map_last_page(ProtFlags::PROT_READ | ProtFlags::PROT_WRITE);
+        let last_words = unsafe {
+            let address = page.add(*PAGE_SIZE - argv.len() * size_of::<Word>()) as *mut Word;
+            std::ptr::copy_nonoverlapping(argv.as_ptr(), address, argv.len());
+            address as Word
+        };
+
+        with_tracee(|pid| {
+            assert_eq!(
+                read_words(pid, argv.as_ptr() as Word).unwrap(),
+                vec![1, 2, 3]
+            );
+            assert_eq!(read_words(pid, last_words).unwrap(), vec![1, 2, 3]);
+        });
+    }
+
+    #[test]
+    fn test_memory_write_bytes() {
+        let page = map_last_page(ProtFlags::PROT_READ | ProtFlags::PROT_WRITE);
+        let address = page as Word;
+
+        with_tracee(|pid| {
+            write_bytes(pid, address, b"0123456789abcdefghij").unwrap();
+            // neither aligned, nor a whole number of words
+            poke_bytes(pid, address + 3, b"XYZWVUTSR").unwrap();
+            let mut bytes = [0; 20];
+            read_bytes(pid, address, &mut bytes).unwrap();
+            assert_eq!(&bytes, b"012XYZWVUTSRcdefghij");
+        });
+    }
+
+    #[test]
+    fn test_memory_write_bytes_read_only_mapping() {
+        let page = map_last_page(ProtFlags::PROT_READ);
+        let address = unsafe { page.add(*PAGE_SIZE - 6) } as Word;
+
+        with_tracee(|pid| {
+            // `process_vm_writev()` fails, `ptrace(POKEDATA)` is used instead
+            write_bytes(pid, address, b"hello\0").unwrap();
+            assert_eq!(read_string(pid, address, 4096).unwrap(), b"hello");
+            assert_eq!(
+                write_bytes(pid, address + 6, b"!").unwrap_err().get_errno(),
+                EFAULT
+            );
+        });
+    }
+}
@@ -1,6 +1,7 @@
 #[macro_use]
 mod abi;
 mod mem;
+pub mod memory;
 mod reader;
 pub mod regs;
 mod writer;
@@ -10,7 +11,7 @@ use libc::c_ulong;
 pub type Word = c_ulong;
 
 pub use self::mem::PtraceMemoryAllocator;
-pub use self::reader::PtraceReader;
+pub use self::reader::{read_path, PtraceReader};
 pub use self::regs::RegVersion::{self, *};
 pub use self::regs::Register::*;
 pub use self::regs::Registers;
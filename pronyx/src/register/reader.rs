@@ -1,9 +1,9 @@
 use crate::errors::*;
+use crate::register::memory;
 use crate::register::{Current, Registers, SysArg, SysArgIndex, Word};
-use libc::{c_void, PATH_MAX};
-use nix::sys::ptrace;
+use libc::PATH_MAX;
 use nix::unistd::Pid;
-use std::mem::{size_of, transmute};
+use std::mem::transmute;
 use std::path::PathBuf;
 
 #[cfg(target_pointer_width = "32")]
@@ -59,8 +59,8 @@ impl PtraceReader for Registers {
 ///
 /// It also checks that the number of bytes isn't too long.
 #[inline]
-fn read_path(pid: Pid, src_path: *mut Word) -> Result<PathBuf> {
-    let bytes = read_string(pid, src_path, PATH_MAX as usize)?;
+pub fn read_path(pid: Pid, src_path: *mut Word) -> Result<PathBuf> {
+    let bytes = memory::read_string(pid, src_path as Word, PATH_MAX as usize)?;
 
     if bytes.len() >= PATH_MAX as usize {
         return Err(Error::errno_with_msg(
@@ -76,77 +76,6 @@ fn read_path(pid: Pid, src_path: *mut Word) -> Result<PathBuf> {
     Ok(PathBuf::from(unsafe { String::from_utf8_unchecked(bytes) }))
 }
 
-/// Reads a string from the memory space of a tracee.
-///
-/// It uses `ptrace(PEEK_DATA)` to read it word by word
-/// (1 word = 1 c_ulong = 1 u32 or 1 u64 = 4 or 8 u8 = 4 or 8 char).
-/// The copy stops when a null character `\0` is encountered (which is not
-/// added), The bytes contained at the string's address are returned as a Vector
-/// of u8.
-///
-/// * `pid` is the pid of the tracee.
-/// * `src_string` is the address of the string in tracee's memory space
-///   (obtained for instance with `get_reg`).
-/// * `max_size` is the maximum number of bytes copied from memory.
-fn read_string(pid: Pid, src_string: *mut Word, max_size: usize) -> Result<Vec<u8>> {
-    let mut bytes: Vec<u8> = Vec::with_capacity(max_size);
-
-    //TODO: belongs_to_heap_prealloc
-    // if (belongs_to_heap_prealloc(tracee, dest_tracee))
-    //	return -EFAULT;
-
-    //TODO: implement HAVE_PROCESS_VM
-
-    let word_size = size_of::<Word>();
-    let nb_trailing_bytes = (max_size % word_size) as isize;
-    let nb_full_words = ((max_size - nb_trailing_bytes as usize) / word_size) as isize;
-
-    // Copy one word by one word, except for the last one.
-    for i in 0..nb_full_words {
-        let src_addr = unsafe { src_string.offset(i) as *mut c_void };
-
-        // ptrace returns a c_long/Word that we will interpret as an 8-letters word
-        let word = ptrace::read(pid, src_addr)? as Word;
-        let letters = convert_word_to_bytes(word);
-
-        for &letter in &letters {
-            // Stop once an end-of-string is detected.
-            if letter as char == '\0' {
-                // bytes.push(letter); // we do not add the null byte to the path
-                bytes.shrink_to_fit();
-
-                return Ok(bytes);
-            }
-            bytes.push(letter);
-        }
-    }
-
-    //todo: add trailing bytes processing (when necessary, need an example where
-    // it's actually used)
-    unimplemented!("trailing bytes not supported!")
-
-    /*
-
-    /* Copy the bytes from the last word carefully since we have
-     * to not overwrite the bytes lying beyond @dest_tracer. */
-
-    word = ptrace(PTRACE_PEEKDATA, tracee->pid, src + i, NULL);
-    if (errno != 0)
-        return -EFAULT;
-
-    dest_word = (uint8_t *)&dest[i];
-    src_word  = (uint8_t *)&word;
-
-    for (j = 0; j < nb_trailing_bytes; j++) {
-        dest_word[j] = src_word[j];
-        if (src_word[j] == '\0')
-            break;
-    }
-
-    return i * sizeof(word_t) + j + 1;
-    */
-}
-
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -223,6 +223,34 @@ impl RegisterSet {
     }
 }
 
+const PTRACE_GET_SYSCALL_INFO: usize = 0x420e;
+const PTRACE_SYSCALL_INFO_ENTRY: u8 = 1;
+
+/// The `AUDIT_ARCH_*` value (see <linux/audit.h>) of the syscalls of the
+/// native ABI, which are the only ones whose number `peek_sys_num()` may
+/// return.
+#[cfg(target_arch = "x86_64")]
+const AUDIT_ARCH_NATIVE: u32 = 0xc000_003e;
+#[cfg(target_arch = "x86")]
+const AUDIT_ARCH_NATIVE: u32 = 0x4000_0003;
+#[cfg(target_arch = "arm")]
+const AUDIT_ARCH_NATIVE: u32 = 0x4000_0028;
+#[cfg(target_arch = "aarch64")]
+const AUDIT_ARCH_NATIVE: u32 = 0xc000_00b7;
+
+/// Beginning of the `struct ptrace_syscall_info` filled by
+/// `PTRACE_GET_SYSCALL_INFO` at a syscall entry, up to the syscall number.
+#[repr(C)]
+#[allow(dead_code)]
+struct SyscallEntryInfo {
+    op: u8,
+    pad: [u8; 3],
+    arch: u32,
+    instruction_pointer: u64,
+    stack_pointer: u64,
+    nr: u64,
+}
+
 #[derive(Debug, Copy, Clone, PartialEq)]
 pub enum RegVersion {
     Current = 0,  // indicates current registers value
@@ -243,6 +271,21 @@ pub enum SysArgIndex {
 }
 use self::SysArgIndex::*;
 
+impl SysArgIndex {
+    /// The argument at position `index` (starting from 0), saturated to the
+    /// last argument.
+    pub fn from_index(index: usize) -> SysArgIndex {
+        match index {
+            0 => SysArg1,
+            1 => SysArg2,
+            2 => SysArg3,
+            3 => SysArg4,
+            4 => SysArg5,
+            _ => SysArg6,
+        }
+    }
+}
+
 #[derive(Debug, Copy, Clone)]
 pub enum Register {
     SysNum,
@@ -300,6 +343,12 @@ impl Registers {
         }
     }
 
+    /// Changes the pid of the tracee, which executed a program from a thread
+    /// other than its leader.
+    pub fn set_pid(&mut self, pid: Pid) {
+        self.pid = pid;
+    }
+
     #[cfg(test)]
     /// Same, but with the initial regs. Useful for tests.
     pub fn from(pid: Pid, raw_regs: RegisterSet) -> Self {
@@ -385,6 +434,41 @@ impl Registers {
         Ok(())
     }
 
+    /// Retrieves the number of the syscall the tracee is stopped at the entry
+    /// of, without fetching the registers: `PTRACE_GET_SYSCALL_INFO` only
+    /// copies what describes the syscall.
+    ///
+    /// Returns `None` if it is not supported (before Linux 5.3), if the
+    /// tracee is not at a syscall entry, or if the syscall is not of the
+    /// native ABI (e.g. `int 0x80` on x86_64), whose number means something
+    /// else: the registers must be fetched then.
+    pub fn peek_sys_num(&self) -> Option<usize> {
+        let mut info = MaybeUninit::<SyscallEntryInfo>::zeroed();
+        let res = unsafe {
+            libc::ptrace(
+                PTRACE_GET_SYSCALL_INFO as _,
+                libc::pid_t::from(self.pid),
+                std::mem::size_of::<SyscallEntryInfo>(),
+                info.as_mut_ptr() as *mut c_void,
+            )
+        };
+        if res <= 0 {
+            return None;
+        }
+        let info = unsafe { info.assume_init() };
+        match info.op {
+            PTRACE_SYSCALL_INFO_ENTRY if info.arch == AUDIT_ARCH_NATIVE => Some(info.nr as usize),
+            _ => None,
+        }
+    }
+
+    /// Whether the `Current` registers were modified since the `Original`
+    /// ones were saved, i.e. whether `push_regs()` has anything to push.
+    #[inline]
+    pub fn were_changed(&self) -> bool {
+        self.regs_were_changed
+    }
+
     /// Pushes the `Current` cached general purpose registers back to
     /// the process, if necessary.
     ///
@@ -696,6 +780,37 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_peek_sys_num_test() {
+        let rootfs_path = get_test_rootfs_path();
+        use crate::sc::nr::{NANOSLEEP, CLOCK_NANOSLEEP};
+
+        fork_test(
+            rootfs_path,
+            // expecting a normal execution
+            0,
+            // parent
+            |tracee, _| {
+                // the syscall number is only given at the entry of a syscall
+                match tracee.regs.peek_sys_num() {
+                    Some(sys_num) => {
+                        assert_eq!(sys_num, tracee.regs.get_sys_num(Current));
+                        sys_num == NANOSLEEP as usize || sys_num == CLOCK_NANOSLEEP as usize
+                    }
+                    None => false,
+                }
+            },
+            // child
+            || {
+                execvp(
+                    &CString::new("/bin/sleep").unwrap(),
+                    &[CString::new(".").unwrap(), CString::new("0").unwrap()],
+                )
+                .expect("failed execvp sleep");
+            },
+        );
+    }
+
     #[test]
     /// Tests that `push_regs` works by voiding the NANOSLEEP syscall.
     /// It fails if the syscall is not cancelled (and in this case it will wait
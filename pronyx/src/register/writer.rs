@@ -1,15 +1,11 @@
 use crate::errors::Result;
-use crate::register::reader::convert_word_to_bytes;
+use crate::register::memory;
 use crate::register::{PtraceMemoryAllocator, Registers, SysArg, SysArgIndex, Word};
-use byteorder::NativeEndian;
-use byteorder::ReadBytesExt;
 use libc::c_void;
-use nix::sys::ptrace;
 
 #[allow(unused)]
 use crate::sc::nr::MKDIR;
 
-use std::io::Read;
 use std::mem;
 use std::os::unix::ffi::OsStrExt;
 use std::path::Path;
@@ -105,63 +101,21 @@ impl PtraceWriter for Registers {
         Ok(tracee_ptr as _)
     }
 
-    /// Copy the `data` to tracee's memory space by ptrace(PTRACE_POKEDATA) and
-    /// ptrace(PTRACE_PEEKDATA). It transmits one word at a time, and the
-    /// boundary case is carefully handled.
+    /// Copy the `data` to tracee's memory space, see `memory::write_bytes()`.
+    /// If `append_null` is set, a null byte is written after it.
     fn write_data(&self, dest_tracee: *mut c_void, data: &[u8], append_null: bool) -> Result<()> {
         //TODO implement belongs_to_heap_prealloc
         // if (belongs_to_heap_prealloc(tracee, dest_tracee))
         // return -EFAULT;
 
-        // TODO: use process_vm_writev() to write data if process_vm feature was
-        // supported.
-
-        // if append null is required, we appen a b'\0' byte to the end of data.
-        let mut buf = if append_null {
-            let null_char_slice: &[u8] = &[b'\0'];
-            data.chain(null_char_slice)
-        } else {
-            data.chain(&[] as &[u8])
-        };
-        let size = if append_null {
-            // the +1 is for the `\0` byte that we will have manually
-            data.len() + 1
+        if append_null {
+            let mut buf = Vec::with_capacity(data.len() + 1);
+            buf.extend_from_slice(data);
+            buf.push(b'\0');
+            memory::write_bytes(self.get_pid(), dest_tracee as Word, &buf)
         } else {
-            data.len()
-        };
-        let word_size = mem::size_of::<Word>();
-        let nb_trailing_bytes = (size % word_size) as isize;
-        let nb_full_words = ((size - nb_trailing_bytes as usize) / word_size) as isize;
-
-        // Copy one word by one word, except for the last one.
-        for i in 0..nb_full_words {
-            // The byteorder crate is used to read the [u8] slice as a [Word] slice.
-            let word = buf.read_uint::<NativeEndian>(word_size).unwrap() as Word;
-            let dest_addr = unsafe { (dest_tracee as *mut Word).offset(i) as *mut c_void };
-
-            ptrace::write(self.get_pid(), dest_addr, word as usize as i64)?
-        }
-
-        // Copy the bytes in the last word carefully since we have to
-        // overwrite only the relevant ones.
-        let last_dest_addr =
-            unsafe { (dest_tracee as *mut Word).offset(nb_full_words) as *mut c_void };
-        let existing_word = ptrace::read(self.get_pid(), last_dest_addr)? as Word;
-        let mut bytes = convert_word_to_bytes(existing_word);
-
-        // The trailing bytes are merged with the existing bytes. For example:
-        // bytes = [0, 0, 0, 0, 0, 0, 119, 0] // the already existing bytes at the dest
-        // addr trailing bytes = [164, 247, 274] // our trailing bytes
-        // fusion = [164, 247, 274, 0, 0, 0, 119, 0] // the fusion of the two
-        for byte in bytes.iter_mut().take(nb_trailing_bytes as usize) {
-            *byte = buf.read_u8().unwrap();
+            memory::write_bytes(self.get_pid(), dest_tracee as Word, data)
         }
-
-        let last_word = convert_bytes_to_word(bytes);
-        // We can now safely write the final word.
-        ptrace::write(self.get_pid(), last_dest_addr, last_word as usize as i64)?;
-
-        Ok(())
     }
 }
 
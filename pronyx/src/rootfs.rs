@@ -0,0 +1,831 @@
+//! Import and export of rootfs images (tarballs), see `pronyx rootfs`.
+//!
+//! A rootfs is unpacked by an unprivileged user, so what needs privileges is
+//! not done but recorded in a manifest next to the rootfs
+//! (`<dir>.manifest.toml`), which is used to write the image back out:
+//!
+//! - the ownership of the files, which all belong to the user on the host;
+//! - the device files, which are replaced by empty regular files;
+//! - the permissions of the files which are not readable or writable by
+//!   their owner, since these permissions are added on the host.
+//!
+//! With `-0`, the devices created by the guest are recorded in the manifest
+//! too, see the `device` module, and so are the extended attributes it sets
+//! which are emulated, see the `xattr` module. The host root `/` has no
+//! manifest.
+//!
+//! The entries of an image which would be unpacked out of the rootfs
+//! (`..`, absolute paths, paths or hard links through a symlink) are
+//! refused.
+
+use std::collections::{BTreeMap, HashMap};
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::os::unix::fs::{symlink, FileTypeExt, MetadataExt, OpenOptionsExt, PermissionsExt};
+use std::path::{Component, Path, PathBuf};
+
+use flate2::read::MultiGzDecoder;
+use flate2::write::GzEncoder;
+use nix::errno::Errno::{EEXIST, EINVAL, ENOTDIR, EPERM};
+use nix::sys::stat::{utimensat, Mode, UtimensatFlags};
+use nix::sys::time::TimeSpec;
+use nix::unistd::mkfifo;
+use serde::{Deserialize, Serialize};
+use tar::{Archive, Builder, EntryType, Header};
+use xz2::read::XzDecoder;
+use xz2::write::XzEncoder;
+
+use crate::errors::{Error, Result, WithContext};
+use crate::sandbox::{ErrorKind, SandboxError};
+
+/// Permissions which are always given to the owner of the unpacked files, so
+/// that the rootfs can be used and exported again.
+pub(crate) const OWNER_FILE_MODE: u32 = 0o600;
+const OWNER_DIR_MODE: u32 = 0o700;
+
+/// Ownership and special files of a rootfs which can't be kept on the host,
+/// see the module documentation.
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Manifest {
+    /// Files which are not a plain root-owned file, by path relative to the
+    /// rootfs.
+    #[serde(default, rename = "file")]
+    pub files: Vec<ManifestFile>,
+    /// Emulated extended attributes, by path relative to the rootfs.
+    #[serde(default, rename = "xattr", skip_serializing_if = "Vec::is_empty")]
+    pub xattrs: Vec<ManifestXattrs>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ManifestFile {
+    pub path: PathBuf,
+    #[serde(default)]
+    pub uid: u64,
+    #[serde(default)]
+    pub gid: u64,
+    /// Permission bits in the image, in octal, when they differ from the ones
+    /// on the host.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mode: Option<String>,
+    /// The device this file stands for, if any.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub device: Option<DeviceKind>,
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub major: u32,
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub minor: u32,
+}
+
+/// The emulated extended attributes of a file.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ManifestXattrs {
+    pub path: PathBuf,
+    /// Values by name, in hexadecimal.
+    pub attributes: BTreeMap<String, String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DeviceKind {
+    Char,
+    Block,
+}
+
+fn is_zero(value: &u32) -> bool {
+    *value == 0
+}
+
+impl ManifestFile {
+    pub(crate) fn mode(&self) -> Result<Option<u32>> {
+        self.mode
+            .as_ref()
+            .map(|mode| {
+                u32::from_str_radix(mode, 8).map_err(|_| {
+                    Error::errno_with_msg(
+                        EINVAL,
+                        format!("{}: invalid mode {:?}", self.path.display(), mode),
+                    )
+                })
+            })
+            .transpose()
+    }
+}
+
+impl Manifest {
+    /// Path of the manifest of the rootfs `dir`.
+    pub fn path(dir: &Path) -> PathBuf {
+        let mut path = dir.as_os_str().to_owned();
+        path.push(".manifest.toml");
+        PathBuf::from(path)
+    }
+
+    /// Whether the rootfs `dir` may have a manifest: not the host root, whose
+    /// manifest would be `/.manifest.toml`.
+    pub(crate) fn is_supported(dir: &Path) -> bool {
+        dir != Path::new("/")
+    }
+
+    /// Read the manifest of the rootfs `dir`, which is empty if there is
+    /// none.
+    pub fn load(dir: &Path) -> Result<Manifest> {
+        let path = Manifest::path(dir);
+        let content = match fs::read_to_string(&path) {
+            Ok(content) => content,
+            Err(error) if error.kind() == io::ErrorKind::NotFound => return Ok(Manifest::default()),
+            Err(error) => {
+                return Err(error)
+                    .with_context(|| format!("{}: cannot read manifest", path.display()));
+            }
+        };
+        toml::from_str(&content).map_err(|error| {
+            Error::errno_with_msg(EINVAL, format!("{}: {}", path.display(), error))
+        })
+    }
+
+    /// Write the manifest of the rootfs `dir`.
+    pub(crate) fn save(&self, dir: &Path) -> Result<()> {
+        let path = Manifest::path(dir);
+        let content =
+            toml::to_string(self).map_err(|error| Error::errno_with_msg(EINVAL, error))?;
+        // written aside first, so that a crash doesn't lose it all
+        let temp = path.with_extension("toml.tmp");
+        fs::write(
+            &temp,
+            format!("# Written by pronyx, see `pronyx rootfs`.\n\n{}", content),
+        )
+        .and_then(|_| fs::rename(&temp, &path))
+        .with_context(|| format!("{}: cannot write manifest", path.display()))
+    }
+
+    /// Change the manifest of the rootfs `dir` as it is saved, so that the
+    /// stores which share it (devices and extended attributes) each only
+    /// write their own part.
+    pub(crate) fn update(dir: &Path, change: impl FnOnce(&mut Manifest)) -> Result<()> {
+        let mut manifest = Manifest::load(dir)?;
+        change(&mut manifest);
+        manifest.save(dir)
+    }
+}
+
+/// Compression of an image.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Compression {
+    None,
+    Gzip,
+    Xz,
+    Zstd,
+}
+
+impl Compression {
+    /// Recognize the compression of an image by its first bytes.
+    fn from_magic(bytes: &[u8]) -> Compression {
+        if bytes.starts_with(&[0x1f, 0x8b]) {
+            Compression::Gzip
+        } else if bytes.starts_with(&[0xfd, b'7', b'z', b'X', b'Z', 0x00]) {
+            Compression::Xz
+        } else if bytes.starts_with(&[0x28, 0xb5, 0x2f, 0xfd]) {
+            Compression::Zstd
+        } else {
+            Compression::None
+        }
+    }
+
+    /// Choose the compression of an image by its file name.
+    fn from_extension(path: &Path) -> Compression {
+        let name = path
+            .file_name()
+            .map(|name| name.to_string_lossy())
+            .unwrap_or_default();
+        if name.ends_with(".gz") || name.ends_with(".tgz") {
+            Compression::Gzip
+        } else if name.ends_with(".xz") || name.ends_with(".txz") {
+            Compression::Xz
+        } else if name.ends_with(".zst") || name.ends_with(".tzst") {
+            Compression::Zstd
+        } else {
+            Compression::None
+        }
+    }
+}
+
+fn open_image(path: &Path) -> Result<Box<dyn Read>> {
+    let mut reader = BufReader::new(File::open(path)?);
+    Ok(match Compression::from_magic(reader.fill_buf()?) {
+        Compression::None => Box::new(reader),
+        Compression::Gzip => Box::new(MultiGzDecoder::new(reader)),
+        Compression::Xz => Box::new(XzDecoder::new(reader)),
+        Compression::Zstd => Box::new(zstd::Decoder::with_buffer(reader)?),
+    })
+}
+
+/// The writer of an image, which must be finished explicitly so that errors
+/// are not lost.
+enum Encoder {
+    None(File),
+    Gzip(GzEncoder<File>),
+    Xz(XzEncoder<File>),
+    Zstd(zstd::Encoder<'static, File>),
+}
+
+impl Encoder {
+    fn new(file: File, compression: Compression) -> Result<Encoder> {
+        Ok(match compression {
+            Compression::None => Encoder::None(file),
+            Compression::Gzip => {
+                Encoder::Gzip(GzEncoder::new(file, flate2::Compression::default()))
+            }
+            Compression::Xz => Encoder::Xz(XzEncoder::new(file, 6)),
+            Compression::Zstd => Encoder::Zstd(zstd::Encoder::new(file, 0)?),
+        })
+    }
+
+    fn finish(self) -> io::Result<()> {
+        let file = match self {
+            Encoder::None(file) => file,
+            Encoder::Gzip(encoder) => encoder.finish()?,
+            Encoder::Xz(encoder) => encoder.finish()?,
+            Encoder::Zstd(encoder) => encoder.finish()?,
+        };
+        file.sync_all()
+    }
+}
+
+impl Write for Encoder {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            Encoder::None(file) => file.write(buf),
+            Encoder::Gzip(encoder) => encoder.write(buf),
+            Encoder::Xz(encoder) => encoder.write(buf),
+            Encoder::Zstd(encoder) => encoder.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            Encoder::None(file) => file.flush(),
+            Encoder::Gzip(encoder) => encoder.flush(),
+            Encoder::Xz(encoder) => encoder.flush(),
+            Encoder::Zstd(encoder) => encoder.flush(),
+        }
+    }
+}
+
+/// Turn the path of an entry into a path relative to the rootfs: leading
+/// `./` are stripped, while absolute paths and `..` are refused.
+fn entry_path(path: &Path) -> Result<PathBuf> {
+    let mut relative = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::Normal(name) => relative.push(name),
+            Component::RootDir | Component::Prefix(_) | Component::ParentDir => {
+                return Err(Error::errno_with_msg(
+                    EPERM,
+                    format!("{}: refusing an entry out of the rootfs", path.display()),
+                ));
+            }
+        }
+    }
+    Ok(relative)
+}
+
+/// Check that the parent directories of `relative` in `dir` are directories,
+/// and not symlinks which could lead out of the rootfs. The missing ones are
+/// created if `create` is set.
+fn check_parents(dir: &Path, relative: &Path, create: bool) -> Result<()> {
+    let mut path = dir.to_path_buf();
+    let parents: Vec<_> = relative.components().collect();
+    for component in parents.iter().take(parents.len().saturating_sub(1)) {
+        path.push(component);
+        match fs::symlink_metadata(&path) {
+            Ok(metadata) if metadata.is_dir() => {}
+            Ok(_) => {
+                return Err(Error::errno_with_msg(
+                    ENOTDIR,
+                    format!(
+                        "{}: refusing an entry through a non-directory",
+                        relative.display()
+                    ),
+                ));
+            }
+            Err(error) if create && error.kind() == io::ErrorKind::NotFound => {
+                fs::create_dir(&path)?;
+            }
+            Err(error) => return Err(error.into()),
+        }
+    }
+    Ok(())
+}
+
+/// Remove what an entry replaces, except a directory replaced by a
+/// directory.
+fn remove_existing(path: &Path, kind: EntryType) -> Result<()> {
+    match fs::symlink_metadata(path) {
+        Ok(metadata) if metadata.is_dir() => {
+            if kind != EntryType::Directory {
+                fs::remove_dir(path)
+                    .with_context(|| format!("{}: cannot replace directory", path.display()))?;
+            }
+        }
+        Ok(_) => fs::remove_file(path)?,
+        Err(error) if error.kind() == io::ErrorKind::NotFound => {}
+        Err(error) => return Err(error.into()),
+    }
+    Ok(())
+}
+
+fn set_mtime(path: &Path, mtime: u64) -> Result<()> {
+    let time = TimeSpec::new(mtime as _, 0);
+    utimensat(None, path, &time, &time, UtimensatFlags::NoFollowSymlink)?;
+    Ok(())
+}
+
+/// Unpack the image `image` (a tarball, possibly compressed with gzip, xz or
+/// zstd) into the new rootfs `dir`, and write its manifest. Returns the
+/// number of unpacked entries.
+fn unpack(image: &Path, dir: &Path) -> Result<usize> {
+    match fs::read_dir(dir) {
+        Ok(mut entries) => {
+            if entries.next().is_some() {
+                return Err(Error::errno_with_msg(
+                    EEXIST,
+                    format!("{}: directory is not empty", dir.display()),
+                ));
+            }
+        }
+        Err(error) if error.kind() == io::ErrorKind::NotFound => fs::create_dir_all(dir)?,
+        Err(error) => return Err(error.into()),
+    }
+    let dir = &dir.canonicalize()?;
+
+    let mut archive = Archive::new(open_image(image)?);
+    let mut manifest: BTreeMap<PathBuf, ManifestFile> = BTreeMap::new();
+    // The times of the directories are set last, since unpacking their
+    // content changes them.
+    let mut directories: Vec<(PathBuf, u64)> = vec![];
+    let mut count = 0;
+
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        let header = entry.header().clone();
+        let kind = header.entry_type();
+        let relative = entry_path(&entry.path()?)?;
+        let path = dir.join(&relative);
+        let mode = header.mode()? & 0o7777;
+        let mut device = None;
+
+        if relative.as_os_str().is_empty() {
+            // The entry of the rootfs itself.
+            if kind == EntryType::Directory {
+                fs::set_permissions(dir, fs::Permissions::from_mode(mode | OWNER_DIR_MODE))?;
+                directories.push((dir.clone(), header.mtime()?));
+            }
+            count += 1;
+            continue;
+        }
+        check_parents(dir, &relative, true)?;
+        remove_existing(&path, kind)?;
+
+        let host_mode = match kind {
+            EntryType::Regular | EntryType::Continuous | EntryType::GNUSparse => {
+                let mut file = OpenOptions::new()
+                    .write(true)
+                    .create_new(true)
+                    .mode(0o600)
+                    .open(&path)?;
+                io::copy(&mut entry, &mut file)?;
+                Some(mode | OWNER_FILE_MODE)
+            }
+            EntryType::Directory => {
+                if !path.is_dir() {
+                    fs::create_dir(&path)?;
+                }
+                directories.push((path.clone(), header.mtime()?));
+                Some(mode | OWNER_DIR_MODE)
+            }
+            EntryType::Symlink => {
+                let target = entry.link_name()?.ok_or_else(|| {
+                    Error::errno_with_msg(EINVAL, format!("{}: no target", relative.display()))
+                })?;
+                symlink(&target, &path)?;
+                None
+            }
+            EntryType::Link => {
+                let target = entry.link_name()?.ok_or_else(|| {
+                    Error::errno_with_msg(EINVAL, format!("{}: no target", relative.display()))
+                })?;
+                let target = entry_path(&target)?;
+                check_parents(dir, &target, false)?;
+                fs::hard_link(dir.join(&target), &path).with_context(|| {
+                    format!(
+                        "{}: cannot link to {}",
+                        relative.display(),
+                        target.display()
+                    )
+                })?;
+                // It is the same file as the target, which was already
+                // handled.
+                count += 1;
+                continue;
+            }
+            EntryType::Char | EntryType::Block => {
+                File::create(&path)?;
+                device = Some((
+                    if kind == EntryType::Char {
+                        DeviceKind::Char
+                    } else {
+                        DeviceKind::Block
+                    },
+                    header.device_major()?.unwrap_or(0),
+                    header.device_minor()?.unwrap_or(0),
+                ));
+                Some(mode | OWNER_FILE_MODE)
+            }
+            EntryType::Fifo => {
+                mkfifo(&path, Mode::from_bits_truncate(0o600))?;
+                Some(mode | OWNER_FILE_MODE)
+            }
+            _ => {
+                warn!("{}: skipping entry of type {:?}", relative.display(), kind);
+                continue;
+            }
+        };
+        if let Some(host_mode) = host_mode {
+            fs::set_permissions(&path, fs::Permissions::from_mode(host_mode))?;
+        }
+        if kind != EntryType::Directory {
+            set_mtime(&path, header.mtime()?)?;
+        }
+
+        let uid = header.uid()?;
+        let gid = header.gid()?;
+        let mode_differs = host_mode.is_some_and(|host_mode| host_mode != mode);
+        if uid != 0 || gid != 0 || mode_differs || device.is_some() {
+            let (device, major, minor) = match device {
+                Some((kind, major, minor)) => (Some(kind), major, minor),
+                None => (None, 0, 0),
+            };
+            manifest.insert(
+                relative.clone(),
+                ManifestFile {
+                    path: relative,
+                    uid,
+                    gid,
+                    mode: mode_differs.then(|| format!("{:04o}", mode)),
+                    device,
+                    major,
+                    minor,
+                },
+            );
+        } else {
+            manifest.remove(&relative);
+        }
+        count += 1;
+    }
+
+    for (path, mtime) in directories.iter().rev() {
+        set_mtime(path, *mtime)?;
+    }
+    Manifest {
+        files: manifest.into_values().collect(),
+        xattrs: vec![],
+    }
+    .save(dir)?;
+    Ok(count)
+}
+
+/// Write the rootfs `dir` to the image `image`, with the ownership and
+/// devices recorded in its manifest. The compression is chosen by the
+/// extension of `image`. Returns the number of written entries.
+fn pack(dir: &Path, image: &Path) -> Result<usize> {
+    let dir = &dir.canonicalize()?;
+    if !dir.is_dir() {
+        return Err(Error::errno_with_msg(
+            ENOTDIR,
+            format!("{}: not a directory", dir.display()),
+        ));
+    }
+    let manifest: HashMap<PathBuf, ManifestFile> = Manifest::load(dir)?
+        .files
+        .into_iter()
+        .map(|file| (file.path.clone(), file))
+        .collect();
+
+    let file = File::create(image)?;
+    let mut builder = Builder::new(Encoder::new(file, Compression::from_extension(image))?);
+    builder.follow_symlinks(false);
+    let mut links: HashMap<(u64, u64), PathBuf> = HashMap::new();
+    let mut count = 0;
+
+    let mut pending = vec![PathBuf::new()];
+    while let Some(relative) = pending.pop() {
+        let path = dir.join(&relative);
+        let metadata = fs::symlink_metadata(&path)?;
+        let file_type = metadata.file_type();
+        let recorded = manifest.get(&relative);
+
+        let mut header = Header::new_gnu();
+        header.set_mtime(metadata.mtime().max(0) as u64);
+        header.set_uid(recorded.map_or(0, |file| file.uid));
+        header.set_gid(recorded.map_or(0, |file| file.gid));
+        let mode = match recorded.map(ManifestFile::mode).transpose()?.flatten() {
+            Some(mode) => mode,
+            None => metadata.mode() & 0o7777,
+        };
+        header.set_mode(mode);
+        header.set_size(0);
+        let name = if relative.as_os_str().is_empty() {
+            PathBuf::from(".")
+        } else {
+            relative.clone()
+        };
+
+        if file_type.is_dir() {
+            header.set_entry_type(EntryType::Directory);
+            builder.append_data(&mut header, &name, io::empty())?;
+            let mut children: Vec<PathBuf> = fs::read_dir(&path)?
+                .map(|child| child.map(|child| relative.join(child.file_name())))
+                .collect::<io::Result<_>>()?;
+            // Sorted, for reproducible images.
+            children.sort_unstable_by(|a, b| b.cmp(a));
+            pending.extend(children);
+        } else if file_type.is_symlink() {
+            header.set_entry_type(EntryType::Symlink);
+            builder.append_link(&mut header, &name, fs::read_link(&path)?)?;
+        } else if let Some(device) = recorded.and_then(|file| file.device) {
+            let file = recorded.unwrap();
+            header.set_entry_type(match device {
+                DeviceKind::Char => EntryType::Char,
+                DeviceKind::Block => EntryType::Block,
+            });
+            header.set_device_major(file.major)?;
+            header.set_device_minor(file.minor)?;
+            builder.append_data(&mut header, &name, io::empty())?;
+        } else if file_type.is_fifo() {
+            header.set_entry_type(EntryType::Fifo);
+            builder.append_data(&mut header, &name, io::empty())?;
+        } else if file_type.is_file() {
+            let key = (metadata.dev(), metadata.ino());
+            match links.get(&key) {
+                Some(target) if metadata.nlink() > 1 => {
+                    header.set_entry_type(EntryType::Link);
+                    builder.append_link(&mut header, &name, target)?;
+                }
+                _ => {
+                    if metadata.nlink() > 1 {
+                        links.insert(key, name.clone());
+                    }
+                    header.set_entry_type(EntryType::Regular);
+                    header.set_size(metadata.len());
+                    builder.append_data(&mut header, &name, File::open(&path)?)?;
+                }
+            }
+        } else {
+            warn!("{}: skipping socket", relative.display());
+            continue;
+        }
+        count += 1;
+    }
+
+    builder.into_inner()?.finish()?;
+    Ok(count)
+}
+
+/// Import the image `image` (a tarball, possibly compressed with gzip, xz or
+/// zstd) as the new rootfs `dir`. Ownership and devices are recorded in a
+/// manifest next to it, see `Manifest::path()`. Returns the number of
+/// imported entries.
+pub fn import_rootfs<P: AsRef<Path>, Q: AsRef<Path>>(
+    image: P,
+    dir: Q,
+) -> std::result::Result<usize, SandboxError> {
+    let image = image.as_ref();
+    unpack(image, dir.as_ref())
+        .map_err(|error| rootfs_error(format!("cannot import {}", image.display()), error))
+}
+
+/// Export the rootfs `dir` to the image `image`, with the ownership and
+/// devices recorded by `import_rootfs()`. The image is compressed according
+/// to its extension (`.gz`, `.xz` or `.zst`). Returns the number of exported
+/// entries.
+pub fn export_rootfs<P: AsRef<Path>, Q: AsRef<Path>>(
+    dir: P,
+    image: Q,
+) -> std::result::Result<usize, SandboxError> {
+    let image = image.as_ref();
+    pack(dir.as_ref(), image)
+        .map_err(|error| rootfs_error(format!("cannot export {}", image.display()), error))
+}
+
+fn rootfs_error(what: String, error: Error) -> SandboxError {
+    let msg = match &error.msg {
+        Some(msg) => format!("{}: {}", what, msg),
+        None => what,
+    };
+    SandboxError::new(ErrorKind::Rootfs, error.with_msg(msg))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Make a new, empty, test directory.
+    fn make_test_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir(&dir).unwrap();
+        dir
+    }
+
+    fn header(kind: EntryType, mode: u32, uid: u64, gid: u64) -> Header {
+        let mut header = Header::new_gnu();
+        header.set_entry_type(kind);
+        header.set_mode(mode);
+        header.set_uid(uid);
+        header.set_gid(gid);
+        header.set_mtime(1_000_000_000);
+        header.set_size(0);
+        header
+    }
+
+    /// Write an image with the entries `(path, header, content or link)`.
+    fn write_image(path: &Path, entries: &[(&str, Header, &str)]) {
+        let mut builder = Builder::new(File::create(path).unwrap());
+        for (name, header, data) in entries {
+            let mut header = header.clone();
+            match header.entry_type() {
+                EntryType::Symlink | EntryType::Link => {
+                    builder.append_link(&mut header, name, data).unwrap()
+                }
+                _ => {
+                    header.set_size(data.len() as u64);
+                    builder
+                        .append_data(&mut header, name, data.as_bytes())
+                        .unwrap()
+                }
+            }
+        }
+        builder.finish().unwrap();
+    }
+
+    #[test]
+    fn test_rootfs_import_export() {
+        let dir = make_test_dir("test_rootfs_import_export");
+        let image = dir.join("image.tar");
+        let mut device = header(EntryType::Char, 0o666, 0, 0);
+        device.set_device_major(1).unwrap();
+        device.set_device_minor(3).unwrap();
+        write_image(
+            &image,
+            &[
+                ("./", header(EntryType::Directory, 0o755, 0, 0), ""),
+                ("./bin", header(EntryType::Directory, 0o555, 0, 0), ""),
+                (
+                    "./bin/busybox",
+                    header(EntryType::Regular, 0o4755, 0, 0),
+                    "elf",
+                ),
+                (
+                    "./bin/sh",
+                    header(EntryType::Link, 0o4755, 0, 0),
+                    "bin/busybox",
+                ),
+                (
+                    "./bin/ls",
+                    header(EntryType::Symlink, 0o777, 0, 0),
+                    "busybox",
+                ),
+                (
+                    "./etc/shadow",
+                    header(EntryType::Regular, 0o640, 0, 42),
+                    "root:*",
+                ),
+                ("./dev/null", device, ""),
+            ],
+        );
+
+        let rootfs = dir.join("rootfs");
+        assert_eq!(import_rootfs(&image, &rootfs).unwrap(), 7);
+        let metadata = fs::metadata(rootfs.join("bin/sh")).unwrap();
+        assert_eq!(
+            metadata.ino(),
+            fs::metadata(rootfs.join("bin/busybox")).unwrap().ino()
+        );
+        assert_eq!(metadata.mode() & 0o7777, 0o4755);
+        assert_eq!(
+            fs::read_link(rootfs.join("bin/ls")).unwrap(),
+            Path::new("busybox")
+        );
+        assert_eq!(
+            fs::read_to_string(rootfs.join("etc/shadow")).unwrap(),
+            "root:*"
+        );
+        assert!(fs::metadata(rootfs.join("dev/null")).unwrap().is_file());
+        assert_eq!(
+            fs::metadata(rootfs.join("bin")).unwrap().mtime(),
+            1_000_000_000
+        );
+
+        let manifest = Manifest::load(&rootfs).unwrap();
+        let paths: Vec<_> = manifest
+            .files
+            .iter()
+            .map(|file| file.path.as_path())
+            .collect();
+        assert_eq!(
+            paths,
+            [
+                Path::new("bin"),
+                Path::new("dev/null"),
+                Path::new("etc/shadow")
+            ]
+        );
+        assert_eq!(manifest.files[0].mode.as_deref(), Some("0555"));
+        assert_eq!(manifest.files[1].device, Some(DeviceKind::Char));
+        assert_eq!((manifest.files[2].uid, manifest.files[2].gid), (0, 42));
+
+        // The exported image has the original ownership, modes and devices,
+        // and the `etc` and `dev` directories which were created on import.
+        let exported = dir.join("exported.tar.gz");
+        assert_eq!(export_rootfs(&rootfs, &exported).unwrap(), 9);
+        let mut archive = Archive::new(open_image(&exported).unwrap());
+        let mut entries = HashMap::new();
+        for entry in archive.entries().unwrap() {
+            let entry = entry.unwrap();
+            let path = entry.path().unwrap().into_owned();
+            entries.insert(path, entry.header().clone());
+        }
+        let shadow = &entries[Path::new("etc/shadow")];
+        assert_eq!((shadow.uid().unwrap(), shadow.gid().unwrap()), (0, 42));
+        assert_eq!(shadow.mode().unwrap(), 0o640);
+        assert_eq!(entries[Path::new("bin")].mode().unwrap(), 0o555);
+        let null = &entries[Path::new("dev/null")];
+        assert_eq!(null.entry_type(), EntryType::Char);
+        assert_eq!(null.device_major().unwrap(), Some(1));
+        assert_eq!(null.device_minor().unwrap(), Some(3));
+        let links: Vec<_> = [Path::new("bin/busybox"), Path::new("bin/sh")]
+            .iter()
+            .map(|path| entries[*path].entry_type())
+            .collect();
+        assert!(links.contains(&EntryType::Link));
+        assert_eq!(
+            entries[Path::new("bin/ls")].entry_type(),
+            EntryType::Symlink
+        );
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_rootfs_import_refuses_escapes() {
+        let dir = make_test_dir("test_rootfs_import_refuses_escapes");
+        let file = header(EntryType::Regular, 0o644, 0, 0);
+        let cases: Vec<Vec<(&str, Header, &str)>> = vec![
+            vec![
+                ("./a", file.clone(), "a"),
+                ("../escaped", file.clone(), "b"),
+            ],
+            vec![("/escaped", file.clone(), "b")],
+            vec![
+                ("link", header(EntryType::Symlink, 0o777, 0, 0), ".."),
+                ("link/escaped", file.clone(), "b"),
+            ],
+            vec![
+                ("link", header(EntryType::Symlink, 0o777, 0, 0), "/etc"),
+                (
+                    "escaped",
+                    header(EntryType::Link, 0o644, 0, 0),
+                    "link/passwd",
+                ),
+            ],
+        ];
+
+        for (index, entries) in cases.iter().enumerate() {
+            let image = dir.join(format!("image{}.tar", index));
+            let mut builder = Builder::new(File::create(&image).unwrap());
+            for (name, header, data) in entries {
+                // `Builder` refuses these paths, so they are written as is.
+                let mut header = header.clone();
+                let bytes = name.as_bytes();
+                header.as_old_mut().name[..bytes.len()].copy_from_slice(bytes);
+                match header.entry_type() {
+                    EntryType::Symlink | EntryType::Link => header.set_link_name(data).unwrap(),
+                    _ => header.set_size(data.len() as u64),
+                }
+                header.set_cksum();
+                builder.append(&header, data.as_bytes()).unwrap();
+            }
+            builder.finish().unwrap();
+
+            let rootfs = dir.join(format!("rootfs{}", index));
+            let error = import_rootfs(&image, &rootfs).unwrap_err();
+            assert_eq!(error.kind(), ErrorKind::Rootfs);
+            assert!(!dir.join("escaped").exists());
+        }
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,1049 @@
+//! Embeddable API to launch and supervise boxes.
+//!
+//! ```no_run
+//! use pronyx::Sandbox;
+//!
+//! let mut sandbox = Sandbox::builder()
+//!     .rootfs("./alpine")
+//!     .bind("/etc/resolv.conf", "/etc/resolv.conf")
+//!     .cwd("/root")
+//!     .root_id(true)
+//!     .env("TERM", "dumb")
+//!     .command(["/bin/sh", "-c", "echo hello"])
+//!     .spawn()?;
+//! let status = sandbox.wait()?;
+//! println!("exited with {}", status.code());
+//! # Ok::<(), pronyx::SandboxError>(())
+//! ```
+
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, Arc};
+use std::thread::{self, JoinHandle};
+
+use nix::sys::signal::{self, Signal};
+use nix::unistd::Pid;
+
+use crate::errors::{Errno, Error};
+use crate::filesystem::binding::Side;
+use crate::filesystem::device::{Device, DeviceStore, DEFAULT_ALLOWED_DEVICES};
+use crate::filesystem::virtual_file::VirtualFiles;
+use crate::filesystem::xattr::XattrStore;
+use crate::filesystem::{FileSystem, Substitutor};
+use crate::process::proot::{show_info, stop_program, PRoot};
+use crate::process::sigactions;
+use crate::profile::Profile;
+use crate::session::{self, Session};
+use crate::utils::Config;
+
+pub use crate::process::proot::{ExitPolicy, ExitStatus, Orphan};
+pub use crate::process::trace::TraceOptions;
+
+pub const DEFAULT_ROOTFS: &'static str = "/";
+pub const DEFAULT_CWD: &'static str = "/";
+pub const DEFAULT_COMMAND: &'static str = "/bin/sh";
+pub const DEFAULT_GRACE_PERIOD: u32 = 10;
+
+/// Which step failed, see `SandboxError::kind()`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ErrorKind {
+    /// The description of the box is invalid (rootfs, binding, cwd, profile...).
+    Config,
+    /// The box could not be started (loader, `fork()`, command not found...).
+    Launch,
+    /// The tracer failed while supervising the box.
+    Supervisor,
+    /// A session could not be reached, or refused a request.
+    Session,
+    /// A rootfs image could not be imported or exported.
+    Rootfs,
+}
+
+/// Error returned by the sandbox API.
+#[derive(Debug, Clone)]
+pub struct SandboxError {
+    kind: ErrorKind,
+    errno: i32,
+    message: String,
+}
+
+impl SandboxError {
+    pub(crate) fn new(kind: ErrorKind, error: Error) -> Self {
+        SandboxError {
+            kind,
+            errno: error.get_errno() as i32,
+            message: match &error.msg {
+                Some(msg) => msg.to_string(),
+                None => error.to_string(),
+            },
+        }
+    }
+
+    pub(crate) fn config(error: Error) -> Self {
+        SandboxError::new(ErrorKind::Config, error)
+    }
+
+    pub fn kind(&self) -> ErrorKind {
+        self.kind
+    }
+
+    /// The `errno` value associated with this error, if any.
+    pub fn errno(&self) -> Option<i32> {
+        match self.errno {
+            0 => None,
+            errno => Some(errno),
+        }
+    }
+
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+}
+
+impl fmt::Display for SandboxError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.message)?;
+        if let Some(errno) = self.errno() {
+            write!(f, " ({})", Errno::from_raw(errno).desc())?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for SandboxError {}
+
+#[derive(Debug, Clone)]
+struct BindingSpec {
+    host: PathBuf,
+    guest: PathBuf,
+    optional: bool,
+    read_only: bool,
+    /// Added by `recommended_bindings()`: skipped if it can't be made.
+    recommended: bool,
+}
+
+/// The host files bound by `recommended_bindings()`, as in PRoot's `-R`.
+const RECOMMENDED_BINDINGS: &[&str] = &[
+    "/dev",
+    "/proc",
+    "/sys",
+    "/run",
+    "/tmp",
+    "/etc/resolv.conf",
+    "/etc/hosts",
+    "/etc/host.conf",
+    "/etc/nsswitch.conf",
+    "/etc/localtime",
+    "/etc/passwd",
+    "/etc/group",
+];
+
+/// Describes a box, see the module documentation.
+#[derive(Debug, Clone)]
+pub struct SandboxBuilder {
+    rootfs: PathBuf,
+    bindings: Vec<BindingSpec>,
+    cwd: PathBuf,
+    command: Vec<String>,
+    attach: Option<Pid>,
+    config: Config,
+    install_sigactions: bool,
+    grace_period: u32,
+    translation_cache: bool,
+}
+
+impl Default for SandboxBuilder {
+    fn default() -> Self {
+        SandboxBuilder {
+            rootfs: PathBuf::from(DEFAULT_ROOTFS),
+            bindings: vec![],
+            cwd: PathBuf::from(DEFAULT_CWD),
+            command: vec![DEFAULT_COMMAND.to_string()],
+            attach: None,
+            config: Config::default(),
+            install_sigactions: false,
+            grace_period: DEFAULT_GRACE_PERIOD,
+            translation_cache: true,
+        }
+    }
+}
+
+impl SandboxBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Apply the profile stored at `path`. Settings made afterwards take
+    /// precedence over the ones of the profile.
+    pub fn profile<P: AsRef<Path>>(mut self, path: P) -> Result<Self, SandboxError> {
+        let profile = Profile::load(path).map_err(SandboxError::config)?;
+
+        if let Some(rootfs) = profile.rootfs() {
+            self.rootfs = rootfs;
+        }
+        match profile.recommended_bindings.as_deref() {
+            Some("full") => self = self.recommended_bindings(true),
+            Some(_) => self = self.recommended_bindings(false).root_id(true),
+            None => {}
+        }
+        for binding in &profile.bind {
+            let host = profile.resolve_host_path(&binding.host);
+            let guest = binding.guest.clone().unwrap_or_else(|| binding.host.clone());
+            self.bindings.push(BindingSpec {
+                host,
+                guest,
+                optional: binding.optional,
+                read_only: binding.read_only,
+                recommended: false,
+            });
+        }
+        if let Some(cwd) = &profile.cwd {
+            self.cwd = cwd.clone();
+        }
+        if let Some(command) = &profile.command {
+            self.command = command.clone();
+        }
+        if let Some(root_id) = profile.identity.root {
+            self.config.root_id = root_id;
+        }
+        if let Some(link2symlink) = profile.link2symlink {
+            self.config.link2symlink = link2symlink;
+        }
+        self.config.hostname = profile.hostname.clone().or(self.config.hostname);
+        self.config.kernel_release = profile
+            .kernel_release
+            .clone()
+            .or(self.config.kernel_release);
+        if let Some(clear) = profile.clear_env {
+            self.config.env.clear = clear;
+        }
+        for (key, value) in &profile.env {
+            self.config.env.set_var(key.clone(), value.clone());
+        }
+        for key in &profile.unset_env {
+            self.config.env.unset_var(key.clone());
+        }
+        if let Some(cpuinfo) = &profile.cpuinfo {
+            self = self.cpuinfo(profile.resolve_host_path(cpuinfo));
+        }
+        for device in profile.allowed_devices() {
+            self = self.allow_device(device);
+        }
+        if let Some(policy) = profile.exit_policy().map_err(SandboxError::config)? {
+            self.config.exit_policy = policy;
+        }
+        if let Some(seconds) = profile.grace_period {
+            self.grace_period = seconds;
+        }
+        if let Some(subreaper) = profile.subreaper {
+            self.config.subreaper = subreaper;
+        }
+        if let Some(enabled) = profile.path_cache {
+            self.translation_cache = enabled;
+        }
+        if let Some(audit) = &profile.audit {
+            self = self.audit(profile.resolve_host_path(audit));
+        }
+        if let Some(options) = profile.trace_options() {
+            self = self.trace(options);
+        }
+        Ok(self)
+    }
+
+    /// Use `path` as the guest root file-system.
+    pub fn rootfs<P: Into<PathBuf>>(mut self, path: P) -> Self {
+        self.rootfs = path.into();
+        self
+    }
+
+    /// Make `host` accessible as `guest` in the box. Later bindings take
+    /// precedence over earlier ones.
+    pub fn bind<P1: Into<PathBuf>, P2: Into<PathBuf>>(mut self, host: P1, guest: P2) -> Self {
+        self.bindings.push(BindingSpec {
+            host: host.into(),
+            guest: guest.into(),
+            optional: false,
+            read_only: false,
+            recommended: false,
+        });
+        self
+    }
+
+    /// Like `bind()`, but the guest can't modify the files of the binding
+    /// (`EROFS`), and `statfs()` reports them as read-only.
+    pub fn bind_read_only<P1: Into<PathBuf>, P2: Into<PathBuf>>(
+        mut self,
+        host: P1,
+        guest: P2,
+    ) -> Self {
+        self.bindings.push(BindingSpec {
+            host: host.into(),
+            guest: guest.into(),
+            optional: false,
+            read_only: true,
+            recommended: false,
+        });
+        self
+    }
+
+    /// Like `bind()`, but the binding is skipped if `host` does not exist.
+    pub fn bind_optional<P1: Into<PathBuf>, P2: Into<PathBuf>>(
+        mut self,
+        host: P1,
+        guest: P2,
+    ) -> Self {
+        self.bindings.push(BindingSpec {
+            host: host.into(),
+            guest: guest.into(),
+            optional: true,
+            read_only: false,
+            recommended: false,
+        });
+        self
+    }
+
+    /// Bind the host files most programs need (`/dev`, `/proc`, `/tmp`,
+    /// `/etc/resolv.conf`...) and, if `home` is set, the home directory of
+    /// the user, like PRoot's `-R`. The ones which are missing on the host
+    /// are skipped, and the ones which are missing in the rootfs are created
+    /// there first (a warning is shown if they still can't be bound).
+    ///
+    /// They replace the recommended bindings added before, and come before
+    /// the other bindings, which thus take precedence over them.
+    pub fn recommended_bindings(mut self, home: bool) -> Self {
+        self.bindings.retain(|binding| !binding.recommended);
+        let mut paths: Vec<PathBuf> = RECOMMENDED_BINDINGS.iter().map(PathBuf::from).collect();
+        if home
+            && let Some(home) = std::env::var_os("HOME").map(PathBuf::from)
+            && home.is_absolute()
+        {
+            paths.push(home);
+        }
+        for (index, path) in paths.into_iter().enumerate() {
+            self.bindings.insert(
+                index,
+                BindingSpec {
+                    host: path.clone(),
+                    guest: path,
+                    optional: true,
+                    read_only: false,
+                    recommended: true,
+                },
+            );
+        }
+        self
+    }
+
+    /// Set the initial working directory, on the guest side.
+    pub fn cwd<P: Into<PathBuf>>(mut self, path: P) -> Self {
+        self.cwd = path.into();
+        self
+    }
+
+    /// Pretend to be root (uid 0, gid 0).
+    pub fn root_id(mut self, root_id: bool) -> Self {
+        self.config.root_id = root_id;
+        self
+    }
+
+    /// Convert hard links to symbolic links.
+    pub fn link2symlink(mut self, link2symlink: bool) -> Self {
+        self.config.link2symlink = link2symlink;
+        self
+    }
+
+    /// Host name reported to the guest by `uname()`.
+    pub fn hostname<S: Into<String>>(mut self, hostname: S) -> Self {
+        self.config.hostname = Some(hostname.into());
+        self
+    }
+
+    /// Kernel release reported to the guest by `uname()`.
+    pub fn kernel_release<S: Into<String>>(mut self, release: S) -> Self {
+        self.config.kernel_release = Some(release.into());
+        self
+    }
+
+    /// Make the guest `/proc/cpuinfo` read the content of the host file
+    /// `path`.
+    pub fn cpuinfo<P: Into<PathBuf>>(mut self, path: P) -> Self {
+        self.config.cpuinfo = Some(path.into());
+        self
+    }
+
+    /// Let the guest open the host device `device` through the device files
+    /// it creates with `-0` (only `null`, `zero`, `full`, `random`, `urandom`
+    /// and `tty` by default).
+    pub fn allow_device(mut self, device: Device) -> Self {
+        self.config.allowed_devices.push(device);
+        self
+    }
+
+    /// Set an environment variable of the initial command, even if it was
+    /// removed earlier (e.g. by the profile).
+    pub fn env<K: Into<String>, V: Into<String>>(mut self, key: K, value: V) -> Self {
+        self.config.env.set_var(key.into(), value.into());
+        self
+    }
+
+    /// Remove an environment variable of the initial command, even if it was
+    /// set earlier (e.g. by the profile).
+    pub fn unset_env<K: Into<String>>(mut self, key: K) -> Self {
+        self.config.env.unset_var(key.into());
+        self
+    }
+
+    /// Start from an empty environment instead of the default guest one.
+    pub fn clear_env(mut self, clear: bool) -> Self {
+        self.config.env.clear = clear;
+        self
+    }
+
+    /// The command to run, searched in the guest `PATH`.
+    pub fn command<I, S>(mut self, command: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.command = command.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Instead of running a command, attach the already running process `pid`
+    /// (and all of its threads) to the box: from then on, its syscalls and the
+    /// ones of its children are translated, and it is the init process.
+    ///
+    /// As any tracee, the process is killed if the tracer terminates.
+    pub fn attach(mut self, pid: i32) -> Self {
+        self.attach = Some(Pid::from_raw(pid));
+        self
+    }
+
+    /// Print the syscalls made in the box, strace-style.
+    pub fn trace(mut self, options: TraceOptions) -> Self {
+        self.config.trace = Some(options);
+        self
+    }
+
+    /// Write a JSON record of each file access made in the box to `path`.
+    pub fn audit<P: Into<PathBuf>>(mut self, path: P) -> Self {
+        self.config.audit = Some(path.into());
+        self
+    }
+
+    /// Configure the signal actions of the whole process for the tracer, as
+    /// the `pronyx` binary does. Disabled by default, since the signal actions
+    /// of an embedding process are not ours to change.
+    ///
+    /// `SIGINT`, `SIGTERM`, `SIGHUP` and `SIGQUIT` are then forwarded to the
+    /// init process, and every tracee is killed once the grace period after the
+    /// first of them is over.
+    pub fn install_sigactions(mut self, install: bool) -> Self {
+        self.install_sigactions = install;
+        self
+    }
+
+    /// When the box terminates: once the init process does (killing the
+    /// processes it leaves behind, see `Sandbox::orphans()`), or once every
+    /// process does (the default).
+    pub fn exit_policy(mut self, policy: ExitPolicy) -> Self {
+        self.config.exit_policy = policy;
+        self
+    }
+
+    /// Make the init process a child subreaper (see `PR_SET_CHILD_SUBREAPER`
+    /// in prctl(2)), as the init of a container: the orphans of the guest are
+    /// reparented to it, and it can wait for them. This does not apply to a
+    /// process attached with `attach()`.
+    ///
+    /// This is not emulated: the real `prctl()` is done on the host by the
+    /// init process before it executes the command, so the guest can read
+    /// and change the attribute, and the processes it leaves behind are only
+    /// reparented to the init process while it is alive.
+    pub fn subreaper(mut self, subreaper: bool) -> Self {
+        self.config.subreaper = subreaper;
+        self
+    }
+
+    /// Seconds given to the tracees to terminate after a forwarded signal (see
+    /// `install_sigactions()`), before they are killed; 0 to wait forever.
+    pub fn grace_period(mut self, seconds: u32) -> Self {
+        self.grace_period = seconds;
+        self
+    }
+
+    /// Cache the translations of the guest paths (enabled by default). The
+    /// cache can be disabled to rule it out when debugging.
+    pub fn translation_cache(mut self, enabled: bool) -> Self {
+        self.translation_cache = enabled;
+        self
+    }
+
+    /// Check the description and build the guest file-system.
+    fn prepare(self) -> Result<Prepared, SandboxError> {
+        let mut fs = FileSystem::new();
+        fs.set_translation_cache(self.translation_cache);
+        fs.set_root(&self.rootfs).map_err(|error| {
+            let msg = format!("cannot use {:?} as rootfs", self.rootfs);
+            SandboxError::config(error.with_msg(msg))
+        })?;
+        for binding in &self.bindings {
+            if binding.optional && !binding.host.exists() {
+                debug!(
+                    "skipping optional binding {:?}: host path is missing",
+                    binding.host
+                );
+                continue;
+            }
+            if binding.recommended {
+                create_mount_point(&fs, &binding.host, &binding.guest);
+            }
+            let result = match binding.read_only {
+                true => fs.add_read_only_binding(&binding.host, &binding.guest),
+                false => fs.add_binding(&binding.host, &binding.guest),
+            };
+            match result {
+                Ok(()) => {}
+                Err(error) if binding.recommended => {
+                    warn!("skipping recommended binding {:?}: {}", binding.host, error);
+                }
+                Err(error) => {
+                    let msg = format!("cannot bind {:?} to {:?}", binding.host, binding.guest);
+                    return Err(SandboxError::config(error.with_msg(msg)));
+                }
+            }
+        }
+        fs.set_cwd(&self.cwd).map_err(|error| {
+            let msg = format!("cannot change to {:?}", self.cwd);
+            SandboxError::config(error.with_msg(msg))
+        })?;
+        if self.command.is_empty() {
+            return Err(SandboxError::config(Error::errno_with_msg(
+                Errno::EINVAL,
+                "the command must not be empty",
+            )));
+        }
+        if let Some(path) = &self.config.cpuinfo
+            && !path.is_file()
+        {
+            return Err(SandboxError::config(Error::errno_with_msg(
+                Errno::ENOENT,
+                format!("{}: cpuinfo file not found", path.display()),
+            )));
+        }
+        fs.set_virtual_files(Some(VirtualFiles::with_builtins(&self.config)));
+        if self.config.root_id {
+            let xattrs = XattrStore::load(fs.get_root()).map_err(SandboxError::config)?;
+            fs.set_xattrs(Some(xattrs));
+            let mut allowed = DEFAULT_ALLOWED_DEVICES.to_vec();
+            allowed.extend_from_slice(&self.config.allowed_devices);
+            let devices = DeviceStore::load(fs.get_root(), allowed).map_err(SandboxError::config)?;
+            fs.set_devices(Some(devices));
+        }
+
+        Ok(Prepared {
+            fs,
+            command: self.command,
+            attach: self.attach,
+            config: self.config,
+            install_sigactions: self.install_sigactions,
+            grace_period: self.grace_period,
+        })
+    }
+
+    /// Set up the session `name` for this box, see `Session`. The command of
+    /// the box is not used, since the processes of a session are started by
+    /// its clients.
+    pub fn session(self, name: &str) -> Result<Session, SandboxError> {
+        let path = session::socket_path(name)?;
+        let prepared = self.prepare()?;
+        let mut proot = PRoot::new(prepared.config);
+        proot
+            .init()
+            .map_err(|error| SandboxError::new(ErrorKind::Launch, error))?;
+        Session::bind(proot, prepared.fs, &path)
+    }
+
+    /// Run the box in the calling thread, and wait until it terminates.
+    pub fn run(self) -> Result<ExitStatus, SandboxError> {
+        let prepared = self.prepare()?;
+        let mut proot = prepared.launch()?;
+        supervise(&mut proot).map(|(status, _)| status)
+    }
+
+    /// Start the box and return without waiting for it.
+    ///
+    /// The box is supervised by a dedicated thread: since only the tracer
+    /// thread may use `ptrace()` on the tracees, everything is done from there.
+    /// Unlike `run()`, the orphans killed on exit are then available from
+    /// `Sandbox::orphans()`.
+    pub fn spawn(self) -> Result<Sandbox, SandboxError> {
+        let prepared = self.prepare()?;
+        let (sender, receiver) = mpsc::channel();
+
+        let supervisor = thread::Builder::new()
+            .name("pronyx-supervisor".to_string())
+            .spawn(move || {
+                let mut proot = match prepared.launch() {
+                    Ok(proot) => proot,
+                    Err(error) => {
+                        let _ = sender.send(Err(error.clone()));
+                        return Err(error);
+                    }
+                };
+                let _ = sender.send(Ok((proot.init_pid.unwrap(), proot.kill_switch())));
+                supervise(&mut proot)
+            })
+            .map_err(|error| SandboxError::new(ErrorKind::Launch, error.into()))?;
+
+        match receiver.recv() {
+            Ok(Ok((pid, kill_requested))) => Ok(Sandbox {
+                pid,
+                kill_requested,
+                supervisor: Some(supervisor),
+                result: None,
+                orphans: vec![],
+            }),
+            Ok(Err(error)) => {
+                let _ = supervisor.join();
+                Err(error)
+            }
+            Err(_) => Err(supervisor_panicked()),
+        }
+    }
+}
+
+/// A box description which has been checked.
+struct Prepared {
+    fs: FileSystem,
+    command: Vec<String>,
+    attach: Option<Pid>,
+    config: Config,
+    install_sigactions: bool,
+    grace_period: u32,
+}
+
+impl Prepared {
+    /// Initialize the tracer and start (or attach) the first tracee.
+    fn launch(self) -> Result<PRoot, SandboxError> {
+        let mut proot = PRoot::new(self.config);
+        proot
+            .init()
+            .and_then(|_| match self.attach {
+                Some(pid) => proot.attach_process(self.fs, pid),
+                None => proot.launch_process(self.fs, self.command),
+            })
+            .map_err(|error| SandboxError::new(ErrorKind::Launch, error))?;
+
+        // This is done after the fork, so that the tracees keep the default
+        // signal actions.
+        if self.install_sigactions {
+            sigactions::prepare_sigactions(stop_program, show_info);
+            sigactions::forward_termination_signals(proot.init_pid.unwrap(), self.grace_period);
+        }
+        Ok(proot)
+    }
+}
+
+/// How the init process terminated, and the orphans killed on exit.
+type Outcome = (ExitStatus, Vec<Orphan>);
+
+/// Listen to and deal with tracees events, until all of them are terminated.
+fn supervise(proot: &mut PRoot) -> Result<Outcome, SandboxError> {
+    proot
+        .event_loop()
+        .map_err(|error| SandboxError::new(ErrorKind::Supervisor, error))?;
+    debug!("first tracee terminated: {:?}", proot.init_status);
+    let status = proot.init_status.ok_or_else(|| {
+        SandboxError::new(
+            ErrorKind::Supervisor,
+            Error::msg("the init process terminated without an exit status"),
+        )
+    })?;
+    Ok((status, proot.orphans().to_vec()))
+}
+
+fn supervisor_panicked() -> SandboxError {
+    SandboxError::new(
+        ErrorKind::Supervisor,
+        Error::msg("the supervisor thread panicked"),
+    )
+}
+
+/// Create the guest path of a binding if it is missing in the rootfs, as an
+/// empty directory or file like its host path, since a binding needs an
+/// existing guest path. A rootfs which can't be written is left as is.
+fn create_mount_point(fs: &FileSystem, host_path: &Path, guest_path: &Path) {
+    let Ok(path) = fs.substitute(guest_path, Side::Guest) else {
+        return;
+    };
+    // a dangling symlink is left to `add_binding()` to report
+    if path.symlink_metadata().is_ok() {
+        return;
+    }
+    let result = match host_path.is_dir() {
+        true => std::fs::create_dir_all(&path),
+        false => path
+            .parent()
+            .map_or(Ok(()), std::fs::create_dir_all)
+            .and_then(|_| std::fs::File::create(&path).map(|_| ())),
+    };
+    match result {
+        Ok(()) => debug!("created the mount point {:?} in the rootfs", path),
+        Err(error) => warn!("cannot create the mount point {:?}: {}", path, error),
+    }
+}
+
+/// Handle of a running box, returned by `SandboxBuilder::spawn()`.
+///
+/// Dropping the handle does not stop the box, which keeps being supervised in
+/// the background.
+#[derive(Debug)]
+pub struct Sandbox {
+    pid: Pid,
+    kill_requested: Arc<AtomicBool>,
+    supervisor: Option<JoinHandle<Result<Outcome, SandboxError>>>,
+    result: Option<Result<ExitStatus, SandboxError>>,
+    orphans: Vec<Orphan>,
+}
+
+impl Sandbox {
+    pub fn builder() -> SandboxBuilder {
+        SandboxBuilder::new()
+    }
+
+    /// Host pid of the init process (i.e. the first tracee).
+    pub fn pid(&self) -> i32 {
+        self.pid.as_raw()
+    }
+
+    /// Kill every process of the box. This does not wait for them, use
+    /// `wait()` for that.
+    pub fn kill(&self) -> Result<(), SandboxError> {
+        if self.result.is_some() {
+            return Ok(());
+        }
+        self.kill_requested.store(true, Ordering::SeqCst);
+        // wake the supervisor up, which then kills the remaining tracees
+        match signal::kill(self.pid, Signal::SIGKILL) {
+            Ok(()) | Err(Errno::ESRCH) => Ok(()),
+            Err(errno) => Err(SandboxError::new(
+                ErrorKind::Supervisor,
+                Error::errno_with_msg(errno, "cannot kill the init process"),
+            )),
+        }
+    }
+
+    /// Return the status of the box if all its processes are terminated,
+    /// without blocking.
+    pub fn try_wait(&mut self) -> Result<Option<ExitStatus>, SandboxError> {
+        match &self.supervisor {
+            Some(supervisor) if !supervisor.is_finished() => Ok(None),
+            _ => self.wait().map(Some),
+        }
+    }
+
+    /// Wait until all the processes of the box are terminated, and return how
+    /// the init process terminated.
+    pub fn wait(&mut self) -> Result<ExitStatus, SandboxError> {
+        if let Some(supervisor) = self.supervisor.take() {
+            let result = supervisor
+                .join()
+                .unwrap_or_else(|_| Err(supervisor_panicked()));
+            self.result = Some(result.map(|(status, orphans)| {
+                self.orphans = orphans;
+                status
+            }));
+        }
+        self.result.clone().unwrap()
+    }
+
+    /// The processes left behind by the init process, which were killed when
+    /// it terminated (see `ExitPolicy::Init`). Empty until `wait()` returns.
+    pub fn orphans(&self) -> &[Orphan] {
+        &self.orphans
+    }
+}
+
+/// Check a profile against the host, without running anything.
+pub fn check_profile<P: AsRef<Path>>(path: P) -> Result<(), SandboxError> {
+    Profile::load(path)
+        .and_then(|profile| profile.check())
+        .map(|_| ())
+        .map_err(SandboxError::config)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use nix::unistd::{self, ForkResult};
+
+    use crate::utils::tests::test_in_subprocess;
+
+    #[test]
+    fn test_sandbox_exit_status() {
+        let status = Sandbox::builder()
+            .command(["sh", "-c", "exit 3"])
+            .run()
+            .unwrap();
+        assert_eq!(status, ExitStatus::Exited(3));
+        assert_eq!(status.code(), 3);
+
+        let mut sandbox = Sandbox::builder().command(["true"]).spawn().unwrap();
+        assert!(sandbox.wait().unwrap().success());
+        // the status is kept once the box is terminated
+        assert!(sandbox.try_wait().unwrap().unwrap().success());
+    }
+
+    #[test]
+    fn test_sandbox_kill() {
+        let mut sandbox = Sandbox::builder()
+            .command(["sh", "-c", "sleep 60 & sleep 60"])
+            .spawn()
+            .unwrap();
+        assert_eq!(sandbox.try_wait().unwrap(), None);
+        sandbox.kill().unwrap();
+        let status = sandbox.wait().unwrap();
+        assert_eq!(status, ExitStatus::Signaled(Signal::SIGKILL as i32));
+        assert_eq!(status.code(), 137);
+    }
+
+    #[test]
+    fn test_sandbox_exit_policy() {
+        let start = std::time::Instant::now();
+        let status = Sandbox::builder()
+            .exit_policy(ExitPolicy::Init)
+            .command(["sh", "-c", "sleep 60 & exit 5"])
+            .run()
+            .unwrap();
+        assert_eq!(status, ExitStatus::Exited(5));
+        assert!(start.elapsed() < std::time::Duration::from_secs(30));
+
+        // the orphan has time to execute `sleep` before it is killed
+        let mut sandbox = Sandbox::builder()
+            .exit_policy(ExitPolicy::Init)
+            .command(["sh", "-c", "sleep 60 & sleep 0.2; exit 5"])
+            .spawn()
+            .unwrap();
+        assert!(sandbox.orphans().is_empty());
+        assert_eq!(sandbox.wait().unwrap(), ExitStatus::Exited(5));
+        assert_eq!(sandbox.orphans().len(), 1);
+        assert_eq!(sandbox.orphans()[0].command, vec!["sleep", "60"]);
+    }
+
+    #[test]
+    fn test_sandbox_subreaper() {
+        // the orphan is reparented to the init process, instead of a host one
+        let script = "p=$(sh -c 'sleep 1 >/dev/null & echo $!'); \
+                      grep -q \"^PPid:[[:space:]]*$$$\" /proc/$p/status";
+        let status = Sandbox::builder()
+            .subreaper(true)
+            .command(["sh", "-c", script])
+            .run()
+            .unwrap();
+        assert_eq!(status, ExitStatus::Exited(0));
+    }
+
+    #[test]
+    fn test_sandbox_grace_period() {
+        // the signal actions are set for the whole process
+        test_in_subprocess(|| {
+            let mut sandbox = Sandbox::builder()
+                .install_sigactions(true)
+                .grace_period(1)
+                .command(["sh", "-c", "trap '' TERM; sleep 30; exit 3"])
+                .spawn()
+                .unwrap();
+            thread::sleep(std::time::Duration::from_millis(500));
+            signal::kill(unistd::getpid(), Signal::SIGTERM).unwrap();
+            // SIGTERM is ignored by the guest, which is killed a second later
+            assert_eq!(
+                sandbox.wait().unwrap(),
+                ExitStatus::Signaled(Signal::SIGKILL as i32)
+            );
+        })
+    }
+
+    #[test]
+    fn test_sandbox_errors() {
+        let error = Sandbox::builder()
+            .rootfs("/non_existing_path")
+            .spawn()
+            .unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::Config);
+
+        let error = Sandbox::builder()
+            .bind("/tmp", "/non_existing_path")
+            .run()
+            .unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::Config);
+
+        let error = Sandbox::builder()
+            .bind_optional("/non_existing_path", "/tmp")
+            .command(["non_existing_command"])
+            .spawn()
+            .unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::Launch);
+        assert_eq!(error.errno(), Some(Errno::ENOENT as i32));
+
+        let error = Sandbox::builder().attach(i32::MAX).run().unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::Launch);
+    }
+
+    #[test]
+    fn test_sandbox_recommended_bindings() {
+        use crate::filesystem::binding::Side;
+        use crate::filesystem::Substitutor;
+
+        let rootfs = std::env::temp_dir()
+            .join(format!("pronyx-recommended-{}", std::process::id()));
+        std::fs::create_dir_all(rootfs.join("proc")).unwrap();
+        std::fs::create_dir_all(rootfs.join("etc")).unwrap();
+        std::fs::write(rootfs.join("etc/hosts"), "").unwrap();
+
+        // the bindings which are missing in the rootfs are created there
+        let prepared = Sandbox::builder()
+            .rootfs(&rootfs)
+            .recommended_bindings(true)
+            .prepare()
+            .unwrap();
+        let substitute = |path: &str| prepared.fs.substitute(path, Side::Guest).unwrap();
+        assert_eq!(substitute("/proc/self"), Path::new("/proc/self"));
+        assert_eq!(substitute("/etc/hosts"), Path::new("/etc/hosts"));
+        assert_eq!(substitute("/etc/group"), Path::new("/etc/group"));
+        assert!(rootfs.join("etc/group").is_file());
+        assert!(rootfs.join("sys").is_dir());
+
+        std::fs::remove_dir_all(&rootfs).unwrap();
+    }
+
+    #[test]
+    fn test_sandbox_profile_runtime_options() {
+        let path = std::env::temp_dir().join(format!("pronyx-runtime-{}.toml", std::process::id()));
+        std::fs::write(
+            &path,
+            r#"
+            recommended_bindings = "safe"
+            exit_policy = "init"
+            grace_period = 3
+            subreaper = true
+            path_cache = false
+            allow_device = ["c:10:200"]
+
+            [[bind]]
+            host = "/etc"
+            guest = "/tmp"
+
+            [trace]
+            filter = ["file"]
+            "#,
+        )
+        .unwrap();
+
+        let sandbox = Sandbox::builder().profile(&path).unwrap();
+        assert!(sandbox.config.root_id);
+        assert_eq!(sandbox.config.exit_policy, ExitPolicy::Init);
+        assert_eq!(sandbox.grace_period, 3);
+        assert!(sandbox.config.subreaper);
+        assert!(!sandbox.translation_cache);
+        assert_eq!(sandbox.config.allowed_devices, vec![Device::char(10, 200)]);
+        let trace = sandbox.config.trace.as_ref().unwrap();
+        assert_eq!(trace.syscalls, vec!["file".to_string()]);
+        // the recommended bindings come before the ones of the profile
+        assert!(sandbox.bindings[0].recommended);
+        assert_eq!(sandbox.bindings.last().unwrap().host, Path::new("/etc"));
+
+        // the flags take precedence, and -R replaces the bindings of "safe"
+        let sandbox = sandbox.recommended_bindings(true).grace_period(7);
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(sandbox.grace_period, 7);
+        let recommended = sandbox
+            .bindings
+            .iter()
+            .take_while(|binding| binding.recommended)
+            .count();
+        assert!(recommended >= RECOMMENDED_BINDINGS.len());
+        // the ones of "safe" are gone
+        assert_eq!(sandbox.bindings.len(), recommended + 1);
+        assert_eq!(sandbox.bindings.last().unwrap().host, Path::new("/etc"));
+    }
+
+    #[test]
+    fn test_sandbox_profile_precedence() {
+        let path = std::env::temp_dir().join(format!("pronyx-env-{}.toml", std::process::id()));
+        std::fs::write(
+            &path,
+            r#"
+            unset_env = ["BAZ", "QUX"]
+            link2symlink = true
+
+            [identity]
+            root = true
+
+            [env]
+            FOO = "profile"
+            BAR = "profile"
+            "#,
+        )
+        .unwrap();
+
+        // the settings made after the profile (e.g. the flags) take precedence
+        let sandbox = Sandbox::builder()
+            .profile(&path)
+            .unwrap()
+            .env("BAZ", "flag")
+            .unset_env("FOO")
+            .root_id(false);
+        std::fs::remove_file(&path).unwrap();
+        assert!(!sandbox.config.root_id);
+        assert!(sandbox.config.link2symlink);
+        let env = sandbox
+            .config
+            .env
+            .build(&FileSystem::with_root("/").unwrap(), 0);
+        assert!(!env.contains_key("FOO"));
+        assert_eq!(env.get("BAR").map(String::as_str), Some("profile"));
+        assert_eq!(env.get("BAZ").map(String::as_str), Some("flag"));
+        assert!(!env.contains_key("QUX"));
+    }
+
+    #[test]
+    fn test_sandbox_attach() {
+        // the process to attach is forked, which is only reliable in a
+        // single-threaded process
+        test_in_subprocess(|| {
+            let dir = std::env::temp_dir().join(format!("pronyx-attach-{}", std::process::id()));
+            std::fs::create_dir_all(&dir).unwrap();
+            std::fs::write(dir.join("marker"), "").unwrap();
+            std::env::set_current_dir(&dir).unwrap();
+
+            let pid = match unsafe { unistd::fork() }.unwrap() {
+                ForkResult::Parent { child } => child,
+                ForkResult::Child => {
+                    // the marker only shows up in "/mnt" once attached to the box
+                    let code = (0..10_000)
+                        .find_map(|_| {
+                            if Path::new("/mnt/marker").exists() {
+                                let cwd = std::env::current_dir().unwrap();
+                                Some(if cwd == Path::new("/mnt") { 7 } else { 5 })
+                            } else {
+                                thread::sleep(std::time::Duration::from_millis(1));
+                                None
+                            }
+                        })
+                        .unwrap_or(3);
+                    unsafe { libc::_exit(code) };
+                }
+            };
+            let status = Sandbox::builder()
+                .bind(&dir, "/mnt")
+                .attach(pid.as_raw())
+                .run();
+            std::fs::remove_dir_all(&dir).unwrap();
+            assert_eq!(status.unwrap(), ExitStatus::Exited(7));
+        })
+    }
+}
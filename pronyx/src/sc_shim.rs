@@ -5,6 +5,7 @@ pub mod sc {
         // map the ones that don't match libc's SYS_ prefix exactly
         // or are missing in certain libc versions
         pub use libc::SYS_execve as EXECVE;
+        pub use libc::SYS_execveat as EXECVEAT;
         pub use libc::SYS_ptrace as PTRACE;
         pub use libc::SYS_wait4 as WAIT4;
         pub use libc::SYS_brk as BRK;
@@ -48,10 +49,15 @@ pub mod sc {
         pub use libc::SYS_faccessat as FACCESSAT;
         pub use libc::SYS_mknodat as MKNODAT;
         pub use libc::SYS_inotify_add_watch as INOTIFY_ADD_WATCH;
+        pub use libc::SYS_fanotify_mark as FANOTIFY_MARK;
         pub use libc::SYS_lgetxattr as LGETXATTR;
         pub use libc::SYS_llistxattr as LLISTXATTR;
         pub use libc::SYS_lremovexattr as LREMOVEXATTR;
         pub use libc::SYS_lsetxattr as LSETXATTR;
+        pub use libc::SYS_fgetxattr as FGETXATTR;
+        pub use libc::SYS_flistxattr as FLISTXATTR;
+        pub use libc::SYS_fremovexattr as FREMOVEXATTR;
+        pub use libc::SYS_fsetxattr as FSETXATTR;
         pub use libc::SYS_lchown as LCHOWN;
         pub use libc::SYS_lstat as LSTAT;
         pub use libc::SYS_unlink as UNLINK;
@@ -68,6 +74,7 @@ pub mod sc {
         pub use libc::SYS_link as LINK;
         pub use libc::SYS_rename as RENAME;
         pub use libc::SYS_renameat as RENAMEAT;
+        pub use libc::SYS_renameat2 as RENAMEAT2;
         pub use libc::SYS_symlink as SYMLINK;
         pub use libc::SYS_symlinkat as SYMLINKAT;
         pub use libc::SYS_uname as UNAME;
@@ -75,9 +82,24 @@ pub mod sc {
         pub use libc::SYS_clock_nanosleep as CLOCK_NANOSLEEP;
         pub use libc::SYS_utime as UTIME;
         pub use libc::SYS_futimesat as FUTIMESAT;
+        pub use libc::SYS_statfs as STATFS;
+        pub use libc::SYS_fstatfs as FSTATFS;
+        #[cfg(any(target_arch = "x86", target_arch = "arm"))]
+        pub use libc::SYS_statfs64 as STATFS64;
+        #[cfg(any(target_arch = "x86", target_arch = "arm"))]
+        pub use libc::SYS_fstatfs64 as FSTATFS64;
 
         pub use libc::SYS_vfork as VFORK;
         pub use libc::SYS_clone as CLONE;
+        pub use libc::SYS_clone3 as CLONE3;
+
+        // file descriptors
+        pub use libc::SYS_dup as DUP;
+        pub use libc::SYS_dup2 as DUP2;
+        pub use libc::SYS_dup3 as DUP3;
+        pub use libc::SYS_fcntl as FCNTL;
+        pub use libc::SYS_close as CLOSE;
+        pub use libc::SYS_close_range as CLOSE_RANGE;
 
         // the 'statx' nightmare
         pub use libc::SYS_statx as STATX;
@@ -0,0 +1,218 @@
+use std::io;
+use std::os::fd::{AsFd, AsRawFd, BorrowedFd};
+use std::os::unix::net::UnixStream;
+use std::path::PathBuf;
+
+use crate::errors::{Errno, Error};
+use crate::process::proot::ExitStatus;
+use crate::sandbox::{ErrorKind, SandboxError};
+use crate::session::protocol::{Connection, Request, Response, PROTOCOL_VERSION};
+use crate::session::socket_path;
+
+/// Client of a session, see the module documentation. A client sends a
+/// single request, hence its methods consume it.
+#[derive(Debug)]
+pub struct SessionClient {
+    connection: Connection,
+    env: Vec<(String, String)>,
+    cwd: Option<PathBuf>,
+}
+
+/// A process started in a session with `SessionClient::exec()`.
+#[derive(Debug)]
+pub struct SessionProcess {
+    pid: i32,
+    connection: Connection,
+}
+
+/// A process of a session, as listed by `SessionClient::ps()`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ProcessInfo {
+    /// Host pid of the process.
+    pub pid: i32,
+    /// Path of its executable, on the guest side.
+    pub exe: Option<PathBuf>,
+    /// Its arguments.
+    pub command: Vec<String>,
+}
+
+impl SessionClient {
+    /// Connect to the session `name`.
+    pub fn connect(name: &str) -> Result<SessionClient, SandboxError> {
+        let path = socket_path(name)?;
+        let stream = UnixStream::connect(&path).map_err(|error| {
+            let error = Error::from(error);
+            let msg = match error.get_errno() {
+                Errno::ENOENT | Errno::ECONNREFUSED => format!("no session named {:?}", name),
+                _ => format!("cannot connect to {:?}", path),
+            };
+            session_error(error.with_msg(msg))
+        })?;
+
+        let mut client = SessionClient {
+            connection: Connection::new(stream),
+            env: vec![],
+            cwd: None,
+        };
+        let response = client.request(
+            &Request::Hello {
+                version: PROTOCOL_VERSION,
+            },
+            &[],
+        )?;
+        match response {
+            Response::Hello { version } if version == PROTOCOL_VERSION => Ok(client),
+            Response::Hello { version } => Err(session_error(Error::errno_with_msg(
+                Errno::EPROTONOSUPPORT,
+                format!("the session speaks the protocol version {}", version),
+            ))),
+            response => Err(unexpected(response)),
+        }
+    }
+
+    /// Set an environment variable of the command started by `exec()`, on
+    /// top of the environment of the session.
+    pub fn env<K: Into<String>, V: Into<String>>(mut self, key: K, value: V) -> Self {
+        self.env.push((key.into(), value.into()));
+        self
+    }
+
+    /// Working directory of the command started by `exec()`, on the guest
+    /// side. The one of the session is used by default.
+    pub fn cwd<P: Into<PathBuf>>(mut self, path: P) -> Self {
+        self.cwd = Some(path.into());
+        self
+    }
+
+    /// Start `command` in the session, with the standard streams of the
+    /// calling process.
+    pub fn exec<I, S>(self, command: I) -> Result<SessionProcess, SandboxError>
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        let (stdin, stdout, stderr) = (io::stdin(), io::stdout(), io::stderr());
+        self.exec_with_stdio(command, [stdin.as_fd(), stdout.as_fd(), stderr.as_fd()])
+    }
+
+    /// Like `exec()`, with `stdio` as the standard input, output and error of
+    /// the command.
+    pub fn exec_with_stdio<I, S>(
+        mut self,
+        command: I,
+        stdio: [BorrowedFd<'_>; 3],
+    ) -> Result<SessionProcess, SandboxError>
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        let request = Request::Exec {
+            command: command.into_iter().map(Into::into).collect(),
+            env: std::mem::take(&mut self.env),
+            cwd: self.cwd.take(),
+        };
+        let fds = stdio.map(|fd| fd.as_raw_fd());
+        match self.request(&request, &fds)? {
+            Response::Started { pid } => Ok(SessionProcess {
+                pid,
+                connection: self.connection,
+            }),
+            response => Err(unexpected(response)),
+        }
+    }
+
+    /// List the processes of the session.
+    pub fn ps(mut self) -> Result<Vec<ProcessInfo>, SandboxError> {
+        let mut processes = vec![];
+        let mut response = self.request(&Request::Ps, &[])?;
+        loop {
+            match response {
+                Response::Process { pid, exe, command } => {
+                    processes.push(ProcessInfo { pid, exe, command })
+                }
+                Response::End => return Ok(processes),
+                response => return Err(unexpected(response)),
+            }
+            response = self.receive()?;
+        }
+    }
+
+    /// Send `signal` to the processes `pids` of the session, or to all of them
+    /// if `pids` is empty.
+    pub fn kill(mut self, signal: i32, pids: &[i32]) -> Result<(), SandboxError> {
+        let request = Request::Kill {
+            signal,
+            pids: pids.to_vec(),
+        };
+        match self.request(&request, &[])? {
+            Response::Ok => Ok(()),
+            response => Err(unexpected(response)),
+        }
+    }
+
+    /// Kill every process of the session and terminate it. Returns once the
+    /// session is stopped.
+    pub fn stop(mut self) -> Result<(), SandboxError> {
+        match self.request(&Request::Stop, &[])? {
+            Response::Ok => Ok(()),
+            response => Err(unexpected(response)),
+        }
+    }
+
+    fn request(&mut self, request: &Request, fds: &[i32]) -> Result<Response, SandboxError> {
+        self.connection
+            .send_line(&request.encode(), fds)
+            .map_err(session_error)?;
+        self.receive()
+    }
+
+    fn receive(&mut self) -> Result<Response, SandboxError> {
+        receive(&mut self.connection)
+    }
+}
+
+impl SessionProcess {
+    /// Host pid of the process.
+    pub fn pid(&self) -> i32 {
+        self.pid
+    }
+
+    /// Wait until the process terminates.
+    pub fn wait(mut self) -> Result<ExitStatus, SandboxError> {
+        match receive(&mut self.connection)? {
+            Response::Terminated(status) => Ok(status),
+            response => Err(unexpected(response)),
+        }
+    }
+}
+
+/// Receive the next response, errors reported by the session included.
+fn receive(connection: &mut Connection) -> Result<Response, SandboxError> {
+    let line = connection
+        .recv_line()
+        .map_err(session_error)?
+        .ok_or_else(|| {
+            session_error(Error::errno_with_msg(
+                Errno::ECONNRESET,
+                "the session closed the connection",
+            ))
+        })?;
+    match Response::decode(&line).map_err(session_error)? {
+        Response::Error { errno, message } => Err(session_error(Error::errno_with_msg(
+            Errno::from_raw(errno),
+            message,
+        ))),
+        response => Ok(response),
+    }
+}
+
+fn unexpected(response: Response) -> SandboxError {
+    session_error(Error::errno_with_msg(
+        Errno::EPROTO,
+        format!("unexpected response from the session: {:?}", response),
+    ))
+}
+
+fn session_error(error: Error) -> SandboxError {
+    SandboxError::new(ErrorKind::Session, error)
+}
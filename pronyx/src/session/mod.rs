@@ -0,0 +1,256 @@
+//! Background sessions.
+//!
+//! A session is a box kept alive behind a UNIX control socket, without an
+//! init process: its bindings, identity and file-system state are set up
+//! once, and commands are then started in it by clients, e.g.:
+//!
+//! ```no_run
+//! use pronyx::{Sandbox, SessionClient};
+//!
+//! // in a first process
+//! Sandbox::builder()
+//!     .rootfs("./alpine")
+//!     .root_id(true)
+//!     .session("dev")?
+//!     .serve()?;
+//!
+//! // in another one
+//! let status = SessionClient::connect("dev")?
+//!     .exec(["/bin/sh", "-c", "echo hello"])?
+//!     .wait()?;
+//! # Ok::<(), pronyx::SandboxError>(())
+//! ```
+//!
+//! The socket of the session `<name>` is `<dir>/<name>.sock`, where `<dir>` is
+//! `$PRONYX_SESSION_DIR`, `$XDG_RUNTIME_DIR/pronyx`, or `/tmp/pronyx-<uid>`.
+//! The `protocol` module describes what is exchanged on it.
+
+mod client;
+mod protocol;
+mod server;
+
+use std::env;
+use std::path::{Path, PathBuf};
+
+use nix::unistd::{self, ForkResult};
+
+use crate::errors::{Errno, Error};
+use crate::filesystem::FileSystem;
+use crate::process::proot::PRoot;
+use crate::sandbox::{ErrorKind, SandboxError};
+
+pub use self::client::{ProcessInfo, SessionClient, SessionProcess};
+use self::server::Server;
+
+/// Path of the control socket of the session `name`.
+pub fn socket_path(name: &str) -> Result<PathBuf, SandboxError> {
+    let valid = !name.is_empty()
+        && !name.starts_with('.')
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || "-_.".contains(c));
+    if !valid {
+        return Err(SandboxError::new(
+            ErrorKind::Config,
+            Error::errno_with_msg(
+                Errno::EINVAL,
+                format!("invalid session name {:?} (expected [A-Za-z0-9._-]+)", name),
+            ),
+        ));
+    }
+
+    let dir = match (
+        env::var_os("PRONYX_SESSION_DIR"),
+        env::var_os("XDG_RUNTIME_DIR"),
+    ) {
+        (Some(dir), _) => PathBuf::from(dir),
+        (None, Some(runtime_dir)) => Path::new(&runtime_dir).join("pronyx"),
+        (None, None) => env::temp_dir().join(format!("pronyx-{}", unistd::getuid())),
+    };
+    Ok(dir.join(format!("{}.sock", name)))
+}
+
+/// A session ready to be served, returned by `SandboxBuilder::session()`.
+pub struct Session {
+    server: Server,
+}
+
+impl Session {
+    /// Create the control socket at `path`, for a box described by `proot`
+    /// (which must be initialized already) and `fs`.
+    pub(crate) fn bind(proot: PRoot, fs: FileSystem, path: &Path) -> Result<Self, SandboxError> {
+        let server = Server::bind(proot, fs, path)
+            .map_err(|error| SandboxError::new(ErrorKind::Session, error))?;
+        Ok(Session { server })
+    }
+
+    /// Path of the control socket.
+    pub fn socket_path(&self) -> &Path {
+        self.server.socket_path()
+    }
+
+    /// Serve the clients in the calling thread, until the session is stopped.
+    ///
+    /// `SIGCHLD` is blocked in this thread; other threads of the process should
+    /// block it too, otherwise they may receive it in place of the session.
+    pub fn serve(self) -> Result<(), SandboxError> {
+        self.server
+            .serve()
+            .map_err(|error| SandboxError::new(ErrorKind::Supervisor, error))
+    }
+
+    /// Serve the clients in a new background process, detached from the
+    /// terminal, and return as soon as it is started.
+    ///
+    /// This relies on `fork()`, and thus must only be called by a
+    /// single-threaded process, as the `pronyx` binary.
+    pub fn serve_in_background(self) -> Result<(), SandboxError> {
+        match unsafe { unistd::fork() } {
+            Ok(ForkResult::Parent { .. }) => {
+                self.server.abandon();
+                Ok(())
+            }
+            Ok(ForkResult::Child) => {
+                let _ = unistd::setsid();
+                detach_standard_streams();
+                let code = match self.serve() {
+                    Ok(()) => 0,
+                    Err(error) => {
+                        error!("session failed: {}", error);
+                        1
+                    }
+                };
+                std::process::exit(code);
+            }
+            Err(errno) => Err(SandboxError::new(
+                ErrorKind::Launch,
+                Error::errno_with_msg(errno, "cannot start the session in the background"),
+            )),
+        }
+    }
+}
+
+/// Redirect the standard streams to `/dev/null`, so that the session does not
+/// keep the terminal (or the pipes of its caller) open.
+fn detach_standard_streams() {
+    use std::os::fd::AsRawFd;
+
+    if let Ok(null) = std::fs::OpenOptions::new()
+        .read(true)
+        .write(true)
+        .open("/dev/null")
+    {
+        for fd in 0..3 {
+            let _ = unistd::dup2(null.as_raw_fd(), fd);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs::File;
+    use std::io::Read;
+    use std::os::fd::AsFd;
+    use std::os::unix::net::UnixStream;
+    use std::time::{Duration, Instant};
+
+    use nix::sys::signal::Signal;
+
+    use crate::process::proot::ExitStatus;
+    use crate::sandbox::Sandbox;
+    use crate::utils::tests::test_in_subprocess;
+
+    #[test]
+    fn test_session_socket_path() {
+        assert!(socket_path("dev-1.2_3")
+            .unwrap()
+            .ends_with("dev-1.2_3.sock"));
+        for name in ["", ".hidden", "a/b", "a b"] {
+            assert_eq!(socket_path(name).unwrap_err().kind(), ErrorKind::Config);
+        }
+    }
+
+    #[test]
+    fn test_session_exec_ps_kill_stop() {
+        // the session blocks SIGCHLD, which is only reliable in a
+        // single-threaded process
+        test_in_subprocess(|| {
+            let name = format!("test-{}", std::process::id());
+            Sandbox::builder()
+                .session(&name)
+                .unwrap()
+                .serve_in_background()
+                .unwrap();
+
+            let null = File::open("/dev/null").unwrap();
+            let (reader, writer) = unistd::pipe().unwrap();
+            let stdio = [null.as_fd(), writer.as_fd(), null.as_fd()];
+            let process = SessionClient::connect(&name)
+                .unwrap()
+                .env("GREETING", "hello")
+                .cwd("/tmp")
+                .exec_with_stdio(["sh", "-c", "echo $GREETING $PWD; exit 3"], stdio)
+                .unwrap();
+            assert_eq!(process.wait().unwrap(), ExitStatus::Exited(3));
+            drop(writer);
+            let mut output = String::new();
+            File::from(reader).read_to_string(&mut output).unwrap();
+            assert_eq!(output, "hello /tmp\n");
+
+            let stdio = [null.as_fd(), null.as_fd(), null.as_fd()];
+            let sleeper = SessionClient::connect(&name)
+                .unwrap()
+                .exec_with_stdio(["sleep", "60"], stdio)
+                .unwrap();
+            // the process shows up as `sleep` once its `execve()` is done
+            let listed = (0..100).any(|_| {
+                std::thread::sleep(std::time::Duration::from_millis(10));
+                let processes = SessionClient::connect(&name).unwrap().ps().unwrap();
+                processes.iter().any(|process| {
+                    process.pid == sleeper.pid() && process.command == ["sleep", "60"]
+                })
+            });
+            assert!(listed);
+
+            let error = SessionClient::connect(&name)
+                .unwrap()
+                .kill(Signal::SIGKILL as i32, &[1])
+                .unwrap_err();
+            assert_eq!(error.errno(), Some(Errno::ESRCH as i32));
+            SessionClient::connect(&name)
+                .unwrap()
+                .kill(Signal::SIGKILL as i32, &[sleeper.pid()])
+                .unwrap();
+            assert_eq!(
+                sleeper.wait().unwrap(),
+                ExitStatus::Signaled(Signal::SIGKILL as i32)
+            );
+
+            SessionClient::connect(&name).unwrap().stop().unwrap();
+            let error = SessionClient::connect(&name).unwrap_err();
+            assert_eq!(error.kind(), ErrorKind::Session);
+            assert_eq!(error.errno(), Some(Errno::ENOENT as i32));
+        })
+    }
+
+    #[test]
+    fn test_session_silent_client() {
+        test_in_subprocess(|| {
+            let name = format!("test-silent-{}", std::process::id());
+            Sandbox::builder()
+                .session(&name)
+                .unwrap()
+                .serve_in_background()
+                .unwrap();
+
+            // a client that never says hello doesn't hold up the others
+            let _silent = UnixStream::connect(socket_path(&name).unwrap()).unwrap();
+            let start = Instant::now();
+            SessionClient::connect(&name).unwrap().ps().unwrap();
+            assert!(start.elapsed() < Duration::from_secs(2));
+
+            SessionClient::connect(&name).unwrap().stop().unwrap();
+        })
+    }
+}
@@ -0,0 +1,502 @@
+//! Wire protocol of the session control socket.
+//!
+//! A message is a single line of fields separated by tabulations, the first
+//! one being the type of the message. Tabulations, line feeds and backslashes
+//! within a field are escaped as `\t`, `\n` and `\\`.
+//!
+//! A connection starts with a handshake, where the client sends its protocol
+//! version (`PRONYX 1`) and the server answers with its own one, or with an
+//! error if it does not support it. The client then sends a single request:
+//!
+//! ```text
+//! EXEC <cwd> <count> <NAME=value>... <arg>...   -> STARTED <pid>, then EXITED <code> | SIGNALED <signal>
+//! PS                                            -> PROCESS <pid> <exe> <arg>... (for each process), END
+//! KILL <signal> <pid>...                        -> OK
+//! STOP                                          -> OK (once every process is terminated)
+//! ```
+//!
+//! Any request may also be answered by `ERROR <errno> <message>`. The standard
+//! input, output and error of the command are passed along with `EXEC`
+//! (`SCM_RIGHTS`), in this order. An empty `<cwd>` keeps the one of the
+//! session, and a `KILL` without pids signals every process of the session.
+
+use std::io::{IoSlice, IoSliceMut};
+use std::os::fd::{AsRawFd, FromRawFd, OwnedFd, RawFd};
+use std::os::unix::net::UnixStream;
+use std::path::PathBuf;
+
+use nix::sys::socket::{self, ControlMessage, ControlMessageOwned, MsgFlags};
+
+use crate::errors::*;
+use crate::process::proot::ExitStatus;
+
+/// Version of the protocol, bumped on every incompatible change.
+pub const PROTOCOL_VERSION: u32 = 1;
+
+/// Messages sent by a client.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Request {
+    Hello {
+        version: u32,
+    },
+    /// Run a new command in the session.
+    Exec {
+        command: Vec<String>,
+        env: Vec<(String, String)>,
+        cwd: Option<PathBuf>,
+    },
+    /// List the processes of the session.
+    Ps,
+    /// Send a signal to processes of the session (all of them if `pids` is
+    /// empty).
+    Kill {
+        signal: i32,
+        pids: Vec<i32>,
+    },
+    /// Kill every process of the session, and terminate it.
+    Stop,
+}
+
+/// Messages sent by the server.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Response {
+    Hello {
+        version: u32,
+    },
+    Ok,
+    Error {
+        errno: i32,
+        message: String,
+    },
+    /// The command of an `Exec` request was started with this pid.
+    Started {
+        pid: i32,
+    },
+    /// The command of an `Exec` request is terminated.
+    Terminated(ExitStatus),
+    /// A process of the session, listed by `Ps`.
+    Process {
+        pid: i32,
+        exe: Option<PathBuf>,
+        command: Vec<String>,
+    },
+    /// End of the list of processes.
+    End,
+}
+
+impl Request {
+    pub fn encode(&self) -> String {
+        let fields = match self {
+            Request::Hello { version } => vec!["PRONYX".to_string(), version.to_string()],
+            Request::Exec { command, env, cwd } => {
+                let mut fields = vec![
+                    "EXEC".to_string(),
+                    cwd.as_ref()
+                        .map(|cwd| cwd.to_string_lossy().into_owned())
+                        .unwrap_or_default(),
+                    env.len().to_string(),
+                ];
+                fields.extend(env.iter().map(|(key, value)| format!("{}={}", key, value)));
+                fields.extend(command.iter().cloned());
+                fields
+            }
+            Request::Ps => vec!["PS".to_string()],
+            Request::Kill { signal, pids } => {
+                let mut fields = vec!["KILL".to_string(), signal.to_string()];
+                fields.extend(pids.iter().map(i32::to_string));
+                fields
+            }
+            Request::Stop => vec!["STOP".to_string()],
+        };
+        join_fields(&fields)
+    }
+
+    pub fn decode(line: &str) -> Result<Request> {
+        let fields = split_fields(line)?;
+        let request = match (fields[0].as_str(), &fields[1..]) {
+            ("PRONYX", [version]) => Request::Hello {
+                version: parse_number(version)?,
+            },
+            ("EXEC", [cwd, count, rest @ ..]) => {
+                let count: usize = parse_number(count)?;
+                if rest.len() <= count {
+                    return Err(protocol_error(line));
+                }
+                let env = rest[..count]
+                    .iter()
+                    .map(|variable| {
+                        variable
+                            .split_once('=')
+                            .map(|(key, value)| (key.to_string(), value.to_string()))
+                            .ok_or_else(|| protocol_error(line))
+                    })
+                    .collect::<Result<_>>()?;
+                Request::Exec {
+                    command: rest[count..].to_vec(),
+                    env,
+                    cwd: match cwd.as_str() {
+                        "" => None,
+                        cwd => Some(PathBuf::from(cwd)),
+                    },
+                }
+            }
+            ("PS", []) => Request::Ps,
+            ("KILL", [signal, pids @ ..]) => Request::Kill {
+                signal: parse_number(signal)?,
+                pids: pids
+                    .iter()
+                    .map(|pid| parse_number(pid))
+                    .collect::<Result<_>>()?,
+            },
+            ("STOP", []) => Request::Stop,
+            _ => return Err(protocol_error(line)),
+        };
+        Ok(request)
+    }
+}
+
+impl Response {
+    pub fn encode(&self) -> String {
+        let fields = match self {
+            Response::Hello { version } => vec!["PRONYX".to_string(), version.to_string()],
+            Response::Ok => vec!["OK".to_string()],
+            Response::Error { errno, message } => {
+                vec!["ERROR".to_string(), errno.to_string(), message.clone()]
+            }
+            Response::Started { pid } => vec!["STARTED".to_string(), pid.to_string()],
+            Response::Terminated(ExitStatus::Exited(code)) => {
+                vec!["EXITED".to_string(), code.to_string()]
+            }
+            Response::Terminated(ExitStatus::Signaled(signal)) => {
+                vec!["SIGNALED".to_string(), signal.to_string()]
+            }
+            Response::Process { pid, exe, command } => {
+                let mut fields = vec![
+                    "PROCESS".to_string(),
+                    pid.to_string(),
+                    exe.as_ref()
+                        .map(|exe| exe.to_string_lossy().into_owned())
+                        .unwrap_or_default(),
+                ];
+                fields.extend(command.iter().cloned());
+                fields
+            }
+            Response::End => vec!["END".to_string()],
+        };
+        join_fields(&fields)
+    }
+
+    pub fn decode(line: &str) -> Result<Response> {
+        let fields = split_fields(line)?;
+        let response = match (fields[0].as_str(), &fields[1..]) {
+            ("PRONYX", [version]) => Response::Hello {
+                version: parse_number(version)?,
+            },
+            ("OK", []) => Response::Ok,
+            ("ERROR", [errno, message]) => Response::Error {
+                errno: parse_number(errno)?,
+                message: message.clone(),
+            },
+            ("STARTED", [pid]) => Response::Started {
+                pid: parse_number(pid)?,
+            },
+            ("EXITED", [code]) => Response::Terminated(ExitStatus::Exited(parse_number(code)?)),
+            ("SIGNALED", [signal]) => {
+                Response::Terminated(ExitStatus::Signaled(parse_number(signal)?))
+            }
+            ("PROCESS", [pid, exe, command @ ..]) => Response::Process {
+                pid: parse_number(pid)?,
+                exe: match exe.as_str() {
+                    "" => None,
+                    exe => Some(PathBuf::from(exe)),
+                },
+                command: command.to_vec(),
+            },
+            ("END", []) => Response::End,
+            _ => return Err(protocol_error(line)),
+        };
+        Ok(response)
+    }
+
+    /// Convert an error into the response reporting it.
+    pub fn from_error(error: &Error) -> Response {
+        Response::Error {
+            errno: error.get_errno() as i32,
+            message: match &error.msg {
+                Some(msg) => msg.to_string(),
+                None => error.to_string(),
+            },
+        }
+    }
+}
+
+fn join_fields(fields: &[String]) -> String {
+    let mut line = String::new();
+    for (index, field) in fields.iter().enumerate() {
+        if index > 0 {
+            line.push('\t');
+        }
+        for c in field.chars() {
+            match c {
+                '\\' => line.push_str("\\\\"),
+                '\t' => line.push_str("\\t"),
+                '\n' => line.push_str("\\n"),
+                c => line.push(c),
+            }
+        }
+    }
+    line
+}
+
+fn split_fields(line: &str) -> Result<Vec<String>> {
+    let mut fields = vec![String::new()];
+    let mut chars = line.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\t' => fields.push(String::new()),
+            '\\' => {
+                let unescaped = match chars.next() {
+                    Some('\\') => '\\',
+                    Some('t') => '\t',
+                    Some('n') => '\n',
+                    _ => return Err(protocol_error(line)),
+                };
+                fields.last_mut().unwrap().push(unescaped);
+            }
+            c => fields.last_mut().unwrap().push(c),
+        }
+    }
+    Ok(fields)
+}
+
+fn parse_number<T: std::str::FromStr>(field: &str) -> Result<T> {
+    field
+        .parse()
+        .map_err(|_| Error::errno_with_msg(EPROTO, format!("invalid number: {:?}", field)))
+}
+
+fn protocol_error(line: &str) -> Error {
+    Error::errno_with_msg(EPROTO, format!("invalid message: {:?}", line))
+}
+
+/// One end of a connection to the control socket, which exchanges messages
+/// (and file descriptors) line by line.
+#[derive(Debug)]
+pub struct Connection {
+    stream: UnixStream,
+    buffer: Vec<u8>,
+    fds: Vec<OwnedFd>,
+}
+
+impl Connection {
+    pub fn new(stream: UnixStream) -> Connection {
+        Connection {
+            stream,
+            buffer: vec![],
+            fds: vec![],
+        }
+    }
+
+    /// Send a message, along with the file descriptors `fds`.
+    pub fn send_line(&mut self, line: &str, fds: &[RawFd]) -> Result<()> {
+        let data = format!("{}\n", line);
+        let mut sent = 0;
+        while sent < data.len() {
+            let control = [ControlMessage::ScmRights(fds)];
+            // the file descriptors are sent along with the first byte only
+            let cmsgs: &[ControlMessage] = if sent == 0 && !fds.is_empty() {
+                &control
+            } else {
+                &[]
+            };
+            sent += socket::sendmsg::<()>(
+                self.stream.as_raw_fd(),
+                &[IoSlice::new(&data.as_bytes()[sent..])],
+                cmsgs,
+                MsgFlags::MSG_NOSIGNAL,
+                None,
+            )
+            .context("cannot write to the control socket")?;
+        }
+        Ok(())
+    }
+
+    /// Receive the next message, or `None` if the peer closed the connection.
+    /// File descriptors sent along with it are kept, see `take_fds()`.
+    pub fn recv_line(&mut self) -> Result<Option<String>> {
+        loop {
+            if let Some(line) = self.buffered_line()? {
+                return Ok(Some(line));
+            }
+            if !self.receive()? {
+                return Ok(None);
+            }
+        }
+    }
+
+    /// The next message, if it was received completely by `receive()`.
+    pub fn buffered_line(&mut self) -> Result<Option<String>> {
+        let end = match self.buffer.iter().position(|&byte| byte == b'\n') {
+            Some(end) => end,
+            None => return Ok(None),
+        };
+        let line: Vec<u8> = self.buffer.drain(..=end).collect();
+        let line = String::from_utf8(line[..end].to_vec())
+            .map_err(|_| Error::errno_with_msg(EPROTO, "message is not valid UTF-8"))?;
+        Ok(Some(line))
+    }
+
+    /// Receive what the peer sent so far, at least one byte, and keep the
+    /// file descriptors sent along with it. Returns `false` if the peer closed
+    /// the connection. Fails with `EAGAIN` if the stream is non-blocking and
+    /// nothing was sent.
+    pub fn receive(&mut self) -> Result<bool> {
+        let mut data = [0u8; 4096];
+        let mut control = nix::cmsg_space!([RawFd; 3]);
+        let (received, fds) = {
+            let mut iov = [IoSliceMut::new(&mut data)];
+            let message = socket::recvmsg::<()>(
+                self.stream.as_raw_fd(),
+                &mut iov,
+                Some(&mut control),
+                MsgFlags::MSG_CMSG_CLOEXEC,
+            )
+            .context("cannot read from the control socket")?;
+            let mut fds = vec![];
+            for cmsg in message.cmsgs()? {
+                if let ControlMessageOwned::ScmRights(received) = cmsg {
+                    fds.extend(received);
+                }
+            }
+            (message.bytes, fds)
+        };
+        self.fds.extend(
+            fds.into_iter()
+                .map(|fd| unsafe { OwnedFd::from_raw_fd(fd) }),
+        );
+        self.buffer.extend_from_slice(&data[..received]);
+        Ok(received > 0)
+    }
+
+    /// File descriptors received so far.
+    pub fn take_fds(&mut self) -> Vec<OwnedFd> {
+        std::mem::take(&mut self.fds)
+    }
+
+    pub fn send(&mut self, response: &Response) -> Result<()> {
+        self.send_line(&response.encode(), &[])
+    }
+
+    pub fn stream(&self) -> &UnixStream {
+        &self.stream
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_protocol_request_round_trip() {
+        let requests = vec![
+            Request::Hello {
+                version: PROTOCOL_VERSION,
+            },
+            Request::Exec {
+                command: vec!["sh".into(), "-c".into(), "echo a\tb\\n\n".into()],
+                env: vec![("FOO".into(), "a=b".into())],
+                cwd: Some(PathBuf::from("/tmp")),
+            },
+            Request::Exec {
+                command: vec!["true".into()],
+                env: vec![],
+                cwd: None,
+            },
+            Request::Ps,
+            Request::Kill {
+                signal: 15,
+                pids: vec![42, 43],
+            },
+            Request::Kill {
+                signal: 9,
+                pids: vec![],
+            },
+            Request::Stop,
+        ];
+        for request in requests {
+            let line = request.encode();
+            assert!(!line.contains('\n'));
+            assert_eq!(Request::decode(&line).unwrap(), request);
+        }
+        assert_eq!(
+            Request::Exec {
+                command: vec!["ls".into(), "-l".into()],
+                env: vec![("A".into(), "1".into())],
+                cwd: None,
+            }
+            .encode(),
+            "EXEC\t\t1\tA=1\tls\t-l"
+        );
+    }
+
+    #[test]
+    fn test_protocol_response_round_trip() {
+        let responses = vec![
+            Response::Hello { version: 1 },
+            Response::Ok,
+            Response::Error {
+                errno: 3,
+                message: "no such process".into(),
+            },
+            Response::Started { pid: 42 },
+            Response::Terminated(ExitStatus::Exited(3)),
+            Response::Terminated(ExitStatus::Signaled(9)),
+            Response::Process {
+                pid: 42,
+                exe: Some(PathBuf::from("/bin/sleep")),
+                command: vec!["sleep".into(), "60".into()],
+            },
+            Response::Process {
+                pid: 43,
+                exe: None,
+                command: vec![],
+            },
+            Response::End,
+        ];
+        for response in responses {
+            assert_eq!(Response::decode(&response.encode()).unwrap(), response);
+        }
+    }
+
+    #[test]
+    fn test_protocol_invalid_messages() {
+        for line in [
+            "",
+            "EXEC\t\t2\tA=1",
+            "EXEC\t\t1\tA\tls",
+            "KILL\tx",
+            "PS\tx",
+            "\\x",
+        ] {
+            assert_eq!(Request::decode(line).unwrap_err().get_errno(), EPROTO);
+        }
+        assert_eq!(Response::decode("EXITED").unwrap_err().get_errno(), EPROTO);
+    }
+
+    #[test]
+    fn test_protocol_connection() {
+        let (left, right) = UnixStream::pair().unwrap();
+        let (mut left, mut right) = (Connection::new(left), Connection::new(right));
+
+        let file = std::fs::File::open("/dev/null").unwrap();
+        left.send_line(&Request::Ps.encode(), &[file.as_raw_fd()])
+            .unwrap();
+        left.send(&Response::End).unwrap();
+        assert_eq!(right.recv_line().unwrap().as_deref(), Some("PS"));
+        assert_eq!(right.take_fds().len(), 1);
+        assert_eq!(right.recv_line().unwrap().as_deref(), Some("END"));
+
+        drop(left);
+        assert_eq!(right.recv_line().unwrap(), None);
+    }
+}
@@ -0,0 +1,348 @@
+use std::collections::HashMap;
+use std::fs;
+use std::io::ErrorKind as IOErrorKind;
+use std::os::fd::{AsFd, AsRawFd, OwnedFd};
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::{Path, PathBuf};
+
+use nix::poll::{self, PollFd, PollFlags, PollTimeout};
+use nix::sys::signal::{self, SigSet, Signal};
+use nix::sys::signalfd::{SfdFlags, SignalFd};
+use nix::unistd::Pid;
+
+use crate::errors::*;
+use crate::filesystem::FileSystem;
+use crate::process::environment::EnvironmentSpec;
+use crate::process::proot::{read_cmdline, PRoot};
+use crate::session::protocol::{Connection, Request, Response, PROTOCOL_VERSION};
+
+/// A client which did not send its request yet.
+struct PendingClient {
+    connection: Connection,
+    /// Whether the handshake is done.
+    greeted: bool,
+}
+
+/// A session: a box without an init process, whose processes are started by
+/// the clients of its control socket.
+pub struct Server {
+    proot: PRoot,
+    /// File-system given to each new process (bindings, cwd...).
+    fs: FileSystem,
+    env: EnvironmentSpec,
+    listener: UnixListener,
+    socket_path: PathBuf,
+    /// Clients whose request is still being received.
+    pending: Vec<PendingClient>,
+    /// Clients waiting for the termination of the process they started.
+    waiting: HashMap<Pid, Connection>,
+    /// Client which asked to stop the session, once it is stopping.
+    stopping: Option<Connection>,
+}
+
+impl Server {
+    /// Create the control socket at `socket_path`. `proot` must be
+    /// initialized already.
+    pub fn bind(proot: PRoot, fs: FileSystem, socket_path: &Path) -> Result<Server> {
+        if let Some(parent) = socket_path.parent() {
+            create_private_dir(parent)?;
+        }
+        if socket_path.exists() {
+            if UnixStream::connect(socket_path).is_ok() {
+                return Err(Error::errno_with_msg(
+                    EADDRINUSE,
+                    format!("a session is already listening on {:?}", socket_path),
+                ));
+            }
+            // left behind by a session which was not stopped properly
+            debug!("removing the stale socket {:?}", socket_path);
+            fs::remove_file(socket_path)
+                .with_context(|| format!("cannot remove the stale socket {:?}", socket_path))?;
+        }
+        let listener = UnixListener::bind(socket_path)
+            .with_context(|| format!("cannot listen on {:?}", socket_path))?;
+        listener.set_nonblocking(true)?;
+
+        Ok(Server {
+            env: proot.config.env.clone(),
+            proot,
+            fs,
+            listener,
+            socket_path: socket_path.to_path_buf(),
+            pending: vec![],
+            waiting: HashMap::new(),
+            stopping: None,
+        })
+    }
+
+    pub fn socket_path(&self) -> &Path {
+        &self.socket_path
+    }
+
+    /// Give the session up in a process which does not serve it (the parent
+    /// of `Session::serve_in_background()`). The socket and the loader belong
+    /// to the serving process, which removes them once the session is stopped,
+    /// but the listening socket is closed here: otherwise, clients could still
+    /// connect to it (and wait forever) once the session is stopped.
+    pub fn abandon(self) {
+        let _ = nix::unistd::close(self.listener.as_raw_fd());
+        std::mem::forget(self);
+    }
+
+    /// Serve the clients, until one of them stops the session.
+    ///
+    /// `SIGCHLD` is blocked in the calling thread, and is received through a
+    /// `signalfd` so that tracee events and clients are waited for at once.
+    /// The clients are read from without blocking, so that a slow one does
+    /// not hold the tracees up: they are only served once their request is
+    /// complete.
+    pub fn serve(mut self) -> Result<()> {
+        let mut mask = SigSet::empty();
+        mask.add(Signal::SIGCHLD);
+        mask.thread_block()
+            .context("cannot block SIGCHLD in the tracer")?;
+        let signals = SignalFd::with_flags(&mask, SfdFlags::SFD_NONBLOCK | SfdFlags::SFD_CLOEXEC)
+            .context("cannot create a signalfd")?;
+
+        info!("session listening on {:?}", self.socket_path);
+        loop {
+            let (child_event, client_event, readable) = {
+                let mut fds = vec![
+                    PollFd::new(signals.as_fd(), PollFlags::POLLIN),
+                    PollFd::new(self.listener.as_fd(), PollFlags::POLLIN),
+                ];
+                fds.extend(self.pending.iter().map(|client| {
+                    PollFd::new(client.connection.stream().as_fd(), PollFlags::POLLIN)
+                }));
+                match poll::poll(&mut fds, PollTimeout::NONE) {
+                    Ok(_) => {}
+                    Err(Errno::EINTR) => continue,
+                    Err(errno) => return Err(Error::errno_with_msg(errno, "poll() failed")),
+                }
+                let ready = |fd: &PollFd| fd.revents().is_some_and(|events| !events.is_empty());
+                let readable: Vec<bool> = fds[2..].iter().map(ready).collect();
+                (ready(&fds[0]), ready(&fds[1]), readable)
+            };
+
+            if child_event {
+                while signals.read_signal()?.is_some() {}
+                self.proot.poll_events()?;
+                self.notify_terminated();
+            }
+            if readable.contains(&true) {
+                let pending = std::mem::take(&mut self.pending);
+                for (mut client, readable) in pending.into_iter().zip(readable) {
+                    if !readable || self.read_client(&mut client) {
+                        self.pending.push(client);
+                    }
+                }
+            }
+            if client_event {
+                self.accept_clients();
+            }
+
+            if self.stopping.is_some() && self.proot.alive_tracees().is_empty() {
+                // the session must be gone for the client once it is answered
+                let _ = fs::remove_file(&self.socket_path);
+                if let Some(mut client) = self.stopping.take() {
+                    let _ = client.send(&Response::Ok);
+                }
+                info!("session stopped");
+                return Ok(());
+            }
+        }
+    }
+
+    fn accept_clients(&mut self) {
+        loop {
+            let stream = match self.listener.accept() {
+                Ok((stream, _)) => stream,
+                Err(error) if error.kind() == IOErrorKind::WouldBlock => return,
+                Err(error) => {
+                    error!("cannot accept a client: {}", error);
+                    return;
+                }
+            };
+            // the responses are small enough not to block, a client which
+            // does not read them is dropped instead
+            if let Err(error) = stream.set_nonblocking(true) {
+                error!("cannot accept a client: {}", error);
+                continue;
+            }
+            self.pending.push(PendingClient {
+                connection: Connection::new(stream),
+                greeted: false,
+            });
+        }
+    }
+
+    /// Serve `client`, which is readable, and report its error if any.
+    /// Returns whether it is still pending.
+    fn read_client(&mut self, client: &mut PendingClient) -> bool {
+        let result = self.serve_client(client);
+        if let Err(error) = &result {
+            debug!("client request failed: {}", error);
+            let _ = client.connection.send(&Response::from_error(error));
+        }
+        result.unwrap_or(false)
+    }
+
+    /// Read what a client sent, and do the handshake and deal with its
+    /// request once they are complete. Returns whether it is still pending.
+    fn serve_client(&mut self, client: &mut PendingClient) -> Result<bool> {
+        match client.connection.receive() {
+            Ok(true) => {}
+            Ok(false) => return Ok(false),
+            Err(error) if error.get_errno() == EAGAIN => return Ok(true),
+            Err(error) => return Err(error),
+        }
+        while let Some(line) = client.connection.buffered_line()? {
+            if client.greeted {
+                let request = Request::decode(&line)?;
+                debug!("session request: {:?}", request);
+                self.serve_request(&mut client.connection, request)?;
+                return Ok(false);
+            }
+            match Request::decode(&line)? {
+                Request::Hello { version } if version == PROTOCOL_VERSION => {}
+                Request::Hello { version } => {
+                    return Err(Error::errno_with_msg(
+                        EPROTONOSUPPORT,
+                        format!(
+                            "unsupported protocol version {} (supported: {})",
+                            version, PROTOCOL_VERSION
+                        ),
+                    ));
+                }
+                _ => return Err(Error::errno_with_msg(EPROTO, "expected a handshake")),
+            }
+            client.connection.send(&Response::Hello {
+                version: PROTOCOL_VERSION,
+            })?;
+            client.greeted = true;
+        }
+        Ok(true)
+    }
+
+    /// Deal with the request of a client. The connection is kept if the
+    /// answer comes later (`Exec`, `Stop`).
+    fn serve_request(&mut self, client: &mut Connection, request: Request) -> Result<()> {
+        match request {
+            Request::Hello { .. } => Err(Error::errno_with_msg(EPROTO, "unexpected handshake")),
+            Request::Exec { command, env, cwd } => {
+                let stdio = client.take_fds();
+                let pid = self.exec(command, env, cwd, stdio)?;
+                client.send(&Response::Started { pid: pid.as_raw() })?;
+                let stream = client.stream().try_clone()?;
+                self.waiting.insert(pid, Connection::new(stream));
+                Ok(())
+            }
+            Request::Ps => {
+                for &pid in self.proot.alive_tracees() {
+                    client.send(&Response::Process {
+                        pid: pid.as_raw(),
+                        exe: self.proot.guest_exe(pid),
+                        command: read_cmdline(pid),
+                    })?;
+                }
+                client.send(&Response::End)
+            }
+            Request::Kill { signal, pids } => {
+                let signal = Signal::try_from(signal)?;
+                let pids: Vec<Pid> = if pids.is_empty() {
+                    self.proot.alive_tracees().to_vec()
+                } else {
+                    pids.into_iter().map(Pid::from_raw).collect()
+                };
+                // Signal nothing unless every pid belongs to the session.
+                if let Some(pid) = pids
+                    .iter()
+                    .find(|pid| !self.proot.alive_tracees().contains(pid))
+                {
+                    return Err(Error::errno_with_msg(
+                        ESRCH,
+                        format!("no process {} in this session", pid),
+                    ));
+                }
+                for pid in pids {
+                    signal::kill(pid, signal)?;
+                }
+                client.send(&Response::Ok)
+            }
+            Request::Stop => {
+                debug!("stopping the session: {:?}", self.proot.alive_tracees());
+                for &pid in self.proot.alive_tracees() {
+                    let _ = signal::kill(pid, Signal::SIGKILL);
+                }
+                let stream = client.stream().try_clone()?;
+                self.stopping = Some(Connection::new(stream));
+                Ok(())
+            }
+        }
+    }
+
+    fn exec(
+        &mut self,
+        command: Vec<String>,
+        env: Vec<(String, String)>,
+        cwd: Option<PathBuf>,
+        stdio: Vec<OwnedFd>,
+    ) -> Result<Pid> {
+        if self.stopping.is_some() {
+            return Err(Error::errno_with_msg(ESHUTDOWN, "the session is stopping"));
+        }
+        let stdio: [OwnedFd; 3] = stdio.try_into().map_err(|_| {
+            Error::errno_with_msg(EINVAL, "expected the 3 standard streams of the command")
+        })?;
+        let mut fs = self.fs.clone();
+        if let Some(cwd) = cwd {
+            fs.set_cwd(&cwd)
+                .with_context(|| format!("cannot change to {:?}", cwd))?;
+        }
+        let mut spec = self.env.clone();
+        for (key, value) in env {
+            spec.set_var(key, value);
+        }
+        self.proot.spawn_process(fs, command, &spec, Some(&stdio))
+    }
+
+    /// Answer the clients whose process is terminated.
+    fn notify_terminated(&mut self) {
+        let pids: Vec<Pid> = self.waiting.keys().copied().collect();
+        for pid in pids {
+            if let Some(status) = self.proot.take_exit_status(pid)
+                && let Some(mut client) = self.waiting.remove(&pid)
+            {
+                let _ = client.send(&Response::Terminated(status));
+            }
+        }
+    }
+}
+
+impl Drop for Server {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.socket_path);
+    }
+}
+
+/// Create `path` if needed, only accessible by the current user since anyone
+/// who can connect to a session can run commands in it.
+fn create_private_dir(path: &Path) -> Result<()> {
+    use std::os::unix::fs::{DirBuilderExt, MetadataExt};
+
+    if !path.exists() {
+        fs::DirBuilder::new()
+            .recursive(true)
+            .mode(0o700)
+            .create(path)
+            .with_context(|| format!("cannot create {:?}", path))?;
+    }
+    let metadata = fs::metadata(path)?;
+    if metadata.uid() != nix::unistd::getuid().as_raw() || metadata.mode() & 0o077 != 0 {
+        return Err(Error::errno_with_msg(
+            EPERM,
+            format!("{:?} must only be accessible by its owner", path),
+        ));
+    }
+    Ok(())
+}
@@ -1,3 +1,10 @@
+use std::path::PathBuf;
+
+use crate::filesystem::device::Device;
+use crate::process::environment::EnvironmentSpec;
+use crate::process::proot::ExitPolicy;
+use crate::process::trace::TraceOptions;
+
 pub trait AsU8Slice {
     fn as_u8_slice(&self) -> &[u8];
 }
@@ -13,10 +20,29 @@ impl<T> AsU8Slice for [T] {
     }
 }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Default)]
 pub struct Config {
     pub root_id: bool,
     pub link2symlink: bool,
+    /// Host name reported to the guest by `uname()`.
+    pub hostname: Option<String>,
+    /// Kernel release reported to the guest by `uname()`.
+    pub kernel_release: Option<String>,
+    /// Content of the guest `/proc/cpuinfo`.
+    pub cpuinfo: Option<PathBuf>,
+    /// Host devices which can be opened through the device files created
+    /// by the fake root, besides `DEFAULT_ALLOWED_DEVICES`.
+    pub allowed_devices: Vec<Device>,
+    /// How the environment of the initial command is built.
+    pub env: EnvironmentSpec,
+    /// Print the syscalls of the tracees, strace-style.
+    pub trace: Option<TraceOptions>,
+    /// Write a JSON audit record of each file access to this file.
+    pub audit: Option<PathBuf>,
+    /// When the event loop returns.
+    pub exit_policy: ExitPolicy,
+    /// Make the processes started by the tracer child subreapers.
+    pub subreaper: bool,
 }
 
 impl Config {
@@ -24,6 +50,7 @@ impl Config {
         Self {
             root_id,
             link2symlink,
+            ..Default::default()
         }
     }
 }
@@ -60,7 +87,7 @@ pub mod tests {
     /// Since each rust unit tests is executed in a different thread, we
     /// should fork a child process to test the proot, otherwise the
     /// calls to `waitpid(-1)` from different unit tests may affect each other
-    fn test_in_subprocess<F: FnOnce()>(func: F) {
+    pub fn test_in_subprocess<F: FnOnce()>(func: F) {
         let pid = unsafe { fork() };
         match pid {
             Ok(ForkResult::Child) => {
@@ -118,7 +145,7 @@ pub mod tests {
                         Stopped(child, SIGSTOP)
                     );
                     tracee
-                        .check_and_set_ptrace_options(&mut info_bag)
+                        .set_ptrace_options()
                         .expect("error when set ptrace options");
 
                     restart(child);
@@ -218,10 +245,7 @@ pub mod tests {
                 let root_path = get_test_rootfs_path();
                 let mut fs = FileSystem::with_root(root_path)?;
                 fs.set_cwd("/")?;
-                let config = Config {
-                    root_id: false,
-                    link2symlink: false,
-                };
+                let config = Config::new(false, false);
                 let mut proot: PRoot = PRoot::new(config);
                 proot.init()?;
                 proot.func_syscall_hook = Some(Box::new(func_syscall_hook));
@@ -232,7 +256,8 @@ pub mod tests {
                             child,
                             Rc::new(RefCell::new(fs)),
                             SigStopStatus::EventloopSync,
-                        );
+                            false,
+                        )?;
                         proot.init_pid = Some(child);
                     }
                     ForkResult::Child => {
@@ -258,10 +283,10 @@ pub mod tests {
                 proot.event_loop()?;
 
                 assert_eq!(
-                    proot.init_exit_code,
+                    proot.init_status.map(|status| status.code()),
                     Some(0),
                     "tracee exited with a bad exit code: {:?}",
-                    proot.init_exit_code
+                    proot.init_status
                 );
                 Ok(())
             };
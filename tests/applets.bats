@@ -270,6 +270,14 @@ function script_test_run_applets_common_tools {
 }
 
 
+@test "test proot-rs run id with -0" {
+    runp proot-rs -0 --rootfs "$ROOTFS" -- /bin/sh -c 'id -u; id -g'
+    [ "$status" -eq 0 ]
+    [ "${lines[0]}" = "0" ]
+    [ "${lines[1]}" = "0" ]
+}
+
+
 @test "test proot-rs run man " {
     check_if_command_exists man
 
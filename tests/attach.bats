@@ -0,0 +1,27 @@
+#!/usr/bin/env bats
+
+load helper
+
+
+@test "test attach translates the paths of a running process" {
+    local fifo="$BATS_TEST_TMPDIR/fifo"
+    mkfifo "$fifo"
+    exec 3<>"$fifo"
+    # the process only looks for the binding once it is told to
+    sh -c 'read line; test -e /mnt/passwd && exit 3' <&3 &
+    local pid=$!
+    proot-rs attach --pid "$pid" --rootfs "$ROOTFS" -b /etc:/mnt &
+    local attach_pid=$!
+    # wait until the process is traced
+    until grep -q "^TracerPid:[[:space:]]*[1-9]" "/proc/$pid/status"; do sleep 0.1; done
+    sleep 0.2
+    echo go >&3
+    run wait "$attach_pid"
+    exec 3>&-
+    [ "$status" -eq 3 ]
+}
+
+@test "test attach to a non-existing process" {
+    runp proot-rs attach --pid 999999999 --rootfs "$ROOTFS"
+    [ "$status" -eq 1 ]
+}
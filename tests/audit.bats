@@ -0,0 +1,18 @@
+#!/usr/bin/env bats
+
+load helper
+
+
+@test "test audit log records guest and host paths" {
+    local audit_file="$BATS_TEST_TMPDIR/audit.json"
+    runp proot-rs --rootfs "$ROOTFS" -b /etc:/mnt --audit "$audit_file" -- /bin/cat /mnt/passwd
+    [ "$status" -eq 0 ]
+    grep -F '"op":"read","guest_path":"/mnt/passwd","host_path":"/etc/passwd","binding":{"host":"/etc","guest":"/mnt"}' "$audit_file"
+}
+
+@test "test audit log records failed accesses" {
+    local audit_file="$BATS_TEST_TMPDIR/audit.json"
+    runp proot-rs --rootfs "$ROOTFS" --audit "$audit_file" -- /bin/cat /non_existing_path
+    [ "$status" -ne 0 ]
+    grep -E '"guest_path":"/non_existing_path".*"result":-2,"errno":"ENOENT"' "$audit_file"
+}
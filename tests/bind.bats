@@ -55,4 +55,49 @@ load helper
     '
     rm -rf "$ROOTFS/tmp/test_bind_with_getdents64"
     [ "$status" -eq 0 ]
-}
\ No newline at end of file
+}
+
+@test "test -R binds the recommended host files" {
+    runp proot-rs -R "$ROOTFS" -- /bin/sh -c "cat /proc/self/comm; id -u"
+    [ "$status" -eq 0 ]
+    [ "${lines[0]}" = "cat" ]
+    [ "${lines[1]}" = "$(id -u)" ]
+}
+
+
+@test "test -S pretends to be root" {
+    runp proot-rs -S "$ROOTFS" -- /bin/sh -c "id -u"
+    [ "$status" -eq 0 ]
+    [ "${lines[0]}" = "0" ]
+}
+
+
+@test "test -R conflicts with --rootfs" {
+    runp proot-rs -R "$ROOTFS" --rootfs "$ROOTFS" -- /bin/true
+    [ "$status" -ne 0 ]
+}
+
+
+@test "test --bind-ro forbids modifications" {
+    mkdir -p "$ROOTFS/tmp/test_bind_ro"
+    echo "content" > "$ROOTFS/tmp/test_bind_ro/file"
+    runp proot-rs --rootfs "$ROOTFS" --bind-ro "$ROOTFS/tmp/test_bind_ro:/mnt" -- /bin/sh -c '
+        cat /mnt/file || exit 1
+        touch /mnt/new && exit 2
+        rm /mnt/file && exit 3
+        echo other > /mnt/file && exit 4
+        ln /mnt/file /tmp/test_bind_ro_link && exit 5
+        mv /mnt/file /mnt/renamed && exit 6
+        exit 0
+    '
+    rm -rf "$ROOTFS/tmp/test_bind_ro"
+    [ "$status" -eq 0 ]
+    [ "${lines[0]}" = "content" ]
+}
+
+
+@test "test statfs() reports the file-system of the binding" {
+    runp proot-rs --rootfs "$ROOTFS" --bind "/proc:/proc" -- /bin/stat -f -c "%t" /proc
+    [ "$status" -eq 0 ]
+    [ "${lines[0]}" = "9fa0" ]
+}
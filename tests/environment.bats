@@ -0,0 +1,90 @@
+#!/usr/bin/env bats
+
+load helper
+
+
+@test "test command is searched in the guest PATH" {
+    runp proot-rs --rootfs "$ROOTFS" -e PATH=/bin -- sh -c 'echo ok'
+    [ "$status" -eq 0 ]
+    [ "${lines[0]}" = "ok" ]
+}
+
+@test "test command not found in the guest PATH" {
+    runp proot-rs --rootfs "$ROOTFS" -e PATH=/nonexistent -- sh -c 'echo ok'
+    [ "$status" -ne 0 ]
+}
+
+@test "test host environment does not leak into the guest" {
+    LD_LIBRARY_PATH=/host/lib runp proot-rs --rootfs "$ROOTFS" -- /bin/sh -c 'echo "${LD_LIBRARY_PATH-unset}"'
+    [ "$status" -eq 0 ]
+    [ "${lines[0]}" = "unset" ]
+}
+
+@test "test guest HOME comes from the guest /etc/passwd" {
+    runp proot-rs --rootfs "$ROOTFS" -0 -- /bin/sh -c 'echo "$HOME"'
+    [ "$status" -eq 0 ]
+    [ "${lines[0]}" = "$(awk -F: '$3 == 0 { print $6 }' "$ROOTFS/etc/passwd")" ]
+}
+
+@test "test --env, --unset-env and --clear-env" {
+    runp proot-rs --rootfs "$ROOTFS" -e FOO=bar --unset-env HOME -- /bin/sh -c 'echo "$FOO ${HOME-unset}"'
+    [ "$status" -eq 0 ]
+    [ "${lines[0]}" = "bar unset" ]
+
+    runp proot-rs --rootfs "$ROOTFS" --clear-env -e FOO=bar -- /usr/bin/env
+    [ "$status" -eq 0 ]
+    [ "${lines[0]}" = "FOO=bar" ]
+}
+
+@test "test guest /etc/passwd and /etc/group have an entry for the user" {
+    local passwd="$(cat "$ROOTFS/etc/passwd")"
+    runp proot-rs --rootfs "$ROOTFS" -- /bin/sh -c 'id -un; id -gn; grep -c "^[^:]*:[^:]*:$(id -u):" /etc/passwd'
+    [ "$status" -eq 0 ]
+    [ "${lines[0]}" = "$(id -un)" ]
+    [ "${lines[1]}" = "$(id -gn)" ]
+    [ "${lines[2]}" = "1" ]
+    # the rootfs is left untouched
+    [ "$(cat "$ROOTFS/etc/passwd")" = "$passwd" ]
+}
+
+@test "test guest /etc/hosts has the host name of the box" {
+    runp proot-rs --rootfs "$ROOTFS" --hostname box -- /bin/sh -c 'grep -w box /etc/hosts'
+    [ "$status" -eq 0 ]
+    [ "${lines[0]}" = "$(printf '127.0.1.1\tbox')" ]
+}
+
+@test "test guest /proc/mounts and /proc/self/mountinfo list the bindings" {
+    runp proot-rs --rootfs "$ROOTFS" -b /proc -b /etc:/tmp -- /bin/sh -c 'cut -d" " -f2 /proc/mounts; cut -d" " -f5 /proc/self/mountinfo'
+    [ "$status" -eq 0 ]
+    [ "${lines[0]}" = "/" ]
+    [ "${lines[1]}" = "/proc" ]
+    [ "${lines[2]}" = "/tmp" ]
+    [ "${lines[3]}" = "/" ]
+    [ "${lines[4]}" = "/proc" ]
+    [ "${lines[5]}" = "/tmp" ]
+}
+
+@test "test guest /proc/version follows -k, and /proc/cpuinfo --cpuinfo" {
+    runp proot-rs --rootfs "$ROOTFS" -b /proc -k 9.9.9 -- /bin/sh -c 'cut -d" " -f3 /proc/version; cat /proc/sys/kernel/osrelease'
+    [ "$status" -eq 0 ]
+    [ "${lines[0]}" = "9.9.9" ]
+    [ "${lines[1]}" = "9.9.9" ]
+
+    local cpuinfo="$(mktemp)"
+    echo "model name : test" > "$cpuinfo"
+    runp proot-rs --rootfs "$ROOTFS" -b /proc --cpuinfo "$cpuinfo" -- /bin/cat /proc/cpuinfo
+    [ "$status" -eq 0 ]
+    [ "${lines[0]}" = "model name : test" ]
+    rm -f "$cpuinfo"
+
+    runp proot-rs --rootfs "$ROOTFS" --cpuinfo "$cpuinfo" -- /bin/true
+    [ "$status" -ne 0 ]
+}
+
+@test "test guest /proc/<pid>/maps and map_files show guest paths" {
+    runp proot-rs --rootfs "$ROOTFS" -b /proc -- /bin/sh -c 'grep -c prooted /proc/$$/maps; grep -c " /bin/busybox$" /proc/$$/maps; readlink /proc/$$/map_files/* | sort -u'
+    [ "${lines[0]}" = "0" ]
+    [ "${lines[1]}" -gt 0 ]
+    [[ ! "$output" =~ prooted ]]
+    [[ "$output" =~ /bin/busybox ]]
+}
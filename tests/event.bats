@@ -7,3 +7,59 @@ load helper
     runp proot-rs --rootfs "$ROOTFS" -- /bin/sh -c '/bin/kill -11 $PPID; /bin/echo "The tracee is still alive, which is not allowed";'
     [[ "$output" != *"still alive"* ]]
 }
+
+@test "test termination signals are forwarded to the init process" {
+    # not through the `proot-rs` function, so that `$!` is the pid of the tracer
+    "$PROOT_RS" --rootfs "$ROOTFS" --grace-period 1 -- /bin/sh -c 'trap "exit 42" TERM; /bin/sleep 30 & wait' &
+    local pid=$!
+    sleep 1
+    kill -TERM "$pid"
+    # the exit status is the one of init, and the remaining tracee is killed
+    run wait "$pid"
+    [ "$status" -eq 42 ]
+}
+
+@test "test tracees are killed after the grace period" {
+    "$PROOT_RS" --rootfs "$ROOTFS" --grace-period 1 -- /bin/sh -c 'trap "" TERM; /bin/sleep 30' &
+    local pid=$!
+    sleep 1
+    kill -TERM "$pid"
+    run wait "$pid"
+    [ "$status" -eq 137 ]
+}
+
+@test "test tracees left by the init process are killed after the grace period" {
+    "$PROOT_RS" --rootfs "$ROOTFS" --grace-period 1 -- /bin/sh -c '(trap "" TERM; /bin/sleep 15) & /bin/sleep 15' &
+    local pid=$!
+    sleep 1
+    local start=$SECONDS
+    kill -TERM "$pid"
+    run wait "$pid"
+    [ "$status" -eq 143 ]
+    [ $((SECONDS - start)) -lt 10 ]
+}
+
+@test "test --exit-policy=init kills and reports the orphans" {
+    runp proot-rs --rootfs "$ROOTFS" --exit-policy=init -- /bin/sh -c '/bin/sleep 60 & /bin/sleep 0.2; exit 5'
+    [ "$status" -eq 5 ]
+    [[ "$output" == *"killed 1 orphan process(es)"* ]]
+    [[ "$output" == *"sleep 60"* ]]
+}
+
+@test "test --subreaper reparents the orphans to the init process" {
+    runp proot-rs --rootfs "$ROOTFS" --subreaper -- /bin/sh -c 'p=$(/bin/sh -c "/bin/sleep 1 >/dev/null & echo \$!"); /bin/grep -q "^PPid:[[:space:]]*$$\$" /proc/$p/status'
+    [ "$status" -eq 0 ]
+}
+
+@test "test a stopped init process stops pronyx until its job is continued" {
+    local output_file="$BATS_TEST_TMPDIR/output"
+    # in its own process group, which is continued as a whole, like a job by `fg`
+    setsid "$PROOT_RS" --rootfs "$ROOTFS" -- /bin/sh -c 'kill -STOP $$; echo continued' > "$output_file" &
+    local pid=$!
+    sleep 1
+    [ "$(cut -d' ' -f3 "/proc/$pid/stat")" = "T" ]
+    kill -CONT -- "-$pid"
+    run wait "$pid"
+    [ "$status" -eq 0 ]
+    [ "$(cat "$output_file")" = "continued" ]
+}
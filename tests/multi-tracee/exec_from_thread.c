@@ -0,0 +1,68 @@
+/**
+ * This program is designed to test the handling of execve() called by a thread
+ * other than the thread group leader.
+ *
+ * The kernel then kills the other threads, and the thread which calls
+ * execve() takes the pid of the leader. The program starts a few threads
+ * which sleep, and a thread which executes the program again, which then
+ * prints its pid and the one it had before.
+ *
+ * The expected result is that the new program runs normally, with the pid of
+ * the former leader.
+ */
+
+#include <pthread.h>
+#include <stdio.h>
+#include <stdlib.h>
+#include <string.h>
+#include <unistd.h>
+
+#define SLEEPING_THREADS 4
+
+static char *self_path;
+
+static void exit_with_error(char *msg) {
+    fprintf(stderr, "%s\n", msg);
+    exit(1);
+}
+
+// Start function for the threads which are killed by execve()
+static void *sleeping_thread(void *arg) {
+    (void)arg;
+    for (;;)
+        sleep(1);
+    return NULL;
+}
+
+// Start function for the thread which calls execve()
+static void *exec_thread(void *arg) {
+    char pid[32];
+    snprintf(pid, sizeof(pid), "%d", getpid());
+    execl(self_path, self_path, "child", pid, (char *)NULL);
+    exit_with_error("Error while execl()");
+    return arg;
+}
+
+int main(int argc, char *argv[]) {
+    pthread_t thread;
+    int i;
+
+    if (argc == 3 && strcmp(argv[1], "child") == 0) {
+        if (atoi(argv[2]) != getpid())
+            exit_with_error("the pid changed across execve()");
+        puts("exec from a thread");
+        return 0;
+    }
+
+    self_path = argv[0];
+    for (i = 0; i < SLEEPING_THREADS; i++) {
+        if (pthread_create(&thread, NULL, sleeping_thread, NULL) != 0)
+            exit_with_error("Error while pthread_create()");
+    }
+    if (pthread_create(&thread, NULL, exec_thread, NULL) != 0)
+        exit_with_error("Error while pthread_create()");
+
+    // The leader waits to be killed by execve()
+    for (;;)
+        sleep(1);
+}
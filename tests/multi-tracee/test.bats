@@ -41,3 +41,14 @@ load ../helper
     [ "${#lines[@]}" -eq 1 ]
 }
 
+
+@test "test execve() called by a thread other than the leader" {
+    # compile test case
+    compile_c_dynamic "$ROOTFS/bin/exec_from_thread" "$BATS_TEST_DIRNAME/exec_from_thread.c"
+    runp proot-rs --rootfs "$ROOTFS" --cwd / -- /bin/exec_from_thread
+    # remember to delete the binary file
+    rm "$ROOTFS/bin/exec_from_thread"
+    [ "$status" -eq 0 ]
+    [ "${lines[0]}" = "exec from a thread" ]
+    [ "${#lines[@]}" -eq 1 ]
+}
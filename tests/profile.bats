@@ -0,0 +1,73 @@
+#!/usr/bin/env bats
+
+load helper
+
+
+function setup() {
+    PROFILE="$BATS_TEST_TMPDIR/box.toml"
+}
+
+@test "test profile with rootfs, cwd and command" {
+    cat > "$PROFILE" <<EOT
+rootfs = "$ROOTFS"
+cwd = "/bin"
+command = ["/bin/sh", "-c", "/bin/pwd -P"]
+EOT
+    runp proot-rs --profile "$PROFILE"
+    [ "$status" -eq 0 ]
+    [ "${lines[0]}" = "/bin" ]
+}
+
+@test "test profile overridden by flags" {
+    cat > "$PROFILE" <<EOT
+rootfs = "$ROOTFS"
+cwd = "/bin"
+EOT
+    runp proot-rs --profile "$PROFILE" --cwd /etc -- /bin/pwd -P
+    [ "$status" -eq 0 ]
+    [ "${lines[0]}" = "/etc" ]
+}
+
+@test "test profile booleans overridden by flags" {
+    cat > "$PROFILE" <<EOT
+rootfs = "$ROOTFS"
+
+[identity]
+root = true
+EOT
+    runp proot-rs --profile "$PROFILE" --no-root-id -- /bin/id -u
+    [ "$status" -eq 0 ]
+    [ "${lines[0]}" = "$(id -u)" ]
+}
+
+@test "test profile from PRONYX_PROFILE" {
+    cat > "$PROFILE" <<EOT
+rootfs = "$ROOTFS"
+hostname = "box"
+kernel_release = "1.2.3-pronyx"
+EOT
+    PRONYX_PROFILE="$PROFILE" runp proot-rs -- /bin/uname -nr
+    [ "$status" -eq 0 ]
+    [ "${lines[0]}" = "box 1.2.3-pronyx" ]
+}
+
+@test "test check-profile" {
+    cat > "$PROFILE" <<EOT
+rootfs = "$ROOTFS"
+
+[[bind]]
+host = "/non_existing_path"
+optional = true
+EOT
+    proot-rs check-profile "$PROFILE"
+
+    cat > "$PROFILE" <<EOT
+rootfs = "$ROOTFS"
+
+[[bind]]
+host = "/non_existing_path"
+EOT
+    runp proot-rs check-profile "$PROFILE"
+    [ "$status" -ne 0 ]
+    [[ "$output" == *"bind[0].host"* ]]
+}
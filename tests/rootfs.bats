@@ -0,0 +1,55 @@
+#!/usr/bin/env bats
+
+load helper
+
+
+function setup() {
+    IMAGE_DIR="$BATS_TEST_TMPDIR/image"
+    mkdir -p "$IMAGE_DIR/bin" "$IMAGE_DIR/etc"
+    echo "root:x:0:0:root:/root:/bin/sh" > "$IMAGE_DIR/etc/passwd"
+    cp "$ROOTFS/bin/busybox" "$IMAGE_DIR/bin/busybox"
+    ln -s busybox "$IMAGE_DIR/bin/sh"
+    ln -s busybox "$IMAGE_DIR/bin/cat"
+}
+
+@test "test rootfs import and run" {
+    tar -C "$IMAGE_DIR" -cJf "$BATS_TEST_TMPDIR/image.tar.xz" .
+    runp proot-rs rootfs import "$BATS_TEST_TMPDIR/image.tar.xz" "$BATS_TEST_TMPDIR/rootfs"
+    [ "$status" -eq 0 ]
+    [ -f "$BATS_TEST_TMPDIR/rootfs.manifest.toml" ]
+    runp proot-rs --rootfs "$BATS_TEST_TMPDIR/rootfs" -- /bin/cat /etc/passwd
+    [ "$status" -eq 0 ]
+    [ "${lines[0]}" = "root:x:0:0:root:/root:/bin/sh" ]
+}
+
+@test "test rootfs export keeps the recorded ownership" {
+    tar -C "$IMAGE_DIR" --owner=1000 --group=42 -czf "$BATS_TEST_TMPDIR/image.tar.gz" ./etc
+    proot-rs rootfs import "$BATS_TEST_TMPDIR/image.tar.gz" "$BATS_TEST_TMPDIR/rootfs"
+    proot-rs rootfs export "$BATS_TEST_TMPDIR/rootfs" "$BATS_TEST_TMPDIR/exported.tar.zst"
+    runp tar --numeric-owner -tvf "$BATS_TEST_TMPDIR/exported.tar.zst" etc/passwd
+    [ "$status" -eq 0 ]
+    [[ "${lines[0]}" == *" 1000/42 "* ]]
+}
+
+@test "test rootfs import refuses path traversal" {
+    tar -C "$BATS_TEST_TMPDIR" -cf "$BATS_TEST_TMPDIR/evil.tar" --absolute-names "../$(basename "$BATS_TEST_TMPDIR")/image/etc/passwd"
+    runp proot-rs rootfs import "$BATS_TEST_TMPDIR/evil.tar" "$BATS_TEST_TMPDIR/rootfs"
+    [ "$status" -ne 0 ]
+}
+
+@test "test mknod of a device with -0 is recorded in the manifest" {
+    ln -s busybox "$IMAGE_DIR/bin/mknod"
+    ln -s busybox "$IMAGE_DIR/bin/stat"
+    mkdir "$IMAGE_DIR/dev"
+    runp proot-rs --rootfs "$IMAGE_DIR" -0 -- /bin/sh -c '
+        /bin/mknod -m 666 /dev/zero c 1 5 || exit 1
+        /bin/stat -c "%F %t:%T %a" /dev/zero
+    '
+    [ "$status" -eq 0 ]
+    [ "${lines[0]}" = "character special file 1:5 666" ]
+    grep -q 'device = "char"' "$IMAGE_DIR.manifest.toml"
+    proot-rs rootfs export "$IMAGE_DIR" "$BATS_TEST_TMPDIR/exported.tar"
+    runp tar -tvf "$BATS_TEST_TMPDIR/exported.tar" dev/zero
+    [ "$status" -eq 0 ]
+    [[ "${lines[0]}" == c* ]]
+}
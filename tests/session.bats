@@ -0,0 +1,60 @@
+#!/usr/bin/env bats
+
+load helper
+
+function setup() {
+    export PRONYX_SESSION_DIR="$BATS_TEST_TMPDIR/sessions"
+    proot-rs daemon --session test --rootfs "$ROOTFS" -b /etc:/mnt -w /tmp
+}
+
+function teardown() {
+    proot-rs stop --session test || true
+}
+
+
+@test "test session runs commands with its bindings and cwd" {
+    runp proot-rs exec --session test -- /bin/sh -c 'pwd; test -e /mnt/passwd && echo bound; exit 3'
+    [ "$status" -eq 3 ]
+    [ "${lines[0]}" = "/tmp" ]
+    [ "${lines[1]}" = "bound" ]
+}
+
+@test "test session exec options and standard input" {
+    function exec_with_input() {
+        echo piped | proot-rs exec --session test -e FOO=bar -w / -- /bin/sh -c 'read line; echo $FOO $PWD $line'
+    }
+    runp exec_with_input
+    [ "$status" -eq 0 ]
+    [ "${lines[0]}" = "bar / piped" ]
+}
+
+@test "test session ps and kill" {
+    proot-rs exec --session test -- /bin/sleep 60 &
+    local exec_pid=$!
+    for i in $(seq 50); do
+        run proot-rs ps --session test
+        [[ "$output" == *"/bin/sleep 60"* ]] && break
+        sleep 0.1
+    done
+    [[ "$output" == *"/bin/sleep 60"* ]]
+
+    runp proot-rs kill --session test -s KILL
+    [ "$status" -eq 0 ]
+    run wait "$exec_pid"
+    [ "$status" -eq 137 ]
+
+    runp proot-rs kill --session test 1
+    [ "$status" -ne 0 ]
+}
+
+@test "test session stop and errors" {
+    runp proot-rs daemon --session test --rootfs "$ROOTFS"
+    [ "$status" -ne 0 ]
+    [[ "$output" == *"already"* ]]
+
+    runp proot-rs stop --session test
+    [ "$status" -eq 0 ]
+    runp proot-rs exec --session test -- /bin/true
+    [ "$status" -ne 0 ]
+    [[ "$output" == *"no session"* ]]
+}
@@ -0,0 +1,24 @@
+#!/usr/bin/env bats
+
+load helper
+
+
+@test "test trace shows guest and host paths" {
+    runp proot-rs --rootfs "$ROOTFS" -b /etc:/mnt --trace --trace-filter=openat,open -- /bin/cat /mnt/passwd
+    [ "$status" -eq 0 ]
+    [[ "$output" == *'"/mnt/passwd" => "/etc/passwd"'* ]]
+}
+
+@test "test trace to a file with errno and timestamps" {
+    local trace_file="$BATS_TEST_TMPDIR/trace.txt"
+    runp proot-rs --rootfs "$ROOTFS" --trace="$trace_file" --trace-filter=file --trace-timestamps -- /bin/cat /non_existing_path
+    [ "$status" -ne 0 ]
+    grep -E '^\[pid [0-9]+\] [0-9]+\.[0-9]{6} open(at)?\(.*"/non_existing_path".* = -1 ENOENT' "$trace_file"
+    # only file syscalls are traced
+    ! grep -E '\] [0-9.]+ (mmap|brk|read)\(' "$trace_file"
+}
+
+@test "test trace filter is checked" {
+    runp proot-rs --rootfs "$ROOTFS" --trace --trace-filter=not_a_syscall -- /bin/true
+    [ "$status" -ne 0 ]
+}